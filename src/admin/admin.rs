@@ -11,6 +11,7 @@
 	oidc::{self, OidcCommand},
 	query::{self, QueryCommand},
 	room::{self, RoomCommand},
+	schedule::{self, ScheduleCommand},
 	server::{self, ServerCommand},
 	token::{self, TokenCommand},
 	user::{self, UserCommand},
@@ -51,6 +52,10 @@ pub enum AdminCommand {
 	#[command(subcommand)]
 	Media(MediaCommand),
 
+	/// Commands for scheduling admin commands
+	#[command(subcommand)]
+	Schedule(ScheduleCommand),
+
 	/// Commands for checking integrity
 	#[command(subcommand)]
 	Check(CheckCommand),
@@ -105,5 +110,10 @@ pub(super) async fn process(command: AdminCommand, context: &Context<'_>) -> Res
 			query::process(command, context).await
 		},
 		| Check(command) => check::process(command, context).await,
+		| Schedule(command) => {
+			// schedule commands are all restricted
+			context.bail_restricted()?;
+			schedule::process(command, context).await
+		},
 	}
 }
@@ -1,5 +1,6 @@
 use std::{
-	collections::{HashMap, HashSet},
+	cmp::Reverse,
+	collections::{BTreeMap, BTreeSet, HashMap, HashSet},
 	fmt::Write,
 	iter::once,
 	time::{Instant, SystemTime},
@@ -13,7 +14,7 @@
 	},
 	trace, utils,
 	utils::{
-		stream::{IterStream, ReadyExt},
+		stream::{IterStream, ReadyExt, TryIgnore},
 		string::EMPTY,
 	},
 	warn,
@@ -29,9 +30,12 @@
 	OwnedRoomOrAliasId, OwnedServerName, RoomId, RoomVersionId, UInt,
 	api::federation::event::get_room_state, events::AnyStateEvent, serde::Raw,
 };
-use service::rooms::{
-	short::{ShortEventId, ShortRoomId},
-	state_compressor::HashSetCompressStateEvent,
+use service::{
+	federation::CheckStatus,
+	rooms::{
+		short::{ShortEventId, ShortRoomId, ShortStateHash},
+		state_compressor::{HashSetCompressStateEvent, StateChain},
+	},
 };
 use tracing_subscriber::EnvFilter;
 
@@ -344,6 +348,108 @@ pub(super) async fn get_pdu(&self, event_id: OwnedEventId) -> Result {
 		.await
 	}
 
+	pub(super) async fn why_rejected(&self, event_id: OwnedEventId) -> Result {
+		let pdu_metadata = &self.services.rooms.pdu_metadata;
+		let timeline = &self.services.rooms.timeline;
+		let status = if pdu_metadata.is_event_rejected(&event_id).await {
+			"rejected"
+		} else if pdu_metadata.is_event_soft_failed(&event_id).await {
+			"soft-failed"
+		} else if timeline.get_pdu_id(&event_id).await.is_ok() {
+			"accepted"
+		} else if timeline.get_pdu(&event_id).await.is_ok() {
+			"outlier"
+		} else {
+			"not found locally"
+		};
+
+		let Ok(audit) = pdu_metadata.ingestion_audit(&event_id).await else {
+			return write!(
+				self,
+				"{event_id} is {status}. No reason was recorded for this event, it may have \
+				 been handled before reasons were recorded."
+			)
+			.await;
+		};
+
+		let mut msg = format!(
+			"{event_id} is {status}.\n\nLast verdict: {}\nCheck: {}\nReason: {}\nOrigin: \
+			 {}\nRoom: {}\nTime: {}\n",
+			audit.outcome.as_str(),
+			audit.check.as_str(),
+			audit.reason,
+			audit.origin,
+			audit.room_id,
+			utils::time::format_millis(audit.ts),
+		);
+
+		if let Ok(pdu) = timeline.get_pdu(&event_id).await {
+			writeln!(msg, "Sender: {}\nType: {}", pdu.sender(), pdu.kind())?;
+		}
+
+		self.write_str(&msg).await
+	}
+
+	pub(super) async fn rejected_events(
+		&self,
+		room_id: OwnedRoomOrAliasId,
+		limit: usize,
+	) -> Result {
+		let room_id = self.services.rooms.alias.resolve(&room_id).await?;
+		let mut audits: Vec<_> = self
+			.services
+			.rooms
+			.pdu_metadata
+			.room_ingestion_audits(&room_id)
+			.collect()
+			.await;
+
+		if audits.is_empty() {
+			return write!(self, "No rejected, soft-failed or dropped events were recorded.")
+				.await;
+		}
+
+		let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+		for (_, audit) in &audits {
+			let count = counts
+				.entry((audit.outcome.as_str(), audit.check.as_str()))
+				.or_default();
+
+			*count = count.saturating_add(1);
+		}
+
+		let mut msg = format!(
+			"{} event(s) of {room_id} were not accepted:\n\n| Verdict | Check | Events |\n| --- \
+			 | --- | --- |\n",
+			audits.len()
+		);
+
+		for ((outcome, check), count) in counts {
+			writeln!(msg, "| {outcome} | {check} | {count} |")?;
+		}
+
+		audits.sort_by_key(|(_, audit)| Reverse(audit.ts));
+		write!(
+			msg,
+			"\n| Time | Event | Origin | Verdict | Check | Reason |\n| --- | --- | --- | --- | \
+			 --- | --- |\n"
+		)?;
+
+		for (event_id, audit) in audits.iter().take(limit) {
+			writeln!(
+				msg,
+				"| {} | {event_id} | {} | {} | {} | {} |",
+				utils::time::format_millis(audit.ts),
+				audit.origin,
+				audit.outcome.as_str(),
+				audit.check.as_str(),
+				audit.reason
+			)?;
+		}
+
+		self.write_str(&msg).await
+	}
+
 	pub(super) async fn get_short_pdu(
 		&self,
 		shortroomid: ShortRoomId,
@@ -877,7 +983,8 @@ pub(super) async fn force_set_room_state_from_server(
 				self.services
 					.rooms
 					.pdu_metadata
-					.clear_pdu_markers(pdu.event_id());
+					.clear_pdu_markers(pdu.event_id())
+					.await;
 			}
 		}
 
@@ -898,7 +1005,8 @@ pub(super) async fn force_set_room_state_from_server(
 			self.services
 				.rooms
 				.pdu_metadata
-				.clear_pdu_markers(&event_id);
+				.clear_pdu_markers(&event_id)
+				.await;
 		}
 
 		info!("Resolving new room state");
@@ -1034,6 +1142,38 @@ pub(super) async fn resolve_true_destination(
 		self.write_str(&msg).await
 	}
 
+	pub(super) async fn federation_report(&self, server_name: Option<OwnedServerName>) -> Result {
+		let server_name = server_name.unwrap_or_else(|| self.services.server.name.clone());
+		let report = self
+			.services
+			.federation
+			.connectivity_report(&server_name)
+			.await?;
+
+		let mut msg = format!(
+			"Federation report for {server_name}: {}\n\n| Check | Status | Detail |\n| --- | \
+			 --- | --- |\n",
+			if report.ok {
+				"no problems found"
+			} else {
+				"problems found"
+			}
+		);
+
+		for check in &report.checks {
+			let status = match check.status {
+				| CheckStatus::Ok => "ok",
+				| CheckStatus::Warning => "warning",
+				| CheckStatus::Failed => "failed",
+				| CheckStatus::Skipped => "skipped",
+			};
+
+			writeln!(msg, "| {} | {status} | {} |", check.name, check.detail)?;
+		}
+
+		self.write_str(&msg).await
+	}
+
 	pub(super) async fn memory_stats(&self, opts: Option<String>) -> Result {
 		const OPTS: &str = "abcdefghijklmnopqrstuvwxyz";
 
@@ -1293,4 +1433,154 @@ pub(super) async fn servers_in_backoff(
 
 		Ok(())
 	}
+
+	pub(super) async fn state_res_report(&self, limit: usize) -> Result {
+		let (rooms, slowest) = self.services.rooms.event_handler.state_res_stats();
+		let (chains, indexed) = self.services.rooms.auth_chain.chain_cover_usage().await;
+
+		let mut msg = format!(
+			"Chain cover index: {indexed} event(s) on {chains} chain(s)\n\nRooms by time spent \
+			 in state resolution ({} room(s)):\n\n| Room | Resolutions | Total | Max | Last \
+			 |\n| --- | --- | --- | --- | --- |\n",
+			rooms.len()
+		);
+
+		for (room_id, room) in rooms.iter().take(limit) {
+			writeln!(
+				msg,
+				"| {room_id} | {} | {:?} | {:?} | {:?} |",
+				room.count, room.total, room.max, room.last
+			)?;
+		}
+
+		writeln!(
+			msg,
+			"\nMost expensive state resolutions:\n\n| Room | When | Total | Auth chain | \
+			 Resolve | State sets | Auth chain difference |\n| --- | --- | --- | --- | --- | \
+			 --- | --- |"
+		)?;
+
+		for resolution in slowest.iter().take(limit) {
+			writeln!(
+				msg,
+				"| {} | {} | {:?} | {:?} | {:?} | {} | {} |",
+				resolution.room_id,
+				utils::time::format_utc(resolution.ts),
+				resolution.total(),
+				resolution.auth_chain_time,
+				resolution.resolve_time,
+				resolution.state_sets,
+				resolution.auth_chain_diff,
+			)?;
+		}
+
+		self.write_str(&msg).await
+	}
+
+	pub(super) async fn state_chains(
+		&self,
+		room_id: Option<OwnedRoomOrAliasId>,
+		events: usize,
+	) -> Result {
+		let state_compressor = &self.services.rooms.state_compressor;
+		let Some(room_id) = room_id else {
+			let stats = state_compressor.state_chain_stats().await;
+			let mut msg = format!(
+				"{} state group(s) storing {} diff entries. Loading the largest one reads {} \
+				 entries.\n\n| Depth | State groups |\n| --- | --- |\n",
+				stats.groups, stats.entries, stats.max_load
+			);
+
+			for (depth, count) in &stats.depths {
+				writeln!(msg, "| {depth} | {count} |")?;
+			}
+
+			return self.write_str(&msg).await;
+		};
+
+		let room_id = self.services.rooms.alias.resolve(&room_id).await?;
+		let shortstatehash = self
+			.services
+			.rooms
+			.state
+			.get_room_shortstatehash(&room_id)
+			.await?;
+
+		let layers = state_compressor.state_layers(shortstatehash).await?;
+		let current = StateChain::from_layers(&layers);
+		let mut msg = format!(
+			"Current state group {shortstatehash}: {} event(s), {} layer(s), {} diff entries to \
+			 load\n\n| Layer | State group | Added | Removed |\n| --- | --- | --- | --- |\n",
+			current.full, current.depth, current.load
+		);
+
+		for (i, layer) in layers.iter().enumerate() {
+			writeln!(
+				msg,
+				"| {i} | {} | {} | {} |",
+				layer.shortstatehash, layer.added, layer.removed
+			)?;
+		}
+
+		let state_accessor = &self.services.rooms.state_accessor;
+		let shortstatehashes: BTreeSet<ShortStateHash> = self
+			.services
+			.rooms
+			.timeline
+			.pdus_rev(&room_id, None)
+			.ignore_err()
+			.take(events)
+			.filter_map(|(_, pdu)| async move {
+				state_accessor.pdu_shortstatehash(&pdu.event_id).await.ok()
+			})
+			.collect()
+			.await;
+
+		let mut chains = Vec::with_capacity(shortstatehashes.len());
+		for shortstatehash in shortstatehashes {
+			if let Ok(layers) = state_compressor.state_layers(shortstatehash).await {
+				chains.push(StateChain::from_layers(&layers));
+			}
+		}
+
+		if !chains.is_empty() {
+			let max_depth = chains.iter().map(|chain| chain.depth).max().unwrap_or(0);
+			let max_load = chains.iter().map(|chain| chain.load).max().unwrap_or(0);
+			let total_depth: usize = chains.iter().map(|chain| chain.depth).sum();
+
+			writeln!(
+				msg,
+				"\n{} state group(s) of the last {events} event(s): average depth {}, maximum \
+				 depth {max_depth}, at most {max_load} diff entries to load",
+				chains.len(),
+				total_depth.checked_div(chains.len()).unwrap_or(0),
+			)?;
+		}
+
+		self.write_str(&msg).await
+	}
+
+	pub(super) async fn recompress_state(
+		&self,
+		max_depth: usize,
+		max_load_factor: usize,
+	) -> Result {
+		if max_depth == 0 {
+			return Err!("The maximum depth must be at least 1.");
+		}
+
+		let recompressed = self
+			.services
+			.rooms
+			.state_compressor
+			.recompress_state(max_depth, max_load_factor)
+			.await?;
+
+		self.write_str(&format!(
+			"Scanned {} state group(s) and rewrote {} of them as snapshots with {} state \
+			 entries in total.",
+			recompressed.scanned, recompressed.rewritten, recompressed.entries
+		))
+		.await
+	}
 }
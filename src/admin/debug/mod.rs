@@ -4,7 +4,10 @@
 use clap::Subcommand;
 use conduwuit::Result;
 use ruma::{OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId, OwnedServerName};
-use service::rooms::short::{ShortEventId, ShortRoomId};
+use service::rooms::{
+	short::{ShortEventId, ShortRoomId},
+	state_compressor::{DEFAULT_MAX_DEPTH, DEFAULT_MAX_LOAD_FACTOR},
+};
 
 use self::tester::TesterCommand;
 use crate::admin_command_dispatch;
@@ -47,6 +50,22 @@ pub enum DebugCommand {
 		event_id: OwnedEventId,
 	},
 
+	/// Show why an incoming event was rejected, soft-failed or dropped
+	WhyRejected {
+		/// An event ID (a $ followed by the base64 reference hash)
+		event_id: OwnedEventId,
+	},
+
+	/// List the incoming events of a room which were rejected, soft-failed or
+	/// dropped, newest first
+	RejectedEvents {
+		room_id: OwnedRoomOrAliasId,
+
+		/// Maximum number of events to list
+		#[arg(short, long, default_value_t = 50)]
+		limit: usize,
+	},
+
 	/// Retrieve and print a PDU by PduId from the Continuwuity database
 	GetShortPdu {
 		/// Shortroomid integer
@@ -204,6 +223,16 @@ pub enum DebugCommand {
 		no_cache: bool,
 	},
 
+	/// Checks whether a server can be reached over federation
+	///
+	/// Resolves the server name without the cache, checks its TLS certificate,
+	/// `/version` endpoint and signing keys. Checks this server if no server
+	/// name is given, including a signed request to ourselves when
+	/// `federation_loopback` is enabled.
+	FederationReport {
+		server_name: Option<OwnedServerName>,
+	},
+
 	/// Print extended memory usage
 	///
 	/// Optional argument is a character mask (a sequence of characters in any
@@ -264,6 +293,45 @@ pub enum DebugCommand {
 		name: Option<OwnedServerName>,
 	},
 
+	/// Report the rooms which spent the most time in state resolution and the
+	/// most expensive state resolutions since startup
+	///
+	/// Also shows the size of the chain cover index used to compute auth chain
+	/// differences.
+	StateResReport {
+		/// Number of rooms and state resolutions to list
+		#[arg(short, long, default_value_t = 10)]
+		limit: usize,
+	},
+
+	/// Report the depth and size of the diff chains of state groups
+	///
+	/// With a room, shows the layers of its current state and summarizes the
+	/// state groups of its most recent events. Without one, scans every state
+	/// group on the server.
+	StateChains {
+		room_id: Option<OwnedRoomOrAliasId>,
+
+		/// Number of recent events of the room whose state groups are checked
+		#[arg(short, long, default_value_t = 1000)]
+		events: usize,
+	},
+
+	/// Rewrite state groups with deep or oversized diff chains into snapshots
+	/// of their full state
+	///
+	/// State groups keep their IDs, so events keep pointing at the same state.
+	RecompressState {
+		/// Maximum number of layers in the diff chain of a state group
+		#[arg(long, default_value_t = DEFAULT_MAX_DEPTH)]
+		max_depth: usize,
+
+		/// Maximum number of diff entries read to load a state group, as a
+		/// multiple of the size of its state
+		#[arg(long, default_value_t = DEFAULT_MAX_LOAD_FACTOR)]
+		max_load_factor: usize,
+	},
+
 	/// Developer test stubs
 	#[command(subcommand)]
 	#[allow(non_snake_case)]
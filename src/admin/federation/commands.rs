@@ -20,7 +20,7 @@ pub(super) async fn enable_room(&self, room_id: OwnedRoomId) -> Result {
 	}
 
 	pub(super) async fn incoming_federation(&self) -> Result {
-		let msg = {
+		let mut msg = {
 			let map = self
 				.services
 				.rooms
@@ -47,6 +47,22 @@ pub(super) async fn incoming_federation(&self) -> Result {
 			msg
 		};
 
+		let depth = self.services.rooms.inbound_queue.depth();
+		writeln!(
+			msg,
+			"\nQueued PDUs in {} rooms from {} origins:",
+			depth.rooms.len(),
+			depth.origins.len()
+		)?;
+		for (room_id, queued) in &depth.rooms {
+			writeln!(msg, "{room_id}: {queued}")?;
+		}
+
+		writeln!(msg, "\nQueued and in-progress PDUs by origin:")?;
+		for (origin, queued) in &depth.origins {
+			writeln!(msg, "{origin}: {queued}")?;
+		}
+
 		self.write_str(&msg).await
 	}
 
@@ -10,6 +10,9 @@
 #[derive(Debug, Subcommand)]
 pub enum FederationCommand {
 	/// List all rooms we are currently handling an incoming pdu from
+	///
+	/// Also shows the number of PDUs waiting in the inbound queue per room and
+	/// per origin.
 	IncomingFederation,
 
 	/// Disables incoming federation handling for a room.
@@ -19,6 +19,7 @@
 pub(crate) mod oidc;
 pub(crate) mod query;
 pub(crate) mod room;
+pub(crate) mod schedule;
 pub(crate) mod server;
 pub(crate) mod token;
 pub(crate) mod user;
@@ -1,5 +1,7 @@
+use std::fmt::Write;
+
 use clap::Subcommand;
-use conduwuit::Result;
+use conduwuit::{Result, utils::time};
 use conduwuit_database::Deserialized as _;
 use futures::StreamExt;
 use ruma::{OwnedRoomId, OwnedUserId, exports::serde::Serialize};
@@ -29,6 +31,34 @@ pub enum AccountDataCommand {
 		/// Optional room ID of the account data
 		room_id: Option<OwnedRoomId>,
 	},
+
+	/// Lists the recorded changes to a user's account data, newest first.
+	History {
+		/// Full user ID
+		user_id: OwnedUserId,
+		/// Only list changes to this account data type
+		kind: Option<String>,
+		/// Optional room ID of the account data
+		#[arg(long)]
+		room_id: Option<OwnedRoomId>,
+		/// Maximum number of changes to list
+		#[arg(short, long, default_value = "20")]
+		limit: usize,
+	},
+
+	/// Restores the value that a recorded change replaced, such as an
+	/// `m.direct` list clobbered by a client.
+	Restore {
+		/// Full user ID
+		user_id: OwnedUserId,
+		/// Account data event type
+		kind: String,
+		/// Count of the change, as listed by `history`
+		count: u64,
+		/// Optional room ID of the account data
+		#[arg(long)]
+		room_id: Option<OwnedRoomId>,
+	},
 }
 
 impl crate::Context<'_> {
@@ -77,4 +107,64 @@ async fn account_data_get(
 		self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{json}\n```"))
 			.await
 	}
+
+	async fn history(
+		&self,
+		user_id: OwnedUserId,
+		kind: Option<String>,
+		room_id: Option<OwnedRoomId>,
+		limit: usize,
+	) -> Result {
+		let changes: Vec<_> = self
+			.services
+			.account_data
+			.history(room_id.as_deref(), &user_id, kind.as_deref(), limit)
+			.await;
+
+		if changes.is_empty() {
+			return self.write_str("No recorded account data changes.").await;
+		}
+
+		let mut msg = format!(
+			"{} change(s), newest first:\n\n| Count | Time | Type | Device | Hash | Previous \
+			 hash |\n| --- | --- | --- | --- | --- | --- |\n",
+			changes.len()
+		);
+
+		for (kind, count, change) in changes {
+			let time = time::format_millis(change.ts);
+			let device = change
+				.device_id
+				.as_ref()
+				.map_or("server", |device_id| device_id.as_str());
+			let previous_hash = change.previous_hash.as_deref().unwrap_or("none");
+
+			writeln!(
+				msg,
+				"| {count} | {time} | {kind} | {device} | `{}` | `{previous_hash}` |",
+				change.hash
+			)?;
+		}
+
+		self.write_str(&msg).await
+	}
+
+	async fn restore(
+		&self,
+		user_id: OwnedUserId,
+		kind: String,
+		count: u64,
+		room_id: Option<OwnedRoomId>,
+	) -> Result {
+		self.services
+			.account_data
+			.restore(room_id.as_deref(), &user_id, &kind, count)
+			.await?;
+
+		self.write_str(&format!(
+			"Restored the value of {kind} from before change {count}. This restore is recorded \
+			 as a new change and can be undone the same way."
+		))
+		.await
+	}
 }
@@ -40,6 +40,9 @@ pub enum RoomAliasCommand {
 		/// If set, only list the aliases for this room
 		room_id: Option<OwnedRoomId>,
 	},
+
+	/// List local aliases pointing to rooms with no local members
+	Dangling,
 }
 
 pub(super) async fn process(command: RoomAliasCommand, context: &Context<'_>) -> Result {
@@ -102,8 +105,34 @@ pub(super) async fn process(command: RoomAliasCommand, context: &Context<'_>) ->
 							.await
 						{
 							| Err(err) => Err!("Failed to remove alias: {err}"),
-							| Ok(()) =>
-								context.write_str(&format!("Removed alias from {id}")).await,
+							| Ok(()) => {
+								let canonical = services
+									.rooms
+									.alias
+									.remove_from_canonical_alias(&id, &room_alias, server_user)
+									.await;
+
+								match canonical {
+									| Ok(true) =>
+										context
+											.write_str(&format!(
+												"Removed alias from {id} and its canonical \
+												 alias event"
+											))
+											.await,
+									| Ok(false) =>
+										context
+											.write_str(&format!("Removed alias from {id}"))
+											.await,
+									| Err(err) =>
+										context
+											.write_str(&format!(
+												"Removed alias from {id}, but failed to update \
+												 its canonical alias event: {err}"
+											))
+											.await,
+								}
+							},
 						},
 					}
 				},
@@ -113,7 +142,7 @@ pub(super) async fn process(command: RoomAliasCommand, context: &Context<'_>) ->
 						| Ok(id) => context.write_str(&format!("Alias resolves to {id}")).await,
 					}
 				},
-				| RoomAliasCommand::List { .. } => unreachable!(),
+				| RoomAliasCommand::List { .. } | RoomAliasCommand::Dangling => unreachable!(),
 			}
 		},
 		| RoomAliasCommand::List { room_id } =>
@@ -155,5 +184,33 @@ pub(super) async fn process(command: RoomAliasCommand, context: &Context<'_>) ->
 				let plain = format!("Aliases:\n{plain_list}");
 				context.write_str(&plain).await
 			},
+		| RoomAliasCommand::Dangling => {
+			let aliases: Vec<(OwnedRoomId, String)> = services
+				.rooms
+				.alias
+				.dangling_local_aliases()
+				.map(|(room_id, localpart)| (room_id, localpart.into()))
+				.collect()
+				.await;
+
+			if aliases.is_empty() {
+				return context
+					.write_str("No aliases point to rooms without local members.")
+					.await;
+			}
+
+			let server_name = services.globals.server_name();
+			let plain_list =
+				aliases
+					.iter()
+					.fold(String::new(), |mut output, (room_id, localpart)| {
+						writeln!(output, "- `#{localpart}:{server_name}` -> {room_id}")
+							.expect("should be able to write to string buffer");
+						output
+					});
+
+			let plain = format!("Aliases pointing to rooms without local members:\n{plain_list}");
+			context.write_str(&plain).await
+		},
 	}
 }
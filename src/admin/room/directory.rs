@@ -1,3 +1,5 @@
+use std::fmt::Write;
+
 use clap::Subcommand;
 use conduwuit::{Err, Result};
 use futures::StreamExt;
@@ -23,17 +25,62 @@ pub enum RoomDirectoryCommand {
 	List {
 		page: Option<usize>,
 	},
+
+	/// Pin a room to the top of the room directory
+	Pin {
+		/// The room id of the room to pin
+		room_id: OwnedRoomId,
+
+		/// Position of the room among pinned rooms, lowest first
+		#[arg(default_value_t = 0)]
+		position: u32,
+	},
+
+	/// Unpin a room from the top of the room directory
+	Unpin {
+		/// The room id of the room to unpin
+		room_id: OwnedRoomId,
+	},
+
+	/// Set or clear the category of a room in the room directory
+	///
+	/// Categories are matched by room directory searches.
+	SetCategory {
+		/// The room id of the room to categorise
+		room_id: OwnedRoomId,
+
+		/// The category to set, or nothing to clear the category
+		category: Option<String>,
+	},
+
+	/// Hide a published room from the room directory listing
+	///
+	/// The room stays published, so room admins cannot re-list it by
+	/// publishing it again.
+	Hide {
+		/// The room id of the room to hide
+		room_id: OwnedRoomId,
+	},
+
+	/// Show a previously hidden room in the room directory listing again
+	Unhide {
+		/// The room id of the room to show
+		room_id: OwnedRoomId,
+	},
+
+	/// Rebuild the room directory search index for all published rooms
+	Reindex,
 }
 
 pub(super) async fn process(command: RoomDirectoryCommand, context: &Context<'_>) -> Result {
 	let services = context.services;
 	match command {
 		| RoomDirectoryCommand::Publish { room_id } => {
-			services.rooms.directory.set_public(&room_id);
+			services.rooms.directory.set_public(&room_id).await;
 			context.write_str("Room published").await
 		},
 		| RoomDirectoryCommand::Unpublish { room_id } => {
-			services.rooms.directory.set_not_public(&room_id);
+			services.rooms.directory.set_not_public(&room_id).await;
 			context.write_str("Room unpublished").await
 		},
 		| RoomDirectoryCommand::List { page } => {
@@ -43,12 +90,16 @@ pub(super) async fn process(command: RoomDirectoryCommand, context: &Context<'_>
 				.rooms
 				.directory
 				.public_rooms()
-				.then(async |room_id| get_room_info(services, &room_id).await)
+				.then(async |room_id| {
+					let entry = services.rooms.directory.entry(&room_id).await;
+					(get_room_info(services, &room_id).await, entry)
+				})
 				.collect()
 				.await;
 
-			rooms.sort_by_key(|r| r.1);
-			rooms.reverse();
+			rooms.sort_by_key(|((_, members, _), entry)| {
+				(entry.pinned.is_none(), entry.pinned, std::cmp::Reverse(*members))
+			});
 
 			let rooms: Vec<_> = rooms
 				.into_iter()
@@ -62,7 +113,22 @@ pub(super) async fn process(command: RoomDirectoryCommand, context: &Context<'_>
 
 			let body = rooms
 				.iter()
-				.map(|(id, members, name)| format!("{id} | Members: {members} | Name: {name}"))
+				.map(|((id, members, name), entry)| {
+					let mut line = format!("{id} | Members: {members} | Name: {name}");
+					if let Some(position) = entry.pinned {
+						write!(line, " | Pinned: {position}")
+							.expect("should be able to write to string buffer");
+					}
+					if let Some(category) = &entry.category {
+						write!(line, " | Category: {category}")
+							.expect("should be able to write to string buffer");
+					}
+					if entry.hidden {
+						line.push_str(" | Hidden");
+					}
+
+					line
+				})
 				.collect::<Vec<_>>()
 				.join("\n");
 
@@ -70,5 +136,69 @@ pub(super) async fn process(command: RoomDirectoryCommand, context: &Context<'_>
 				.write_str(&format!("Rooms (page {page}):\n```\n{body}\n```"))
 				.await
 		},
+		| RoomDirectoryCommand::Pin { room_id, position } => {
+			services
+				.rooms
+				.directory
+				.update_entry(&room_id, |entry| entry.pinned = Some(position))
+				.await;
+
+			context
+				.write_str(&format!("Pinned {room_id} at position {position}"))
+				.await
+		},
+		| RoomDirectoryCommand::Unpin { room_id } => {
+			services
+				.rooms
+				.directory
+				.update_entry(&room_id, |entry| entry.pinned = None)
+				.await;
+
+			context.write_str("Room unpinned").await
+		},
+		| RoomDirectoryCommand::SetCategory { room_id, category } => {
+			let message = match &category {
+				| Some(category) => format!("Set category of {room_id} to {category:?}"),
+				| None => format!("Cleared category of {room_id}"),
+			};
+
+			services
+				.rooms
+				.directory
+				.update_entry(&room_id, |entry| entry.category = category)
+				.await;
+
+			context.write_str(&message).await
+		},
+		| RoomDirectoryCommand::Hide { room_id } => {
+			services
+				.rooms
+				.directory
+				.update_entry(&room_id, |entry| entry.hidden = true)
+				.await;
+
+			context
+				.write_str("Room hidden from the room directory")
+				.await
+		},
+		| RoomDirectoryCommand::Unhide { room_id } => {
+			services
+				.rooms
+				.directory
+				.update_entry(&room_id, |entry| entry.hidden = false)
+				.await;
+
+			context.write_str("Room shown in the room directory").await
+		},
+		| RoomDirectoryCommand::Reindex => {
+			let rooms: Vec<_> = services.rooms.directory.public_rooms().collect().await;
+			for room_id in &rooms {
+				services.rooms.directory.index_room(room_id).await;
+			}
+
+			context
+				.write_str(&format!("Reindexed {} published rooms", rooms.len()))
+				.await
+		},
 	}
 }
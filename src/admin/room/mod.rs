@@ -3,14 +3,16 @@
 mod directory;
 mod info;
 mod moderation;
+mod state_reset;
+mod upgrade;
 
 use clap::Subcommand;
 use conduwuit::Result;
-use ruma::OwnedRoomId;
+use ruma::{OwnedRoomId, OwnedRoomOrAliasId, OwnedUserId, RoomVersionId};
 
 use self::{
 	alias::RoomAliasCommand, directory::RoomDirectoryCommand, info::RoomInfoCommand,
-	moderation::RoomModerationCommand,
+	moderation::RoomModerationCommand, state_reset::RoomStateResetCommand,
 };
 use crate::admin_command_dispatch;
 
@@ -56,8 +58,40 @@ pub enum RoomCommand {
 	/// Manage the room directory
 	Directory(RoomDirectoryCommand),
 
+	#[command(subcommand)]
+	/// Detect and repair state resets
+	StateReset(RoomStateResetCommand),
+
 	/// Check if we know about a room
 	Exists {
 		room_id: OwnedRoomId,
 	},
+
+	/// Upgrade every room of a space hierarchy to a new room version
+	///
+	/// Rooms are upgraded children first, then the spaces containing them.
+	/// Local aliases, the room directory entry and the transferable state
+	/// events like power levels are moved to each replacement room, spaces are
+	/// pointed at the replacements, and the local members of each room are
+	/// joined to its replacement.
+	UpgradeSpace {
+		/// The space at the top of the hierarchy
+		space_id: OwnedRoomOrAliasId,
+
+		/// The room version to upgrade to
+		version: RoomVersionId,
+
+		/// Local user performing the upgrades, who must be joined to every room
+		/// and allowed to upgrade it. Defaults to the server user.
+		#[arg(long)]
+		user: Option<OwnedUserId>,
+
+		/// Only report which rooms would be upgraded
+		#[arg(long)]
+		dry_run: bool,
+
+		/// Don't join local members to the replacement rooms
+		#[arg(long)]
+		no_join: bool,
+	},
 }
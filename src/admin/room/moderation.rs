@@ -149,7 +149,7 @@ async fn ban_room(&self, room: OwnedRoomOrAliasId) -> Result {
 			})
 			.await;
 
-		self.services.rooms.directory.set_not_public(&room_id); // remove from the room directory
+		self.services.rooms.directory.set_not_public(&room_id).await; // remove from the room directory
 		self.services.rooms.metadata.ban_room(&room_id, true); // prevent further joins
 		self.services.rooms.metadata.disable_room(&room_id, true); // disable federation
 
@@ -320,7 +320,7 @@ async fn ban_list_of_rooms(&self) -> Result {
 
 			self.services.rooms.metadata.ban_room(&room_id, true);
 			// unpublish from room directory, ignore errors
-			self.services.rooms.directory.set_not_public(&room_id);
+			self.services.rooms.directory.set_not_public(&room_id).await;
 			self.services.rooms.metadata.disable_room(&room_id, true);
 		}
 
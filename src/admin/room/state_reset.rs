@@ -0,0 +1,206 @@
+use std::fmt::Write;
+
+use clap::Subcommand;
+use conduwuit::{Err, Result, utils::time};
+use futures::StreamExt;
+use ruma::{OwnedEventId, OwnedRoomOrAliasId, OwnedServerName};
+
+use crate::admin_command_dispatch;
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum RoomStateResetCommand {
+	/// List suspected state resets, newest first
+	#[command(name = "list")]
+	ListStateResets {
+		/// Only list the state resets of this room
+		room_id: Option<OwnedRoomOrAliasId>,
+
+		/// Maximum number of state resets to list
+		#[arg(short, long, default_value_t = 20)]
+		limit: usize,
+	},
+
+	/// Show the memberships and power levels a suspected state reset reverted
+	#[command(name = "show")]
+	ShowStateReset {
+		room_id: OwnedRoomOrAliasId,
+
+		/// The number of the state reset, as shown by `list`
+		count: u64,
+	},
+
+	/// Resolve the room state again together with the state of trusted
+	/// servers
+	///
+	/// Shows the changes the repair would make to the current room state. Run
+	/// again with `--apply` to replace the room state with the repaired one.
+	#[command(name = "repair")]
+	RepairStateReset {
+		room_id: OwnedRoomOrAliasId,
+
+		/// Servers whose view of the room state is trusted
+		#[arg(required = true)]
+		servers: Vec<OwnedServerName>,
+
+		/// Leave our own room state out of the resolution
+		#[arg(long)]
+		exclude_local: bool,
+
+		/// Apply the repair instead of only showing it
+		#[arg(long)]
+		apply: bool,
+	},
+}
+
+impl crate::Context<'_> {
+	async fn list_state_resets(
+		&self,
+		room_id: Option<OwnedRoomOrAliasId>,
+		limit: usize,
+	) -> Result {
+		let room_id = match room_id {
+			| Some(room_id) => Some(self.services.rooms.alias.resolve(&room_id).await?),
+			| None => None,
+		};
+
+		let resets: Vec<_> = self
+			.services
+			.rooms
+			.state_reset
+			.state_resets(room_id.as_deref())
+			.take(limit)
+			.collect()
+			.await;
+
+		if resets.is_empty() {
+			return self.write_str("No state resets were recorded.").await;
+		}
+
+		let mut msg = String::from(
+			"| Room | Count | Time | Event | Regressions | Repaired |\n| --- | --- | --- | --- \
+			 | --- | --- |\n",
+		);
+
+		for (room_id, count, reset) in resets {
+			writeln!(
+				msg,
+				"| {room_id} | {count} | {} | {} | {} | {} |",
+				time::format_millis(reset.ts),
+				reset.event_id,
+				reset.regressions.len(),
+				reset.repaired
+			)?;
+		}
+
+		self.write_str(&msg).await
+	}
+
+	async fn show_state_reset(&self, room_id: OwnedRoomOrAliasId, count: u64) -> Result {
+		let room_id = self.services.rooms.alias.resolve(&room_id).await?;
+		let Ok(reset) = self
+			.services
+			.rooms
+			.state_reset
+			.get_state_reset(&room_id, count)
+			.await
+		else {
+			return Err!("No state reset {count} was recorded in {room_id}.");
+		};
+
+		let previous = reset
+			.previous_shortstatehash
+			.map_or_else(|| "none".to_owned(), |shortstatehash| shortstatehash.to_string());
+
+		let mut msg = format!(
+			"State reset in {room_id} at {} after {}\nState group: {previous} -> {}\nRepaired: \
+			 {}\n\n| Type | State key | Previous | Current | Change |\n| --- | --- | --- | --- \
+			 | --- |\n",
+			time::format_millis(reset.ts),
+			reset.event_id,
+			reset.shortstatehash,
+			reset.repaired
+		);
+
+		for regression in &reset.regressions {
+			let current = regression
+				.current
+				.as_ref()
+				.map_or_else(|| "none".to_owned(), ToString::to_string);
+
+			writeln!(
+				msg,
+				"| {} | {} | {} | {current} | {} |",
+				regression.event_type,
+				regression.state_key,
+				regression.previous,
+				regression.description
+			)?;
+		}
+
+		self.write_str(&msg).await
+	}
+
+	async fn repair_state_reset(
+		&self,
+		room_id: OwnedRoomOrAliasId,
+		servers: Vec<OwnedServerName>,
+		exclude_local: bool,
+		apply: bool,
+	) -> Result {
+		let room_id = self.services.rooms.alias.resolve(&room_id).await?;
+		let state_reset = &self.services.rooms.state_reset;
+		let plan = state_reset
+			.plan_repair(&room_id, &servers, exclude_local)
+			.await?;
+
+		let used: Vec<_> = plan.servers.iter().map(ToString::to_string).collect();
+		let mut msg = format!("Resolved the state of {room_id} with {}.\n", used.join(", "));
+		for (server, error) in &plan.failed {
+			writeln!(msg, "Could not get the state from {server}: {error}")?;
+		}
+
+		if plan.changes.is_empty() {
+			writeln!(msg, "\nThe repaired room state is the same as the current one.")?;
+			return self.write_str(&msg).await;
+		}
+
+		write!(
+			msg,
+			"\n| Type | State key | Before | After | Change |\n| --- | --- | --- | --- | --- |\n"
+		)?;
+
+		for change in &plan.changes {
+			let event = |event_id: Option<&OwnedEventId>| {
+				event_id.map_or_else(|| "none".to_owned(), ToString::to_string)
+			};
+
+			writeln!(
+				msg,
+				"| {} | {} | {} | {} | {} |",
+				change.event_type,
+				change.state_key,
+				event(change.before.as_ref()),
+				event(change.after.as_ref()),
+				change.summary.as_deref().unwrap_or("")
+			)?;
+		}
+
+		if !apply {
+			write!(
+				msg,
+				"\n{} change(s) would be made. Run the command again with `--apply` to replace \
+				 the room state.",
+				plan.changes.len()
+			)?;
+
+			return self.write_str(&msg).await;
+		}
+
+		let changes = plan.changes.len();
+		state_reset.apply_repair(&room_id, plan).await?;
+		write!(msg, "\nApplied {changes} change(s) to the room state.")?;
+
+		self.write_str(&msg).await
+	}
+}
@@ -0,0 +1,337 @@
+use std::fmt::Write;
+
+use api::client::upgrade_room_helper;
+use conduwuit::{Err, Result, matrix::pdu::PartialPdu, utils::ReadyExt, warn};
+use futures::{FutureExt, StreamExt};
+use ruma::{
+	OwnedRoomId, OwnedRoomOrAliasId, OwnedUserId, RoomId, RoomVersionId, UserId,
+	events::{
+		StateEventType, TimelineEventType,
+		room::member::{MembershipState, RoomMemberEventContent},
+		space::child::SpaceChildEventContent,
+	},
+	room::RoomType,
+};
+use serde_json::value::to_raw_value;
+use service::rooms::summary::Accessibility;
+
+/// Outcome of the upgrade of a room of a space hierarchy.
+enum Outcome {
+	Skipped(String),
+	Planned,
+	Upgraded {
+		new_room_id: OwnedRoomId,
+		joined: usize,
+		failed_joins: usize,
+	},
+	Failed(String),
+}
+
+impl crate::Context<'_> {
+	pub(super) async fn upgrade_space(
+		&self,
+		space_id: OwnedRoomOrAliasId,
+		version: RoomVersionId,
+		user_id: Option<OwnedUserId>,
+		dry_run: bool,
+		no_join: bool,
+	) -> Result {
+		let space_id = self.services.rooms.alias.resolve(&space_id).await?;
+		let sender_user = user_id.unwrap_or_else(|| self.services.globals.server_user.clone());
+		if !self.services.globals.user_is_local(&sender_user) {
+			return Err!("{sender_user} is not a local user.");
+		}
+
+		let Accessibility::Accessible(hierarchy) = self
+			.services
+			.rooms
+			.summary
+			.get_room_hierarchy_for_user(&sender_user, space_id.clone(), None, false)
+			.await?
+		else {
+			return Err!("{sender_user} cannot see the room hierarchy of {space_id}.");
+		};
+
+		// Spaces come before their children in the hierarchy. Walking it backwards
+		// upgrades the children first, so the new room IDs are already listed in
+		// the spaces by the time those are upgraded and their children copied.
+		let rooms: Vec<(OwnedRoomId, bool)> = hierarchy
+			.into_iter()
+			.rev()
+			.map(|chunk| {
+				let is_space = chunk.summary.room_type == Some(RoomType::Space);
+				(chunk.summary.room_id, is_space)
+			})
+			.collect();
+
+		let spaces: Vec<OwnedRoomId> = rooms
+			.iter()
+			.filter(|(_, is_space)| *is_space)
+			.map(|(room_id, _)| room_id.clone())
+			.collect();
+
+		let mut report = Vec::with_capacity(rooms.len());
+		for (room_id, _) in &rooms {
+			let current_version = self
+				.services
+				.rooms
+				.state
+				.get_room_version(room_id)
+				.await
+				.map_or_else(|_| "unknown".to_owned(), |version| version.to_string());
+
+			if let Some(reason) = self
+				.upgrade_skip_reason(room_id, &version, &sender_user)
+				.await
+			{
+				report.push((room_id.clone(), current_version, Outcome::Skipped(reason)));
+				continue;
+			}
+
+			if dry_run {
+				report.push((room_id.clone(), current_version, Outcome::Planned));
+				continue;
+			}
+
+			let local_members: Vec<OwnedUserId> = self
+				.services
+				.rooms
+				.state_cache
+				.room_members(room_id)
+				.ready_filter(|user_id| {
+					self.services.globals.user_is_local(user_id) && *user_id != sender_user
+				})
+				.collect()
+				.await;
+
+			let new_room_id =
+				match upgrade_room_helper(self.services, &sender_user, room_id, &version, &[])
+					.boxed()
+					.await
+				{
+					| Ok(new_room_id) => new_room_id,
+					| Err(e) => {
+						warn!(%room_id, "Failed to upgrade room: {e}");
+						report.push((
+							room_id.clone(),
+							current_version,
+							Outcome::Failed(e.to_string()),
+						));
+						continue;
+					},
+				};
+
+			self.remove_replaced_child(&sender_user, room_id, &new_room_id, &spaces)
+				.await;
+
+			let (joined, failed_joins) = if no_join {
+				(0, 0)
+			} else {
+				self.join_local_members(&sender_user, &new_room_id, &local_members)
+					.await
+			};
+
+			report.push((room_id.clone(), current_version, Outcome::Upgraded {
+				new_room_id,
+				joined,
+				failed_joins,
+			}));
+		}
+
+		let mut msg = format!(
+			"{} {} room(s) of {space_id} to version {version}:\n\n| Room | Version | Result \
+			 |\n| --- | --- | --- |\n",
+			if dry_run { "Would upgrade" } else { "Upgraded" },
+			report
+				.iter()
+				.filter(|(.., outcome)| {
+					matches!(outcome, Outcome::Planned | Outcome::Upgraded { .. })
+				})
+				.count(),
+		);
+
+		for (room_id, current_version, outcome) in &report {
+			let result = match outcome {
+				| Outcome::Skipped(reason) => format!("skipped: {reason}"),
+				| Outcome::Planned => "will be upgraded".to_owned(),
+				| Outcome::Upgraded { new_room_id, joined, failed_joins } => format!(
+					"replaced by {new_room_id}, {joined} local member(s) joined, {failed_joins} \
+					 failed"
+				),
+				| Outcome::Failed(error) => format!("failed: {error}"),
+			};
+
+			writeln!(msg, "| {room_id} | {current_version} | {result} |")?;
+		}
+
+		self.write_str(&msg).await
+	}
+
+	async fn upgrade_skip_reason(
+		&self,
+		room_id: &RoomId,
+		version: &RoomVersionId,
+		sender_user: &UserId,
+	) -> Option<String> {
+		if self.services.admin.is_admin_room(room_id).await {
+			return Some("admin room".to_owned());
+		}
+
+		if !self
+			.services
+			.rooms
+			.state_cache
+			.is_joined(sender_user, room_id)
+			.await
+		{
+			return Some(format!("{sender_user} is not joined"));
+		}
+
+		if self
+			.services
+			.rooms
+			.state
+			.get_room_version(room_id)
+			.await
+			.is_ok_and(|current| current == *version)
+		{
+			return Some("already on the target version".to_owned());
+		}
+
+		if self
+			.services
+			.rooms
+			.state_accessor
+			.room_state_get(room_id, &StateEventType::RoomTombstone, "")
+			.await
+			.is_ok()
+		{
+			return Some("already replaced".to_owned());
+		}
+
+		None
+	}
+
+	/// Removes the upgraded room from the spaces which now list its
+	/// replacement, so that they don't keep pointing at the tombstoned room.
+	async fn remove_replaced_child(
+		&self,
+		sender_user: &UserId,
+		old_room_id: &RoomId,
+		new_room_id: &RoomId,
+		spaces: &[OwnedRoomId],
+	) {
+		let state_accessor = &self.services.rooms.state_accessor;
+		for space_id in spaces {
+			let lists_replacement = state_accessor
+				.room_state_get_content::<SpaceChildEventContent>(
+					space_id,
+					&StateEventType::SpaceChild,
+					new_room_id.as_str(),
+				)
+				.await
+				.is_ok();
+
+			let lists_old_room = state_accessor
+				.room_state_get_content::<SpaceChildEventContent>(
+					space_id,
+					&StateEventType::SpaceChild,
+					old_room_id.as_str(),
+				)
+				.await
+				.is_ok_and(|child| !child.via.is_empty());
+
+			if !lists_replacement || !lists_old_room {
+				continue;
+			}
+
+			let state_lock = self.services.rooms.state.mutex.lock(space_id).await;
+			if let Err(e) = self
+				.services
+				.rooms
+				.timeline
+				.build_and_append_pdu(
+					PartialPdu {
+						event_type: TimelineEventType::SpaceChild,
+						content: to_raw_value(&serde_json::json!({}))
+							.expect("empty object is valid json"),
+						state_key: Some(old_room_id.as_str().into()),
+						..Default::default()
+					},
+					sender_user,
+					Some(space_id),
+					&state_lock,
+				)
+				.boxed()
+				.await
+			{
+				warn!(%space_id, %old_room_id, "Failed to remove upgraded room from space: {e}");
+			}
+		}
+	}
+
+	/// Joins the local members of an upgraded room to its replacement,
+	/// inviting them first where the join rules require it. Returns the number
+	/// of members who joined and who couldn't.
+	async fn join_local_members(
+		&self,
+		sender_user: &UserId,
+		room_id: &RoomId,
+		members: &[OwnedUserId],
+	) -> (usize, usize) {
+		let membership = &self.services.rooms.membership;
+		let (mut joined, mut failed) = (0_usize, 0_usize);
+		for user_id in members {
+			if !self.services.users.status(user_id).await.is_active() {
+				continue;
+			}
+
+			if membership
+				.join_room(user_id, room_id, None, &[])
+				.boxed()
+				.await
+				.is_ok()
+			{
+				joined = joined.saturating_add(1);
+				continue;
+			}
+
+			let state_lock = self.services.rooms.state.mutex.lock(room_id).await;
+			let invited = self
+				.services
+				.rooms
+				.timeline
+				.build_and_append_pdu(
+					PartialPdu::state(
+						user_id.as_str(),
+						&RoomMemberEventContent::new(MembershipState::Invite),
+					),
+					sender_user,
+					Some(room_id),
+					&state_lock,
+				)
+				.boxed()
+				.await;
+
+			drop(state_lock);
+			let result = match invited {
+				| Ok(_) => membership
+					.join_room(user_id, room_id, None, &[])
+					.boxed()
+					.await
+					.map(|_| ()),
+				| Err(e) => Err(e),
+			};
+
+			match result {
+				| Ok(()) => joined = joined.saturating_add(1),
+				| Err(e) => {
+					warn!(%user_id, %room_id, "Failed to join upgraded room: {e}");
+					failed = failed.saturating_add(1);
+				},
+			}
+		}
+
+		(joined, failed)
+	}
+}
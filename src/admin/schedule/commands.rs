@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use conduwuit::{Err, Result, utils::time};
+use futures::StreamExt;
+use service::scheduler;
+
+impl crate::Context<'_> {
+	pub(super) async fn add(&self, quiet: bool, args: Vec<String>) -> Result {
+		let (schedule, command) = split_schedule(&args)?;
+		let id = self.services.scheduler.add(
+			&schedule,
+			command,
+			quiet,
+			self.sender.map(ToOwned::to_owned),
+		)?;
+
+		let job = self.services.scheduler.get(&id).await?;
+		let next = scheduler::Service::next_run(&job)
+			.map_or_else(|| "never".to_owned(), time::format_utc);
+
+		self.write_str(&format!("Scheduled command `{id}`. It will next run at {next}."))
+			.await
+	}
+
+	pub(super) async fn list(&self) -> Result {
+		let jobs: Vec<_> = self.services.scheduler.jobs().collect().await;
+		if jobs.is_empty() {
+			return self.write_str("No commands are scheduled.").await;
+		}
+
+		let mut out = String::from(
+			"| ID | Schedule | Command | Next run | Last run |\n| --- | --- | --- | --- | --- \
+			 |\n",
+		);
+
+		for (id, job) in &jobs {
+			let next = if job.paused {
+				"paused".to_owned()
+			} else {
+				scheduler::Service::next_run(job)
+					.map_or_else(|| "never".to_owned(), time::format_utc)
+			};
+
+			let last = match (job.last_run, job.last_succeeded) {
+				| (None, _) => "never".to_owned(),
+				| (Some(last_run), succeeded) => {
+					let last_run = time::format_millis(last_run);
+					match succeeded {
+						| Some(false) => format!("{last_run} (failed)"),
+						| _ => last_run,
+					}
+				},
+			};
+
+			writeln!(
+				out,
+				"| `{id}` | `{}` | `{}` | {next} | {last} |",
+				job.schedule, job.command
+			)?;
+		}
+
+		self.write_str(&out).await
+	}
+
+	pub(super) async fn pause(&self, id: String) -> Result {
+		self.services.scheduler.set_paused(&id, true).await?;
+		self.write_str(&format!("Paused scheduled command `{id}`."))
+			.await
+	}
+
+	pub(super) async fn resume(&self, id: String) -> Result {
+		self.services.scheduler.set_paused(&id, false).await?;
+		self.write_str(&format!("Resumed scheduled command `{id}`."))
+			.await
+	}
+
+	pub(super) async fn remove(&self, id: String) -> Result {
+		self.services.scheduler.remove(&id).await?;
+		self.write_str(&format!("Removed scheduled command `{id}`."))
+			.await
+	}
+}
+
+/// Splits the arguments of `schedule add` into the schedule and the command.
+/// Admin commands are split on whitespace without regard for quotes, so a
+/// quoted schedule arrives as several arguments.
+fn split_schedule(args: &[String]) -> Result<(String, String)> {
+	let Some(first) = args.first() else {
+		return Err!("Missing schedule.");
+	};
+
+	let len = if first.starts_with('@') {
+		1
+	} else if first.starts_with('"') {
+		args.iter()
+			.position(|arg| arg.ends_with('"') && arg.len() > 1)
+			.or_else(|| (first.len() > 1 && first.ends_with('"')).then_some(0))
+			.map(|end| end.saturating_add(1))
+			.ok_or_else(|| conduwuit::err!("Unterminated quoted schedule."))?
+	} else {
+		5
+	};
+
+	if args.len() <= len {
+		return Err!("Missing command to schedule.");
+	}
+
+	let schedule = args[..len].join(" ").trim_matches('"').to_owned();
+	let command = args[len..].join(" ");
+
+	Ok((schedule, command))
+}
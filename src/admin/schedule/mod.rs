@@ -0,0 +1,51 @@
+mod commands;
+
+use clap::Subcommand;
+use conduwuit::Result;
+
+use crate::admin_command_dispatch;
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum ScheduleCommand {
+	/// Schedule an admin command to run periodically
+	///
+	/// The schedule is a quoted cron expression (minute, hour, day of month,
+	/// month and day of week, in UTC) or a shorthand such as `@daily`,
+	/// followed by the command to run without the `!admin` prefix. For
+	/// example:
+	///
+	/// `!admin schedule add "0 3 * * *" media delete-past-remote-media 30d`
+	///
+	/// The output of each run is posted to the admin room.
+	Add {
+		/// Only post the output to the admin room when the command fails
+		#[arg(short, long)]
+		quiet: bool,
+
+		/// The schedule followed by the command
+		#[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+		args: Vec<String>,
+	},
+
+	/// List scheduled commands
+	List,
+
+	/// Pause a scheduled command
+	Pause {
+		/// The ID of the scheduled command
+		id: String,
+	},
+
+	/// Resume a paused scheduled command
+	Resume {
+		/// The ID of the scheduled command
+		id: String,
+	},
+
+	/// Remove a scheduled command
+	Remove {
+		/// The ID of the scheduled command
+		id: String,
+	},
+}
@@ -0,0 +1,122 @@
+use std::fmt::Write;
+
+use clap::{Args, Subcommand};
+use conduwuit::{
+	Err, Result,
+	log::{Level, history},
+	utils::time,
+};
+use ruma::{OwnedRoomId, OwnedUserId};
+
+use crate::admin_command_dispatch;
+
+/// Upper bound on the number of log events printed by a single command.
+const MAX_LINES: usize = 500;
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum LogsCommand {
+	/// Show the most recent log events
+	Tail {
+		/// Number of events to show
+		#[arg(short = 'n', long, default_value_t = 50)]
+		lines: usize,
+
+		#[command(flatten)]
+		filter: LogFilter,
+	},
+
+	/// Search recent log events for text in their message or fields
+	Search {
+		/// Case-insensitive text to search for
+		query: String,
+
+		/// Maximum number of events to show
+		#[arg(short = 'n', long, default_value_t = 50)]
+		lines: usize,
+
+		#[command(flatten)]
+		filter: LogFilter,
+	},
+}
+
+#[derive(Debug, Args)]
+pub struct LogFilter {
+	/// Least severe level to show (error, warn, info, debug or trace)
+	#[arg(short, long)]
+	level: Option<Level>,
+
+	/// Only show events whose target (module path) starts with this
+	#[arg(short, long)]
+	target: Option<String>,
+
+	/// Only show events which occurred within a span of this name
+	#[arg(short, long)]
+	span: Option<String>,
+
+	/// Only show events about this room
+	#[arg(short, long)]
+	room: Option<OwnedRoomId>,
+
+	/// Only show events about this user
+	#[arg(short, long)]
+	user: Option<OwnedUserId>,
+}
+
+impl LogFilter {
+	fn into_filter(self, text: Option<String>) -> history::Filter {
+		history::Filter {
+			level: self.level,
+			target: self.target,
+			span: self.span,
+			room_id: self.room.map(Into::into),
+			user_id: self.user.map(Into::into),
+			text,
+		}
+	}
+}
+
+impl crate::Context<'_> {
+	async fn tail(&self, lines: usize, filter: LogFilter) -> Result {
+		self.print_logs(&filter.into_filter(None), lines).await
+	}
+
+	async fn search(&self, query: String, lines: usize, filter: LogFilter) -> Result {
+		self.print_logs(&filter.into_filter(Some(query)), lines)
+			.await
+	}
+
+	async fn print_logs(&self, filter: &history::Filter, lines: usize) -> Result {
+		self.bail_restricted()?;
+
+		let history = &self.services.server.log.history;
+		if !history.is_enabled() {
+			return Err!("The log history is disabled. Set `log_history_capacity` to enable it.");
+		}
+
+		let records = history.search(filter, lines.min(MAX_LINES));
+		if records.is_empty() {
+			return self.write_str("No matching log events.").await;
+		}
+
+		let mut out = String::new();
+		for record in &records {
+			write!(
+				out,
+				"{} {:>5} {}: {}",
+				time::format(record.timestamp, "%Y-%m-%d %H:%M:%S%.3f"),
+				record.level,
+				record.target,
+				record.message,
+			)?;
+
+			for (key, value) in &record.fields {
+				write!(out, " {key}={value}")?;
+			}
+
+			out.push('\n');
+		}
+
+		self.write_str(&format!("```\n{out}```")).await
+	}
+}
@@ -1,10 +1,12 @@
 mod commands;
+mod logs;
 
 use std::path::PathBuf;
 
 use clap::Subcommand;
 use conduwuit::Result;
 
+use self::logs::LogsCommand;
 use crate::admin_command_dispatch;
 
 #[admin_command_dispatch]
@@ -58,4 +60,8 @@ pub enum ServerCommand {
 
 	/// Build information
 	BuildInfo,
+
+	/// Query recent log events
+	#[command(subcommand)]
+	Logs(LogsCommand),
 }
@@ -1,10 +1,13 @@
-use std::collections::{BTreeMap, HashSet};
+use std::{
+	collections::{BTreeMap, HashSet},
+	fmt::Write,
+};
 
 use api::client::{
 	full_user_deactivate, leave_room, recreate_push_rules_and_return, remote_leave_room,
 };
 use conduwuit::{
-	Err, Result, debug_warn, info,
+	Err, Result, debug_warn, err, info,
 	matrix::{Event, pdu::PartialPdu},
 	utils::{self, ReadyExt},
 	warn,
@@ -19,6 +22,7 @@
 		room::{power_levels::RoomPowerLevelsEventContent, redaction::RoomRedactionEventContent},
 		tag::{TagEvent, TagEventContent, TagInfo},
 	},
+	push::Ruleset,
 };
 use service::users::{AccountStatus, DeviceToken, HashedPassword};
 
@@ -1144,4 +1148,154 @@ pub(super) async fn reset_push_rules(&self, user_id: String) -> Result {
 		self.write_str("Reset user's push rules to the server default.")
 			.await
 	}
+
+	pub(super) async fn set_default_push_rules(&self) -> Result {
+		if self.body.len() < 2
+			|| !self.body[0].trim().starts_with("```")
+			|| self.body.last().unwrap_or(&"").trim() != "```"
+		{
+			return Err!("Expected code block in command body. Add --help for details.");
+		}
+
+		let string = self.body[1..self.body.len().saturating_sub(1)].join("\n");
+		let ruleset: Ruleset = serde_json::from_str(&string)
+			.map_err(|e| err!("Invalid push ruleset in command body: {e}"))?;
+
+		self.services.pusher.set_default_push_rules(&ruleset)?;
+		self.write_str(
+			"Set the default push rules. New accounts will start out with them; use \
+			 `apply-default-push-rules` for existing accounts.",
+		)
+		.await
+	}
+
+	pub(super) async fn show_default_push_rules(&self) -> Result {
+		let Ok(ruleset) = self.services.pusher.get_default_push_rules().await else {
+			return self.write_str("No default push rules are set.").await;
+		};
+
+		let json = serde_json::to_string_pretty(&ruleset)?;
+		self.write_str(&format!("```json\n{json}\n```")).await
+	}
+
+	pub(super) async fn clear_default_push_rules(&self) -> Result {
+		self.services.pusher.clear_default_push_rules();
+		self.write_str("Removed the default push rules.").await
+	}
+
+	pub(super) async fn apply_default_push_rules(
+		&self,
+		user_id: Option<String>,
+		all: bool,
+	) -> Result {
+		let user_ids = match (user_id, all) {
+			| (Some(user_id), _) =>
+				vec![parse_active_local_user_id(self.services, &user_id).await?],
+			| (None, true) =>
+				self.services
+					.users
+					.stream_local_users()
+					.filter_map(|user_id| async move {
+						self.services
+							.users
+							.status(&user_id)
+							.await
+							.is_active()
+							.then_some(user_id)
+					})
+					.collect()
+					.await,
+			| (None, false) => return Err!("Specify a user or pass --all."),
+		};
+
+		let mut applied: usize = 0;
+		let mut failed: usize = 0;
+		for user_id in &user_ids {
+			match self.services.pusher.apply_default_push_rules(user_id).await {
+				| Ok(()) => applied = applied.saturating_add(1),
+				| Err(e) => {
+					warn!("Failed to apply the default push rules to {user_id}: {e}");
+					failed = failed.saturating_add(1);
+				},
+			}
+		}
+
+		self.write_str(&format!(
+			"Applied the default push rules to {applied} account(s), {failed} failed."
+		))
+		.await
+	}
+
+	pub(super) async fn dehydrated_device(&self, user_id: String) -> Result {
+		let user_id = parse_local_user_id(self.services, &user_id)?;
+		let users = &self.services.users;
+
+		let Ok(device_id) = users.get_dehydrated_device_id(&user_id).await else {
+			return self
+				.write_str(&format!("{user_id} has no dehydrated device."))
+				.await;
+		};
+
+		let device = users.get_device_metadata(&user_id, &device_id).await.ok();
+		let display_name = device
+			.as_ref()
+			.and_then(|device| device.display_name.as_deref())
+			.unwrap_or("none");
+		let created = device
+			.as_ref()
+			.and_then(|device| device.last_seen_ts)
+			.map_or_else(
+				|| "unknown".to_owned(),
+				|ts| utils::time::format_millis(ts.get().into()),
+			);
+
+		let backlog = users.count_to_device_events(&user_id, &device_id).await;
+		let limit = match self
+			.services
+			.server
+			.config
+			.dehydrated_device_max_to_device_events
+		{
+			| 0 => "unlimited".to_owned(),
+			| limit => limit.to_string(),
+		};
+
+		let one_time_keys = users.count_one_time_keys(&user_id, &device_id).await;
+		let fallback_keys = users
+			.list_unused_fallback_key_types(&user_id, &device_id)
+			.await;
+
+		let mut msg = format!(
+			"Dehydrated device of {user_id}:\n```\nDevice ID: {device_id}\nDisplay name: \
+			 {display_name}\nCreated: {created}\nTo-device events: {backlog} (limit: {limit})\n"
+		);
+
+		writeln!(msg, "One-time keys: {one_time_keys:?}")?;
+		writeln!(msg, "Unused fallback keys: {fallback_keys:?}")?;
+		msg.push_str("```");
+
+		self.write_str(&msg).await
+	}
+
+	pub(super) async fn dehydrated_devices(&self) -> Result {
+		let users = &self.services.users;
+		let (devices, backlog) = users
+			.users_with_dehydrated_device()
+			.fold((0_usize, 0_usize), |(devices, backlog), user_id| async move {
+				let events = match users.get_dehydrated_device_id(&user_id).await {
+					| Ok(device_id) => users.count_to_device_events(&user_id, &device_id).await,
+					| Err(_) => 0,
+				};
+
+				(devices.saturating_add(1), backlog.saturating_add(events))
+			})
+			.await;
+
+		let pruned = users.dehydrated_device_events_pruned();
+		self.write_str(&format!(
+			"{devices} dehydrated device(s) with {backlog} to-device event(s) waiting. {pruned} \
+			 event(s) pruned since startup."
+		))
+		.await
+	}
 }
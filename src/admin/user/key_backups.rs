@@ -0,0 +1,202 @@
+use std::fmt::Write;
+
+use clap::Subcommand;
+use conduwuit::{Err, Result, err, utils::time};
+use futures::StreamExt;
+use ruma::UserId;
+
+use crate::{admin_command_dispatch, utils::parse_local_user_id};
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum UserKeyBackupsCommand {
+	/// List the key backup versions of a user, with their algorithm, session
+	/// count and last upload time
+	#[command(name = "list")]
+	ListKeyBackups {
+		user_id: String,
+	},
+
+	/// Count the backed up sessions of each room in a backup version
+	#[command(name = "rooms")]
+	KeyBackupRooms {
+		user_id: String,
+
+		/// The backup version, defaults to the latest one
+		version: Option<String>,
+	},
+
+	/// Check a backup version for sessions that are corrupted or don't match
+	/// the backup's algorithm
+	#[command(name = "check")]
+	CheckKeyBackup {
+		user_id: String,
+
+		/// The backup version, defaults to the latest one
+		version: Option<String>,
+	},
+
+	/// Delete every backup version of a user except the latest one
+	///
+	/// Clients only use the latest version, but sessions only present in older
+	/// versions are lost.
+	///
+	/// Requires the `--yes-i-want-to-do-this` flag.
+	#[command(name = "purge-stale")]
+	PurgeStaleKeyBackups {
+		user_id: String,
+
+		#[arg(long)]
+		yes_i_want_to_do_this: bool,
+	},
+}
+
+impl crate::Context<'_> {
+	async fn list_key_backups(&self, user_id: String) -> Result {
+		let user_id = parse_local_user_id(self.services, &user_id)?;
+		let key_backups = &self.services.key_backups;
+
+		let versions: Vec<_> = key_backups.backup_versions(&user_id).collect().await;
+		if versions.is_empty() {
+			return self
+				.write_str(&format!("{user_id} has no key backups."))
+				.await;
+		}
+
+		let latest = key_backups.get_latest_backup_version(&user_id).await.ok();
+
+		let mut msg = format!(
+			"{} key backup version(s) of {user_id}:\n\n| Version | Algorithm | Sessions | Etag \
+			 | Last upload |\n| --- | --- | --- | --- | --- |\n",
+			versions.len()
+		);
+
+		for (version, algorithm) in versions {
+			let algorithm: String = algorithm
+				.get_field("algorithm")
+				.ok()
+				.flatten()
+				.unwrap_or_else(|| "invalid".to_owned());
+			let sessions = key_backups.count_keys(&user_id, &version).await;
+			let etag = key_backups.get_etag(&user_id, &version).await;
+			let last_upload = key_backups
+				.last_upload(&user_id, &version)
+				.await
+				.map_or_else(|_| "unknown".to_owned(), time::format_millis);
+			let latest = if latest.as_ref() == Some(&version) {
+				" (latest)"
+			} else {
+				""
+			};
+
+			writeln!(
+				msg,
+				"| {version}{latest} | {algorithm} | {sessions} | {etag} | {last_upload} |"
+			)?;
+		}
+
+		self.write_str(&msg).await
+	}
+
+	async fn key_backup_rooms(&self, user_id: String, version: Option<String>) -> Result {
+		let user_id = parse_local_user_id(self.services, &user_id)?;
+		let version = self.key_backup_version(&user_id, version).await?;
+
+		let rooms = self
+			.services
+			.key_backups
+			.count_keys_per_room(&user_id, &version)
+			.await;
+
+		let total: usize = rooms.values().sum();
+		let mut msg = format!(
+			"{total} session(s) in {} room(s) in backup version {version}:\n```\n",
+			rooms.len()
+		);
+
+		for (room_id, count) in rooms {
+			writeln!(msg, "{room_id}\t{count}")?;
+		}
+
+		msg.push_str("```");
+		self.write_str(&msg).await
+	}
+
+	async fn check_key_backup(&self, user_id: String, version: Option<String>) -> Result {
+		let user_id = parse_local_user_id(self.services, &user_id)?;
+		let version = self.key_backup_version(&user_id, version).await?;
+
+		let key_backups = &self.services.key_backups;
+		let sessions = key_backups.count_keys(&user_id, &version).await;
+		let bad = key_backups.check_backup(&user_id, &version).await?;
+		if bad.is_empty() {
+			return self
+				.write_str(&format!(
+					"All {sessions} session(s) in backup version {version} look valid."
+				))
+				.await;
+		}
+
+		let mut msg = format!(
+			"{} of {sessions} session(s) in backup version {version} are broken:\n```\n",
+			bad.len()
+		);
+
+		for session in bad {
+			writeln!(msg, "{} {}: {}", session.room_id, session.session_id, session.reason)?;
+		}
+
+		msg.push_str("```");
+		self.write_str(&msg).await
+	}
+
+	async fn purge_stale_key_backups(
+		&self,
+		user_id: String,
+		yes_i_want_to_do_this: bool,
+	) -> Result {
+		if !yes_i_want_to_do_this {
+			return Err!(
+				"You must pass the --yes-i-want-to-do-this flag to ensure you really want to \
+				 delete all but the latest key backup version.",
+			);
+		}
+
+		let user_id = parse_local_user_id(self.services, &user_id)?;
+		let deleted = self
+			.services
+			.key_backups
+			.purge_stale_backups(&user_id)
+			.await?;
+
+		self.write_str(&format!(
+			"Deleted {} stale key backup version(s) of {user_id}: {}",
+			deleted.len(),
+			deleted.join(", ")
+		))
+		.await
+	}
+
+	async fn key_backup_version(
+		&self,
+		user_id: &UserId,
+		version: Option<String>,
+	) -> Result<String> {
+		match version {
+			| Some(version) => {
+				self.services
+					.key_backups
+					.get_backup(user_id, &version)
+					.await
+					.map_err(|_| err!("Backup version {version} not found."))?;
+
+				Ok(version)
+			},
+			| None =>
+				self.services
+					.key_backups
+					.get_latest_backup_version(user_id)
+					.await,
+		}
+	}
+}
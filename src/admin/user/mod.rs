@@ -1,9 +1,11 @@
 mod commands;
+mod key_backups;
 
 use clap::Subcommand;
 use conduwuit::Result;
 use ruma::{OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId};
 
+use self::key_backups::UserKeyBackupsCommand;
 use crate::admin_command_dispatch;
 
 #[admin_command_dispatch]
@@ -282,9 +284,51 @@ pub enum UserCommand {
 		yes_i_want_to_do_this: bool,
 	},
 
+	/// Shows a user's dehydrated device, its one-time keys and the number of
+	/// to-device events waiting for it
+	DehydratedDevice {
+		user_id: String,
+	},
+
+	/// Shows the number of dehydrated devices on the server and the to-device
+	/// events waiting for them
+	DehydratedDevices,
+
+	#[command(subcommand)]
+	/// Inspect and maintain the server-side key backups of local users
+	KeyBackups(UserKeyBackupsCommand),
+
 	/// Resets the push-rules (notification settings) of the target user to the
 	/// server defaults.
 	ResetPushRules {
 		user_id: String,
 	},
+
+	/// Sets the server's default push rules, which new accounts start out
+	/// with.
+	///
+	/// Specify a codeblock containing a push ruleset in the format of
+	/// `GET /_matrix/client/v3/pushrules/global/`. Custom rules are placed
+	/// before the user's own rules of the same kind, in the order given. Rules
+	/// with a server-default ID, such as `.m.rule.is_room_mention`, change the
+	/// enabled state and actions of that rule.
+	SetDefaultPushRules,
+
+	/// Shows the server's default push rules.
+	ShowDefaultPushRules,
+
+	/// Removes the server's default push rules. Accounts they were applied to
+	/// keep them.
+	ClearDefaultPushRules,
+
+	/// Applies the server's default push rules to existing accounts, keeping
+	/// the rest of their rules.
+	ApplyDefaultPushRules {
+		/// The user to apply the default push rules to
+		user_id: Option<String>,
+
+		/// Apply the default push rules to every local user
+		#[arg(long, conflicts_with = "user_id")]
+		all: bool,
+	},
 }
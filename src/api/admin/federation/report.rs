@@ -0,0 +1,32 @@
+use axum::extract::State;
+use conduwuit::{Err, Result};
+use ruminuwuity::admin::continuwuity::federation::report;
+use serde_json::value::to_raw_value;
+
+use crate::Ruma;
+
+/// # `GET /_continuwuity/admin/federation/report`
+///
+/// Checks whether a server can be reached over federation and returns the
+/// result of each check.
+pub(crate) async fn connectivity_report(
+	State(services): State<crate::State>,
+	body: Ruma<report::v1::Request>,
+) -> Result<report::v1::Response> {
+	let sender_user = body.identity.expect_sender_user()?;
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let server_name = body
+		.server_name
+		.clone()
+		.unwrap_or_else(|| services.server.name.clone());
+
+	let report = services
+		.federation
+		.connectivity_report(&server_name)
+		.await?;
+
+	Ok(report::v1::Response::new(to_raw_value(&report)?))
+}
@@ -0,0 +1,36 @@
+use axum::extract::State;
+use conduwuit::{Err, Result};
+use ruminuwuity::admin::continuwuity::federation::request;
+use serde_json::value::to_raw_value;
+use service::federation::SignedGet;
+
+use crate::Ruma;
+
+/// # `GET /_continuwuity/admin/federation/request`
+///
+/// Performs a signed federation request to another server and returns its
+/// response as JSON. Only the endpoints allowed by
+/// `federation_request_endpoints` can be queried.
+pub(crate) async fn signed_request(
+	State(services): State<crate::State>,
+	body: Ruma<request::v1::Request>,
+) -> Result<request::v1::Response> {
+	let sender_user = body.identity.expect_sender_user()?;
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let request = SignedGet {
+		destination: body.destination.clone(),
+		endpoint: body.endpoint,
+		room_id: body.room_id.clone(),
+		event_id: body.event_id.clone(),
+	};
+
+	let response = services
+		.federation
+		.signed_get(sender_user, &request)
+		.await?;
+
+	Ok(request::v1::Response::new(to_raw_value(&response)?))
+}
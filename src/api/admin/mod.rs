@@ -1 +1,3 @@
+pub mod federation;
 pub mod rooms;
+pub mod server;
@@ -72,7 +72,7 @@ pub(crate) async fn ban_room(
 				.await?;
 		}
 
-		services.rooms.directory.set_not_public(&body.room_id); // remove from the room directory
+		services.rooms.directory.set_not_public(&body.room_id).await; // remove from the room directory
 		services.rooms.metadata.ban_room(&body.room_id, true); // prevent further joins
 		services.rooms.metadata.disable_room(&body.room_id, true); // disable federation
 
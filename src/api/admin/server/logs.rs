@@ -0,0 +1,121 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use axum::{
+	body::Body,
+	extract::State,
+	http::header,
+	response::{IntoResponse, Response},
+};
+use conduwuit::{
+	Err, Result, err,
+	log::{
+		Level,
+		history::{Filter, Record},
+	},
+	utils::time,
+};
+use futures::{StreamExt, stream};
+use ruminuwuity::admin::continuwuity::server::logs;
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+
+use crate::Ruma;
+
+/// Interval after which an empty line is sent to keep an idle stream open.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Upper bound on the number of past log events sent before following.
+const MAX_BACKLOG: usize = 10_000;
+
+type FollowState = (Receiver<Arc<Record>>, Filter);
+
+/// # `GET /_continuwuity/admin/server/logs`
+///
+/// Streams recent log events matching the query as newline-delimited JSON.
+/// With `follow=true` the response stays open and new events are sent as
+/// they are logged, with an empty line sent periodically while idle.
+pub(crate) async fn stream_logs(
+	State(services): State<crate::State>,
+	body: Ruma<logs::v1::Request>,
+) -> Result<Response> {
+	let sender_user = body.identity.expect_sender_user()?;
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let query = body.body;
+	let history = &services.server.log.history;
+	if !history.is_enabled() {
+		return Err!(Request(NotFound("The log history is disabled.")));
+	}
+
+	let filter = Filter {
+		level: query
+			.level
+			.as_deref()
+			.map(str::parse::<Level>)
+			.transpose()
+			.map_err(|e| err!(Request(InvalidParam("Invalid level: {e}"))))?,
+		target: query.target,
+		span: query.span,
+		room_id: query.room_id,
+		user_id: query.user_id,
+		text: query.text,
+	};
+
+	// Subscribe before reading the backlog so no events are missed in between.
+	let receiver = query.follow.then(|| history.subscribe());
+	let backlog = history.search(&filter, query.backlog.min(MAX_BACKLOG));
+	let backlog = stream::iter(backlog).map(|record| Ok::<_, Infallible>(record_line(&record)));
+
+	let body = match receiver {
+		| None => Body::from_stream(backlog),
+		| Some(receiver) => {
+			let server = services.server.clone();
+			let live = stream::unfold((receiver, filter), follow)
+				.take_until(async move { server.until_shutdown().await });
+
+			Body::from_stream(backlog.chain(live))
+		},
+	};
+
+	Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response())
+}
+
+async fn follow(
+	(mut receiver, filter): FollowState,
+) -> Option<(Result<String, Infallible>, FollowState)> {
+	let line = loop {
+		match tokio::time::timeout(KEEPALIVE_INTERVAL, receiver.recv()).await {
+			| Err(_) => break "\n".to_owned(),
+			| Ok(Ok(record)) if filter.matches(&record) => break record_line(&record),
+			| Ok(Ok(_)) => {},
+			| Ok(Err(RecvError::Lagged(skipped))) =>
+				break format!("{}\n", serde_json::json!({ "skipped": skipped })),
+			| Ok(Err(RecvError::Closed)) => return None,
+		}
+	};
+
+	Some((Ok(line), (receiver, filter)))
+}
+
+fn record_line(record: &Record) -> String {
+	// Fields are ordered innermost first; insert in reverse so they take
+	// precedence over outer span fields of the same name.
+	let fields: serde_json::Map<_, _> = record
+		.fields
+		.iter()
+		.rev()
+		.map(|(key, value)| ((*key).to_owned(), value.as_str().into()))
+		.collect();
+
+	let line = serde_json::json!({
+		"timestamp": time::format(record.timestamp, "%+"),
+		"level": record.level.as_str(),
+		"target": record.target,
+		"spans": record.spans,
+		"message": record.message,
+		"fields": fields,
+	});
+
+	format!("{line}\n")
+}
@@ -2,7 +2,7 @@
 use conduwuit::{Err, Result, err};
 use conduwuit_service::Services;
 use ruma::{
-	RoomId, UserId,
+	DeviceId, RoomId, UserId,
 	api::client::config::{
 		get_global_account_data, get_room_account_data, set_global_account_data,
 		set_room_account_data,
@@ -32,6 +32,7 @@ pub(crate) async fn set_global_account_data_route(
 		&services,
 		None,
 		&body.user_id,
+		body.identity.sender_device(),
 		&body.event_type.to_string(),
 		body.data.json(),
 	)
@@ -57,6 +58,7 @@ pub(crate) async fn set_room_account_data_route(
 		&services,
 		Some(&body.room_id),
 		&body.user_id,
+		body.identity.sender_device(),
 		&body.event_type.to_string(),
 		body.data.json(),
 	)
@@ -113,6 +115,7 @@ async fn set_account_data(
 	services: &Services,
 	room_id: Option<&RoomId>,
 	sender_user: &UserId,
+	sender_device: Option<&DeviceId>,
 	event_type_s: &str,
 	data: &RawJsonValue,
 ) -> Result {
@@ -128,9 +131,10 @@ async fn set_account_data(
 
 	services
 		.account_data
-		.update(
+		.update_from(
 			room_id,
 			sender_user,
+			sender_device,
 			event_type_s.into(),
 			&json!({
 				"type": event_type_s,
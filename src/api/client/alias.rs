@@ -1,5 +1,5 @@
 use axum::extract::State;
-use conduwuit::{Err, Result};
+use conduwuit::{Err, Result, debug_warn};
 use ruma::api::client::alias::{create_alias, delete_alias, get_alias};
 
 use crate::Ruma;
@@ -55,7 +55,7 @@ pub(crate) async fn create_alias_route(
 ///
 /// Deletes a room alias from this server.
 ///
-/// - TODO: Update canonical alias event
+/// - Removes the alias from the room's canonical alias event, if present
 pub(crate) async fn delete_alias_route(
 	State(services): State<crate::State>,
 	body: Ruma<delete_alias::v3::Request>,
@@ -72,13 +72,28 @@ pub(crate) async fn delete_alias_route(
 		.appservice_checks(&body.room_alias, body.identity.appservice_info())
 		.await?;
 
+	let room_id = services
+		.rooms
+		.alias
+		.resolve_local_alias(&body.room_alias)
+		.await;
+
 	services
 		.rooms
 		.alias
 		.remove_alias(&body.room_alias, sender_user)
 		.await?;
 
-	// TODO: update alt_aliases?
+	if let Ok(room_id) = room_id {
+		if let Err(e) = services
+			.rooms
+			.alias
+			.remove_from_canonical_alias(&room_id, &body.room_alias, sender_user)
+			.await
+		{
+			debug_warn!(%room_id, "Failed to remove {} from canonical alias: {e}", body.room_alias);
+		}
+	}
 
 	Ok(delete_alias::v3::Response::new())
 }
@@ -10,15 +10,11 @@
 use futures::StreamExt;
 use ruma::{
 	RoomId, ServerName, UInt, UserId,
-	api::{
-		client::{
-			directory::{
-				get_public_rooms, get_public_rooms_filtered, get_room_visibility,
-				set_room_visibility,
-			},
-			room,
+	api::client::{
+		directory::{
+			get_public_rooms, get_public_rooms_filtered, get_room_visibility, set_room_visibility,
 		},
-		federation,
+		room,
 	},
 	assign,
 	directory::{Filter, PublicRoomsChunk, RoomNetwork, RoomTypeFilter},
@@ -33,7 +29,9 @@
 ///
 /// Lists the public rooms on this server.
 ///
-/// - Rooms are ordered by the number of joined members
+/// - Rooms pinned by admins are listed first, in pin order
+/// - Other rooms are ordered by the number of joined members
+/// - Rooms hidden by admins are not listed
 #[tracing::instrument(skip_all, name = "publicrooms", level = "info")]
 pub(crate) async fn get_public_rooms_filtered_route(
 	State(services): State<crate::State>,
@@ -68,7 +66,9 @@ pub(crate) async fn get_public_rooms_filtered_route(
 ///
 /// Lists the public rooms on this server.
 ///
-/// - Rooms are ordered by the number of joined members
+/// - Rooms pinned by admins are listed first, in pin order
+/// - Other rooms are ordered by the number of joined members
+/// - Rooms hidden by admins are not listed
 #[tracing::instrument(skip_all, name = "publicrooms", level = "info")]
 pub(crate) async fn get_public_rooms_route(
 	State(services): State<crate::State>,
@@ -150,7 +150,7 @@ pub(crate) async fn set_room_visibility_route(
 				)));
 			}
 
-			services.rooms.directory.set_public(&body.room_id);
+			services.rooms.directory.set_public(&body.room_id).await;
 
 			if services.server.config.admin_room_notices {
 				services
@@ -163,7 +163,8 @@ pub(crate) async fn set_room_visibility_route(
 			}
 			info!("{sender_user} made {0} public to the room directory", body.room_id);
 		},
-		| room::Visibility::Private => services.rooms.directory.set_not_public(&body.room_id),
+		| room::Visibility::Private =>
+			services.rooms.directory.set_not_public(&body.room_id).await,
 		| _ => {
 			return Err!(Request(InvalidParam("Room visibility type is not supported.",)));
 		},
@@ -205,19 +206,9 @@ pub(crate) async fn get_public_rooms_filtered_helper(
 		server.filter(|server_name| !services.globals.server_is_ours(server_name))
 	{
 		let response = services
-			.sending
-			.send_federation_request(
-				other_server,
-				assign!(federation::directory::get_public_rooms_filtered::v1::Request::new(), {
-					limit,
-					since: since.map(ToOwned::to_owned),
-					filter: assign!(Filter::new(), {
-						generic_search_term: filter.generic_search_term.clone(),
-						room_types: filter.room_types.clone(),
-					}),
-					room_network: RoomNetwork::Matrix,
-				}),
-			)
+			.rooms
+			.directory
+			.remote_public_rooms(other_server, limit, since, filter)
 			.await?;
 
 		return Ok(assign!(get_public_rooms_filtered::v3::Response::new(), {
@@ -252,11 +243,21 @@ pub(crate) async fn get_public_rooms_filtered_helper(
 		}
 	}
 
-	let mut all_rooms: Vec<PublicRoomsChunk> = services
+	let matching_rooms = match filter.generic_search_term.as_deref() {
+		| Some(search_term) => services.rooms.directory.search(search_term).await,
+		| None => None,
+	};
+
+	let mut all_rooms: Vec<(Option<u32>, PublicRoomsChunk)> = services
 		.rooms
 		.directory
-		.public_rooms()
-		.wide_then(async |room_id| {
+		.listed_rooms()
+		.ready_filter(|(room_id, _)| {
+			matching_rooms
+				.as_ref()
+				.is_none_or(|matching_rooms| matching_rooms.contains(room_id))
+		})
+		.wide_then(async |(room_id, entry)| {
 			let summary = services
 				.rooms
 				.summary
@@ -264,43 +265,23 @@ pub(crate) async fn get_public_rooms_filtered_helper(
 				.await
 				.expect("room in public room directory should exist");
 
-			summary.into()
+			(entry.pinned, summary.into())
 		})
-		.ready_filter_map(|chunk: PublicRoomsChunk| {
-			if !filter.room_types.is_empty() && !filter.room_types.contains(&RoomTypeFilter::from(chunk.room_type.clone())) {
-				return None;
-			}
-
-			if let Some(query) = filter.generic_search_term.as_ref().map(|q| q.to_lowercase()) {
-				if let Some(name) = &chunk.name {
-					if name.to_lowercase().contains(&query) {
-						return Some(chunk);
-					}
-				}
-
-				if let Some(topic) = &chunk.topic {
-					if topic.to_lowercase().contains(&query) {
-						return Some(chunk);
-					}
-				}
-
-				if let Some(canonical_alias) = &chunk.canonical_alias {
-					if canonical_alias.as_str().to_lowercase().contains(&query) {
-						return Some(chunk);
-					}
-				}
-
-				return None;
-			}
-
-			// No search term
-			Some(chunk)
+		.ready_filter(|(_, chunk): &(_, PublicRoomsChunk)| {
+			filter.room_types.is_empty()
+				|| filter
+					.room_types
+					.contains(&RoomTypeFilter::from(chunk.room_type.clone()))
 		})
-		// We need to collect all, so we can sort by member count
+		// We need to collect all, so we can sort by pin position and member count
 		.collect()
 		.await;
 
-	all_rooms.sort_by_key(|r| std::cmp::Reverse(r.num_joined_members));
+	all_rooms.sort_by_key(|(pinned, chunk)| {
+		(pinned.is_none(), *pinned, std::cmp::Reverse(chunk.num_joined_members))
+	});
+
+	let all_rooms: Vec<_> = all_rooms.into_iter().map(|(_, chunk)| chunk).collect();
 
 	let total_room_count_estimate = UInt::try_from(all_rooms.len())
 		.unwrap_or_else(|_| uint!(0))
@@ -157,6 +157,13 @@ pub(crate) async fn get_keys_route(
 ) -> Result<get_keys::v3::Response> {
 	let sender_user = body.identity.expect_sender_user()?;
 
+	// The devices of members omitted from a partial-state join aren't known yet.
+	services
+		.rooms
+		.partial_state
+		.wait_for_full_state_of_user(sender_user)
+		.await;
+
 	get_keys_helper(
 		&services,
 		Some(sender_user),
@@ -355,6 +362,12 @@ pub(crate) async fn get_key_changes_route(
 ) -> Result<get_key_changes::v3::Response> {
 	let sender_user = body.identity.expect_sender_user()?;
 
+	services
+		.rooms
+		.partial_state
+		.wait_for_full_state_of_user(sender_user)
+		.await;
+
 	let mut device_list_updates = HashSet::new();
 
 	let from = body
@@ -12,14 +12,18 @@
 };
 use reqwest::Url;
 use ruma::{
-	UserId,
-	api::client::{
-		authenticated_media::{
-			get_content, get_content_as_filename, get_content_thumbnail, get_media_config,
-			get_media_preview,
+	MilliSecondsSinceUnixEpoch, UInt, UserId,
+	api::{
+		client::{
+			authenticated_media::{
+				get_content, get_content_as_filename, get_content_thumbnail, get_media_config,
+				get_media_preview,
+			},
+			media::{create_content, create_content_async, create_mxc_uri},
 		},
-		media::create_content,
+		error::ErrorKind,
 	},
+	assign,
 };
 use service::media::mxc::Mxc;
 
@@ -70,6 +74,60 @@ pub(crate) async fn create_content_route(
 	Ok(create_content::v3::Response::new(mxc.to_string().into()))
 }
 
+/// # `POST /_matrix/media/v1/create`
+///
+/// Reserves an MXC URI for the user to upload content to later with `PUT
+/// /_matrix/media/v3/upload/{serverName}/{mediaId}`.
+pub(crate) async fn create_mxc_uri_route(
+	State(services): State<crate::State>,
+	body: Ruma<create_mxc_uri::v1::Request>,
+) -> Result<create_mxc_uri::v1::Response> {
+	let user = body.identity.expect_sender_user()?;
+	if services.users.is_suspended(user).await? {
+		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
+	}
+
+	let (content_uri, expires_at) = services.media.create_pending(user).await?;
+	let unused_expires_at = UInt::try_from(expires_at)
+		.ok()
+		.map(MilliSecondsSinceUnixEpoch);
+
+	Ok(assign!(create_mxc_uri::v1::Response::new(content_uri), { unused_expires_at }))
+}
+
+/// # `PUT /_matrix/media/v3/upload/{serverName}/{mediaId}`
+///
+/// Uploads content to an MXC URI reserved with `POST
+/// /_matrix/media/v1/create`.
+pub(crate) async fn create_content_async_route(
+	State(services): State<crate::State>,
+	body: Ruma<create_content_async::v3::Request>,
+) -> Result<create_content_async::v3::Response> {
+	let user = body.identity.expect_sender_user()?;
+	if services.users.is_suspended(user).await? {
+		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
+	}
+
+	if !services.globals.server_is_ours(&body.server_name) {
+		return Err!(Request(NotFound("Media ID was not reserved on this server.")));
+	}
+
+	let filename = body.filename.as_deref();
+	let content_type = body.content_type.as_deref();
+	let content_disposition = make_content_disposition(None, content_type, filename);
+	let mxc = Mxc {
+		server_name: &body.server_name,
+		media_id: &body.media_id,
+	};
+
+	services
+		.media
+		.upload_pending(&mxc, user, Some(&content_disposition), content_type, &body.file)
+		.await?;
+
+	Ok(create_content_async::v3::Response::new())
+}
+
 /// # `GET /_matrix/client/v1/media/thumbnail/{serverName}/{mediaId}`
 ///
 /// Load media thumbnail from our server or over federation.
@@ -100,6 +158,7 @@ pub(crate) async fn get_content_thumbnail_route(
 			},
 			| _ => return Err!(Request(Unknown("Unknown error when fetching thumbnail."))),
 		},
+		| Err(e @ conduwuit::Error::Request(ErrorKind::NotYetUploaded, ..)) => return Err(e),
 		| Err(_) => return Err!(Request(Unknown("Unknown error when fetching thumbnail."))),
 	};
 
@@ -140,6 +199,7 @@ pub(crate) async fn get_content_route(
 			},
 			| _ => return Err!(Request(Unknown("Unknown error when fetching file."))),
 		},
+		| Err(e @ conduwuit::Error::Request(ErrorKind::NotYetUploaded, ..)) => return Err(e),
 		| Err(_) => return Err!(Request(Unknown("Unknown error when fetching file."))),
 	};
 
@@ -181,6 +241,7 @@ pub(crate) async fn get_content_as_filename_route(
 			},
 			| _ => return Err!(Request(Unknown("Unknown error when fetching file."))),
 		},
+		| Err(e @ conduwuit::Error::Request(ErrorKind::NotYetUploaded, ..)) => return Err(e),
 		| Err(_) => return Err!(Request(Unknown("Unknown error when fetching file."))),
 	};
 
@@ -245,6 +306,8 @@ async fn fetch_thumbnail_meta(
 	timeout_ms: Duration,
 	dim: &Dim,
 ) -> Result<FileMeta> {
+	services.media.wait_for_upload(mxc, timeout_ms).await?;
+
 	if let Some(filemeta) = services.media.get_thumbnail(mxc, dim).await? {
 		return Ok(filemeta);
 	}
@@ -265,6 +328,8 @@ async fn fetch_file_meta(
 	user: &UserId,
 	timeout_ms: Duration,
 ) -> Result<FileMeta> {
+	services.media.wait_for_upload(mxc, timeout_ms).await?;
+
 	if let Some(filemeta) = services.media.get(mxc).await? {
 		return Ok(filemeta);
 	}
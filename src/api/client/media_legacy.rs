@@ -135,6 +135,11 @@ pub(crate) async fn get_content_legacy_route(
 		media_id: &body.media_id,
 	};
 
+	services
+		.media
+		.wait_for_upload(&mxc, body.timeout_ms)
+		.await?;
+
 	match services.media.get(&mxc).await? {
 		| Some(FileMeta {
 			content,
@@ -228,6 +233,11 @@ pub(crate) async fn get_content_as_filename_legacy_route(
 		media_id: &body.media_id,
 	};
 
+	services
+		.media
+		.wait_for_upload(&mxc, body.timeout_ms)
+		.await?;
+
 	match services.media.get(&mxc).await? {
 		| Some(FileMeta {
 			content,
@@ -321,6 +331,11 @@ pub(crate) async fn get_content_thumbnail_legacy_route(
 	};
 
 	let dim = Dim::from_ruma(body.width, body.height, body.method.clone())?;
+	services
+		.media
+		.wait_for_upload(&mxc, body.timeout_ms)
+		.await?;
+
 	match services.media.get_thumbnail(&mxc, &dim).await? {
 		| Some(FileMeta {
 			content,
@@ -26,6 +26,7 @@
 /// specific membership).
 ///
 /// - Only works if the user is currently joined
+/// - Waits for the full state of rooms joined with partial state
 pub(crate) async fn get_member_events_route(
 	State(services): State<crate::State>,
 	body: Ruma<get_member_events::v3::Request>,
@@ -43,6 +44,12 @@ pub(crate) async fn get_member_events_route(
 		return Err!(Request(Forbidden("You don't have permission to view this room.")));
 	}
 
+	services
+		.rooms
+		.partial_state
+		.wait_for_full_state(&body.room_id)
+		.await;
+
 	let chunk = services
 		.rooms
 		.state_accessor
@@ -65,6 +72,7 @@ pub(crate) async fn get_member_events_route(
 ///
 /// - The sender user must be in the room
 /// - TODO: An appservice just needs a puppet joined
+/// - Waits for the full state of rooms joined with partial state
 pub(crate) async fn joined_members_route(
 	State(services): State<crate::State>,
 	body: Ruma<joined_members::v3::Request>,
@@ -78,6 +86,12 @@ pub(crate) async fn joined_members_route(
 		return Err!(Request(Forbidden("You don't have permission to view this room.")));
 	}
 
+	services
+		.rooms
+		.partial_state
+		.wait_for_full_state(&body.room_id)
+		.await;
+
 	let joined = services
 		.rooms
 		.state_cache
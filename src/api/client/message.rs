@@ -110,7 +110,7 @@ pub(crate) async fn get_message_events_route(
 		services
 			.rooms
 			.timeline
-			.backfill_if_required(room_id, from)
+			.backfill_if_required(room_id, from, limit)
 			.boxed()
 			.await
 			.log_err()
@@ -141,6 +141,16 @@ pub(crate) async fn get_message_events_route(
 		.take(limit)
 		.then(async |mut pdu| {
 			pdu.1.set_unsigned(Some(sender_user));
+			if services
+				.rooms
+				.timeline
+				.timeline_gap(pdu.1.event_id())
+				.await
+				.is_ok()
+			{
+				pdu.1.add_gap_marker().log_err().ok();
+			}
+
 			if let Err(e) = services
 				.rooms
 				.pdu_metadata
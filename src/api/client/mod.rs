@@ -16,6 +16,7 @@
 pub(super) mod membership;
 pub(super) mod message;
 pub(super) mod mutual_rooms;
+pub(super) mod notifications;
 pub(super) mod oauth;
 pub(super) mod openid;
 pub(super) mod presence;
@@ -62,6 +63,7 @@
 pub use membership::{leave_all_rooms, leave_room, remote_leave_room};
 pub(super) use message::*;
 pub(super) use mutual_rooms::*;
+pub(super) use notifications::*;
 pub(super) use oauth::*;
 pub(super) use openid::*;
 pub(super) use presence::*;
@@ -72,6 +74,7 @@
 pub(super) use redact::*;
 pub(super) use relations::*;
 pub(super) use report::*;
+pub use room::upgrade_room_helper;
 pub(super) use room::*;
 pub(super) use search::*;
 pub(super) use send::*;
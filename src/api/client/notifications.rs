@@ -0,0 +1,86 @@
+use axum::extract::State;
+use conduwuit::{Result, matrix::Event};
+use futures::StreamExt;
+use ruma::{
+	MilliSecondsSinceUnixEpoch, UInt,
+	api::client::push::get_notifications::{self, v3::Notification},
+	assign, uint,
+};
+
+use crate::Ruma;
+
+/// # `GET /_matrix/client/v3/notifications`
+///
+/// Paginates the events which notified the user, newest first, from the
+/// notification log recorded when push rules are evaluated.
+pub(crate) async fn get_notifications_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_notifications::v3::Request>,
+) -> Result<get_notifications::v3::Response> {
+	let sender_user = body.identity.expect_sender_user()?;
+
+	// Use limit or else 10, with maximum 100
+	let limit = body
+		.limit
+		.unwrap_or_else(|| uint!(10))
+		.try_into()
+		.unwrap_or(10)
+		.min(100);
+
+	let from = body
+		.from
+		.as_deref()
+		.and_then(|from| from.parse::<u64>().ok());
+
+	let only_highlight = body.only.as_deref() == Some("highlight");
+
+	let notifications: Vec<(u64, Notification)> = services
+		.rooms
+		.user
+		.notifications(sender_user, from)
+		.filter(|(_, notification)| {
+			let include = !only_highlight || notification.highlight;
+			async move { include }
+		})
+		.filter_map(|(count, notification)| async move {
+			let pdu = services
+				.rooms
+				.timeline
+				.get_pdu(&notification.event_id)
+				.await
+				.ok()?;
+
+			let read = services
+				.rooms
+				.read_receipt
+				.private_read_get_count(&notification.room_id, sender_user)
+				.await
+				.is_ok_and(|read| read >= count);
+
+			let ts = UInt::try_from(notification.ts).unwrap_or_default();
+			let notification = Notification::new(
+				notification.actions,
+				pdu.into_format(),
+				read,
+				notification.room_id,
+				MilliSecondsSinceUnixEpoch(ts),
+			);
+
+			Some((count, notification))
+		})
+		.take(limit)
+		.collect()
+		.await;
+
+	let next_token = notifications
+		.last()
+		.filter(|_| notifications.len() >= limit)
+		.map(|(count, _)| count.to_string());
+
+	let notifications = notifications
+		.into_iter()
+		.map(|(_, notification)| notification)
+		.collect();
+
+	Ok(assign!(get_notifications::v3::Response::new(notifications), { next_token }))
+}
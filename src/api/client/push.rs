@@ -114,18 +114,9 @@ pub(crate) async fn get_pushrules_global_route(
 		// user somehow has non-existent push rule event. recreate it and return server
 		// default silently
 
-		let global_ruleset = Ruleset::server_default(sender_user);
-		let event = PushRulesEvent::new(PushRulesEventContent::new(global_ruleset.clone()));
-
-		services
-			.account_data
-			.update(
-				None,
-				sender_user,
-				GlobalAccountDataEventType::PushRules.to_string().into(),
-				&serde_json::to_value(event)?,
-			)
-			.await?;
+		let global_ruleset = recreate_push_rules_and_return(&services, sender_user)
+			.await?
+			.global;
 
 		return Ok(get_pushrules_global_scope::v3::Response::new(global_ruleset));
 	};
@@ -227,6 +218,7 @@ pub(crate) async fn set_pushrule_route(
 	body: Ruma<set_pushrule::v3::Request>,
 ) -> Result<set_pushrule::v3::Response> {
 	let sender_user = body.identity.expect_sender_user()?;
+	let sender_device = body.identity.sender_device();
 	let body = &body.body;
 	let mut account_data: PushRulesEvent = services
 		.account_data
@@ -269,7 +261,13 @@ pub(crate) async fn set_pushrule_route(
 	let ty = GlobalAccountDataEventType::PushRules;
 	services
 		.account_data
-		.update(None, sender_user, ty.to_string().into(), &serde_json::to_value(account_data)?)
+		.update_from(
+			None,
+			sender_user,
+			sender_device,
+			ty.to_string().into(),
+			&serde_json::to_value(account_data)?,
+		)
 		.await?;
 
 	Ok(set_pushrule::v3::Response::new())
@@ -317,6 +315,7 @@ pub(crate) async fn set_pushrule_actions_route(
 	body: Ruma<set_pushrule_actions::v3::Request>,
 ) -> Result<set_pushrule_actions::v3::Response> {
 	let sender_user = body.identity.expect_sender_user()?;
+	let sender_device = body.identity.sender_device();
 
 	let mut account_data: PushRulesEvent = services
 		.account_data
@@ -336,7 +335,13 @@ pub(crate) async fn set_pushrule_actions_route(
 	let ty = GlobalAccountDataEventType::PushRules;
 	services
 		.account_data
-		.update(None, sender_user, ty.to_string().into(), &serde_json::to_value(account_data)?)
+		.update_from(
+			None,
+			sender_user,
+			sender_device,
+			ty.to_string().into(),
+			&serde_json::to_value(account_data)?,
+		)
 		.await?;
 
 	Ok(set_pushrule_actions::v3::Response::new())
@@ -384,6 +389,7 @@ pub(crate) async fn set_pushrule_enabled_route(
 	body: Ruma<set_pushrule_enabled::v3::Request>,
 ) -> Result<set_pushrule_enabled::v3::Response> {
 	let sender_user = body.identity.expect_sender_user()?;
+	let sender_device = body.identity.sender_device();
 
 	let mut account_data: PushRulesEvent = services
 		.account_data
@@ -403,7 +409,13 @@ pub(crate) async fn set_pushrule_enabled_route(
 	let ty = GlobalAccountDataEventType::PushRules;
 	services
 		.account_data
-		.update(None, sender_user, ty.to_string().into(), &serde_json::to_value(account_data)?)
+		.update_from(
+			None,
+			sender_user,
+			sender_device,
+			ty.to_string().into(),
+			&serde_json::to_value(account_data)?,
+		)
 		.await?;
 
 	Ok(set_pushrule_enabled::v3::Response::new())
@@ -417,6 +429,7 @@ pub(crate) async fn delete_pushrule_route(
 	body: Ruma<delete_pushrule::v3::Request>,
 ) -> Result<delete_pushrule::v3::Response> {
 	let sender_user = body.identity.expect_sender_user()?;
+	let sender_device = body.identity.sender_device();
 
 	let mut account_data: PushRulesEvent = services
 		.account_data
@@ -445,7 +458,13 @@ pub(crate) async fn delete_pushrule_route(
 	let ty = GlobalAccountDataEventType::PushRules;
 	services
 		.account_data
-		.update(None, sender_user, ty.to_string().into(), &serde_json::to_value(account_data)?)
+		.update_from(
+			None,
+			sender_user,
+			sender_device,
+			ty.to_string().into(),
+			&serde_json::to_value(account_data)?,
+		)
 		.await?;
 
 	Ok(delete_pushrule::v3::Response::new())
@@ -467,7 +486,8 @@ pub(crate) async fn get_pushers_route(
 ///
 /// Adds a pusher for the sender user.
 ///
-/// - TODO: Handle `append`
+/// Unless `append` is set, pushers other users have with the same app ID and
+/// pushkey are removed.
 pub(crate) async fn set_pushers_route(
 	State(services): State<crate::State>,
 	body: Ruma<set_pusher::v3::Request>,
@@ -484,12 +504,13 @@ pub(crate) async fn set_pushers_route(
 }
 
 /// user somehow has bad push rules, these must always exist per spec.
-/// so recreate it and return server default silently
+/// so recreate it and return server default silently, including the admin's
+/// default push rules
 pub async fn recreate_push_rules_and_return(
 	services: &Services,
 	sender_user: &ruma::UserId,
 ) -> Result<get_pushrules_all::v3::Response> {
-	let global_ruleset = Ruleset::server_default(sender_user);
+	let global_ruleset = services.pusher.default_ruleset(sender_user).await;
 	let event = PushRulesEvent::new(PushRulesEventContent::new(global_ruleset.clone()));
 
 	services
@@ -5,10 +5,11 @@
 use ruma::{
 	MilliSecondsSinceUnixEpoch,
 	api::client::{read_marker::set_read_marker, receipt::create_receipt},
+	assign,
 	events::{
 		RoomAccountDataEventType,
 		fully_read::{FullyReadEvent, FullyReadEventContent},
-		receipt::{Receipt, ReceiptEvent, ReceiptEventContent, ReceiptType},
+		receipt::{Receipt, ReceiptEvent, ReceiptEventContent, ReceiptThread, ReceiptType},
 	},
 };
 
@@ -45,7 +46,8 @@ pub(crate) async fn set_read_marker_route(
 		services
 			.rooms
 			.user
-			.reset_notification_counts(sender_user, &body.room_id);
+			.reset_notification_counts(sender_user, &body.room_id)
+			.await;
 	}
 
 	// ping presence
@@ -100,10 +102,12 @@ pub(crate) async fn set_read_marker_route(
 			)));
 		};
 
-		services
-			.rooms
-			.read_receipt
-			.private_read_set(&body.room_id, sender_user, count);
+		services.rooms.read_receipt.private_read_set(
+			&body.room_id,
+			sender_user,
+			count,
+			&ReceiptThread::Unthreaded,
+		);
 	}
 
 	services.sync.wake(sender_user).await;
@@ -130,10 +134,21 @@ pub(crate) async fn create_receipt_route(
 		&body.receipt_type,
 		create_receipt::v3::ReceiptType::Read | create_receipt::v3::ReceiptType::ReadPrivate
 	) {
-		services
-			.rooms
-			.user
-			.reset_notification_counts(sender_user, &body.room_id);
+		let user = &services.rooms.user;
+		match &body.thread {
+			| ReceiptThread::Main => {
+				user.reset_main_notification_counts(sender_user, &body.room_id)
+					.await;
+			},
+			| ReceiptThread::Thread(thread) => {
+				user.reset_thread_notification_counts(sender_user, &body.room_id, thread)
+					.await;
+			},
+			| _ => {
+				user.reset_notification_counts(sender_user, &body.room_id)
+					.await;
+			},
+		}
 	}
 
 	// ping presence
@@ -159,14 +174,14 @@ pub(crate) async fn create_receipt_route(
 				.await?;
 		},
 		| create_receipt::v3::ReceiptType::Read => {
+			let receipt = assign!(Receipt::new(MilliSecondsSinceUnixEpoch::now()), {
+				thread: body.thread.clone(),
+			});
 			let receipt_content = [(
 				body.event_id.clone(),
 				BTreeMap::from_iter([(
 					ReceiptType::Read,
-					BTreeMap::from_iter([(
-						sender_user.to_owned(),
-						Receipt::new(MilliSecondsSinceUnixEpoch::now()),
-					)]),
+					BTreeMap::from_iter([(sender_user.to_owned(), receipt)]),
 				)]),
 			)];
 
@@ -197,10 +212,12 @@ pub(crate) async fn create_receipt_route(
 				)));
 			};
 
-			services
-				.rooms
-				.read_receipt
-				.private_read_set(&body.room_id, sender_user, count);
+			services.rooms.read_receipt.private_read_set(
+				&body.room_id,
+				sender_user,
+				count,
+				&body.thread,
+			);
 		},
 		| _ => {
 			return Err!(Request(InvalidParam(warn!(
@@ -604,7 +604,7 @@ pub(crate) async fn create_room_route(
 	}
 
 	if body.visibility == room::Visibility::Public {
-		services.rooms.directory.set_public(&room_id);
+		services.rooms.directory.set_public(&room_id).await;
 
 		if services.server.config.admin_room_notices {
 			services
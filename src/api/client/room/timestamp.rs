@@ -0,0 +1,46 @@
+use axum::extract::State;
+use conduwuit::{Err, Event, Result};
+use ruma::api::client::room::get_event_by_timestamp;
+
+use crate::Ruma;
+
+/// # `GET /_matrix/client/v1/rooms/{roomId}/timestamp_to_event`
+///
+/// Finds the event closest to a timestamp in the given direction, asking
+/// other servers in the room when local history is missing.
+pub(crate) async fn get_event_by_timestamp_route(
+	State(services): State<crate::State>,
+	ref body: Ruma<get_event_by_timestamp::v1::Request>,
+) -> Result<get_event_by_timestamp::v1::Response> {
+	let sender_user = body.identity.expect_sender_user()?;
+	let room_id = &body.room_id;
+
+	if !services
+		.rooms
+		.state_accessor
+		.user_can_see_state_events(sender_user, room_id)
+		.await
+	{
+		return Err!(Request(Forbidden("You don't have permission to view this room.")));
+	}
+
+	let pdu = services
+		.rooms
+		.timeline
+		.event_by_timestamp(room_id, body.ts, body.dir)
+		.await?;
+
+	if !services
+		.rooms
+		.state_accessor
+		.user_can_see_event(sender_user, room_id, pdu.event_id())
+		.await
+	{
+		return Err!(Request(NotFound("Unable to find an event in that direction.")));
+	}
+
+	Ok(get_event_by_timestamp::v1::Response::new(
+		pdu.event_id().to_owned(),
+		pdu.origin_server_ts(),
+	))
+}
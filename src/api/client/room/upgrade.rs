@@ -10,7 +10,7 @@
 };
 use futures::{FutureExt, StreamExt};
 use ruma::{
-	OwnedEventId, OwnedRoomId, RoomId, UserId,
+	OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, RoomVersionId, UserId,
 	api::{client::room::upgrade_room, error::ErrorKind},
 	assign,
 	events::{
@@ -27,6 +27,7 @@
 	room_version_rules::RoomIdFormatVersion,
 };
 use serde_json::value::to_raw_value;
+use service::Services;
 
 use crate::router::Ruma;
 
@@ -50,7 +51,7 @@
 ///
 /// See: https://github.com/matrix-org/matrix-spec-proposals/pull/4168
 async fn update_parents(
-	services: &crate::State,
+	services: &Services,
 	sender: &UserId,
 	old_room_id: &RoomId,
 	new_room_id: &RoomId,
@@ -147,7 +148,7 @@ async fn update_parents(
 ///
 /// See: https://github.com/matrix-org/matrix-spec-proposals/pull/4168
 async fn update_children(
-	services: &crate::State,
+	services: &Services,
 	sender: &UserId,
 	old_room_id: &RoomId,
 	new_room_id: &RoomId,
@@ -271,7 +272,7 @@ async fn update_children(
 /// - Sends a tombstone event into the current room
 /// - Sender user joins the room
 /// - Transfers some state events
-/// - Moves local aliases
+/// - Moves local aliases and the room directory entry
 /// - Modifies old room power levels to prevent users from speaking
 pub(crate) async fn upgrade_room_route(
 	State(services): State<crate::State>,
@@ -279,10 +280,37 @@ pub(crate) async fn upgrade_room_route(
 ) -> Result<upgrade_room::v3::Response> {
 	let sender_user = body.identity.expect_sender_user()?;
 
+	if services.users.is_suspended(sender_user).await? {
+		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
+	}
+
+	let replacement_room_id = upgrade_room_helper(
+		&services,
+		sender_user,
+		&body.room_id,
+		&body.new_version,
+		&body.additional_creators,
+	)
+	.boxed()
+	.await?;
+
+	// Return the replacement room id
+	Ok(upgrade_room::v3::Response::new(replacement_room_id))
+}
+
+/// Upgrades a room to a new room version on behalf of a local user, and
+/// returns the replacement room.
+pub async fn upgrade_room_helper(
+	services: &Services,
+	sender_user: &UserId,
+	room_id: &RoomId,
+	new_version: &RoomVersionId,
+	additional_creators: &[OwnedUserId],
+) -> Result<OwnedRoomId> {
 	let (supported, forbid_unstable, is_unstable) = (
-		services.server.supported_room_version(&body.new_version),
+		services.server.supported_room_version(new_version),
 		!services.config.allow_unstable_room_versions,
-		UNSTABLE_ROOM_VERSIONS.contains(&body.new_version),
+		UNSTABLE_ROOM_VERSIONS.contains(new_version),
 	);
 	if !supported || (forbid_unstable && is_unstable) {
 		return Err(Error::BadRequest(
@@ -291,19 +319,15 @@ pub(crate) async fn upgrade_room_route(
 		));
 	}
 
-	if services.users.is_suspended(sender_user).await? {
-		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
-	}
-
 	// Make sure this isn't the admin room
 	// Admin room upgrades are hacky and should be done manually instead.
-	if services.admin.is_admin_room(&body.room_id).await {
+	if services.admin.is_admin_room(room_id).await {
 		return Err!(Request(Forbidden("Upgrading the admin room this way is not allowed.")));
 	}
 
 	// 1. Check that the user has permission to send m.room.tombstone events in the
 	//    room.
-	let old_room_state_lock = services.rooms.state.mutex.lock(body.room_id.as_str()).await;
+	let old_room_state_lock = services.rooms.state.mutex.lock(room_id.as_str()).await;
 
 	// Check tombstone permission by attempting to create (but not send) the event.
 	services
@@ -318,7 +342,7 @@ pub(crate) async fn upgrade_room_route(
 				),
 			),
 			sender_user,
-			Some(&body.room_id),
+			Some(room_id),
 			&old_room_state_lock,
 		)
 		.await
@@ -342,7 +366,7 @@ pub(crate) async fn upgrade_room_route(
 			services
 				.rooms
 				.state
-				.get_forward_extremities(&body.room_id)
+				.get_forward_extremities(room_id)
 				.collect::<Vec<OwnedEventId>>()
 				.await[0]
 				.clone(),
@@ -351,7 +375,7 @@ pub(crate) async fn upgrade_room_route(
 	let old_create_event: RoomCreateEventContent = services
 		.rooms
 		.state_accessor
-		.room_state_get_content(&body.room_id, &StateEventType::RoomCreate, "")
+		.room_state_get_content(room_id, &StateEventType::RoomCreate, "")
 		.await
 		.map_err(|_| err!(Database("Found room without m.room.create event.")))?;
 	let create_event_content = if new_version_rules.authorization.use_room_create_sender {
@@ -365,16 +389,16 @@ pub(crate) async fn upgrade_room_route(
 			create_event_content,
 			{
 				additional_creators: if new_version_rules.authorization.additional_room_creators {
-					body.additional_creators.clone()
+					additional_creators.to_vec()
 				} else { Vec::new() },
 				creator: if new_version_rules.authorization.use_room_create_sender {
 					None
 				} else { Some(sender_user.to_owned()) },
-				predecessor: Some(assign!(PreviousRoom::new(body.room_id.clone()), {
+				predecessor: Some(assign!(PreviousRoom::new(room_id.to_owned()), {
 					event_id: last_event,
 				})),
 				room_type: old_create_event.room_type.clone(),
-				room_version: body.new_version.clone(),
+				room_version: new_version.clone(),
 			}
 		)
 	};
@@ -394,7 +418,7 @@ pub(crate) async fn upgrade_room_route(
 		// risk of concurrent in-flight collisions.
 		services.rooms.state.mutex.lock("!new-room").await
 	};
-	debug!("Upgrading {} to room version {}", &body.room_id, &body.new_version);
+	debug!("Upgrading {} to room version {}", room_id, new_version);
 	let create_event_id = services
 		.rooms
 		.timeline
@@ -433,7 +457,7 @@ pub(crate) async fn upgrade_room_route(
 			(Some(new_room_id), lock)
 		};
 
-	debug!("Upgraded {} to {}", &body.room_id, replacement_room_id.as_deref().unwrap());
+	debug!("Upgraded {} to {}", room_id, replacement_room_id.as_deref().unwrap());
 	// Join the new room
 	services
 		.rooms
@@ -458,13 +482,13 @@ pub(crate) async fn upgrade_room_route(
 		let state_keys = services
 			.rooms
 			.state_accessor
-			.room_state_keys(&body.room_id, event_type)
+			.room_state_keys(room_id, event_type)
 			.await?;
 		for state_key in state_keys {
 			let mut event_content = match services
 				.rooms
 				.state_accessor
-				.room_state_get(&body.room_id, event_type, &state_key)
+				.room_state_get(room_id, event_type, &state_key)
 				.await
 			{
 				| Ok(v) => v.content().to_owned(),
@@ -473,9 +497,7 @@ pub(crate) async fn upgrade_room_route(
 			// If this is a power levels event, and the new room version has creators,
 			// we need to make sure they dont appear in the users block of power levels.
 			if *event_type == StateEventType::RoomPowerLevels {
-				let creators = body
-					.additional_creators
-					.clone()
+				let creators = additional_creators
 					.iter()
 					.chain(std::iter::once(&sender_user.to_owned()))
 					.map(ToOwned::to_owned)
@@ -529,11 +551,7 @@ pub(crate) async fn upgrade_room_route(
 	}
 
 	// 4. Move any local aliases to the new room
-	let mut local_aliases = services
-		.rooms
-		.alias
-		.local_aliases_for_room(&body.room_id)
-		.boxed();
+	let mut local_aliases = services.rooms.alias.local_aliases_for_room(room_id).boxed();
 
 	while let Some(alias) = local_aliases.next().await {
 		debug!(?alias, "Migrating alias");
@@ -550,9 +568,25 @@ pub(crate) async fn upgrade_room_route(
 		)?;
 	}
 
+	// Move the room directory entry to the new room
+	if services.rooms.directory.is_public_room(room_id).await {
+		let new_room_id = replacement_room_id.as_deref().unwrap();
+		let entry = services.rooms.directory.entry(room_id).await;
+
+		debug!(?new_room_id, "Migrating room directory entry");
+		services.rooms.directory.set_public(new_room_id).await;
+		services
+			.rooms
+			.directory
+			.update_entry(new_room_id, |new_entry| *new_entry = entry)
+			.await;
+
+		services.rooms.directory.set_not_public(room_id).await;
+	}
+
 	// 5. Send a `m.room.tombstone` event to the old room to indicate that it is not
 	//    intended to be used any further.
-	debug!(target=?body.room_id, "Sending tombstone to old room");
+	debug!(target=?room_id, "Sending tombstone to old room");
 	services
 		.rooms
 		.timeline
@@ -565,7 +599,7 @@ pub(crate) async fn upgrade_room_route(
 				),
 			),
 			sender_user,
-			Some(&body.room_id),
+			Some(room_id),
 			&old_room_state_lock,
 		)
 		.await?;
@@ -574,7 +608,7 @@ pub(crate) async fn upgrade_room_route(
 	let mut power_levels = services
 		.rooms
 		.state_accessor
-		.get_room_power_levels(&body.room_id)
+		.get_room_power_levels(room_id)
 		.await;
 
 	// Setting events_default and invite to the greater of 50 and users_default + 1
@@ -594,7 +628,7 @@ pub(crate) async fn upgrade_room_route(
 	// 6. Modify the power levels in the old room to prevent sending of events and
 	// inviting new users
 	// Spec dictates that this is allowed to fail.
-	debug!(target=?body.room_id, ?new_level, "Raising power level in old room to lock it");
+	debug!(target=?room_id, ?new_level, "Raising power level in old room to lock it");
 	services
 		.rooms
 		.timeline
@@ -604,7 +638,7 @@ pub(crate) async fn upgrade_room_route(
 				&RoomPowerLevelsEventContent::try_from(power_levels).unwrap(),
 			),
 			sender_user,
-			Some(&body.room_id),
+			Some(room_id),
 			&old_room_state_lock,
 		)
 		.boxed()
@@ -613,42 +647,31 @@ pub(crate) async fn upgrade_room_route(
 
 	// MSC4168: Update spaces that reference this room to point at the new room.
 	debug!("Updating parent spaces");
-	update_parents(
-		&services,
-		sender_user,
-		&body.room_id,
-		replacement_room_id.as_deref().unwrap(),
-	)
-	.await
-	.inspect_err(|e| {
-		error!(
-			old_room_id=?body.room_id,
-			new_room_id=?replacement_room_id.as_deref().unwrap(),
-			%e,
-			"failed to update parent spaces during room upgrade"
-		);
-	})
-	.ok();
+	update_parents(services, sender_user, room_id, replacement_room_id.as_deref().unwrap())
+		.await
+		.inspect_err(|e| {
+			error!(
+				old_room_id=?room_id,
+				new_room_id=?replacement_room_id.as_deref().unwrap(),
+				%e,
+				"failed to update parent spaces during room upgrade"
+			);
+		})
+		.ok();
 
 	// MSC4168: Update child rooms to point at the new space, where possible
 	debug!("Updating space children");
-	update_children(
-		&services,
-		sender_user,
-		&body.room_id,
-		replacement_room_id.as_deref().unwrap(),
-	)
-	.await
-	.inspect_err(|e| {
-		error!(
-			old_room_id=?body.room_id,
-			new_room_id=?replacement_room_id.as_deref().unwrap(),
-			%e,
-			"failed to update space children during room upgrade"
-		);
-	})
-	.ok();
+	update_children(services, sender_user, room_id, replacement_room_id.as_deref().unwrap())
+		.await
+		.inspect_err(|e| {
+			error!(
+				old_room_id=?room_id,
+				new_room_id=?replacement_room_id.as_deref().unwrap(),
+				%e,
+				"failed to update space children during room upgrade"
+			);
+		})
+		.ok();
 
-	// Return the replacement room id
-	Ok(upgrade_room::v3::Response::new(replacement_room_id.unwrap()))
+	Ok(replacement_room_id.expect("replacement room id should be known by now"))
 }
@@ -20,7 +20,7 @@
 	future::{OptionFuture, join, join3, join4, try_join, try_join3},
 };
 use ruma::{
-	MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedUserId, RoomId, UserId,
+	MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
 	api::client::sync::sync_events::{
 		UnreadNotificationsCount,
 		v3::{
@@ -37,7 +37,10 @@
 	serde::Raw,
 	uint,
 };
-use service::{account_data::AnyRawAccountDataEvent, rooms::short::ShortStateHash};
+use service::{
+	account_data::AnyRawAccountDataEvent,
+	rooms::{short::ShortStateHash, user::ThreadCounts},
+};
 use tokio::pin;
 
 use super::{load_timeline, share_encrypted_room};
@@ -104,7 +107,7 @@ pub(super) async fn load_joined_room(
 	let joined_room = assign!(JoinedRoom::new(), {
 		account_data,
 		summary: summary.unwrap_or_default(),
-		unread_notifications: notification_counts.unwrap_or_default(),
+		unread_notifications: notification_counts.main.unwrap_or_default(),
 		timeline,
 		state: if sync_context.use_state_after {
 			RoomState::After(state_events)
@@ -113,7 +116,7 @@ pub(super) async fn load_joined_room(
 		},
 		ephemeral,
 		sticky,
-		unread_thread_notifications: BTreeMap::new(),
+		unread_thread_notifications: notification_counts.threads,
 	});
 
 	Ok((joined_room, device_list_updates))
@@ -255,10 +258,18 @@ struct StateAndTimeline {
 	timeline: Timeline,
 	sticky: Sticky,
 	summary: Option<RoomSummary>,
-	notification_counts: Option<UnreadNotificationsCount>,
+	notification_counts: NotificationCounts,
 	device_list_updates: DeviceListUpdates,
 }
 
+/// Unread notification counts for the room and, if the client asked for them
+/// separately, for each of its threads.
+#[derive(Default)]
+struct NotificationCounts {
+	main: Option<UnreadNotificationsCount>,
+	threads: BTreeMap<OwnedEventId, UnreadNotificationsCount>,
+}
+
 async fn build_sticky_events(
 	services: &Services,
 	sync_context: SyncContext<'_>,
@@ -613,10 +624,15 @@ async fn build_state_events(
 #[tracing::instrument(level = "debug", skip_all)]
 async fn build_notification_counts(
 	services: &Services,
-	SyncContext { syncing_user, last_sync_end_count, .. }: SyncContext<'_>,
+	SyncContext {
+		syncing_user,
+		last_sync_end_count,
+		filter,
+		..
+	}: SyncContext<'_>,
 	room_id: &RoomId,
 	timeline: &TimelinePdus,
-) -> Result<Option<UnreadNotificationsCount>> {
+) -> Result<NotificationCounts> {
 	// determine whether to actually update the notification counts
 	let should_send_notification_counts = async {
 		// if we're going to sync some timeline events, the notification count has
@@ -647,32 +663,51 @@ async fn build_notification_counts(
 		false
 	};
 
-	if should_send_notification_counts.await {
+	if !should_send_notification_counts.await {
+		return Ok(NotificationCounts::default());
+	}
+
+	let user = &services.rooms.user;
+	if !filter.room.timeline.unread_thread_notifications {
 		let (notification_count, highlight_count) = join(
-			services
-				.rooms
-				.user
-				.notification_count(syncing_user, room_id)
-				.map(TryInto::try_into)
-				.unwrap_or(uint!(0)),
-			services
-				.rooms
-				.user
-				.highlight_count(syncing_user, room_id)
-				.map(TryInto::try_into)
-				.unwrap_or(uint!(0)),
+			user.notification_count(syncing_user, room_id),
+			user.highlight_count(syncing_user, room_id),
 		)
 		.await;
 
 		trace!(%notification_count, %highlight_count, "syncing new notification counts");
 
-		Ok(Some(assign!(UnreadNotificationsCount::new(), {
-			notification_count: Some(notification_count),
-			highlight_count: Some(highlight_count),
-		})))
-	} else {
-		Ok(None)
+		return Ok(NotificationCounts {
+			main: Some(unread_notifications_count(ThreadCounts {
+				notification_count,
+				highlight_count,
+			})),
+			threads: BTreeMap::new(),
+		});
 	}
+
+	let (main, threads) = join(
+		user.main_notification_counts(syncing_user, room_id),
+		user.thread_notification_counts(syncing_user, room_id),
+	)
+	.await;
+
+	trace!(?main, threads = threads.len(), "syncing new thread-aware notification counts");
+
+	Ok(NotificationCounts {
+		main: Some(unread_notifications_count(main)),
+		threads: threads
+			.into_iter()
+			.map(|(thread, counts)| (thread, unread_notifications_count(counts)))
+			.collect(),
+	})
+}
+
+fn unread_notifications_count(counts: ThreadCounts) -> UnreadNotificationsCount {
+	assign!(UnreadNotificationsCount::new(), {
+		notification_count: Some(counts.notification_count.try_into().unwrap_or(uint!(0))),
+		highlight_count: Some(counts.highlight_count.try_into().unwrap_or(uint!(0))),
+	})
 }
 
 /// Check if the syncing user joined the room since their last incremental sync.
@@ -869,6 +904,14 @@ async fn build_device_list_updates(
 		return Ok(DeviceListUpdates::new());
 	}
 
+	// the member list of a partial-state room is missing the members omitted
+	// from the join, so wait for it before deriving device lists from it
+	services
+		.rooms
+		.partial_state
+		.wait_for_full_state(room_id)
+		.await;
+
 	let mut device_list_updates = DeviceListUpdates::new();
 
 	// add users with changed keys to the `changed` list
@@ -1392,6 +1392,14 @@ async fn collect_e2ee<'a, Rooms>(
 	);
 
 	for room_id in all_joined_rooms {
+		// The member list of a partial-state room is incomplete until its full state
+		// is fetched.
+		services
+			.rooms
+			.partial_state
+			.wait_for_full_state(room_id)
+			.await;
+
 		let Ok(current_shortstatehash) =
 			services.rooms.state.get_room_shortstatehash(room_id).await
 		else {
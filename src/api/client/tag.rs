@@ -36,9 +36,10 @@ pub(crate) async fn update_tag_route(
 
 	services
 		.account_data
-		.update(
+		.update_from(
 			Some(&body.room_id),
 			sender_user,
+			body.identity.sender_device(),
 			RoomAccountDataEventType::Tag,
 			&serde_json::to_value(tags_event)?,
 		)
@@ -68,9 +69,10 @@ pub(crate) async fn delete_tag_route(
 
 	services
 		.account_data
-		.update(
+		.update_from(
 			Some(&body.room_id),
 			sender_user,
+			body.identity.sender_device(),
 			RoomAccountDataEventType::Tag,
 			&serde_json::to_value(tags_event)?,
 		)
@@ -1,13 +1,20 @@
 use axum::extract::State;
 use conduwuit::{
-	Result, at, debug_warn,
+	Err, Result, at, debug_warn,
 	matrix::{
 		Event,
 		pdu::{PduCount, PduEvent},
 	},
 };
+use conduwuit_service::Services;
 use futures::StreamExt;
-use ruma::{api::client::threads::get_threads, assign, uint};
+use ruma::{
+	EventId, RoomId, UserId,
+	api::client::threads::{
+		get_thread_subscription, get_threads, subscribe_thread, unsubscribe_thread,
+	},
+	assign, uint,
+};
 
 use crate::Ruma;
 
@@ -76,3 +83,103 @@ pub(crate) async fn get_threads_route(
 
 	Ok(assign!(get_threads::v1::Response::new(chunk), { next_batch }))
 }
+
+/// # `GET /_matrix/client/unstable/io.element.msc4306/rooms/{roomId}/thread/{threadRoot}/subscription`
+///
+/// Gets whether the user is subscribed to a thread, and whether the
+/// subscription was made automatically.
+pub(crate) async fn get_thread_subscription_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_thread_subscription::unstable::Request>,
+) -> Result<get_thread_subscription::unstable::Response> {
+	let sender_user = body.identity.expect_sender_user()?;
+	check_thread_visible(&services, sender_user, &body.room_id, &body.thread_root).await?;
+
+	match services
+		.rooms
+		.threads
+		.get_thread_subscription(sender_user, &body.room_id, &body.thread_root)
+		.await
+	{
+		| Ok(subscription) if subscription.subscribed =>
+			Ok(get_thread_subscription::unstable::Response::new(subscription.automatic)),
+		| _ => Err!(Request(NotFound("Not subscribed to this thread."))),
+	}
+}
+
+/// # `PUT /_matrix/client/unstable/io.element.msc4306/rooms/{roomId}/thread/{threadRoot}/subscription`
+///
+/// Subscribes the user to a thread. Automatic subscriptions name the event that
+/// caused them, and are refused if the user has unsubscribed since.
+pub(crate) async fn subscribe_thread_route(
+	State(services): State<crate::State>,
+	body: Ruma<subscribe_thread::unstable::Request>,
+) -> Result<subscribe_thread::unstable::Response> {
+	let sender_user = body.identity.expect_sender_user()?;
+	check_thread_visible(&services, sender_user, &body.room_id, &body.thread_root).await?;
+
+	let threads = &services.rooms.threads;
+	match body.automatic.as_deref() {
+		| Some(cause) => {
+			threads
+				.auto_subscribe_thread_by_event(
+					sender_user,
+					&body.room_id,
+					&body.thread_root,
+					cause,
+				)
+				.await?;
+		},
+		| None => {
+			threads
+				.subscribe_thread(sender_user, &body.room_id, &body.thread_root)
+				.await?;
+		},
+	}
+
+	Ok(subscribe_thread::unstable::Response::new())
+}
+
+/// # `DELETE /_matrix/client/unstable/io.element.msc4306/rooms/{roomId}/thread/{threadRoot}/subscription`
+///
+/// Unsubscribes the user from a thread and clears its unread notification
+/// counts.
+pub(crate) async fn unsubscribe_thread_route(
+	State(services): State<crate::State>,
+	body: Ruma<unsubscribe_thread::unstable::Request>,
+) -> Result<unsubscribe_thread::unstable::Response> {
+	let sender_user = body.identity.expect_sender_user()?;
+	check_thread_visible(&services, sender_user, &body.room_id, &body.thread_root).await?;
+
+	services
+		.rooms
+		.threads
+		.unsubscribe_thread(sender_user, &body.room_id, &body.thread_root)
+		.await?;
+
+	services
+		.rooms
+		.user
+		.reset_thread_notification_counts(sender_user, &body.room_id, &body.thread_root)
+		.await;
+
+	Ok(unsubscribe_thread::unstable::Response::new())
+}
+
+async fn check_thread_visible(
+	services: &Services,
+	sender_user: &UserId,
+	room_id: &RoomId,
+	thread_root: &EventId,
+) -> Result {
+	if !services
+		.rooms
+		.state_accessor
+		.user_can_see_event(sender_user, room_id, thread_root)
+		.await
+	{
+		return Err!(Request(NotFound("Thread root not found.")));
+	}
+
+	Ok(())
+}
@@ -42,6 +42,7 @@ pub fn build(router: Router<State>, state: State) -> Router<State> {
 		.ruma_route(&client::threepid::delete_3pid_route)
 		.ruma_route(&client::check_registration_token_validity)
 		.ruma_route(&client::get_capabilities_route)
+		.ruma_route(&client::get_notifications_route)
 		.ruma_route(&client::get_pushrules_all_route)
 		.ruma_route(&client::get_pushrules_global_route)
 		.ruma_route(&client::set_pushrule_route)
@@ -52,6 +53,7 @@ pub fn build(router: Router<State>, state: State) -> Router<State> {
 		.ruma_route(&client::set_pushrule_actions_route)
 		.ruma_route(&client::delete_pushrule_route)
 		.ruma_route(&client::get_room_event_route)
+		.ruma_route(&client::get_event_by_timestamp_route)
 		.ruma_route(&client::get_room_aliases_route)
 		.ruma_route(&client::get_filter_route)
 		.ruma_route(&client::create_filter_route)
@@ -149,6 +151,8 @@ pub fn build(router: Router<State>, state: State) -> Router<State> {
 		.ruma_route(&client::turn_server_route)
 		.ruma_route(&client::send_event_to_device_route)
 		.ruma_route(&client::create_content_route)
+		.ruma_route(&client::create_mxc_uri_route)
+		.ruma_route(&client::create_content_async_route)
 		.ruma_route(&client::get_content_thumbnail_route)
 		.ruma_route(&client::get_content_route)
 		.ruma_route(&client::get_content_as_filename_route)
@@ -173,6 +177,9 @@ pub fn build(router: Router<State>, state: State) -> Router<State> {
 		.ruma_route(&client::set_pushers_route)
 		.ruma_route(&client::upgrade_room_route)
 		.ruma_route(&client::get_threads_route)
+		.ruma_route(&client::get_thread_subscription_route)
+		.ruma_route(&client::subscribe_thread_route)
+		.ruma_route(&client::unsubscribe_thread_route)
 		.ruma_route(&client::get_relating_events_with_rel_type_and_event_type_route)
 		.ruma_route(&client::get_relating_events_with_rel_type_route)
 		.ruma_route(&client::get_relating_events_route)
@@ -193,7 +200,11 @@ pub fn build(router: Router<State>, state: State) -> Router<State> {
 		.merge(client::oauth::router(state))
 		.route("/_continuwuity/server_version", get(client::continuwuity_server_version))
 		.ruma_route(&admin::rooms::ban::ban_room)
-		.ruma_route(&admin::rooms::list::list_rooms);
+		.ruma_route(&admin::rooms::list::list_rooms)
+		// The log stream is not a ruma response, so only its request is typed.
+		.route("/_continuwuity/admin/server/logs", get(admin::server::logs::stream_logs))
+		.ruma_route(&admin::federation::report::connectivity_report)
+		.ruma_route(&admin::federation::request::signed_request);
 
 	if config.allow_federation {
 		router = router
@@ -208,6 +219,7 @@ pub fn build(router: Router<State>, state: State) -> Router<State> {
 			.ruma_route(&server::send_transaction_message_route)
 			.ruma_route(&server::get_event_route)
 			.ruma_route(&server::get_backfill_route)
+			.ruma_route(&server::get_event_by_timestamp_route)
 			.ruma_route(&server::get_missing_events_route)
 			.ruma_route(&server::get_event_authorization_route)
 			.ruma_route(&server::get_room_state_route)
@@ -21,6 +21,11 @@ pub(crate) async fn get_content_route(
 		media_id: &body.media_id,
 	};
 
+	services
+		.media
+		.wait_for_upload(&mxc, body.timeout_ms)
+		.await?;
+
 	let Some(FileMeta {
 		content,
 		content_type,
@@ -58,6 +63,11 @@ pub(crate) async fn get_content_thumbnail_route(
 		media_id: &body.media_id,
 	};
 
+	services
+		.media
+		.wait_for_upload(&mxc, body.timeout_ms)
+		.await?;
+
 	let Some(FileMeta {
 		content,
 		content_type,
@@ -91,6 +91,17 @@ pub(crate) async fn send_transaction_message_route(
 			wait_for_result(receiver).await
 		},
 		| FederationTxnState::Started { receiver, sender } => {
+			// Only new transactions are refused while the origin has too many
+			// queued PDUs, so retransmissions still get their cached response.
+			if let Err(e) = services
+				.rooms
+				.inbound_queue
+				.check_backpressure(&body.identity)
+			{
+				services.transactions.remove_federation_txn(&txn_key);
+				return Err(e);
+			}
+
 			// We're the first, spawn the processing task
 			services
 				.server
@@ -294,13 +305,6 @@ async fn handle_room(
 	room_id: OwnedRoomId,
 	pdus: impl Iterator<Item = Pdu> + Send,
 ) -> std::result::Result<Vec<(OwnedEventId, Result)>, TransactionError> {
-	let _room_lock = services
-		.rooms
-		.event_handler
-		.mutex_federation
-		.lock(room_id.as_str())
-		.await;
-
 	let room_id = &room_id;
 	let mut pdu_map: HashMap<OwnedEventId, CanonicalJsonObject> = pdus
 		.into_iter()
@@ -315,22 +319,32 @@ async fn handle_room(
 			debug_warn!("Failed to build local DAG for room {room_id}: {e}");
 			pdu_map.keys().cloned().collect()
 		});
-	let mut results = Vec::with_capacity(sorted_event_ids.len());
-	for event_id in sorted_event_ids {
-		let value = pdu_map
-			.remove(&event_id)
-			.expect("sorted event IDs must be from the original map");
-		services
-			.server
-			.check_running()
-			.map_err(|_| TransactionError::ShuttingDown)?;
-		let result = services
-			.rooms
-			.event_handler
-			.handle_incoming_pdu(origin, room_id, &event_id, value, false)
-			.boxed()
-			.await
-			.map(|_| ());
+
+	let pdus = sorted_event_ids
+		.into_iter()
+		.map(|event_id| {
+			let value = pdu_map
+				.remove(&event_id)
+				.expect("sorted event IDs must be from the original map");
+			(event_id, value)
+		})
+		.collect();
+
+	// The PDUs are handled by the inbound queue workers, which take the room
+	// lock and take turns between rooms and origins.
+	let receivers = services
+		.rooms
+		.inbound_queue
+		.enqueue(origin, room_id, pdus)
+		.await
+		.map_err(|e| {
+			error!(%room_id, "Failed to queue incoming PDUs: {e}");
+			TransactionError::Unexpected
+		})?;
+
+	let mut results = Vec::with_capacity(receivers.len());
+	for (event_id, receiver) in receivers {
+		let result = receiver.await.map_err(|_| TransactionError::ShuttingDown)?;
 		results.push((event_id, result));
 	}
 	Ok(results)
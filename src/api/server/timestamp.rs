@@ -0,0 +1,48 @@
+use axum::extract::State;
+use conduwuit::{Err, Event, Result};
+use ruma::api::federation::event::get_event_by_timestamp;
+
+use super::AccessCheck;
+use crate::Ruma;
+
+/// # `GET /_matrix/federation/v1/timestamp_to_event/{roomId}`
+///
+/// Finds the event closest to a timestamp in the given direction in our
+/// local timeline.
+pub(crate) async fn get_event_by_timestamp_route(
+	State(services): State<crate::State>,
+	ref body: Ruma<get_event_by_timestamp::v1::Request>,
+) -> Result<get_event_by_timestamp::v1::Response> {
+	AccessCheck {
+		services: &services,
+		origin: &body.identity,
+		room_id: &body.room_id,
+		event_id: None,
+	}
+	.assert()
+	.await?;
+
+	let Some(pdu) = services
+		.rooms
+		.timeline
+		.local_event_by_timestamp(&body.room_id, body.ts, body.dir)
+		.await
+		.event
+	else {
+		return Err!(Request(NotFound("Unable to find an event in that direction.")));
+	};
+
+	if !services
+		.rooms
+		.state_accessor
+		.server_can_see_event(&body.identity, &body.room_id, pdu.event_id())
+		.await
+	{
+		return Err!(Request(NotFound("Unable to find an event in that direction.")));
+	}
+
+	Ok(get_event_by_timestamp::v1::Response::new(
+		pdu.event_id().to_owned(),
+		pdu.origin_server_ts(),
+	))
+}
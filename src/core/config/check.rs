@@ -58,6 +58,38 @@ pub fn check(config: &Config) -> Result {
 		));
 	}
 
+	if config.unix_socket_path.is_some() && !config.listeners.is_empty() {
+		return Err!(Config(
+			"listeners",
+			"Listeners cannot be combined with 'unix_socket_path'. Please specify only one."
+		));
+	}
+
+	for (name, listener) in &config.listeners {
+		if listener.get_bind_hosts().is_empty() || listener.get_bind_ports().is_empty() {
+			return Err!(Config(
+				"listeners",
+				"Listener {name:?} has no addresses or ports to listen on"
+			));
+		}
+
+		if listener.resources.is_empty() {
+			warn!(
+				"Listener {name:?} has no resources enabled and will answer every request with \
+				 404"
+			);
+		}
+
+		if let Some(tls) = &listener.tls {
+			if tls.certs.is_none() || tls.key.is_none() {
+				return Err!(Config(
+					"listeners",
+					"Listener {name:?} requires both 'certs' and 'key' in its TLS settings"
+				));
+			}
+		}
+	}
+
 	if config.unix_socket_path.is_none() && config.get_bind_hosts().is_empty() {
 		return Err!(Config("address", "No TCP addresses were specified to listen on"));
 	}
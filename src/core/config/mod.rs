@@ -135,6 +135,43 @@ pub struct Config {
 	#[serde(default = "default_unix_socket_perms")]
 	pub unix_socket_perms: u32,
 
+	/// Named listeners, each with its own bind addresses, TLS settings and set
+	/// of enabled resources. This allows e.g. serving federation on port 8448
+	/// while keeping the admin API on an internal address, without a reverse
+	/// proxy splitting traffic by path.
+	///
+	/// When any listeners are defined, the top-level `address`, `port` and
+	/// `tls` options are ignored, and only the listeners are bound. Listeners
+	/// cannot be combined with `unix_socket_path`.
+	///
+	/// Available resources are "client", "federation", "media", "admin"
+	/// (the `/_continuwuity/admin` API) and "web" (the built-in web pages).
+	/// Server discovery (`/.well-known/matrix/server`) is part of "client", as
+	/// other servers fetch it from the same HTTPS port as clients. If
+	/// `resources` is omitted, all resources are enabled. Requests for a
+	/// disabled resource are answered with 404.
+	///
+	/// Example:
+	/// ```ignore
+	/// [global.listeners.public]
+	/// address = ["0.0.0.0", "::"]
+	/// port = 8008
+	/// resources = ["client", "media", "web"]
+	///
+	/// [global.listeners.federation]
+	/// address = ["0.0.0.0", "::"]
+	/// port = 8448
+	/// resources = ["federation", "media"]
+	/// tls = { certs = "/path/to/cert.crt", key = "/path/to/cert.key" }
+	///
+	/// [global.listeners.internal]
+	/// address = "127.0.0.1"
+	/// port = 8009
+	/// resources = ["admin"]
+	/// ```
+	#[serde(default)]
+	pub listeners: BTreeMap<String, ListenerConfig>,
+
 	/// This is the only directory where continuwuity will save its data,
 	/// including media. Note: this was previously "/var/lib/matrix-conduit".
 	///
@@ -540,6 +577,9 @@ pub struct Config {
 	/// response. This can be used for things like backfilling room data and
 	/// getting missing events.
 	///
+	/// Backfilling into a gap in a room's history requests at most 100 events
+	/// at a time.
+	///
 	/// default: 1024
 	#[serde(default = "default_max_fetch_prev_events")]
 	pub max_fetch_prev_events: u16,
@@ -555,6 +595,24 @@ pub struct Config {
 	#[serde(default = "default_max_concurrent_inbound_transactions")]
 	pub max_concurrent_inbound_transactions: usize,
 
+	/// Number of workers handling PDUs from the inbound federation queue.
+	/// Each worker handles one room at a time.
+	///
+	/// 0 uses as many workers as max_concurrent_inbound_transactions, as
+	/// handling PDUs mostly waits on other servers and the database.
+	///
+	/// default: 0
+	#[serde(default)]
+	pub inbound_pdu_workers: usize,
+
+	/// Maximum number of PDUs from a single server which may be waiting to be
+	/// handled. Transactions from the server are refused until its queue
+	/// drains below this number.
+	///
+	/// default: 1000
+	#[serde(default = "default_max_queued_inbound_pdus_per_origin")]
+	pub max_queued_inbound_pdus_per_origin: usize,
+
 	/// Maximum age (in seconds) for cached federation transaction responses.
 	/// Entries older than this will be removed during cleanup.
 	///
@@ -865,6 +923,40 @@ pub struct Config {
 	#[serde(default = "true_fn")]
 	pub allow_encryption: bool,
 
+	/// Maximum number of to-device events kept for a user's dehydrated device.
+	/// A dehydrated device only receives its events when the user restores it
+	/// on a new login, so they otherwise pile up indefinitely. The limit is
+	/// enforced hourly by dropping the oldest events.
+	///
+	/// Set to 0 to keep every event.
+	///
+	/// default: 10000
+	#[serde(default = "default_dehydrated_device_max_to_device_events")]
+	pub dehydrated_device_max_to_device_events: usize,
+
+	/// Maximum number of to-device events kept for each device. Devices which
+	/// stopped syncing otherwise keep accumulating them. The limit is enforced
+	/// hourly by dropping the oldest events. Enforcing it scans every device's
+	/// inbox, so it is disabled by default.
+	///
+	/// Set to 0 to keep every event.
+	///
+	/// default: 0
+	#[serde(default)]
+	pub max_to_device_events_per_device: usize,
+
+	/// Remove devices of local users which haven't been seen for this many
+	/// seconds, along with their keys and to-device events. The admin room is
+	/// notified of removed devices. Dehydrated devices are kept, and users can
+	/// opt out by setting the `org.continuwuity.device_expiry` global account
+	/// data to `{"opt_out": true}`.
+	///
+	/// Set to 0 to never remove inactive devices.
+	///
+	/// default: 0
+	#[serde(default)]
+	pub inactive_device_expiry_secs: u64,
+
 	/// Controls whether federation is allowed or not. It is not recommended to
 	/// disable this after the fact due to potential federation breakage.
 	#[serde(default = "true_fn")]
@@ -878,6 +970,38 @@ pub struct Config {
 	#[serde(default)]
 	pub federation_loopback: bool,
 
+	/// Federation endpoints which server admins may query through the
+	/// `GET /_continuwuity/admin/federation/request` API, to see what remote
+	/// servers answer to requests signed with this server's key. Every request
+	/// is logged. Leave empty to disable the API.
+	///
+	/// Possible values: "version", "keys", "event", "state", "state_ids",
+	/// "event_auth"
+	///
+	/// example: ["version", "keys", "event"]
+	///
+	/// default: []
+	#[serde(default)]
+	pub federation_request_endpoints: Vec<String>,
+
+	/// Join remote rooms with partial state (MSC3706). The resident server then
+	/// omits the other members from the join, so that large rooms can be used
+	/// right away. The full state is fetched in the background; the member list
+	/// and device lists of the room are only complete once that finishes.
+	///
+	/// Events received before then are authorised against their auth events
+	/// only, and re-checked against the full state once it has been fetched;
+	/// events which fail that check are soft-failed after the fact.
+	#[serde(default)]
+	pub partial_state_joins: bool,
+
+	/// Check every change of a room's current state caused by an incoming
+	/// event for memberships and power levels which went back to an earlier
+	/// event, a sign of a state reset. Suspected resets are logged, recorded
+	/// for `!admin rooms state-reset` and announced in the admin room.
+	#[serde(default = "true_fn")]
+	pub state_reset_detection: bool,
+
 	/// Always calls /forget on behalf of the user if leaving a room. This is a
 	/// part of MSC4267 "Automatically forgetting rooms on leave"
 	#[serde(default)]
@@ -905,6 +1029,30 @@ pub struct Config {
 	#[serde(default)]
 	pub lockdown_public_room_directory: bool,
 
+	/// How long (in seconds) public room lists fetched from remote servers are
+	/// cached for. Lists which are still being requested are refreshed in the
+	/// background, so browsing other servers' directories does not wait on
+	/// federation. Set this to 0 to disable caching.
+	///
+	/// default: 300
+	#[serde(default = "default_remote_room_directory_cache_ttl")]
+	pub remote_room_directory_cache_ttl: u64,
+
+	/// How long (in seconds) room aliases of other servers are cached for
+	/// after being resolved over federation, at most one week. Set this to 0 to
+	/// disable caching.
+	///
+	/// default: 600
+	#[serde(default = "default_remote_alias_cache_ttl")]
+	pub remote_alias_cache_ttl: u64,
+
+	/// How long (in seconds) room aliases which other servers reported as
+	/// non-existent are cached for. Set this to 0 to disable negative caching.
+	///
+	/// default: 60
+	#[serde(default = "default_remote_alias_negative_cache_ttl")]
+	pub remote_alias_negative_cache_ttl: u64,
+
 	/// Set this to true to allow federating device display names / allow
 	/// external users to see your device display name. If federation is
 	/// disabled entirely (`allow_federation`), this is inherently false. For
@@ -1162,6 +1310,21 @@ pub struct Config {
 	#[serde(default)]
 	pub log_thread_ids: bool,
 
+	/// Number of recent log events kept in memory, which can be searched and
+	/// followed with `!admin server logs` and the admin API. Set to 0 to
+	/// disable the log history.
+	///
+	/// default: 4096
+	#[serde(default = "default_log_history_capacity")]
+	pub log_history_capacity: usize,
+
+	/// Log filter for events kept in the log history, independent of the
+	/// console `log` filter. The syntax is the same as `log`.
+	///
+	/// default: "info"
+	#[serde(default = "default_log_history_filter")]
+	pub log_history_filter: String,
+
 	/// Enable journald logging on Unix platforms
 	///
 	/// When enabled, log output will be sent to the systemd journal
@@ -1543,6 +1706,16 @@ pub struct Config {
 	#[serde(default = "true_fn")]
 	pub rocksdb_compaction: bool,
 
+	/// Re-compress room state in the background on startup. State groups whose
+	/// chain of diffs grew deeper than the server builds today, or which need
+	/// more than twice the size of their state in diffs to load, are rewritten
+	/// as full snapshots. This speeds up loading the state of long-lived rooms
+	/// at the cost of some disk space.
+	///
+	/// The same can be done on demand with `!admin debug recompress-state`.
+	#[serde(default)]
+	pub state_recompress_on_startup: bool,
+
 	/// Level of statistics collection. Some admin commands to display database
 	/// statistics may require this option to be set. Database performance may
 	/// be impacted by higher settings.
@@ -1589,6 +1762,35 @@ pub struct Config {
 	#[serde(default = "default_notification_push_path")]
 	pub notification_push_path: String,
 
+	/// Maximum age (in seconds) of entries in the notification log, which
+	/// backs the `/notifications` endpoint used by clients to list recent
+	/// mentions and notifications. Older entries are removed periodically.
+	///
+	/// Set to 0 to keep notifications forever.
+	///
+	/// default: 2592000 (30 days)
+	#[serde(default = "default_notification_log_max_age_secs")]
+	pub notification_log_max_age_secs: u64,
+
+	/// Number of changes to keep in the history of each account data type of
+	/// each user, for the `!admin query account-data history` and `restore`
+	/// commands. Every entry includes the previous value, so that a version
+	/// clobbered by a misbehaving client can be restored.
+	///
+	/// Set to 0 to disable account data history.
+	///
+	/// default: 20
+	#[serde(default = "default_account_data_history_size")]
+	pub account_data_history_size: usize,
+
+	/// Account data types whose changes are not recorded in the account data
+	/// history. Types that clients update constantly, like read markers, would
+	/// otherwise add a write of the previous value to every update.
+	///
+	/// default: ["m.fully_read"]
+	#[serde(default = "default_account_data_history_ignored_types")]
+	pub account_data_history_ignored_types: Vec<String>,
+
 	/// Allow local (your server only) presence updates/requests.
 	///
 	/// Local presence must be enabled for outgoing presence to function.
@@ -1802,6 +2004,22 @@ pub struct Config {
 	#[serde(default)]
 	pub prune_missing_media: bool,
 
+	/// How long (in seconds) a media ID reserved with `POST
+	/// /_matrix/media/v1/create` may remain unused before the reservation
+	/// expires. Clients can send events referencing the reserved MXC URI
+	/// before uploading the content to it.
+	///
+	/// default: 86400 (24 hours)
+	#[serde(default = "default_media_pending_upload_expiry_secs")]
+	pub media_pending_upload_expiry_secs: u64,
+
+	/// Maximum number of unexpired media ID reservations a single user may
+	/// have awaiting upload at once.
+	///
+	/// default: 5
+	#[serde(default = "default_max_pending_media_uploads")]
+	pub max_pending_media_uploads: usize,
+
 	/// List of forbidden server names via regex patterns that we will block
 	/// incoming AND outgoing federation with, and block client room joins /
 	/// remote user invites.
@@ -1878,6 +2096,9 @@ pub struct Config {
 	/// continuwuity will still send join requests to servers in this list if
 	/// the room couldn't be joined via other servers it federates with.
 	///
+	/// PDUs received from servers in this list are also handled after PDUs
+	/// from other servers.
+	///
 	/// example: ["example.com"]
 	///
 	/// default: []
@@ -2416,6 +2637,57 @@ pub struct TlsConfig {
 	pub dual_protocol: bool,
 }
 
+/// A named listener configured under `[global.listeners.<name>]`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ListenerConfig {
+	/// The address(es) this listener binds to. Defaults to localhost.
+	#[serde(default = "default_address")]
+	address: ListeningAddr,
+
+	/// The port(s) this listener binds to.
+	port: ListeningPort,
+
+	/// Serve this listener over TLS with the given certificate and key.
+	#[serde(default)]
+	pub tls: Option<TlsConfig>,
+
+	/// The resources served on this listener. Defaults to all resources.
+	#[serde(default = "ListenerResource::all")]
+	pub resources: BTreeSet<ListenerResource>,
+}
+
+/// A group of endpoints which can be enabled on a listener.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenerResource {
+	/// The client-server API, including client and server discovery.
+	Client,
+	/// The server-server API and server keys.
+	Federation,
+	/// The legacy and authenticated media repository.
+	Media,
+	/// The `/_continuwuity/admin` API.
+	Admin,
+	/// The built-in web pages.
+	Web,
+}
+
+impl ListenerResource {
+	#[must_use]
+	pub fn all() -> BTreeSet<Self> {
+		[Self::Client, Self::Federation, Self::Media, Self::Admin, Self::Web].into()
+	}
+}
+
+impl ListenerConfig {
+	#[must_use]
+	pub fn get_bind_addrs(&self) -> Vec<SocketAddr> { bind_addrs(&self.address, &self.port) }
+
+	fn get_bind_hosts(&self) -> Vec<IpAddr> { self.address.hosts() }
+
+	fn get_bind_ports(&self) -> Vec<u16> { self.port.ports() }
+}
+
 #[allow(rustdoc::broken_intra_doc_links, rustdoc::bare_urls)]
 #[derive(Clone, Debug, Deserialize, Default)]
 #[config_example_generator(filename = "conduwuit-example.toml", section = "global.well_known")]
@@ -2860,36 +3132,43 @@ pub fn new(raw_config: &Figment) -> Result<Self> {
 	}
 
 	#[must_use]
-	pub fn get_bind_addrs(&self) -> Vec<SocketAddr> {
-		let mut addrs = Vec::with_capacity(
-			self.get_bind_hosts()
-				.len()
-				.saturating_mul(self.get_bind_ports().len()),
-		);
-		for host in &self.get_bind_hosts() {
-			for port in &self.get_bind_ports() {
-				addrs.push(SocketAddr::new(*host, *port));
-			}
-		}
+	pub fn get_bind_addrs(&self) -> Vec<SocketAddr> { bind_addrs(&self.address, &self.port) }
 
-		addrs
-	}
+	fn get_bind_hosts(&self) -> Vec<IpAddr> { self.address.hosts() }
+
+	fn get_bind_ports(&self) -> Vec<u16> { self.port.ports() }
 
-	fn get_bind_hosts(&self) -> Vec<IpAddr> {
-		match &self.address.addrs {
+	pub fn check(&self) -> Result<(), Error> { check(self) }
+}
+
+impl ListeningAddr {
+	fn hosts(&self) -> Vec<IpAddr> {
+		match &self.addrs {
 			| Left(addr) => vec![*addr],
 			| Right(addrs) => addrs.clone(),
 		}
 	}
+}
 
-	fn get_bind_ports(&self) -> Vec<u16> {
-		match &self.port.ports {
+impl ListeningPort {
+	fn ports(&self) -> Vec<u16> {
+		match &self.ports {
 			| Left(port) => vec![*port],
 			| Right(ports) => ports.clone(),
 		}
 	}
+}
 
-	pub fn check(&self) -> Result<(), Error> { check(self) }
+fn bind_addrs(address: &ListeningAddr, port: &ListeningPort) -> Vec<SocketAddr> {
+	let (hosts, ports) = (address.hosts(), port.ports());
+	let mut addrs = Vec::with_capacity(hosts.len().saturating_mul(ports.len()));
+	for host in &hosts {
+		for port in &ports {
+			addrs.push(SocketAddr::new(*host, *port));
+		}
+	}
+
+	addrs
 }
 
 fn true_fn() -> bool { true }
@@ -3006,6 +3285,8 @@ fn default_max_fetch_prev_events() -> u16 { 1024 }
 
 fn default_max_concurrent_inbound_transactions() -> usize { 150 }
 
+fn default_max_queued_inbound_pdus_per_origin() -> usize { 1000 }
+
 fn default_transaction_id_cache_max_age_secs() -> u64 { 60 * 60 * 2 }
 
 fn default_transaction_id_cache_max_entries() -> usize { 8192 }
@@ -3041,11 +3322,27 @@ pub fn default_log() -> String {
 		.to_owned()
 }
 
+fn default_log_history_capacity() -> usize { 4096 }
+
+fn default_log_history_filter() -> String { "info".to_owned() }
+
 #[must_use]
 pub fn default_log_span_events() -> String { "none".into() }
 
 fn default_notification_push_path() -> String { "/_matrix/push/v1/notify".to_owned() }
 
+fn default_notification_log_max_age_secs() -> u64 { 60 * 60 * 24 * 30 }
+
+fn default_account_data_history_size() -> usize { 20 }
+
+fn default_account_data_history_ignored_types() -> Vec<String> { vec!["m.fully_read".to_owned()] }
+
+fn default_dehydrated_device_max_to_device_events() -> usize { 10_000 }
+
+fn default_media_pending_upload_expiry_secs() -> u64 { 60 * 60 * 24 }
+
+fn default_max_pending_media_uploads() -> usize { 5 }
+
 fn default_openid_token_ttl() -> u64 { 60 * 60 }
 
 fn default_login_token_ttl() -> u64 { 2 * 60 * 1000 }
@@ -3137,6 +3434,12 @@ fn default_url_preview_max_spider_size() -> usize {
 
 fn default_url_preview_timeout() -> u64 { 120 }
 
+fn default_remote_room_directory_cache_ttl() -> u64 { 300 }
+
+fn default_remote_alias_cache_ttl() -> u64 { 600 }
+
+fn default_remote_alias_negative_cache_ttl() -> u64 { 60 }
+
 fn default_new_user_displayname_suffix() -> String { "🏳️‍⚧️".to_owned() }
 
 fn default_sentry_endpoint() -> Option<Url> { None }
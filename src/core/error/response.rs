@@ -67,9 +67,15 @@ pub(super) fn bad_request_code(kind: &ErrorKind) -> StatusCode {
 	use ErrorKind::*;
 
 	match kind {
+		// 504
+		| NotYetUploaded => StatusCode::GATEWAY_TIMEOUT,
+
 		// 429
 		| LimitExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
 
+		// 409
+		| CannotOverwriteMedia | ConflictingUnsubscription => StatusCode::CONFLICT,
+
 		// 413
 		| TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
 
@@ -0,0 +1,285 @@
+//! In-memory ring buffer of recent log events, which can be searched or
+//! followed live from the admin room and admin API.
+
+use std::{collections::VecDeque, fmt, sync::Arc, time::SystemTime};
+
+use tokio::sync::broadcast;
+use tracing::{
+	Level,
+	field::{Field, Visit},
+	span::{Attributes, Id, Record as SpanRecord},
+};
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan};
+
+use crate::SyncMutex;
+
+/// Number of records buffered for each live subscriber before it starts
+/// missing records.
+const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// Log history state.
+pub struct History {
+	records: SyncMutex<VecDeque<Arc<Record>>>,
+	capacity: usize,
+	sender: broadcast::Sender<Arc<Record>>,
+}
+
+/// A log event retained in the history.
+#[derive(Debug)]
+pub struct Record {
+	pub timestamp: SystemTime,
+	pub level: Level,
+	pub target: &'static str,
+	/// Names of the spans the event occurred in, outermost first.
+	pub spans: Vec<&'static str>,
+	/// Fields of the event followed by the fields of its spans, innermost
+	/// first.
+	pub fields: Vec<Value>,
+	pub message: String,
+}
+
+pub type Value = (&'static str, String);
+
+/// Criteria for selecting records from the history. Unset criteria match
+/// every record.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+	/// Least severe level to include.
+	pub level: Option<Level>,
+	/// Prefix of the event target (usually the module path).
+	pub target: Option<String>,
+	/// Name of a span the event must have occurred in.
+	pub span: Option<String>,
+	/// Value of a `room_id` field of the event or its spans.
+	pub room_id: Option<String>,
+	/// Value of a `user_id` or `sender` field of the event or its spans.
+	pub user_id: Option<String>,
+	/// Case-insensitive text to find in the message or any field value.
+	pub text: Option<String>,
+}
+
+pub struct Layer {
+	history: Arc<History>,
+}
+
+/// Fields recorded on a span, stored in its extensions.
+struct SpanFields(Vec<Value>);
+
+struct Visitor<'a> {
+	values: &'a mut Vec<Value>,
+}
+
+impl History {
+	#[must_use]
+	pub fn new(capacity: usize) -> Self {
+		let (sender, _) = broadcast::channel(SUBSCRIBER_BUFFER);
+		Self {
+			records: SyncMutex::new(VecDeque::with_capacity(capacity)),
+			capacity,
+			sender,
+		}
+	}
+
+	/// Whether log events are retained at all.
+	#[inline]
+	#[must_use]
+	pub fn is_enabled(&self) -> bool { self.capacity > 0 }
+
+	/// Maximum number of records retained.
+	#[inline]
+	#[must_use]
+	pub fn capacity(&self) -> usize { self.capacity }
+
+	/// Number of records currently retained.
+	#[must_use]
+	pub fn len(&self) -> usize { self.records.lock().len() }
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool { self.records.lock().is_empty() }
+
+	/// Returns up to `limit` of the most recent records matching the filter,
+	/// oldest first.
+	#[must_use]
+	pub fn search(&self, filter: &Filter, limit: usize) -> Vec<Arc<Record>> {
+		let mut records: Vec<_> = self
+			.records
+			.lock()
+			.iter()
+			.rev()
+			.filter(|record| filter.matches(record))
+			.take(limit)
+			.cloned()
+			.collect();
+
+		records.reverse();
+		records
+	}
+
+	/// Subscribes to records as they are added to the history. Slow
+	/// subscribers miss records rather than holding back logging.
+	#[must_use]
+	pub fn subscribe(&self) -> broadcast::Receiver<Arc<Record>> { self.sender.subscribe() }
+
+	fn push(&self, record: Record) {
+		if !self.is_enabled() {
+			return;
+		}
+
+		let record = Arc::new(record);
+		{
+			let mut records = self.records.lock();
+			if records.len() >= self.capacity {
+				records.pop_front();
+			}
+
+			records.push_back(record.clone());
+		}
+
+		// Fails only when nobody is subscribed.
+		_ = self.sender.send(record);
+	}
+}
+
+impl Record {
+	/// Finds the value of the innermost field with the given name.
+	#[must_use]
+	pub fn field(&self, name: &str) -> Option<&str> {
+		self.fields
+			.iter()
+			.find(|(key, _)| *key == name)
+			.map(|(_, value)| value.as_str())
+	}
+
+	fn has_field_value(&self, names: &[&str], value: &str) -> bool {
+		self.fields
+			.iter()
+			.filter(|(key, _)| names.contains(key))
+			.any(|(_, field)| field.trim_matches('"') == value)
+	}
+}
+
+impl Filter {
+	#[must_use]
+	pub fn matches(&self, record: &Record) -> bool {
+		if self.level.is_some_and(|level| record.level > level) {
+			return false;
+		}
+
+		if self
+			.target
+			.as_deref()
+			.is_some_and(|target| !record.target.starts_with(target))
+		{
+			return false;
+		}
+
+		if self
+			.span
+			.as_deref()
+			.is_some_and(|span| !record.spans.contains(&span))
+		{
+			return false;
+		}
+
+		if self
+			.room_id
+			.as_deref()
+			.is_some_and(|room_id| !record.has_field_value(&["room_id"], room_id))
+		{
+			return false;
+		}
+
+		if self.user_id.as_deref().is_some_and(|user_id| {
+			!record.has_field_value(&["user_id", "sender", "sender_user"], user_id)
+		}) {
+			return false;
+		}
+
+		self.text.as_deref().is_none_or(|text| {
+			let text = text.to_lowercase();
+			record.message.to_lowercase().contains(&text)
+				|| record
+					.fields
+					.iter()
+					.any(|(_, value)| value.to_lowercase().contains(&text))
+		})
+	}
+}
+
+impl Layer {
+	#[inline]
+	pub fn new(history: &Arc<History>) -> Self { Self { history: history.clone() } }
+}
+
+impl fmt::Debug for Layer {
+	#[inline]
+	fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+		formatter.debug_struct("history::Layer").finish()
+	}
+}
+
+impl<S> tracing_subscriber::Layer<S> for Layer
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+		let Some(span) = ctx.span(id) else {
+			return;
+		};
+
+		let mut values = Vec::new();
+		attrs.record(&mut Visitor { values: &mut values });
+		span.extensions_mut().insert(SpanFields(values));
+	}
+
+	fn on_record(&self, id: &Id, values: &SpanRecord<'_>, ctx: Context<'_, S>) {
+		let Some(span) = ctx.span(id) else {
+			return;
+		};
+
+		if let Some(SpanFields(fields)) = span.extensions_mut().get_mut::<SpanFields>() {
+			values.record(&mut Visitor { values: fields });
+		}
+	}
+
+	fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+		let mut fields = Vec::new();
+		event.record(&mut Visitor { values: &mut fields });
+
+		let message = fields
+			.iter()
+			.position(|(key, _)| *key == "message")
+			.map(|pos| fields.remove(pos).1)
+			.unwrap_or_default();
+
+		let mut spans = Vec::new();
+		if let Some(scope) = ctx.event_scope(event) {
+			for span in scope {
+				spans.push(span.name());
+				if let Some(SpanFields(values)) = span.extensions().get::<SpanFields>() {
+					fields.extend(values.iter().cloned());
+				}
+			}
+		}
+
+		spans.reverse();
+		let metadata = event.metadata();
+		self.history.push(Record {
+			timestamp: SystemTime::now(),
+			level: *metadata.level(),
+			target: metadata.target(),
+			spans,
+			fields,
+			message,
+		});
+	}
+}
+
+impl Visit for Visitor<'_> {
+	fn record_debug(&mut self, f: &Field, v: &dyn fmt::Debug) {
+		self.values.push((f.name(), format!("{v:?}")));
+	}
+
+	fn record_str(&mut self, f: &Field, v: &str) { self.values.push((f.name(), v.to_owned())); }
+}
@@ -5,11 +5,13 @@
 pub mod console;
 pub mod fmt;
 pub mod fmt_span;
+pub mod history;
 mod reload;
 mod suppress;
 
 pub use capture::Capture;
 pub use console::{ConsoleFormat, ConsoleWriter, is_systemd_mode};
+pub use history::History;
 pub use reload::{LogLevelReloadHandles, ReloadHandle};
 pub use suppress::Suppress;
 pub use tracing::Level;
@@ -25,6 +27,9 @@ pub struct Log {
 
 	/// Tracing capture state for ephemeral/oneshot uses.
 	pub capture: std::sync::Arc<capture::State>,
+
+	/// Recent log events retained for the admin room and admin API.
+	pub history: std::sync::Arc<History>,
 }
 
 // Wraps for logging macros. Use these macros rather than extern tracing:: or
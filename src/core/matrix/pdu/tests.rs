@@ -108,3 +108,17 @@ fn set_unsigned_adds_the_sticky_ttl() {
 
 	assert!(unsigned["msc4354_sticky_duration_ttl_ms"].is_number());
 }
+
+#[test]
+fn gap_marker_keeps_other_unsigned_fields() {
+	let mut pdu = sticky_pdu(&serde_json::json!({ "duration_ms": 300_000 }));
+	pdu.add_age().expect("age is added");
+	pdu.add_gap_marker().expect("gap marker is added");
+
+	let unsigned: serde_json::Value =
+		serde_json::from_str(pdu.unsigned.as_deref().expect("unsigned is set").get())
+			.expect("unsigned is valid");
+
+	assert_eq!(unsigned["io.continuwuity.gap"], true);
+	assert!(unsigned["age"].is_number());
+}
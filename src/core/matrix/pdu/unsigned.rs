@@ -6,6 +6,10 @@
 use super::{Pdu, sticky};
 use crate::{Result, err, result::LogErr};
 
+/// Unsigned key marking events whose prev events are missing from our
+/// timeline, i.e. the history right before them is incomplete.
+const GAP_UNSIGNED_KEY: &str = "io.continuwuity.gap";
+
 impl Pdu {
 	/// Set the `unsigned` field of the PDU using only information in the PDU.
 	/// Some unsigned data is already set within the database (eg. prev events,
@@ -84,6 +88,22 @@ pub fn add_sticky_duration_ttl(&mut self) -> Result {
 		Ok(())
 	}
 
+	pub fn add_gap_marker(&mut self) -> Result {
+		use BTreeMap as Map;
+
+		let mut unsigned: Map<&str, Box<RawJsonValue>> = self
+			.unsigned
+			.as_deref()
+			.map(RawJsonValue::get)
+			.map_or_else(|| Ok(Map::new()), serde_json::from_str)
+			.map_err(|e| err!(Database("Invalid unsigned in pdu event: {e}")))?;
+
+		unsigned.insert(GAP_UNSIGNED_KEY, to_raw_value(&true)?);
+		self.unsigned = Some(to_raw_value(&unsigned)?);
+
+		Ok(())
+	}
+
 	pub fn add_relation(&mut self, name: &str, pdu: Option<&Self>) -> Result {
 		use serde_json::Map;
 
@@ -0,0 +1,535 @@
+//! Chain cover index
+//!
+//! Every event in the auth DAG is placed on a chain at a sequence number, such
+//! that each event on a chain has the previous event of that chain in its auth
+//! chain. A position `(chain, seq)` therefore covers every earlier event of its
+//! chain, and links from a position to positions on other chains cover the rest
+//! of the event's auth events. The auth chain difference of a set of state sets
+//! then comes down to comparing how far each set reaches along every chain,
+//! instead of loading and diffing their full auth chains.
+//!
+//! State events extend the chain of the event they replace when that event is
+//! the tip of its chain, which keeps the number of chains of a room close to
+//! the number of distinct state keys in its auth DAG.
+
+use std::collections::{HashMap, HashSet};
+
+use futures::Future;
+use ruma::{EventId, OwnedEventId, RoomId};
+
+use crate::{
+	Err, Result, debug_error, error,
+	matrix::{Event, Pdu},
+};
+
+/// Position of an event in the chain cover index, as its chain and its
+/// sequence number on that chain. Sequence numbers start at 1.
+pub type ChainPos = (u64, u64);
+
+/// Storage of the chain cover index and of the events it indexes.
+pub trait ChainStore: Sync {
+	/// Gets the position of an indexed event.
+	fn get_position(&self, event_id: &EventId) -> impl Future<Output = Option<ChainPos>> + Send;
+
+	/// Places an event at a position, which becomes the tip of its chain.
+	fn set_position(&self, event_id: &EventId, pos: ChainPos) -> impl Future<Output = ()> + Send;
+
+	/// Gets the sequence number of the last event of a chain, or 0.
+	fn chain_tip(&self, chain: u64) -> impl Future<Output = u64> + Send;
+
+	/// Allocates the ID of a new chain.
+	fn new_chain(&self) -> Result<u64>;
+
+	/// Gets the furthest sequence number of the target chain linked from a
+	/// position, or 0.
+	fn get_link(&self, pos: ChainPos, target_chain: u64) -> impl Future<Output = u64> + Send;
+
+	/// Links a position to a position on another chain.
+	fn set_link(&self, pos: ChainPos, target: ChainPos);
+
+	/// Gets the links of the positions `from..=to` of a chain to other chains.
+	fn chain_links(
+		&self,
+		chain: u64,
+		from: u64,
+		to: u64,
+	) -> impl Future<Output = Result<Vec<ChainPos>>> + Send;
+
+	/// Gets the events at the positions `from..=to` of a chain.
+	fn chain_events(
+		&self,
+		chain: u64,
+		from: u64,
+		to: u64,
+	) -> impl Future<Output = Result<Vec<OwnedEventId>>> + Send;
+
+	/// Gets an event to index.
+	fn get_event(&self, event_id: &EventId) -> impl Future<Output = Option<Pdu>> + Send;
+}
+
+/// Computes the events which are in the auth chains of some but not all of
+/// the given state sets, given as the positions of their events.
+pub async fn auth_chain_difference<S: ChainStore>(
+	store: &S,
+	state_sets: &[Vec<ChainPos>],
+) -> Result<HashSet<OwnedEventId>> {
+	let mut reaches = Vec::with_capacity(state_sets.len());
+	for positions in state_sets {
+		reaches.push(chain_reach(store, positions).await?);
+	}
+
+	let chains: HashSet<u64> = reaches.iter().flat_map(HashMap::keys).copied().collect();
+
+	let mut difference = HashSet::new();
+	for chain in chains {
+		let reach = |reach: &HashMap<u64, u64>| reach.get(&chain).copied().unwrap_or(0);
+		let min = reaches.iter().map(reach).min().unwrap_or(0);
+		let max = reaches.iter().map(reach).max().unwrap_or(0);
+		if min >= max {
+			continue;
+		}
+
+		difference.extend(
+			store
+				.chain_events(chain, min.saturating_add(1), max)
+				.await?,
+		);
+	}
+
+	Ok(difference)
+}
+
+/// Computes the furthest sequence number reached on each chain by the auth
+/// chains of the events at the given positions.
+async fn chain_reach<S: ChainStore>(
+	store: &S,
+	positions: &[ChainPos],
+) -> Result<HashMap<u64, u64>> {
+	let mut todo: Vec<ChainPos> = Vec::new();
+	for &(chain, seq) in positions {
+		todo.push((chain, seq.saturating_sub(1)));
+		todo.extend(store.chain_links(chain, seq, seq).await?);
+	}
+
+	let mut reach = HashMap::<u64, u64>::new();
+	while let Some((chain, seq)) = todo.pop() {
+		let reached = reach.get(&chain).copied().unwrap_or(0);
+		if seq <= reached {
+			continue;
+		}
+
+		reach.insert(chain, seq);
+		todo.extend(
+			store
+				.chain_links(chain, reached.saturating_add(1), seq)
+				.await?,
+		);
+	}
+
+	Ok(reach)
+}
+
+/// Indexes an event and every auth event it transitively depends on which
+/// isn't indexed yet, auth events first. Fails without placing the event if
+/// one of those events is missing, as its position would not cover the missing
+/// part of its auth chain. Indexing the same room concurrently is not allowed.
+pub async fn index_event<S: ChainStore>(
+	store: &S,
+	room_id: &RoomId,
+	event_id: &EventId,
+) -> Result {
+	let mut todo: Vec<(OwnedEventId, bool)> = vec![(event_id.to_owned(), false)];
+	while let Some((event_id, expanded)) = todo.pop() {
+		if store.get_position(&event_id).await.is_some() {
+			continue;
+		}
+
+		let Some(pdu) = store.get_event(&event_id).await else {
+			return Err!(Request(NotFound(debug_error!(
+				%event_id,
+				"Could not find pdu to index in the chain cover"
+			))));
+		};
+
+		if pdu
+			.room_id()
+			.is_some_and(|pdu_room_id| pdu_room_id != room_id)
+		{
+			return Err!(Request(Forbidden(error!(
+				%event_id,
+				%room_id,
+				wrong_room_id = ?pdu.room_id(),
+				"auth event for incorrect room"
+			))));
+		}
+
+		if !expanded {
+			todo.push((event_id, true));
+			todo.extend(
+				pdu.auth_events()
+					.map(|auth_event| (auth_event.to_owned(), false)),
+			);
+
+			continue;
+		}
+
+		let mut auth_positions = Vec::new();
+		let mut extends = None;
+		for auth_event in pdu.auth_events() {
+			let Some(pos) = store.get_position(auth_event).await else {
+				return Err!(Request(NotFound(debug_error!(
+					%event_id,
+					%auth_event,
+					"Auth event is not indexed in the chain cover"
+				))));
+			};
+
+			if extends.is_none()
+				&& pdu.state_key().is_some()
+				&& store.chain_tip(pos.0).await == pos.1
+				&& store.get_event(auth_event).await.is_some_and(|auth| {
+					auth.kind() == pdu.kind() && auth.state_key() == pdu.state_key()
+				}) {
+				extends = Some(pos);
+			}
+
+			auth_positions.push(pos);
+		}
+
+		let (chain, seq) = match extends {
+			| Some((chain, seq)) => (chain, seq.saturating_add(1)),
+			| None => (store.new_chain()?, 1),
+		};
+
+		store.set_position(&event_id, (chain, seq)).await;
+
+		for (target_chain, target_seq) in auth_positions {
+			if target_chain == chain {
+				continue;
+			}
+
+			if target_seq > store.get_link((chain, seq), target_chain).await {
+				store.set_link((chain, seq), (target_chain, target_seq));
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		collections::{BTreeMap, HashMap, HashSet},
+		sync::Mutex,
+	};
+
+	use ruma::{EventId, OwnedEventId, events::TimelineEventType};
+	use serde_json::{json, value::to_raw_value as to_raw_json_value};
+
+	use super::{ChainPos, ChainStore, auth_chain_difference, index_event};
+	use crate::{
+		Result,
+		matrix::{
+			Event, Pdu,
+			state_res::test_utils::{
+				INITIAL_EVENTS, TestStore, alice, bob, charlie, event_id, member_content_ban,
+				member_content_join, room_id, to_pdu_event,
+			},
+		},
+	};
+
+	#[derive(Default)]
+	struct Index {
+		positions: HashMap<OwnedEventId, ChainPos>,
+		chains: BTreeMap<ChainPos, OwnedEventId>,
+		tips: HashMap<u64, u64>,
+		links: BTreeMap<(u64, u64, u64), u64>,
+		next_chain: u64,
+	}
+
+	struct MemoryStore {
+		events: HashMap<OwnedEventId, Pdu>,
+		index: Mutex<Index>,
+	}
+
+	impl ChainStore for MemoryStore {
+		async fn get_position(&self, event_id: &EventId) -> Option<ChainPos> {
+			self.index.lock().unwrap().positions.get(event_id).copied()
+		}
+
+		async fn set_position(&self, event_id: &EventId, pos: ChainPos) {
+			let mut index = self.index.lock().unwrap();
+			index.positions.insert(event_id.to_owned(), pos);
+			index.chains.insert(pos, event_id.to_owned());
+			index.tips.insert(pos.0, pos.1);
+		}
+
+		async fn chain_tip(&self, chain: u64) -> u64 {
+			self.index
+				.lock()
+				.unwrap()
+				.tips
+				.get(&chain)
+				.copied()
+				.unwrap_or(0)
+		}
+
+		fn new_chain(&self) -> Result<u64> {
+			let mut index = self.index.lock().unwrap();
+			index.next_chain += 1;
+			Ok(index.next_chain)
+		}
+
+		async fn get_link(&self, (chain, seq): ChainPos, target_chain: u64) -> u64 {
+			let index = self.index.lock().unwrap();
+			index
+				.links
+				.get(&(chain, seq, target_chain))
+				.copied()
+				.unwrap_or(0)
+		}
+
+		fn set_link(&self, (chain, seq): ChainPos, (target_chain, target_seq): ChainPos) {
+			let mut index = self.index.lock().unwrap();
+			index.links.insert((chain, seq, target_chain), target_seq);
+		}
+
+		async fn chain_links(&self, chain: u64, from: u64, to: u64) -> Result<Vec<ChainPos>> {
+			let index = self.index.lock().unwrap();
+			Ok(index
+				.links
+				.range((chain, from, 0)..=(chain, to, u64::MAX))
+				.map(|(&(_, _, target_chain), &target_seq)| (target_chain, target_seq))
+				.collect())
+		}
+
+		async fn chain_events(
+			&self,
+			chain: u64,
+			from: u64,
+			to: u64,
+		) -> Result<Vec<OwnedEventId>> {
+			let index = self.index.lock().unwrap();
+			Ok(index
+				.chains
+				.range((chain, from)..=(chain, to))
+				.map(|(_, event_id)| event_id.clone())
+				.collect())
+		}
+
+		async fn get_event(&self, event_id: &EventId) -> Option<Pdu> {
+			self.events.get(event_id).cloned()
+		}
+	}
+
+	/// The initial events, then a fork where alice raises bob's power level and
+	/// bob changes the topic and leaves, against a fork where alice changes the
+	/// topic and bans bob, and a fork which builds on both.
+	fn forked_events() -> HashMap<OwnedEventId, Pdu> {
+		let mut events = INITIAL_EVENTS();
+		let forks = [
+			to_pdu_event(
+				"PA",
+				alice(),
+				TimelineEventType::RoomPowerLevels,
+				Some(""),
+				to_raw_json_value(&json!({ "users": { alice(): 100, bob(): 50 } })).unwrap(),
+				&["CREATE", "IMA", "IPOWER"],
+				&["IMC"],
+			),
+			to_pdu_event(
+				"TB",
+				bob(),
+				TimelineEventType::RoomTopic,
+				Some(""),
+				to_raw_json_value(&json!({ "topic": "bob" })).unwrap(),
+				&["CREATE", "IMB", "PA"],
+				&["PA"],
+			),
+			to_pdu_event(
+				"LB",
+				bob(),
+				TimelineEventType::RoomMember,
+				Some(bob().as_str()),
+				to_raw_json_value(&json!({ "membership": "leave" })).unwrap(),
+				&["CREATE", "IMB", "PA"],
+				&["TB"],
+			),
+			to_pdu_event(
+				"TA",
+				alice(),
+				TimelineEventType::RoomTopic,
+				Some(""),
+				to_raw_json_value(&json!({ "topic": "alice" })).unwrap(),
+				&["CREATE", "IMA", "IPOWER"],
+				&["IMC"],
+			),
+			to_pdu_event(
+				"BB",
+				alice(),
+				TimelineEventType::RoomMember,
+				Some(bob().as_str()),
+				member_content_ban(),
+				&["CREATE", "IMA", "IPOWER", "IMB"],
+				&["TA"],
+			),
+			to_pdu_event(
+				"PA2",
+				alice(),
+				TimelineEventType::RoomPowerLevels,
+				Some(""),
+				to_raw_json_value(&json!({ "users": { alice(): 100, charlie(): 50 } })).unwrap(),
+				&["CREATE", "IMA", "PA"],
+				&["LB", "BB"],
+			),
+			to_pdu_event(
+				"MC",
+				charlie(),
+				TimelineEventType::RoomMember,
+				Some(charlie().as_str()),
+				member_content_join(),
+				&["CREATE", "IJR", "PA2", "IMC"],
+				&["PA2"],
+			),
+		];
+
+		events.extend(
+			forks
+				.into_iter()
+				.map(|pdu| (pdu.event_id().to_owned(), pdu)),
+		);
+		events
+	}
+
+	fn state_set(ids: &[&str]) -> Vec<OwnedEventId> {
+		ids.iter().copied().map(event_id).collect()
+	}
+
+	/// The auth chain difference as computed from the full auth chains.
+	fn full_difference(
+		events: &HashMap<OwnedEventId, Pdu>,
+		state_sets: &[Vec<OwnedEventId>],
+	) -> HashSet<OwnedEventId> {
+		let store = TestStore(events.clone());
+		let chains: Vec<HashSet<OwnedEventId>> = state_sets
+			.iter()
+			.map(|state_set| {
+				let auth_events = state_set
+					.iter()
+					.flat_map(|event_id| events[event_id].auth_events())
+					.map(ToOwned::to_owned)
+					.collect();
+
+				store.auth_event_ids(room_id(), auth_events).unwrap()
+			})
+			.collect();
+
+		let union: HashSet<OwnedEventId> = chains.iter().flatten().cloned().collect();
+		union
+			.into_iter()
+			.filter(|event_id| !chains.iter().all(|chain| chain.contains(event_id)))
+			.collect()
+	}
+
+	async fn chain_difference(
+		store: &MemoryStore,
+		state_sets: &[Vec<OwnedEventId>],
+	) -> HashSet<OwnedEventId> {
+		let mut positions = Vec::new();
+		for state_set in state_sets {
+			let mut set = Vec::new();
+			for event_id in state_set {
+				index_event(store, room_id(), event_id).await.unwrap();
+				set.extend(store.get_position(event_id).await);
+			}
+
+			positions.push(set);
+		}
+
+		auth_chain_difference(store, &positions).await.unwrap()
+	}
+
+	async fn check(order: &[&str], state_sets: &[Vec<OwnedEventId>]) {
+		let events = forked_events();
+		let store = MemoryStore {
+			events: events.clone(),
+			index: Mutex::default(),
+		};
+
+		// Index some events up front, the rest lazily, so that chains get extended
+		// in different orders.
+		for id in order {
+			index_event(&store, room_id(), &event_id(id)).await.unwrap();
+		}
+
+		let expected = full_difference(&events, state_sets);
+		assert_eq!(chain_difference(&store, state_sets).await, expected);
+	}
+
+	fn forks() -> Vec<Vec<OwnedEventId>> {
+		vec![
+			state_set(&["CREATE", "IMA", "PA", "IJR", "LB", "IMC", "TB"]),
+			state_set(&["CREATE", "IMA", "IPOWER", "IJR", "BB", "IMC", "TA"]),
+		]
+	}
+
+	#[tokio::test]
+	async fn two_forks() { check(&[], &forks()).await; }
+
+	#[tokio::test]
+	async fn two_forks_indexed_in_order() {
+		check(&["IMC", "PA", "TB", "LB", "TA", "BB", "PA2", "MC"], &forks()).await;
+	}
+
+	#[tokio::test]
+	async fn two_forks_indexed_out_of_order() {
+		check(&["BB", "MC", "TA", "LB"], &forks()).await;
+	}
+
+	#[tokio::test]
+	async fn three_forks() {
+		let mut state_sets = forks();
+		state_sets.push(state_set(&["CREATE", "IMA", "PA2", "IJR", "BB", "MC", "TA"]));
+
+		check(&[], &state_sets).await;
+		check(&["MC"], &state_sets).await;
+	}
+
+	#[tokio::test]
+	async fn merged_fork_against_initial_state() {
+		let state_sets = vec![
+			state_set(&["CREATE", "IMA", "PA2", "IJR", "BB", "MC", "TA"]),
+			state_set(&["CREATE", "IMA", "IPOWER", "IJR", "IMB", "IMC"]),
+		];
+
+		check(&[], &state_sets).await;
+	}
+
+	#[tokio::test]
+	async fn missing_auth_event() {
+		let mut events = forked_events();
+		events.remove(&event_id("PA"));
+		let store = MemoryStore { events, index: Mutex::default() };
+
+		assert!(
+			index_event(&store, room_id(), &event_id("LB"))
+				.await
+				.is_err()
+		);
+		assert_eq!(store.get_position(&event_id("LB")).await, None);
+		assert_eq!(store.get_position(&event_id("TB")).await, None);
+
+		// Events whose auth chain is complete are still indexed.
+		index_event(&store, room_id(), &event_id("TA"))
+			.await
+			.unwrap();
+		assert!(store.get_position(&event_id("TA")).await.is_some());
+	}
+
+	#[tokio::test]
+	async fn identical_sets() {
+		let state_set = forks().remove(0);
+		check(&[], &[state_set.clone(), state_set]).await;
+	}
+}
@@ -1,5 +1,6 @@
 #![cfg_attr(test, allow(warnings))]
 
+pub mod chain_cover;
 pub(crate) mod error;
 pub mod event_auth;
 mod power_levels;
@@ -74,7 +75,6 @@
 /// event is part of the same room.
 //#[tracing::instrument(level = "debug", skip(state_sets, auth_chain_sets,
 //#[tracing::instrument(level event_fetch))]
-#[allow(clippy::cognitive_complexity)]
 pub async fn resolve<'a, Pdu, Sets, SetIter, Hasher, Fetch, FetchFut, Exists, ExistsFut>(
 	room_version: &RoomVersionRules,
 	state_sets: Sets,
@@ -92,6 +92,52 @@ pub async fn resolve<'a, Pdu, Sets, SetIter, Hasher, Fetch, FetchFut, Exists, Ex
 	Hasher: BuildHasher + Send + Sync,
 	Pdu: Event + Clone + Send + Sync,
 	for<'b> &'b Pdu: Event + Send,
+{
+	let auth_chain_diff = get_auth_chain_diff(auth_chain_sets).collect().await;
+
+	resolve_with_auth_chain_diff(
+		room_version,
+		state_sets,
+		auth_chain_diff,
+		event_fetch,
+		event_exists,
+	)
+	.await
+}
+
+/// Resolve sets of state events like [`resolve`], given the auth chain
+/// difference of the state sets instead of their full auth chains.
+///
+/// ## Arguments
+///
+/// * `auth_chain_diff` - The events which are in the auth chains of some but
+///   not all of the `state_sets`.
+#[allow(clippy::cognitive_complexity)]
+pub async fn resolve_with_auth_chain_diff<
+	'a,
+	Pdu,
+	Sets,
+	SetIter,
+	Fetch,
+	FetchFut,
+	Exists,
+	ExistsFut,
+>(
+	room_version: &RoomVersionRules,
+	state_sets: Sets,
+	auth_chain_diff: HashSet<OwnedEventId>,
+	event_fetch: &Fetch,
+	event_exists: &Exists,
+) -> Result<StateMap<OwnedEventId>>
+where
+	Fetch: Fn(OwnedEventId) -> FetchFut + Sync,
+	FetchFut: Future<Output = Option<Pdu>> + Send,
+	Exists: Fn(OwnedEventId) -> ExistsFut + Sync,
+	ExistsFut: Future<Output = bool> + Send,
+	Sets: IntoIterator<IntoIter = SetIter> + Send,
+	SetIter: Iterator<Item = &'a StateMap<OwnedEventId>> + Clone + Send,
+	Pdu: Event + Clone + Send + Sync,
+	for<'b> &'b Pdu: Event + Send,
 {
 	let stateres_version = room_version.state_res;
 	debug!(version = ?stateres_version, "State resolution starting");
@@ -129,7 +175,9 @@ pub async fn resolve<'a, Pdu, Sets, SetIter, Hasher, Fetch, FetchFut, Exists, Ex
 	// synapse says `full_set = {eid for eid in full_conflicted_set if eid in
 	// event_map}`
 	// Hydra: Also consider the conflicted state subgraph
-	let all_conflicted: HashSet<_> = get_auth_chain_diff(auth_chain_sets)
+	let all_conflicted: HashSet<_> = auth_chain_diff
+		.into_iter()
+		.stream()
 		.chain(conflicting.into_values().flatten().stream())
 		.broad_filter_map(async |id| event_exists(id.clone()).await.then_some(id))
 		.chain(conflicted_state_subgraph.into_iter().stream())
@@ -40,6 +40,8 @@ pub fn unstable_features() -> BTreeMap<String, bool> {
 		("org.matrix.simplified_msc3575".to_owned(), true),
 		// invite filtering (https://github.com/matrix-org/matrix-spec-proposals/pull/4155)
 		("org.matrix.msc4155".to_owned(), true),
+		// thread subscriptions (https://github.com/matrix-org/matrix-spec-proposals/pull/4306)
+		("org.matrix.msc4306".to_owned(), true),
 		// profile change propagation (https://github.com/matrix-org/matrix-spec-proposals/pull/4466)
 		("computer.gingershaped.msc4466".to_owned(), true),
 	])
@@ -0,0 +1,238 @@
+//! Cron-style schedules.
+//!
+//! Schedules use the five standard fields `minute hour day-of-month month
+//! day-of-week`, each accepting `*`, values, ranges (`1-5`), lists (`1,15`)
+//! and steps (`*/10`, `0-30/5`). Months and weekdays may also be given by
+//! their three-letter English names. The shorthands `@hourly`, `@daily`,
+//! `@weekly`, `@monthly` and `@yearly` are accepted too. As in Vixie cron,
+//! when both the day-of-month and day-of-week fields are restricted, a day
+//! matching either field matches. All times are UTC.
+
+use std::{fmt, str::FromStr, time::SystemTime};
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Timelike, Utc};
+
+use crate::{Err, Error, Result, err};
+
+/// A parsed cron schedule.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Schedule {
+	source: String,
+	minutes: u64,
+	hours: u64,
+	days: u64,
+	months: u64,
+	weekdays: u64,
+	any_day: bool,
+	any_weekday: bool,
+}
+
+struct Field {
+	name: &'static str,
+	min: u32,
+	max: u32,
+	names: &'static [&'static str],
+}
+
+const MINUTE: Field = Field {
+	name: "minute",
+	min: 0,
+	max: 59,
+	names: &[],
+};
+const HOUR: Field = Field {
+	name: "hour",
+	min: 0,
+	max: 23,
+	names: &[],
+};
+const DAY: Field = Field {
+	name: "day of month",
+	min: 1,
+	max: 31,
+	names: &[],
+};
+const MONTH: Field = Field {
+	name: "month",
+	min: 1,
+	max: 12,
+	names: &[
+		"jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+	],
+};
+const WEEKDAY: Field = Field {
+	name: "day of week",
+	min: 0,
+	max: 7,
+	names: &["sun", "mon", "tue", "wed", "thu", "fri", "sat"],
+};
+
+/// How far ahead to look for the next occurrence of a schedule. Long enough
+/// to cover schedules which only match on leap days.
+const MAX_SEARCH_DAYS: usize = 366 * 8;
+
+impl Schedule {
+	/// Whether the schedule fires at the minute containing the given time.
+	#[must_use]
+	pub fn matches(&self, time: SystemTime) -> bool {
+		let time = DateTime::<Utc>::from(time);
+		has(self.minutes, time.minute())
+			&& has(self.hours, time.hour())
+			&& self.date_matches(time.date_naive())
+	}
+
+	/// Finds the first time strictly after the given time at which the
+	/// schedule fires.
+	#[must_use]
+	pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+		let start = DateTime::<Utc>::from(after)
+			.with_second(0)?
+			.with_nanosecond(0)?
+			.checked_add_signed(TimeDelta::minutes(1))?;
+
+		let mut date = start.date_naive();
+		for _ in 0..MAX_SEARCH_DAYS {
+			if self.date_matches(date) {
+				let times = (0..24)
+					.filter(|&hour| has(self.hours, hour))
+					.flat_map(|hour| {
+						(0..60)
+							.filter(|&minute| has(self.minutes, minute))
+							.map(move |minute| (hour, minute))
+					});
+
+				for (hour, minute) in times {
+					let time = date.and_hms_opt(hour, minute, 0)?.and_utc();
+					if time >= start {
+						return Some(time.into());
+					}
+				}
+			}
+
+			date = date.succ_opt()?;
+		}
+
+		None
+	}
+
+	fn date_matches(&self, date: NaiveDate) -> bool {
+		if !has(self.months, date.month()) {
+			return false;
+		}
+
+		let day = has(self.days, date.day());
+		let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+		match (self.any_day, self.any_weekday) {
+			| (true, _) => weekday,
+			| (false, true) => day,
+			| (false, false) => day || weekday,
+		}
+	}
+}
+
+impl FromStr for Schedule {
+	type Err = Error;
+
+	fn from_str(source: &str) -> Result<Self> {
+		let source = source.trim();
+		let expanded = match source.to_lowercase().as_str() {
+			| "@yearly" | "@annually" => "0 0 1 1 *",
+			| "@monthly" => "0 0 1 * *",
+			| "@weekly" => "0 0 * * 0",
+			| "@daily" | "@midnight" => "0 0 * * *",
+			| "@hourly" => "0 * * * *",
+			| shorthand if shorthand.starts_with('@') => {
+				return Err!("Unknown schedule shorthand {source:?}");
+			},
+			| _ => source,
+		};
+
+		let fields: Vec<_> = expanded.split_whitespace().collect();
+		let &[minute, hour, day, month, weekday] = fields.as_slice() else {
+			return Err!(
+				"Schedule {source:?} must have five fields: minute hour day-of-month month \
+				 day-of-week"
+			);
+		};
+
+		let mut weekdays = parse_field(&WEEKDAY, weekday)?;
+		// Sunday may be written as either 0 or 7.
+		if has(weekdays, 7) {
+			weekdays = (weekdays & !(1 << 7)) | 1;
+		}
+
+		Ok(Self {
+			source: source.to_owned(),
+			minutes: parse_field(&MINUTE, minute)?,
+			hours: parse_field(&HOUR, hour)?,
+			days: parse_field(&DAY, day)?,
+			months: parse_field(&MONTH, month)?,
+			weekdays,
+			any_day: day.starts_with('*'),
+			any_weekday: weekday.starts_with('*'),
+		})
+	}
+}
+
+impl fmt::Display for Schedule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.source) }
+}
+
+fn parse_field(field: &Field, expr: &str) -> Result<u64> {
+	let mut bits = 0_u64;
+	for part in expr.split(',') {
+		let (range, step) = match part.split_once('/') {
+			| Some((range, step)) => (range, step.parse::<usize>().unwrap_or_default()),
+			| None => (part, 1),
+		};
+
+		if step == 0 {
+			return Err!("Invalid step in {} field {expr:?}", field.name);
+		}
+
+		let (start, end) = match range.split_once('-') {
+			| _ if range == "*" => (field.min, field.max),
+			| Some((start, end)) => (parse_value(field, start)?, parse_value(field, end)?),
+			| None => {
+				let value = parse_value(field, range)?;
+				(value, if part.contains('/') { field.max } else { value })
+			},
+		};
+
+		if start > end {
+			return Err!("Invalid range in {} field {expr:?}", field.name);
+		}
+
+		for value in (start..=end).step_by(step) {
+			bits |= 1 << value;
+		}
+	}
+
+	Ok(bits)
+}
+
+fn parse_value(field: &Field, value: &str) -> Result<u32> {
+	let lowercase = value.to_lowercase();
+	let parsed = field
+		.names
+		.iter()
+		.zip(field.min..)
+		.find(|(name, _)| **name == lowercase)
+		.map(|(_, index)| index)
+		.map_or_else(|| value.parse::<u32>().ok(), Some)
+		.ok_or_else(|| err!("Invalid value {value:?} in {} field", field.name))?;
+
+	if !(field.min..=field.max).contains(&parsed) {
+		return Err!(
+			"Value {parsed} in {} field is out of range {}-{}",
+			field.name,
+			field.min,
+			field.max
+		);
+	}
+
+	Ok(parsed)
+}
+
+#[inline]
+fn has(bits: u64, value: u32) -> bool { bits & (1 << value) != 0 }
@@ -295,3 +295,98 @@ fn is_within_bounds() {
 	assert!(is_within_bounds(now, now, TimeDirection::Before));
 	assert!(is_within_bounds(now, now, TimeDirection::After));
 }
+
+#[test]
+fn time_parse_format_roundtrip() {
+	use chrono::{TimeZone, Utc};
+	use utils::time::{format, parse};
+
+	let ts = parse("20301231235959Z", "%Y%m%d%H%M%SZ").unwrap();
+	assert_eq!(
+		ts,
+		Utc.with_ymd_and_hms(2030, 12, 31, 23, 59, 59)
+			.unwrap()
+			.into()
+	);
+	assert_eq!(format(ts, "%Y%m%d%H%M%SZ"), "20301231235959Z");
+	assert!(parse("2030-12-31", "%Y%m%d%H%M%SZ").is_err());
+}
+
+#[test]
+fn cron_schedule_next() {
+	use chrono::{TimeZone, Utc};
+	use utils::cron::Schedule;
+
+	let schedule: Schedule = "0 3 * * *".parse().unwrap();
+	let from = Utc.with_ymd_and_hms(2025, 1, 1, 3, 0, 30).unwrap();
+	assert!(schedule.matches(from.into()));
+	assert_eq!(
+		schedule.next_after(from.into()),
+		Some(Utc.with_ymd_and_hms(2025, 1, 2, 3, 0, 0).unwrap().into())
+	);
+
+	let schedule: Schedule = "*/15 9-17 * * mon-fri".parse().unwrap();
+	let saturday = Utc.with_ymd_and_hms(2025, 1, 4, 12, 0, 0).unwrap();
+	assert!(!schedule.matches(saturday.into()));
+	assert_eq!(
+		schedule.next_after(saturday.into()),
+		Some(Utc.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap().into())
+	);
+
+	let schedule: Schedule = "@monthly".parse().unwrap();
+	assert_eq!(
+		schedule.next_after(saturday.into()),
+		Some(Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap().into())
+	);
+}
+
+#[test]
+fn cron_schedule_never() {
+	use chrono::{TimeZone, Utc};
+	use utils::cron::Schedule;
+
+	// February never has a 30th
+	let schedule: Schedule = "0 0 30 2 *".parse().unwrap();
+	let from = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+	assert_eq!(schedule.next_after(from.into()), None);
+}
+
+#[test]
+fn time_format_millis() {
+	use utils::time::format_millis;
+
+	assert_eq!(format_millis(0), "1970-01-01 00:00:00 UTC");
+	assert_eq!(format_millis(1_906_545_600_999), "2030-06-01 12:00:00 UTC");
+}
+
+#[test]
+fn cron_schedule_day_fields() {
+	use chrono::{TimeZone, Utc};
+	use utils::cron::Schedule;
+
+	// Either the 13th or any Friday
+	let schedule: Schedule = "0 0 13 * 5".parse().unwrap();
+	let friday = Utc.with_ymd_and_hms(2025, 1, 3, 0, 0, 0).unwrap();
+	let thirteenth = Utc.with_ymd_and_hms(2025, 1, 13, 0, 0, 0).unwrap();
+	let other = Utc.with_ymd_and_hms(2025, 1, 14, 0, 0, 0).unwrap();
+	assert!(schedule.matches(friday.into()));
+	assert!(schedule.matches(thirteenth.into()));
+	assert!(!schedule.matches(other.into()));
+
+	// Sunday as 7
+	let schedule: Schedule = "0 0 * * 7".parse().unwrap();
+	let sunday = Utc.with_ymd_and_hms(2025, 1, 5, 0, 0, 0).unwrap();
+	assert!(schedule.matches(sunday.into()));
+}
+
+#[test]
+fn cron_schedule_invalid() {
+	use utils::cron::Schedule;
+
+	assert!("* * * *".parse::<Schedule>().is_err());
+	assert!("60 * * * *".parse::<Schedule>().is_err());
+	assert!("*/0 * * * *".parse::<Schedule>().is_err());
+	assert!("5-1 * * * *".parse::<Schedule>().is_err());
+	assert!("0 0 * foo *".parse::<Schedule>().is_err());
+	assert!("@sometimes".parse::<Schedule>().is_err());
+}
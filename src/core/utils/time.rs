@@ -58,6 +58,30 @@ pub fn format(ts: SystemTime, str: &str) -> String {
 	dt.format(str).to_string()
 }
 
+/// Formats a time as a UTC date and time to the second, the way times are
+/// shown to admins.
+#[must_use]
+pub fn format_utc(ts: SystemTime) -> String { format(ts, "%F %T UTC") }
+
+/// Formats milliseconds since the unix epoch with [`format_utc`].
+#[must_use]
+pub fn format_millis(ts: u64) -> String {
+	let ts = UNIX_EPOCH
+		.checked_add(Duration::from_millis(ts))
+		.unwrap_or(UNIX_EPOCH);
+
+	format_utc(ts)
+}
+
+/// Parses a UTC time written in the given format, the inverse of [`format`].
+pub fn parse(s: &str, fmt: &str) -> Result<SystemTime> {
+	use chrono::NaiveDateTime;
+
+	NaiveDateTime::parse_from_str(s, fmt)
+		.map(|dt| dt.and_utc().into())
+		.map_err(|error| err!("'{s:?}' is not a valid time: {error}"))
+}
+
 #[must_use]
 #[allow(clippy::as_conversions, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
 pub fn pretty(d: Duration) -> String {
@@ -41,6 +41,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "backupid_etag",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "backupid_lastupload",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "backupkeyid_backup",
 		..descriptor::RANDOM_SMALL
@@ -49,10 +53,26 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "bannedroomids",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "chainid_maxseq",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "chainidseq_shorteventid",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "chainlinks",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "clientid_clientmetadata",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "directoryterm_roomid",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "disabledroomids",
 		..descriptor::RANDOM_SMALL
@@ -88,6 +108,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		index_size: 512,
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "eventid_timelinegap",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "global",
 		..descriptor::RANDOM_SMALL
@@ -116,6 +140,14 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "mediaid_file",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "mediaid_pending",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "userid_pendingmedia",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "mediaid_user",
 		..descriptor::RANDOM_SMALL
@@ -157,10 +189,18 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "pushkey_deviceid",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "pushkey_userid",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "presenceid_presence",
 		..descriptor::SEQUENTIAL_SMALL
 	},
+	Descriptor {
+		name: "preveventid_timelinegap",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "readreceiptid_readreceipt",
 		..descriptor::RANDOM
@@ -193,6 +233,14 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomid_joinedcount",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomid_directoryentry",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "roomid_directoryterms",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomid_pduleaves",
 		..descriptor::RANDOM_SMALL
@@ -207,10 +255,26 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		val_size_hint: Some(8),
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomidcount_statereset",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "roomcount_inboundpdu",
+		..descriptor::RANDOM
+	},
 	Descriptor {
 		name: "roomid_mindepth",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomid_partialstate",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "roomideventid_partialstaterecheck",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomserverids",
 		..descriptor::RANDOM_SMALL
@@ -223,6 +287,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomuserdataid_accountdata",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "useridroomtypecount_accountdatahistory",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomuserid_invitecount",
 		val_size_hint: Some(8),
@@ -250,6 +318,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomuserid_privateread",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomuserthreadid_privateread",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomuseroncejoinedids",
 		..descriptor::RANDOM
@@ -258,6 +330,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomusertype_roomuserdataid",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "scheduleid_job",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "senderkey_pusher",
 		..descriptor::RANDOM_SMALL
@@ -298,6 +374,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		key_size_hint: Some(8),
 		..descriptor::SEQUENTIAL
 	},
+	Descriptor {
+		name: "shorteventid_chainpos",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "shorteventid_eventid",
 		cache_disp: CacheDisp::Unique,
@@ -335,6 +415,14 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		key_size_hint: Some(48),
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "eventid_ingestionaudit",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "roomidts_ingestionaudit",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "statehash_shortstatehash",
 		val_size_hint: Some(8),
@@ -486,6 +574,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userroomid_highlightcount",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "userroomthreadid_highlightcount",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userroomid_invitestate",
 		..descriptor::RANDOM_SMALL
@@ -506,6 +598,22 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userroomid_notificationcount",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "userroomthreadid_notificationcount",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "userroomthreadid_subscription",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "useridcount_notification",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "tsuseridcount_notification",
+		..descriptor::SEQUENTIAL_SMALL
+	},
 	Descriptor {
 		name: "userroomid_invitesender",
 		..descriptor::RANDOM_SMALL
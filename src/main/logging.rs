@@ -4,7 +4,9 @@
 	Result,
 	config::Config,
 	debug_warn, err,
-	log::{ConsoleFormat, ConsoleWriter, LogLevelReloadHandles, capture, fmt_span},
+	log::{
+		ConsoleFormat, ConsoleWriter, History, LogLevelReloadHandles, capture, fmt_span, history,
+	},
 	result::UnwrapOrErr,
 	warn,
 };
@@ -23,7 +25,7 @@
 #[allow(clippy::redundant_clone)]
 pub(crate) fn init(
 	config: &Config,
-) -> Result<(LogLevelReloadHandles, TracingFlameGuard, Arc<capture::State>)> {
+) -> Result<(LogLevelReloadHandles, TracingFlameGuard, Arc<capture::State>, Arc<History>)> {
 	let reload_handles = LogLevelReloadHandles::default();
 
 	let console_span_events = fmt_span::from_str(&config.log_span_events).unwrap_or_err();
@@ -47,9 +49,25 @@ pub(crate) fn init(
 	let cap_state = Arc::new(capture::State::new());
 	let cap_layer = capture::Layer::new(&cap_state);
 
+	let history = Arc::new(History::new(config.log_history_capacity));
+	let history_layer = if history.is_enabled() {
+		let history_filter = EnvFilter::builder()
+			.with_regex(config.log_filter_regex)
+			.parse(&config.log_history_filter)
+			.map_err(|e| err!(Config("log_history_filter", "{e}.")))?;
+
+		let (history_reload_filter, history_reload_handle) = reload::Layer::new(history_filter);
+		reload_handles.add("history", Box::new(history_reload_handle));
+
+		Some(history::Layer::new(&history).with_filter(history_reload_filter))
+	} else {
+		None
+	};
+
 	let subscriber = Registry::default()
 		.with(console_layer.with_filter(console_reload_filter))
-		.with(cap_layer);
+		.with(cap_layer)
+		.with(history_layer);
 
 	// If journald logging is enabled on Unix platforms, create a separate
 	// subscriber for it
@@ -151,7 +169,7 @@ pub(crate) fn init(
 	#[cfg_attr(not(feature = "perf_measurements"), allow(clippy::let_unit_value))]
 	let flame_guard = ();
 
-	let ret = (reload_handles, flame_guard, cap_state);
+	let ret = (reload_handles, flame_guard, cap_state, history);
 
 	// Enable the tokio console. This is slightly kludgy because we're judggling
 	// compile-time and runtime conditions to elide it, each of those changing the
@@ -44,7 +44,7 @@ pub(crate) fn new(
 			.and_then(|raw| update(raw, args))
 			.and_then(|raw| Config::new(&raw))?;
 
-		let (tracing_reload_handle, tracing_flame_guard, capture) =
+		let (tracing_reload_handle, tracing_flame_guard, capture, history) =
 			crate::logging::init(&config)?;
 
 		config.check()?;
@@ -71,6 +71,7 @@ pub(crate) fn new(
 			server: Arc::new(conduwuit_core::Server::new(config, runtime.cloned(), Log {
 				reload: tracing_reload_handle,
 				capture,
+				history,
 			})),
 
 			services: None.into(),
@@ -0,0 +1,107 @@
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	net::SocketAddr,
+	sync::Arc,
+};
+
+use axum::{
+	Router,
+	extract::{Request, State},
+	middleware::{Next, from_fn_with_state},
+	response::{IntoResponse, Response},
+};
+use axum_server::Handle as ServerHandle;
+use conduwuit::{
+	Error, Result, Server,
+	config::{ListenerConfig, ListenerResource},
+	debug, info,
+};
+use futures::future::try_join_all;
+use http::StatusCode;
+use ruma::api::error::ErrorKind;
+
+use super::plain;
+
+type Resources = Arc<BTreeSet<ListenerResource>>;
+
+/// Serve each configured listener with the resources enabled on it.
+pub(super) async fn serve(
+	server: &Arc<Server>,
+	app: Router,
+	handle: ServerHandle<SocketAddr>,
+	listeners: &BTreeMap<String, ListenerConfig>,
+) -> Result {
+	let listeners = listeners.iter().map(|(name, listener)| {
+		serve_listener(server, app.clone(), handle.clone(), name, listener)
+	});
+
+	try_join_all(listeners).await?;
+
+	Ok(())
+}
+
+async fn serve_listener(
+	server: &Arc<Server>,
+	app: Router,
+	handle: ServerHandle<SocketAddr>,
+	name: &str,
+	listener: &ListenerConfig,
+) -> Result {
+	let addrs = listener.get_bind_addrs();
+	let resources: Resources = Arc::new(listener.resources.clone());
+	info!("Starting listener {name:?} serving {resources:?}");
+
+	let app = app.layer(from_fn_with_state(resources, filter_resources));
+	match &listener.tls {
+		#[cfg(feature = "direct_tls")]
+		| Some(tls) => super::tls::serve(server, app, handle, addrs, tls).await,
+
+		#[cfg(not(feature = "direct_tls"))]
+		| Some(_) => conduwuit::Err!(Config(
+			"listeners",
+			"conduwuit was not built with direct TLS support (\"direct_tls\")"
+		)),
+
+		| None => plain::serve(server, app, handle, addrs).await,
+	}
+}
+
+/// Rejects requests for resources which are not enabled on the listener they
+/// arrived on.
+async fn filter_resources(
+	State(resources): State<Resources>,
+	req: Request,
+	next: Next,
+) -> Response {
+	let resource = classify(req.uri().path());
+	if resources.contains(&resource) {
+		return next.run(req).await;
+	}
+
+	debug!(?resource, path = req.uri().path(), "Resource is not enabled on this listener");
+	Error::Request(ErrorKind::Unrecognized, "not found :(".into(), StatusCode::NOT_FOUND)
+		.into_response()
+}
+
+/// Determines which resource a request path belongs to.
+fn classify(path: &str) -> ListenerResource {
+	const FEDERATION: &[&str] =
+		&["/_matrix/federation/", "/_matrix/key/", "/_continuwuity/local_user_count"];
+	const MEDIA: &[&str] = &["/_matrix/media/", "/_matrix/client/v1/media/"];
+	const ADMIN: &[&str] = &["/_continuwuity/admin/"];
+	const CLIENT: &[&str] = &["/_matrix/", "/.well-known/", "/_continuwuity/server_version"];
+
+	let matches = |prefixes: &[&str]| prefixes.iter().any(|prefix| path.starts_with(prefix));
+
+	if matches(FEDERATION) {
+		ListenerResource::Federation
+	} else if matches(MEDIA) {
+		ListenerResource::Media
+	} else if matches(ADMIN) {
+		ListenerResource::Admin
+	} else if matches(CLIENT) {
+		ListenerResource::Client
+	} else {
+		ListenerResource::Web
+	}
+}
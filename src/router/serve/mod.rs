@@ -1,3 +1,4 @@
+mod listener;
 mod plain;
 #[cfg(feature = "direct_tls")]
 mod tls;
@@ -29,11 +30,13 @@ pub(super) async fn serve(
 
 	let addrs = config.get_bind_addrs();
 	let (app, _guard) = layers::build(&services)?;
-	if cfg!(unix) && config.unix_socket_path.is_some() {
+	if !config.listeners.is_empty() {
+		listener::serve(server, app, handle, &config.listeners).await
+	} else if cfg!(unix) && config.unix_socket_path.is_some() {
 		unix::serve(server, app, shutdown).await
 	} else if config.tls.certs.is_some() {
 		#[cfg(feature = "direct_tls")]
-		return tls::serve(server, app, handle, addrs).await;
+		return tls::serve(server, app, handle, addrs, &config.tls).await;
 
 		#[cfg(not(feature = "direct_tls"))]
 		return conduwuit::Err!(Config(
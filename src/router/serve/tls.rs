@@ -6,7 +6,7 @@
 	ServerExt,
 	axum_server::{bind_rustls, tls_rustls::RustlsConfig},
 };
-use conduwuit::{Result, Server, err};
+use conduwuit::{Result, Server, config::TlsConfig, err};
 use tokio::task::JoinSet;
 use tracing::{debug, info, warn};
 
@@ -15,8 +15,8 @@ pub(super) async fn serve(
 	app: Router,
 	handle: ServerHandle<SocketAddr>,
 	addrs: Vec<SocketAddr>,
+	tls: &TlsConfig,
 ) -> Result {
-	let tls = &server.config.tls;
 	let certs = tls.certs.as_ref().ok_or_else(|| {
 		err!(Config("tls.certs", "Missing required value in tls config section"))
 	})?;
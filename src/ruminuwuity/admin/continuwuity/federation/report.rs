@@ -0,0 +1,43 @@
+pub mod v1 {
+	use ruma::{
+		OwnedServerName,
+		api::{auth_scheme::AccessToken, request, response},
+		metadata,
+	};
+	use serde_json::value::RawValue as RawJsonValue;
+
+	metadata! {
+		method: GET,
+		rate_limited: false,
+		authentication: AccessToken,
+		history: {
+			1.0 => "/_continuwuity/admin/federation/report",
+		}
+	}
+
+	#[request]
+	#[derive(Default)]
+	pub struct Request {
+		/// The server to check, this server if not given.
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub server_name: Option<OwnedServerName>,
+	}
+
+	#[response]
+	pub struct Response {
+		/// The result of each check against the server.
+		#[ruma_api(body)]
+		pub report: Box<RawJsonValue>,
+	}
+
+	impl Request {
+		#[must_use]
+		pub fn new() -> Self { Self::default() }
+	}
+
+	impl Response {
+		#[must_use]
+		pub fn new(report: Box<RawJsonValue>) -> Self { Self { report } }
+	}
+}
@@ -0,0 +1,100 @@
+pub mod v1 {
+	use ruma::{
+		OwnedEventId, OwnedRoomId, OwnedServerName,
+		api::{auth_scheme::AccessToken, request, response},
+		metadata,
+	};
+	use serde::{Deserialize, Serialize};
+	use serde_json::value::RawValue as RawJsonValue;
+
+	metadata! {
+		method: GET,
+		rate_limited: false,
+		authentication: AccessToken,
+		history: {
+			1.0 => "/_continuwuity/admin/federation/request",
+		}
+	}
+
+	#[request]
+	pub struct Request {
+		/// The server to send the request to.
+		#[ruma_api(query)]
+		pub destination: OwnedServerName,
+
+		/// The federation endpoint to query.
+		#[ruma_api(query)]
+		pub endpoint: SignedEndpoint,
+
+		/// The room, for the endpoints which need one.
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub room_id: Option<OwnedRoomId>,
+
+		/// The event, for the endpoints which need one.
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub event_id: Option<OwnedEventId>,
+	}
+
+	#[response]
+	pub struct Response {
+		/// The response of the remote server.
+		#[ruma_api(body)]
+		pub response: Box<RawJsonValue>,
+	}
+
+	/// Federation endpoints which can be queried on behalf of a server admin.
+	#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+	#[serde(rename_all = "snake_case")]
+	pub enum SignedEndpoint {
+		/// `GET /_matrix/federation/v1/version`
+		Version,
+
+		/// `GET /_matrix/key/v2/server`
+		Keys,
+
+		/// `GET /_matrix/federation/v1/event/{eventId}`
+		Event,
+
+		/// `GET /_matrix/federation/v1/state/{roomId}`
+		State,
+
+		/// `GET /_matrix/federation/v1/state_ids/{roomId}`
+		StateIds,
+
+		/// `GET /_matrix/federation/v1/event_auth/{roomId}/{eventId}`
+		EventAuth,
+	}
+
+	impl Request {
+		#[must_use]
+		pub fn new(destination: OwnedServerName, endpoint: SignedEndpoint) -> Self {
+			Self {
+				destination,
+				endpoint,
+				room_id: None,
+				event_id: None,
+			}
+		}
+	}
+
+	impl Response {
+		#[must_use]
+		pub fn new(response: Box<RawJsonValue>) -> Self { Self { response } }
+	}
+
+	impl SignedEndpoint {
+		#[must_use]
+		pub fn as_str(&self) -> &'static str {
+			match self {
+				| Self::Version => "version",
+				| Self::Keys => "keys",
+				| Self::Event => "event",
+				| Self::State => "state",
+				| Self::StateIds => "state_ids",
+				| Self::EventAuth => "event_auth",
+			}
+		}
+	}
+}
@@ -0,0 +1,73 @@
+pub mod v1 {
+	use ruma::{
+		api::{auth_scheme::AccessToken, request, response},
+		metadata,
+	};
+
+	metadata! {
+		method: GET,
+		rate_limited: false,
+		authentication: AccessToken,
+		history: {
+			1.0 => "/_continuwuity/admin/server/logs",
+		}
+	}
+
+	#[request]
+	#[derive(Default)]
+	pub struct Request {
+		/// Least severe level to include.
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub level: Option<String>,
+
+		/// Prefix of the event target.
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub target: Option<String>,
+
+		/// Name of a span the event must have occurred in.
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub span: Option<String>,
+
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub room_id: Option<String>,
+
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub user_id: Option<String>,
+
+		/// Case-insensitive text to find in the message or fields.
+		#[ruma_api(query)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub text: Option<String>,
+
+		/// Number of past events to send first.
+		#[ruma_api(query)]
+		#[serde(default, skip_serializing_if = "ruma::serde::is_default")]
+		pub backlog: usize,
+
+		/// Keep the response open and send new events as they are logged.
+		#[ruma_api(query)]
+		#[serde(default, skip_serializing_if = "ruma::serde::is_default")]
+		pub follow: bool,
+	}
+
+	/// The log events are streamed as newline-delimited JSON instead of being
+	/// returned in this response.
+	#[response]
+	#[derive(Default)]
+	pub struct Response {}
+
+	impl Request {
+		#[must_use]
+		pub fn new() -> Self { Self::default() }
+	}
+
+	impl Response {
+		#[must_use]
+		pub fn new() -> Self { Self::default() }
+	}
+}
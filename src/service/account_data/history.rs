@@ -0,0 +1,175 @@
+//! Account data history
+//!
+//! Every change to a user's account data is recorded together with the value
+//! it replaced, so that admins can find out which device clobbered an entry
+//! and restore it. The history is bounded per user, room and type by
+//! `account_data_history_size`; types listed in
+//! `account_data_history_ignored_types` are not recorded.
+
+use base64::{Engine as _, prelude::BASE64_STANDARD_NO_PAD};
+use conduwuit::{
+	Err, Result, err,
+	utils::{self, hash::sha256, stream::TryIgnore},
+};
+use database::{Deserialized, Handle, Ignore, Interfix, Json};
+use futures::StreamExt;
+use ruma::{DeviceId, OwnedDeviceId, RoomId, UserId};
+use serde::{Deserialize, Serialize};
+
+/// One change to a user's account data.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccountDataChange {
+	/// When the change was made, in milliseconds since the unix epoch.
+	pub ts: u64,
+
+	/// The device that made the change, or none if it was made by the server,
+	/// an appservice or an admin.
+	pub device_id: Option<OwnedDeviceId>,
+
+	/// Unpadded base64 SHA-256 hash of the new value.
+	pub hash: String,
+
+	/// Hash of the value that was replaced, if there was one.
+	pub previous_hash: Option<String>,
+
+	/// The value that was replaced, if there was one.
+	pub previous: Option<serde_json::Value>,
+}
+
+type HistoryKey<'a> = (Ignore, Ignore, &'a str, u64);
+
+impl super::Service {
+	/// Gets up to `limit` of the recorded changes to a user's account data,
+	/// newest first, optionally only those of one type. Items are the type,
+	/// the count of the change and the change.
+	pub async fn history(
+		&self,
+		room_id: Option<&RoomId>,
+		user_id: &UserId,
+		kind: Option<&str>,
+		limit: usize,
+	) -> Vec<(String, u64, AccountDataChange)> {
+		if let Some(kind) = kind {
+			let prefix = (user_id, room_id, kind, Interfix);
+			return self
+				.db
+				.useridroomtypecount_accountdatahistory
+				.rev_stream_prefix(&prefix)
+				.ignore_err()
+				.map(|((.., count), change): (HistoryKey<'_>, _)| {
+					(kind.to_owned(), count, change)
+				})
+				.take(limit)
+				.collect()
+				.await;
+		}
+
+		// Keys are ordered by type before count, so the changes of all types are
+		// only in order once sorted. The history is bounded per type, which bounds
+		// this too.
+		let prefix = (user_id, room_id, Interfix);
+		let mut changes: Vec<_> = self
+			.db
+			.useridroomtypecount_accountdatahistory
+			.stream_prefix(&prefix)
+			.ignore_err()
+			.map(|((.., kind, count), change): (HistoryKey<'_>, _)| {
+				(kind.to_owned(), count, change)
+			})
+			.collect()
+			.await;
+
+		changes.sort_unstable_by(|(_, a, _), (_, b, _)| b.cmp(a));
+		changes.truncate(limit);
+		changes
+	}
+
+	/// Restores the value a recorded change replaced. The restore is itself
+	/// recorded, so it can be undone the same way.
+	pub async fn restore(
+		&self,
+		room_id: Option<&RoomId>,
+		user_id: &UserId,
+		kind: &str,
+		count: u64,
+	) -> Result {
+		let key = (user_id, room_id, kind, count);
+		let change: AccountDataChange = self
+			.db
+			.useridroomtypecount_accountdatahistory
+			.qry(&key)
+			.await
+			.deserialized()
+			.map_err(|_| err!(Request(NotFound("No change to {kind} with count {count}."))))?;
+
+		let Some(previous) = change.previous else {
+			return Err!(Request(NotFound(
+				"{kind} did not exist before this change, so there is nothing to restore."
+			)));
+		};
+
+		self.update_from(room_id, user_id, None, kind.into(), &previous)
+			.await
+	}
+
+	/// Records a change and drops the oldest changes beyond the configured
+	/// history size.
+	pub(super) async fn record_change(
+		&self,
+		(user_id, room_id, kind, count): (&UserId, Option<&RoomId>, &str, u64),
+		change: AccountDataChange,
+	) {
+		let config = &self.services.server.config;
+		let max = config.account_data_history_size;
+		if max == 0
+			|| config
+				.account_data_history_ignored_types
+				.iter()
+				.any(|ignored| ignored == kind)
+		{
+			return;
+		}
+
+		let key = (user_id, room_id, kind, count);
+		self.db
+			.useridroomtypecount_accountdatahistory
+			.put(key, Json(change));
+
+		let prefix = (user_id, room_id, kind, Interfix);
+		let keys: Vec<Vec<u8>> = self
+			.db
+			.useridroomtypecount_accountdatahistory
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.map(<[u8]>::to_vec)
+			.collect()
+			.await;
+
+		for key in keys.iter().rev().skip(max) {
+			self.db.useridroomtypecount_accountdatahistory.remove(key);
+		}
+	}
+}
+
+impl AccountDataChange {
+	pub(super) fn new(
+		device_id: Option<&DeviceId>,
+		data: &serde_json::Value,
+		previous: Option<Handle<'_>>,
+	) -> Self {
+		let previous: Option<serde_json::Value> =
+			previous.and_then(|previous| serde_json::from_slice(&previous).ok());
+
+		Self {
+			ts: utils::millis_since_unix_epoch(),
+			device_id: device_id.map(ToOwned::to_owned),
+			hash: hash(data),
+			previous_hash: previous.as_ref().map(hash),
+			previous,
+		}
+	}
+}
+
+fn hash(value: &serde_json::Value) -> String {
+	BASE64_STANDARD_NO_PAD.encode(sha256::hash(value.to_string().as_bytes()))
+}
@@ -1,13 +1,15 @@
+mod history;
+
 use std::sync::Arc;
 
 use conduwuit::{
-	Err, Result, err,
+	Err, Result, Server, err,
 	utils::{ReadyExt, result::LogErr, stream::TryIgnore},
 };
 use database::{Deserialized, Handle, Ignore, Json, Map};
 use futures::{Stream, StreamExt, TryFutureExt};
 use ruma::{
-	OwnedRoomId, OwnedUserId, RoomId, UserId,
+	DeviceId, OwnedRoomId, OwnedUserId, RoomId, UserId,
 	events::{
 		AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, GlobalAccountDataEventType,
 		RoomAccountDataEventType,
@@ -16,6 +18,7 @@
 };
 use serde::Deserialize;
 
+pub use self::history::AccountDataChange;
 use crate::{Dep, globals, sync};
 
 #[derive(Debug)]
@@ -32,9 +35,11 @@ pub struct Service {
 struct Data {
 	roomuserdataid_accountdata: Arc<Map>,
 	roomusertype_roomuserdataid: Arc<Map>,
+	useridroomtypecount_accountdatahistory: Arc<Map>,
 }
 
 struct Services {
+	server: Arc<Server>,
 	globals: Dep<globals::Service>,
 	sync: Dep<sync::Service>,
 }
@@ -43,12 +48,16 @@ impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			services: Services {
+				server: args.server.clone(),
 				globals: args.depend::<globals::Service>("globals"),
 				sync: args.depend::<sync::Service>("sync"),
 			},
 			db: Data {
 				roomuserdataid_accountdata: args.db["roomuserdataid_accountdata"].clone(),
 				roomusertype_roomuserdataid: args.db["roomusertype_roomuserdataid"].clone(),
+				useridroomtypecount_accountdatahistory: args.db
+					["useridroomtypecount_accountdatahistory"]
+					.clone(),
 			},
 		}))
 	}
@@ -59,13 +68,28 @@ fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 impl Service {
 	/// Places one event in the account data of the user and removes the
 	/// previous entry.
-	#[allow(clippy::needless_pass_by_value)]
 	pub async fn update(
 		&self,
 		room_id: Option<&RoomId>,
 		user_id: &UserId,
 		event_type: RoomAccountDataEventType,
 		data: &serde_json::Value,
+	) -> Result<()> {
+		self.update_from(room_id, user_id, None, event_type, data)
+			.await
+	}
+
+	/// Places one event in the account data of the user on behalf of one of
+	/// their devices and removes the previous entry. The change is recorded in
+	/// the account data history.
+	#[allow(clippy::needless_pass_by_value)]
+	pub async fn update_from(
+		&self,
+		room_id: Option<&RoomId>,
+		user_id: &UserId,
+		device_id: Option<&DeviceId>,
+		event_type: RoomAccountDataEventType,
+		data: &serde_json::Value,
 	) -> Result<()> {
 		if data.get("type").is_none() || data.get("content").is_none() {
 			return Err!(Request(InvalidParam("Account data doesn't have all required fields.")));
@@ -81,6 +105,16 @@ pub async fn update(
 		let prev = self.db.roomusertype_roomuserdataid.qry(&key).await;
 		self.db.roomusertype_roomuserdataid.put(key, roomuserdataid);
 
+		let prev_data = match &prev {
+			| Ok(prev) => self.db.roomuserdataid_accountdata.get(prev).await.ok(),
+			| Err(_) => None,
+		};
+
+		let change = AccountDataChange::new(device_id, data, prev_data);
+		let kind = event_type.to_string();
+		self.record_change((user_id, room_id, &kind, count), change)
+			.await;
+
 		// Remove old entry
 		if let Ok(prev) = prev {
 			self.db.roomuserdataid_accountdata.remove(&prev);
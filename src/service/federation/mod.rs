@@ -1,4 +1,6 @@
 mod execute;
+mod report;
+mod signed_get;
 
 use std::{
 	collections::{HashMap, HashSet},
@@ -16,7 +18,11 @@
 	api::error::{ErrorKind, LimitExceededErrorData, RetryAfter},
 };
 
-use crate::{Dep, client, moderation, server_keys};
+pub use self::{
+	report::{CheckStatus, ConnectivityCheck, ConnectivityReport},
+	signed_get::{SignedEndpoint, SignedGet},
+};
+use crate::{Dep, client, globals, moderation, server_keys};
 
 pub struct Service {
 	services: Services,
@@ -27,6 +33,7 @@ pub struct Service {
 struct Services {
 	server: Arc<Server>,
 	client: Dep<client::Service>,
+	globals: Dep<globals::Service>,
 	server_keys: Dep<server_keys::Service>,
 	moderation: Dep<moderation::Service>,
 }
@@ -38,6 +45,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			services: Services {
 				server: args.server.clone(),
 				client: args.depend::<client::Service>("client"),
+				globals: args.depend::<globals::Service>("globals"),
 				server_keys: args.depend::<server_keys::Service>("server_keys"),
 				moderation: args.depend::<moderation::Service>("moderation"),
 			},
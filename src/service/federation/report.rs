@@ -0,0 +1,482 @@
+//! Federation connectivity report
+//!
+//! Runs the checks a remote server would go through to reach a server: server
+//! name resolution, the TLS certificate, the `/version` endpoint and the
+//! signing keys. For this server, a signed request is also sent to ourselves
+//! when `federation_loopback` is enabled.
+
+use std::{
+	collections::BTreeMap,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use conduwuit::{Err, Result, utils::time};
+use der::{
+	Reader, SliceReader, Tag,
+	asn1::{GeneralizedTime, UtcTime},
+};
+use reqwest::tls::TlsInfo;
+use resolvematrix::{
+	resolution::{Resolution, ResolvedDestination},
+	server::{MatrixResolver, MatrixResolverBuilder},
+};
+use ruma::{
+	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, OwnedServerName, ServerName,
+	api::federation::{
+		discovery::{ServerSigningKeys, get_server_keys, get_server_version},
+		query::get_profile_information,
+	},
+	signatures::{PublicKeyMap, verify_json},
+};
+use serde::Serialize;
+
+use crate::client;
+
+/// Certificates expiring sooner than this are reported as a warning.
+const CERTIFICATE_EXPIRY_WARNING: Duration = Duration::from_hours(14 * 24);
+
+/// The results of the federation checks against one server.
+#[derive(Debug, Serialize)]
+pub struct ConnectivityReport {
+	pub server_name: OwnedServerName,
+
+	/// Whether none of the checks failed.
+	pub ok: bool,
+
+	pub checks: Vec<ConnectivityCheck>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectivityCheck {
+	pub name: &'static str,
+	pub status: CheckStatus,
+	pub detail: String,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+	Ok,
+	Warning,
+	Failed,
+	Skipped,
+}
+
+impl ConnectivityCheck {
+	fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+		Self { name, status, detail: detail.into() }
+	}
+}
+
+impl super::Service {
+	/// Checks whether `server_name` can be reached over federation, bypassing
+	/// the resolver cache.
+	pub async fn connectivity_report(
+		&self,
+		server_name: &ServerName,
+	) -> Result<ConnectivityReport> {
+		let config = &self.services.server.config;
+		if !config.allow_federation {
+			return Err!(Config("allow_federation", "Federation is disabled."));
+		}
+
+		let is_ours = server_name == self.services.server.name;
+		let mut checks = Vec::new();
+		if !self.is_healthy(server_name) {
+			let retry_after = self.retry_after(server_name).unwrap_or_default();
+			checks.push(ConnectivityCheck::new(
+				"backoff",
+				CheckStatus::Warning,
+				format!(
+					"Recent requests failed, requests are held back for another {}.",
+					time::pretty(retry_after)
+				),
+			));
+		}
+
+		let resolver = MatrixResolverBuilder::new()
+			.dangerous_tls_accept_invalid_certs(config.allow_invalid_tls_certificates_yes_i_know_what_the_fuck_i_am_doing_with_this_and_i_know_this_is_insecure)
+			.http_client(self.services.client.dns.clone())
+			.dns_resolver(self.services.client.dns_resolver.clone())
+			.build()?;
+
+		match resolver.resolve_server(server_name.as_str()).await {
+			| Ok(actual) => {
+				checks.push(resolution_check(&actual));
+				checks.push(self.check_tls(&resolver, &actual).await);
+			},
+			| Err(e) => {
+				checks.push(ConnectivityCheck::new(
+					"resolution",
+					CheckStatus::Failed,
+					e.to_string(),
+				));
+				checks.push(ConnectivityCheck::new(
+					"tls",
+					CheckStatus::Skipped,
+					"The server name could not be resolved.",
+				));
+			},
+		}
+
+		checks.push(self.check_version(server_name).await);
+		checks.push(self.check_keys(server_name, is_ours).await);
+		if is_ours {
+			checks.push(self.check_loopback(server_name).await);
+		}
+
+		Ok(ConnectivityReport {
+			server_name: server_name.to_owned(),
+			ok: checks
+				.iter()
+				.all(|check| check.status != CheckStatus::Failed),
+			checks,
+		})
+	}
+
+	/// Connects to the resolved destination with certificate verification
+	/// enabled and reports when the certificate expires.
+	async fn check_tls(
+		&self,
+		resolver: &MatrixResolver,
+		actual: &Resolution,
+	) -> ConnectivityCheck {
+		const NAME: &str = "tls";
+
+		let client = client::base(&self.services.server.config).and_then(|builder| {
+			builder
+				.dns_resolver(resolver.create_dns_resolver())
+				.danger_accept_invalid_certs(false)
+				.tls_info(true)
+				.build()
+				.map_err(Into::into)
+		});
+
+		let client = match client {
+			| Ok(client) => client,
+			| Err(e) => return ConnectivityCheck::new(NAME, CheckStatus::Failed, e.to_string()),
+		};
+
+		let url = format!(
+			"{}/_matrix/federation/v1/version",
+			actual.base_url().as_str().trim_end_matches('/')
+		);
+
+		let response = match client.get(&url).send().await {
+			| Ok(response) => response,
+			| Err(e) => {
+				return ConnectivityCheck::new(
+					NAME,
+					CheckStatus::Failed,
+					format!("Could not connect to {url} with a verified certificate: {e}"),
+				);
+			},
+		};
+
+		let not_after = response
+			.extensions()
+			.get::<TlsInfo>()
+			.and_then(TlsInfo::peer_certificate)
+			.and_then(certificate_not_after);
+
+		let Some(not_after) = not_after else {
+			return ConnectivityCheck::new(
+				NAME,
+				CheckStatus::Ok,
+				format!("The certificate for {} is valid.", actual.host),
+			);
+		};
+
+		let expiry = time::format_utc(not_after);
+		match not_after.duration_since(SystemTime::now()) {
+			| Err(_) => ConnectivityCheck::new(
+				NAME,
+				CheckStatus::Failed,
+				format!("The certificate for {} expired on {expiry}.", actual.host),
+			),
+			| Ok(remaining) => ConnectivityCheck::new(
+				NAME,
+				if remaining < CERTIFICATE_EXPIRY_WARNING {
+					CheckStatus::Warning
+				} else {
+					CheckStatus::Ok
+				},
+				format!(
+					"The certificate for {} is valid and expires on {expiry}, in {}.",
+					actual.host,
+					time::pretty(remaining)
+				),
+			),
+		}
+	}
+
+	async fn check_version(&self, server_name: &ServerName) -> ConnectivityCheck {
+		const NAME: &str = "version";
+
+		match self
+			.execute_unauthenticated(server_name, get_server_version::v1::Request::new())
+			.await
+		{
+			| Ok(response) => {
+				let (name, version) = response
+					.server
+					.map(|server| (server.name, server.version))
+					.unwrap_or_default();
+
+				ConnectivityCheck::new(
+					NAME,
+					CheckStatus::Ok,
+					format!(
+						"{} {}",
+						name.as_deref().unwrap_or("unknown"),
+						version.as_deref().unwrap_or("unknown")
+					),
+				)
+			},
+			| Err(e) => ConnectivityCheck::new(NAME, CheckStatus::Failed, e.to_string()),
+		}
+	}
+
+	/// Fetches the signing keys of the server directly and checks their
+	/// self-signature and validity. For this server, also checks that the
+	/// served keys are our own.
+	async fn check_keys(&self, server_name: &ServerName, is_ours: bool) -> ConnectivityCheck {
+		const NAME: &str = "keys";
+
+		let server_key = match self
+			.execute_unauthenticated(server_name, get_server_keys::v2::Request::new())
+			.await
+		{
+			| Ok(response) => response.server_key,
+			| Err(e) => return ConnectivityCheck::new(NAME, CheckStatus::Failed, e.to_string()),
+		};
+
+		let parsed = server_key
+			.deserialize()
+			.map_err(|e| e.to_string())
+			.and_then(|keys| {
+				serde_json::from_str::<CanonicalJsonObject>(server_key.json().get())
+					.map(|object| (keys, object))
+					.map_err(|e| e.to_string())
+			});
+
+		let (keys, object): (ServerSigningKeys, _) = match parsed {
+			| Ok(parsed) => parsed,
+			| Err(e) => {
+				return ConnectivityCheck::new(
+					NAME,
+					CheckStatus::Failed,
+					format!("The server returned invalid keys: {e}"),
+				);
+			},
+		};
+
+		if keys.server_name != server_name {
+			return ConnectivityCheck::new(
+				NAME,
+				CheckStatus::Failed,
+				format!("The keys are for {} instead.", keys.server_name),
+			);
+		}
+
+		let public_keys: PublicKeyMap = BTreeMap::from([(
+			server_name.to_string(),
+			keys.verify_keys
+				.iter()
+				.map(|(key_id, key)| (key_id.to_string(), key.key.clone()))
+				.collect(),
+		)]);
+
+		if let Err(e) = verify_json(&public_keys, &object) {
+			return ConnectivityCheck::new(
+				NAME,
+				CheckStatus::Failed,
+				format!("The keys are not signed with themselves: {e}"),
+			);
+		}
+
+		if is_ours {
+			let (key_id, verify_key) = self.services.server_keys.active_verify_key();
+			if keys
+				.verify_keys
+				.get(key_id)
+				.is_none_or(|key| key.key != verify_key.key)
+			{
+				return ConnectivityCheck::new(
+					NAME,
+					CheckStatus::Failed,
+					format!(
+						"The served keys do not include our signing key {key_id}, requests may \
+						 be reaching another server."
+					),
+				);
+			}
+		}
+
+		let key_ids: Vec<_> = keys.verify_keys.keys().map(ToString::to_string).collect();
+		if keys.valid_until_ts <= MilliSecondsSinceUnixEpoch::now() {
+			return ConnectivityCheck::new(
+				NAME,
+				CheckStatus::Warning,
+				format!("The keys {} are expired.", key_ids.join(", ")),
+			);
+		}
+
+		ConnectivityCheck::new(
+			NAME,
+			CheckStatus::Ok,
+			format!("The keys {} are correctly signed.", key_ids.join(", ")),
+		)
+	}
+
+	/// Sends a signed request to ourselves, which checks that other servers can
+	/// reach us and that we accept our own signatures.
+	async fn check_loopback(&self, server_name: &ServerName) -> ConnectivityCheck {
+		const NAME: &str = "loopback";
+
+		if !self.services.server.config.federation_loopback {
+			return ConnectivityCheck::new(
+				NAME,
+				CheckStatus::Skipped,
+				"Enable federation_loopback to send a signed request to ourselves.",
+			);
+		}
+
+		let server_user = self.services.globals.server_user.clone();
+		match self
+			.execute(server_name, get_profile_information::v1::Request::new(server_user))
+			.await
+		{
+			| Ok(_) => ConnectivityCheck::new(
+				NAME,
+				CheckStatus::Ok,
+				"A signed request to ourselves was accepted.",
+			),
+			| Err(e) => ConnectivityCheck::new(NAME, CheckStatus::Failed, e.to_string()),
+		}
+	}
+}
+
+fn resolution_check(actual: &Resolution) -> ConnectivityCheck {
+	let destination = match &actual.destination {
+		| ResolvedDestination::Literal(addr) => addr.to_string(),
+		| ResolvedDestination::Named(host, port) => format!("{host}:{port}"),
+	};
+
+	ConnectivityCheck::new(
+		"resolution",
+		CheckStatus::Ok,
+		format!(
+			"{destination} with SNI {} ({}{})",
+			actual.host,
+			actual.resolution_step,
+			if actual.is_override { ", override" } else { "" }
+		),
+	)
+}
+
+/// Reads the expiry time of a DER encoded X.509 certificate.
+fn certificate_not_after(certificate: &[u8]) -> Option<SystemTime> {
+	let not_after = SliceReader::new(certificate)
+		.and_then(|mut reader| {
+			reader.sequence(|certificate| {
+				let not_after = certificate.sequence(tbs_certificate_not_after)?;
+				skip_remaining(certificate)?;
+				Ok(not_after)
+			})
+		})
+		.ok()?;
+
+	UNIX_EPOCH.checked_add(not_after)
+}
+
+/// Reads the expiry time from the `TBSCertificate` of an X.509 certificate,
+/// as a duration since the unix epoch.
+fn tbs_certificate_not_after<'r, R: Reader<'r>>(
+	tbs_certificate: &mut R,
+) -> der::Result<Duration> {
+	// Skip the optional version, then the serial number, signature algorithm
+	// and issuer to get to the validity.
+	if tbs_certificate.peek_tag()?.is_context_specific() {
+		tbs_certificate.tlv_bytes()?;
+	}
+
+	for _ in 0..3 {
+		tbs_certificate.tlv_bytes()?;
+	}
+
+	let not_after = tbs_certificate.sequence(|validity| {
+		validity.tlv_bytes()?;
+		match validity.peek_tag()? {
+			| Tag::UtcTime => Ok(validity.decode::<UtcTime>()?.to_unix_duration()),
+			| Tag::GeneralizedTime =>
+				Ok(validity.decode::<GeneralizedTime>()?.to_unix_duration()),
+			| tag => Err(tag.unexpected_error(None)),
+		}
+	})?;
+
+	skip_remaining(tbs_certificate)?;
+
+	Ok(not_after)
+}
+
+/// Skips the remaining elements of a sequence.
+fn skip_remaining<'r, R: Reader<'r>>(reader: &mut R) -> der::Result<()> {
+	while !reader.is_finished() {
+		reader.tlv_bytes()?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, UNIX_EPOCH};
+
+	use base64::{Engine as _, engine::general_purpose::STANDARD};
+
+	use super::certificate_not_after;
+
+	/// Self-signed certificate valid until 2030-06-01 12:00:00, as a UTCTime.
+	const UTC_TIME: &str = "MIIBgTCCASegAwIBAgIUIj92Qs4XbHOfJFd0o35Y32tkh0YwCgYIKoZIzj0EAwIwFjEUMBIGA1UEAwwLZXhhbXBsZS5vcmcwHhcNMjUwMTAxMDAwMDAwWhcNMzAwNjAxMTIwMDAwWjAWMRQwEgYDVQQDDAtleGFtcGxlLm9yZzBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABCBFoDHMo6GqNjzULDHkPm8IrluK1Ju7bG5qVixYL9x+WRE4eVo6+Zyz+FC/OHlNiFMuuqrIjIzMM1ShK4jZNbWjUzBRMB0GA1UdDgQWBBRToBDJsdCMmdFfUUxrGm8/xuk/cjAfBgNVHSMEGDAWgBRToBDJsdCMmdFfUUxrGm8/xuk/cjAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIQCvxZ4TC30JDSLEtTOdxDcvJWEOGuRvZ5aXHhErxWCbxgIgG3G1//FM0Ooqewr3LhcFHuKnZGAcH0Zt46zlwwBmh7w=";
+
+	/// Self-signed certificate valid until 2056-03-01 00:00:00, past 2049 so as
+	/// a GeneralizedTime.
+	const GENERALIZED_TIME: &str = "MIIBgjCCASmgAwIBAgIUXRKRQM1lXv0UrciO6ds9WJQzlxIwCgYIKoZIzj0EAwIwFjEUMBIGA1UEAwwLZXhhbXBsZS5vcmcwIBcNMjUwMTAxMDAwMDAwWhgPMjA1NjAzMDEwMDAwMDBaMBYxFDASBgNVBAMMC2V4YW1wbGUub3JnMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEIEWgMcyjoao2PNQsMeQ+bwiuW4rUm7tsbmpWLFgv3H5ZETh5Wjr5nLP4UL84eU2IUy66qsiMjMwzVKEriNk1taNTMFEwHQYDVR0OBBYEFFOgEMmx0IyZ0V9RTGsabz/G6T9yMB8GA1UdIwQYMBaAFFOgEMmx0IyZ0V9RTGsabz/G6T9yMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgQqtl7nWfQiXeiq5zsalV3UiKPThFQmTyMitThk+rlykCIHUJkFkLJH28C2dZkmGYtlg31gY0/lUJwk7c7HbM71+J";
+
+	/// Self-signed certificate valid until 1999-01-01 00:00:00, as a UTCTime.
+	const UTC_TIME_1900S: &str = "MIIBgjCCASegAwIBAgIUHBFzvn5P6ZOucdLBUSVkm5X2seAwCgYIKoZIzj0EAwIwFjEUMBIGA1UEAwwLZXhhbXBsZS5vcmcwHhcNOTUwMTAxMDAwMDAwWhcNOTkwMTAxMDAwMDAwWjAWMRQwEgYDVQQDDAtleGFtcGxlLm9yZzBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABCBFoDHMo6GqNjzULDHkPm8IrluK1Ju7bG5qVixYL9x+WRE4eVo6+Zyz+FC/OHlNiFMuuqrIjIzMM1ShK4jZNbWjUzBRMB0GA1UdDgQWBBRToBDJsdCMmdFfUUxrGm8/xuk/cjAfBgNVHSMEGDAWgBRToBDJsdCMmdFfUUxrGm8/xuk/cjAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQDmMqErnrz7ZpxUuiTe8wDWsRmai9ONKkiAC8AZmhdoiQIhAJyif8sMmhZSUy+6UsTve8ONfCft/hveIyHt8zzt/0hH";
+
+	fn not_after(certificate: &str) -> Option<u64> {
+		let der = STANDARD.decode(certificate).expect("valid base64");
+
+		certificate_not_after(&der)?
+			.duration_since(UNIX_EPOCH)
+			.ok()
+			.as_ref()
+			.map(Duration::as_secs)
+	}
+
+	#[test]
+	fn utc_time() {
+		assert_eq!(not_after(UTC_TIME), Some(1_906_545_600));
+	}
+
+	#[test]
+	fn generalized_time() {
+		assert_eq!(not_after(GENERALIZED_TIME), Some(2_719_094_400));
+	}
+
+	#[test]
+	fn utc_time_before_2000() {
+		assert_eq!(not_after(UTC_TIME_1900S), Some(915_148_800));
+	}
+
+	#[test]
+	fn truncated_certificate() {
+		let der = STANDARD.decode(UTC_TIME).expect("valid base64");
+
+		assert_eq!(certificate_not_after(&der[..200]), None);
+		assert_eq!(certificate_not_after(&[]), None);
+	}
+}
@@ -0,0 +1,146 @@
+//! Signed federation requests for trusted local tools
+//!
+//! Server admins can have the server perform a fixed set of read-only
+//! federation requests, to see exactly what a remote server answers us. Only
+//! the endpoints listed in `federation_request_endpoints` are allowed, and
+//! every request is logged along with the admin who made it.
+
+use conduwuit::{Err, Result, err, info, warn};
+use ruma::{
+	OwnedEventId, OwnedRoomId, OwnedServerName, UserId,
+	api::federation::{
+		authorization::get_event_authorization,
+		discovery::{get_server_keys, get_server_version},
+		event::{get_event, get_room_state, get_room_state_ids},
+	},
+};
+pub use ruminuwuity::admin::continuwuity::federation::request::v1::SignedEndpoint;
+use serde_json::{Value as JsonValue, json};
+
+/// A federation request made on behalf of a server admin.
+#[derive(Clone, Debug)]
+pub struct SignedGet {
+	pub destination: OwnedServerName,
+	pub endpoint: SignedEndpoint,
+	pub room_id: Option<OwnedRoomId>,
+	pub event_id: Option<OwnedEventId>,
+}
+
+impl super::Service {
+	/// Performs a federation request on behalf of a server admin and returns
+	/// the response as JSON. The endpoint must be allowed by
+	/// `federation_request_endpoints`.
+	pub async fn signed_get(&self, sender: &UserId, request: &SignedGet) -> Result<JsonValue> {
+		let endpoint = request.endpoint.as_str();
+		let allowed = self
+			.services
+			.server
+			.config
+			.federation_request_endpoints
+			.iter()
+			.any(|allowed| allowed == endpoint);
+
+		if !allowed {
+			warn!(
+				%sender,
+				destination = %request.destination,
+				endpoint,
+				"Refused federation request for an endpoint which is not allowed"
+			);
+
+			return Err!(Request(Forbidden(
+				"The {endpoint} endpoint is not allowed by federation_request_endpoints."
+			)));
+		}
+
+		let result = self.perform_signed_get(request).await;
+		info!(
+			%sender,
+			destination = %request.destination,
+			endpoint,
+			room_id = ?request.room_id,
+			event_id = ?request.event_id,
+			success = result.is_ok(),
+			"Federation request on behalf of an admin"
+		);
+
+		result
+	}
+
+	async fn perform_signed_get(&self, request: &SignedGet) -> Result<JsonValue> {
+		let dest = &request.destination;
+		let room_id = || {
+			request
+				.room_id
+				.clone()
+				.ok_or_else(|| err!(Request(MissingParam("room_id is required."))))
+		};
+
+		let event_id = || {
+			request
+				.event_id
+				.clone()
+				.ok_or_else(|| err!(Request(MissingParam("event_id is required."))))
+		};
+
+		let response = match request.endpoint {
+			| SignedEndpoint::Version => {
+				let response = self
+					.execute_unauthenticated(dest, get_server_version::v1::Request::new())
+					.await?;
+
+				json!({ "server": response.server })
+			},
+			| SignedEndpoint::Keys => {
+				let response = self
+					.execute_unauthenticated(dest, get_server_keys::v2::Request::new())
+					.await?;
+
+				json!({ "server_key": response.server_key })
+			},
+			| SignedEndpoint::Event => {
+				let response = self
+					.execute(dest, get_event::v1::Request::new(event_id()?))
+					.await?;
+
+				json!({
+					"origin": response.origin,
+					"origin_server_ts": response.origin_server_ts,
+					"pdu": response.pdu,
+				})
+			},
+			| SignedEndpoint::State => {
+				let response = self
+					.execute(dest, get_room_state::v1::Request::new(event_id()?, room_id()?))
+					.await?;
+
+				json!({
+					"pdus": response.pdus,
+					"auth_chain": response.auth_chain,
+				})
+			},
+			| SignedEndpoint::StateIds => {
+				let response = self
+					.execute(dest, get_room_state_ids::v1::Request::new(event_id()?, room_id()?))
+					.await?;
+
+				json!({
+					"pdu_ids": response.pdu_ids,
+					"auth_chain_ids": response.auth_chain_ids,
+				})
+			},
+			| SignedEndpoint::EventAuth => {
+				let response = self
+					.execute(
+						dest,
+						get_event_authorization::v1::Request::new(room_id()?, event_id()?),
+					)
+					.await?;
+
+				json!({ "auth_chain": response.auth_chain })
+			},
+		};
+
+		Ok(response)
+	}
+}
@@ -1,3 +1,5 @@
+mod report;
+
 use std::{collections::BTreeMap, sync::Arc};
 
 use conduwuit::{
@@ -12,6 +14,7 @@
 	serde::Raw,
 };
 
+pub use self::report::BadSession;
 use crate::{Dep, globals};
 
 pub struct Service {
@@ -22,6 +25,7 @@ pub struct Service {
 struct Data {
 	backupid_algorithm: Arc<Map>,
 	backupid_etag: Arc<Map>,
+	backupid_lastupload: Arc<Map>,
 	backupkeyid_backup: Arc<Map>,
 }
 
@@ -35,6 +39,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			db: Data {
 				backupid_algorithm: args.db["backupid_algorithm"].clone(),
 				backupid_etag: args.db["backupid_etag"].clone(),
+				backupid_lastupload: args.db["backupid_lastupload"].clone(),
 				backupkeyid_backup: args.db["backupkeyid_backup"].clone(),
 			},
 			services: Services {
@@ -67,6 +72,7 @@ pub async fn delete_backup(&self, user_id: &UserId, version: &str) {
 		let key = (user_id, version);
 		self.db.backupid_algorithm.del(key);
 		self.db.backupid_etag.del(key);
+		self.db.backupid_lastupload.del(key);
 
 		let key = (user_id, version, Interfix);
 		self.db
@@ -163,6 +169,8 @@ pub async fn add_key(
 			.backupkeyid_backup
 			.put_raw(key, key_data.json().get());
 
+		self.set_last_upload(user_id, version);
+
 		Ok(())
 	}
 
@@ -0,0 +1,177 @@
+//! Server-side view of key backups for admins
+//!
+//! The server can't decrypt backed up sessions, but it can tell whether they
+//! are well-formed and match the algorithm of the backup version they were
+//! uploaded to, which is usually enough to explain "unable to decrypt"
+//! reports.
+
+use std::collections::BTreeMap;
+
+use conduwuit::{
+	Result,
+	utils::{
+		self,
+		stream::{ReadyExt, TryIgnore},
+	},
+};
+use database::{Deserialized, Ignore, Interfix};
+use futures::{Stream, StreamExt};
+use ruma::{
+	OwnedRoomId, UserId,
+	api::client::backup::{BackupAlgorithm, KeyBackupData},
+	serde::Raw,
+};
+
+/// Session data fields required by each backup algorithm.
+const ALGORITHM_FIELDS: &[(&str, &[&str])] = &[
+	("m.megolm_backup.v1.curve25519-aes-sha2", &["ephemeral", "ciphertext", "mac"]),
+	("m.megolm_backup.v1.aes-hmac-sha2", &["iv", "ciphertext", "mac"]),
+	("org.matrix.msc3270.v1.aes-hmac-sha2", &["iv", "ciphertext", "mac"]),
+];
+
+/// A backed up session the server considers broken.
+#[derive(Debug)]
+pub struct BadSession {
+	pub room_id: OwnedRoomId,
+	pub session_id: String,
+	pub reason: String,
+}
+
+impl super::Service {
+	/// Streams every backup version of the user with its algorithm, oldest
+	/// first.
+	pub fn backup_versions<'a>(
+		&'a self,
+		user_id: &'a UserId,
+	) -> impl Stream<Item = (String, Raw<BackupAlgorithm>)> + Send + 'a {
+		type KeyVal<'a> = ((Ignore, &'a str), Raw<BackupAlgorithm>);
+
+		let prefix = (user_id, Interfix);
+		self.db
+			.backupid_algorithm
+			.stream_prefix(&prefix)
+			.ignore_err()
+			.map(|((_, version), algorithm): KeyVal<'_>| (version.to_owned(), algorithm))
+	}
+
+	/// Gets when keys were last uploaded to a backup version, in milliseconds
+	/// since the unix epoch.
+	pub async fn last_upload(&self, user_id: &UserId, version: &str) -> Result<u64> {
+		let key = (user_id, version);
+		self.db.backupid_lastupload.qry(&key).await.deserialized()
+	}
+
+	/// Counts the backed up sessions of each room in a backup version.
+	pub async fn count_keys_per_room(
+		&self,
+		user_id: &UserId,
+		version: &str,
+	) -> BTreeMap<OwnedRoomId, usize> {
+		type Key = (Ignore, Ignore, OwnedRoomId, Ignore);
+
+		let mut rooms = BTreeMap::new();
+		let prefix = (user_id, version, Interfix);
+		self.db
+			.backupkeyid_backup
+			.keys_prefix(&prefix)
+			.ignore_err()
+			.ready_for_each(|(_, _, room_id, _): Key| {
+				let count: &mut usize = rooms.entry(room_id).or_default();
+				*count = count.saturating_add(1);
+			})
+			.await;
+
+		rooms
+	}
+
+	/// Checks every session of a backup version, reporting the ones that can't
+	/// be parsed or whose session data doesn't fit the backup's algorithm.
+	pub async fn check_backup(&self, user_id: &UserId, version: &str) -> Result<Vec<BadSession>> {
+		type KeyVal<'a> = ((Ignore, Ignore, OwnedRoomId, &'a str), Raw<KeyBackupData>);
+
+		let algorithm: String = self
+			.get_backup(user_id, version)
+			.await?
+			.get_field("algorithm")?
+			.unwrap_or_default();
+
+		let required = ALGORITHM_FIELDS
+			.iter()
+			.find(|(name, _)| *name == algorithm)
+			.map(|(_, fields)| *fields);
+
+		let prefix = (user_id, version, Interfix);
+		let bad = self
+			.db
+			.backupkeyid_backup
+			.stream_prefix(&prefix)
+			.ignore_err()
+			.ready_filter_map(|((_, _, room_id, session_id), key_data): KeyVal<'_>| {
+				check_session(&key_data, required).map(|reason| BadSession {
+					room_id,
+					session_id: session_id.to_owned(),
+					reason,
+				})
+			})
+			.collect()
+			.await;
+
+		Ok(bad)
+	}
+
+	/// Deletes every backup version of the user except the latest one, which
+	/// is the only one clients use. Returns the deleted versions.
+	pub async fn purge_stale_backups(&self, user_id: &UserId) -> Result<Vec<String>> {
+		let latest = self.get_latest_backup_version(user_id).await?;
+		let stale: Vec<String> = self
+			.backup_versions(user_id)
+			.map(|(version, _)| version)
+			.ready_filter(|version| *version != latest)
+			.collect()
+			.await;
+
+		for version in &stale {
+			self.delete_backup(user_id, version).await;
+		}
+
+		Ok(stale)
+	}
+
+	pub(super) fn set_last_upload(&self, user_id: &UserId, version: &str) {
+		let key = (user_id, version);
+		self.db
+			.backupid_lastupload
+			.put(key, utils::millis_since_unix_epoch());
+	}
+}
+
+/// Describes what's wrong with a backed up session, if anything.
+fn check_session(key_data: &Raw<KeyBackupData>, required: Option<&[&str]>) -> Option<String> {
+	let key_data: KeyBackupData = match key_data.deserialize() {
+		| Ok(key_data) => key_data,
+		| Err(e) => return Some(format!("invalid key backup data: {e}")),
+	};
+
+	let session_data: serde_json::Map<String, serde_json::Value> =
+		match key_data.session_data.deserialize_as() {
+			| Ok(session_data) => session_data,
+			| Err(e) => return Some(format!("invalid session data: {e}")),
+		};
+
+	let missing: Vec<_> = required?
+		.iter()
+		.filter(|field| {
+			!session_data
+				.get(**field)
+				.is_some_and(serde_json::Value::is_string)
+		})
+		.copied()
+		.collect();
+
+	(!missing.is_empty()).then(|| {
+		format!(
+			"session data does not match the backup algorithm, missing {}",
+			missing.join(", ")
+		)
+	})
+}
@@ -4,15 +4,17 @@
 	Err, Result, debug, debug_info, err,
 	utils::{ReadyExt, str_from_bytes, stream::TryIgnore, string_from_bytes},
 };
-use database::{Database, Interfix, Map};
-use futures::StreamExt;
+use database::{Database, Deserialized, Ignore, Interfix, Json, Map};
+use futures::{Stream, StreamExt};
 use ruma::{OwnedMxcUri, OwnedUserId, UserId, http_headers::ContentDisposition};
 
-use super::{preview::UrlPreviewData, thumbnail::Dim};
+use super::{pending::Pending, preview::UrlPreviewData, thumbnail::Dim};
 use crate::media::mxc::Mxc;
 
 pub(crate) struct Data {
 	mediaid_file: Arc<Map>,
+	mediaid_pending: Arc<Map>,
+	userid_pendingmedia: Arc<Map>,
 	mediaid_user: Arc<Map>,
 	url_previews: Arc<Map>,
 }
@@ -28,6 +30,8 @@ impl Data {
 	pub(super) fn new(db: &Arc<Database>) -> Self {
 		Self {
 			mediaid_file: db["mediaid_file"].clone(),
+			mediaid_pending: db["mediaid_pending"].clone(),
+			userid_pendingmedia: db["userid_pendingmedia"].clone(),
 			mediaid_user: db["mediaid_user"].clone(),
 			url_previews: db["url_previews"].clone(),
 		}
@@ -166,6 +170,38 @@ pub(crate) async fn get_all_media_keys(&self) -> Vec<Vec<u8>> {
 	}
 
 	#[inline]
+	pub(super) fn set_pending(&self, mxc: &Mxc<'_>, pending: &Pending) {
+		self.mediaid_pending.put(mxc, Json(pending));
+		self.userid_pendingmedia
+			.put_raw((&pending.user_id, mxc), []);
+	}
+
+	pub(super) async fn get_pending(&self, mxc: &Mxc<'_>) -> Result<Pending> {
+		self.mediaid_pending.qry(mxc).await.deserialized()
+	}
+
+	pub(super) fn remove_pending(&self, mxc: &Mxc<'_>, user: &UserId) {
+		self.mediaid_pending.del(mxc);
+		self.userid_pendingmedia.del((user, mxc));
+	}
+
+	/// Lists the media IDs the user reserved, including expired reservations
+	/// which were not removed yet.
+	pub(super) fn user_pending<'a>(
+		&'a self,
+		user: &'a UserId,
+	) -> impl Stream<Item = OwnedMxcUri> + Send + 'a {
+		self.userid_pendingmedia
+			.keys_prefix(&(user, Interfix))
+			.ignore_err()
+			.map(|(_, mxc): (Ignore, OwnedMxcUri)| mxc)
+	}
+
+	/// Lists all reserved media IDs awaiting upload.
+	pub(super) fn all_pending(&self) -> impl Stream<Item = (OwnedMxcUri, Pending)> + Send + '_ {
+		self.mediaid_pending.stream().ignore_err()
+	}
+
 	pub(super) fn remove_url_preview(&self, url: &str) -> Result<()> {
 		self.url_previews.remove(url.as_bytes());
 		Ok(())
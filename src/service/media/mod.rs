@@ -1,26 +1,35 @@
 mod data;
 pub(super) mod migrations;
 pub mod mxc;
+mod pending;
 mod preview;
 mod remote;
 mod tests;
 mod thumbnail;
-use std::{path::PathBuf, sync::Arc, time::SystemTime};
+use std::{
+	collections::HashMap,
+	path::PathBuf,
+	sync::Arc,
+	time::{Duration, SystemTime},
+};
 
 use async_trait::async_trait;
 use base64::{Engine as _, engine::general_purpose};
 use conduwuit::{
-	Err, Result, Server, debug, debug_error, debug_info, debug_warn, err, error, trace,
+	Err, Result, Server, SyncMutex, debug, debug_error, debug_info, debug_warn, err, error,
+	trace,
 	utils::{
 		self, MutexMap,
 		time::{self, TimeDirection},
 	},
 	warn,
 };
-use ruma::{OwnedMxcUri, UserId, http_headers::ContentDisposition};
+use ruma::{OwnedMxcUri, OwnedUserId, UserId, http_headers::ContentDisposition};
 use tokio::{
 	fs,
 	io::{AsyncReadExt, AsyncWriteExt, BufReader},
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
 };
 
 use self::data::{Data, Metadata};
@@ -36,8 +45,13 @@ pub struct FileMeta {
 
 pub struct Service {
 	url_preview_mutex: MutexMap<String, ()>,
+	pending_user_mutex: MutexMap<OwnedUserId, ()>,
+	pending_upload_mutex: MutexMap<String, ()>,
 	pub(super) db: Data,
 	services: Services,
+	/// Downloads waiting for asynchronous uploads, by MXC URI.
+	upload_waiters: SyncMutex<HashMap<String, Arc<Notify>>>,
+	interrupt: Notify,
 }
 
 struct Services {
@@ -51,6 +65,9 @@ struct Services {
 /// generated MXC ID (`media-id`) length
 pub const MXC_LENGTH: usize = 32;
 
+/// Interval between removals of expired media ID reservations.
+const PRUNE_PENDING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 /// Cache control for immutable objects.
 pub const CACHE_CONTROL_IMMUTABLE: &str = "public,max-age=31536000,immutable";
 
@@ -62,6 +79,8 @@ impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			url_preview_mutex: MutexMap::new(),
+			pending_user_mutex: MutexMap::new(),
+			pending_upload_mutex: MutexMap::new(),
 			db: Data::new(args.db),
 			services: Services {
 				server: args.server.clone(),
@@ -70,15 +89,30 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				sending: args.depend::<sending::Service>("sending"),
 				moderation: args.depend::<moderation::Service>("moderation"),
 			},
+			upload_waiters: SyncMutex::default(),
+			interrupt: Notify::new(),
 		}))
 	}
 
 	async fn worker(self: Arc<Self>) -> Result<()> {
 		self.create_media_dir().await?;
 
+		let mut i = interval(PRUNE_PENDING_INTERVAL);
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			self.prune_pending().await;
+		}
+
 		Ok(())
 	}
 
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
@@ -110,6 +144,8 @@ pub async fn create(
 			err!(Database(error!("Failed to write media file for MXC {mxc} at key {key:?}: {e}")))
 		})?;
 
+		f.flush().await?;
+
 		Ok(())
 	}
 
@@ -0,0 +1,212 @@
+//! Asynchronous uploads
+//!
+//! Clients may reserve an MXC URI with `POST /_matrix/media/v1/create` and
+//! upload the content to it later. Until the upload arrives, downloads of the
+//! URI wait for it up to the client's timeout, at most two minutes, and then
+//! fail with `M_NOT_YET_UPLOADED`. Unused reservations expire after
+//! `media_pending_upload_expiry_secs`.
+
+use std::{pin::pin, time::Duration};
+
+use conduwuit::{Err, Result, debug, debug_info, utils};
+use futures::StreamExt;
+use ruma::{
+	OwnedMxcUri, OwnedUserId, UserId,
+	api::error::{ErrorKind, LimitExceededErrorData},
+	http_headers::ContentDisposition,
+};
+use serde::{Deserialize, Serialize};
+use tokio::time::{Instant, sleep_until};
+
+use super::MXC_LENGTH;
+use crate::media::mxc::Mxc;
+
+/// Longest time a download waits for a reserved media ID to be uploaded,
+/// regardless of the client's `timeout_ms`.
+const MAX_UPLOAD_WAIT: Duration = Duration::from_secs(120);
+
+/// A reserved media ID awaiting upload.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Pending {
+	/// The user who reserved the media ID; only they may upload to it.
+	pub user_id: OwnedUserId,
+
+	/// Milliseconds since the epoch when the reservation expires.
+	pub expires_at: u64,
+}
+
+impl Pending {
+	#[inline]
+	#[must_use]
+	pub fn is_expired(&self) -> bool { self.expires_at <= utils::millis_since_unix_epoch() }
+}
+
+impl super::Service {
+	/// Reserves a new media ID for the user to upload to later. Returns the
+	/// MXC URI and when the reservation expires, in milliseconds since the
+	/// epoch.
+	pub async fn create_pending(&self, user: &UserId) -> Result<(OwnedMxcUri, u64)> {
+		// Count and reserve under the user's lock, so concurrent requests
+		// cannot both pass the limit.
+		let _lock = self.pending_user_mutex.lock(user).await;
+
+		let limit = self.services.server.config.max_pending_media_uploads;
+		let pending = self
+			.db
+			.user_pending(user)
+			.filter(|mxc| {
+				let mxc = Mxc::try_from(mxc);
+				async move {
+					match mxc {
+						| Ok(mxc) => self.is_pending(&mxc).await,
+						| Err(_) => false,
+					}
+				}
+			})
+			.count()
+			.await;
+
+		if pending >= limit {
+			return Err(conduwuit::Error::BadRequest(
+				ErrorKind::LimitExceeded(LimitExceededErrorData::new()),
+				"Too many media uploads are pending for this user.",
+			));
+		}
+
+		let expiry = self
+			.services
+			.server
+			.config
+			.media_pending_upload_expiry_secs
+			.saturating_mul(1000);
+		let expires_at = utils::millis_since_unix_epoch().saturating_add(expiry);
+		let media_id = utils::random_string(MXC_LENGTH);
+		let mxc = Mxc {
+			server_name: self.services.globals.server_name(),
+			media_id: &media_id,
+		};
+
+		self.db
+			.set_pending(&mxc, &Pending { user_id: user.to_owned(), expires_at });
+
+		debug!(%mxc, %user, "Reserved media ID for asynchronous upload");
+		Ok((mxc.to_string().into(), expires_at))
+	}
+
+	/// Uploads the content of a media ID reserved with
+	/// [`create_pending`](Self::create_pending).
+	pub async fn upload_pending(
+		&self,
+		mxc: &Mxc<'_>,
+		user: &UserId,
+		content_disposition: Option<&ContentDisposition>,
+		content_type: Option<&str>,
+		file: &[u8],
+	) -> Result<()> {
+		// Held until the reservation is completed, so a concurrent upload to
+		// the same media ID finds it already uploaded.
+		let mxc_str = mxc.to_string();
+		let _lock = self.pending_upload_mutex.lock(mxc_str.as_str()).await;
+
+		let Ok(pending) = self.db.get_pending(mxc).await else {
+			if self.get_metadata(mxc).await.is_some() {
+				return Err!(Request(CannotOverwriteMedia("Media has already been uploaded.")));
+			}
+
+			return Err!(Request(NotFound("Media ID was not reserved or has expired.")));
+		};
+
+		if pending.user_id != user {
+			return Err!(Request(Forbidden("Media ID was reserved by another user.")));
+		}
+
+		if pending.is_expired() {
+			self.db.remove_pending(mxc, &pending.user_id);
+			self.wake_upload_waiters(mxc);
+			return Err!(Request(NotFound("Media ID was not reserved or has expired.")));
+		}
+
+		self.create(mxc, Some(user), content_disposition, content_type, file)
+			.await?;
+
+		// The file is fully written, so woken downloads can read it.
+		self.db.remove_pending(mxc, &pending.user_id);
+		self.wake_upload_waiters(mxc);
+
+		Ok(())
+	}
+
+	/// Whether the media ID is reserved and still awaiting upload.
+	pub async fn is_pending(&self, mxc: &Mxc<'_>) -> bool {
+		self.db
+			.get_pending(mxc)
+			.await
+			.is_ok_and(|pending| !pending.is_expired())
+	}
+
+	/// Waits up to `timeout` for the content of a reserved media ID to be
+	/// uploaded, at most `MAX_UPLOAD_WAIT`. Returns immediately if the media ID
+	/// is not awaiting upload.
+	pub async fn wait_for_upload(&self, mxc: &Mxc<'_>, timeout: Duration) -> Result<()> {
+		let deadline = Instant::now()
+			.checked_add(timeout.min(MAX_UPLOAD_WAIT))
+			.unwrap_or_else(Instant::now);
+
+		let notify = self
+			.upload_waiters
+			.lock()
+			.entry(mxc.to_string())
+			.or_default()
+			.clone();
+
+		loop {
+			// Register for the notification before checking so an upload
+			// completing in between is not missed.
+			let mut uploaded = pin!(notify.notified());
+			uploaded.as_mut().enable();
+
+			if !self.is_pending(mxc).await {
+				self.wake_upload_waiters(mxc);
+				return Ok(());
+			}
+
+			tokio::select! {
+				() = uploaded => (),
+				() = sleep_until(deadline) => {
+					return Err!(Request(NotYetUploaded("Media has not been uploaded yet.")));
+				},
+			}
+		}
+	}
+
+	/// Wakes the downloads waiting for a media ID which is no longer awaiting
+	/// upload.
+	fn wake_upload_waiters(&self, mxc: &Mxc<'_>) {
+		if let Some(notify) = self.upload_waiters.lock().remove(&mxc.to_string()) {
+			notify.notify_waiters();
+		}
+	}
+
+	/// Removes expired reservations.
+	pub(super) async fn prune_pending(&self) {
+		let expired: Vec<_> = self
+			.db
+			.all_pending()
+			.filter_map(|(mxc, pending)| async move {
+				pending.is_expired().then_some((mxc, pending.user_id))
+			})
+			.collect()
+			.await;
+
+		for (mxc, user_id) in &expired {
+			if let Ok(mxc) = Mxc::try_from(mxc) {
+				self.db.remove_pending(&mxc, user_id);
+				self.wake_upload_waiters(&mxc);
+			}
+		}
+
+		if !expired.is_empty() {
+			debug_info!(count = expired.len(), "Removed expired media ID reservations");
+		}
+	}
+}
@@ -73,6 +73,9 @@ async fn fresh(services: &Services) -> Result<()> {
 	db["global"].insert(b"fix_local_invite_state", []);
 	db["global"].insert(SPLIT_USERID_PASSWORD, []);
 	db["global"].insert(DROP_ROOMSYNCTOKEN_SHORTSTATEHASH, []);
+	db["global"].insert(POPULATED_DIRECTORY_SEARCH_INDEX, []);
+	db["global"].insert(REMOVED_ORPHANED_ONE_TIME_KEYS, []);
+	db["global"].insert(POPULATED_PUSHKEY_USERID_INDEX, []);
 
 	// Create the admin room and server user on first run
 	info!("Creating admin room and server user");
@@ -255,6 +258,41 @@ async fn migrate(services: &Services) -> Result<()> {
 			})?;
 	}
 
+	if db["global"]
+		.get(POPULATED_DIRECTORY_SEARCH_INDEX)
+		.await
+		.is_not_found()
+	{
+		info!("Running migration 'populate_directory_search_index'");
+		populate_directory_search_index(services)
+			.await
+			.map_err(|e| {
+				err!("Failed to run 'populate_directory_search_index' migration': {e}")
+			})?;
+	}
+
+	if db["global"]
+		.get(REMOVED_ORPHANED_ONE_TIME_KEYS)
+		.await
+		.is_not_found()
+	{
+		info!("Running migration 'remove_orphaned_one_time_keys'");
+		remove_orphaned_one_time_keys(services)
+			.await
+			.map_err(|e| err!("Failed to run 'remove_orphaned_one_time_keys' migration': {e}"))?;
+	}
+
+	if db["global"]
+		.get(POPULATED_PUSHKEY_USERID_INDEX)
+		.await
+		.is_not_found()
+	{
+		info!("Running migration 'populate_pushkey_userid_index'");
+		populate_pushkey_userid_index(services)
+			.await
+			.map_err(|e| err!("Failed to run 'populate_pushkey_userid_index' migration': {e}"))?;
+	}
+
 	assert_eq!(
 		services.globals.db.database_version().await,
 		DATABASE_VERSION,
@@ -901,3 +939,38 @@ async fn obliterate_roomsynctoken_shortstatehash_with_extreme_prejudice(
 
 	Ok(())
 }
+
+const POPULATED_DIRECTORY_SEARCH_INDEX: &str = "populate_directory_search_index";
+async fn populate_directory_search_index(services: &Services) -> Result {
+	let rooms: Vec<OwnedRoomId> = services.rooms.directory.public_rooms().collect().await;
+	for room_id in &rooms {
+		services.rooms.directory.index_room(room_id).await;
+	}
+
+	info!(rooms = rooms.len(), "Populated room directory search index.");
+
+	services.db["global"].insert(POPULATED_DIRECTORY_SEARCH_INDEX, []);
+	Ok(())
+}
+
+const REMOVED_ORPHANED_ONE_TIME_KEYS: &str = "remove_orphaned_one_time_keys";
+async fn remove_orphaned_one_time_keys(services: &Services) -> Result {
+	// Devices used to be removed without their one-time and fallback keys
+
+	let devices = services.users.remove_orphaned_one_time_keys().await;
+
+	info!(devices, "Removed one-time keys of removed devices.");
+
+	services.db["global"].insert(REMOVED_ORPHANED_ONE_TIME_KEYS, []);
+	Ok(())
+}
+
+const POPULATED_PUSHKEY_USERID_INDEX: &str = "populate_pushkey_userid_index";
+async fn populate_pushkey_userid_index(services: &Services) -> Result {
+	let pushers = services.pusher.index_pushkeys().await;
+
+	info!(pushers, "Populated pushkey index.");
+
+	services.db["global"].insert(POPULATED_PUSHKEY_USERID_INDEX, []);
+	Ok(())
+}
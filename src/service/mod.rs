@@ -33,6 +33,7 @@
 pub mod pusher;
 pub mod registration_tokens;
 pub mod rooms;
+pub mod scheduler;
 pub mod sending;
 pub mod server_keys;
 pub mod sync;
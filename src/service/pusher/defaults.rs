@@ -0,0 +1,228 @@
+//! Server-wide default push rules
+//!
+//! Admins can layer their own rules over the spec's server-default ruleset.
+//! The overrides are stored as a ruleset: custom rules are inserted into the
+//! user's rules in the order given, before the user's own rules of the same
+//! kind, while rules with a server-default ID (starting with a dot) only
+//! change the enabled state and actions of that predefined rule.
+
+use conduwuit_core::{Err, Result, err, warn};
+use conduwuit_database::{Deserialized, Json};
+use ruma::{
+	UserId,
+	events::{
+		GlobalAccountDataEventType,
+		push_rules::{PushRulesEvent, PushRulesEventContent},
+	},
+	push::{
+		Action, NewConditionalPushRule, NewPatternedPushRule, NewPushRule, NewSimplePushRule,
+		RuleKind, Ruleset,
+	},
+};
+
+const DEFAULT_PUSH_RULES: &[u8] = b"default_push_rules";
+
+impl super::Service {
+	/// Gets the admin's overrides of the server-default push rules.
+	pub async fn get_default_push_rules(&self) -> Result<Ruleset> {
+		self.db.global.get(DEFAULT_PUSH_RULES).await.deserialized()
+	}
+
+	/// Replaces the admin's overrides of the server-default push rules,
+	/// checking that they can be applied to a new account.
+	pub fn set_default_push_rules(&self, overrides: &Ruleset) -> Result {
+		let mut ruleset = Ruleset::server_default(&self.services.globals.server_user);
+		apply_push_rules(&mut ruleset, overrides)?;
+
+		self.db.global.raw_put(DEFAULT_PUSH_RULES, Json(overrides));
+
+		Ok(())
+	}
+
+	/// Removes the admin's overrides of the server-default push rules.
+	pub fn clear_default_push_rules(&self) { self.db.global.remove(DEFAULT_PUSH_RULES); }
+
+	/// The push rules a new account starts out with: the spec's server-default
+	/// rules with the admin's overrides applied.
+	pub async fn default_ruleset(&self, user_id: &UserId) -> Ruleset {
+		let mut ruleset = Ruleset::server_default(user_id);
+		let Ok(overrides) = self.get_default_push_rules().await else {
+			return ruleset;
+		};
+
+		if let Err(e) = apply_push_rules(&mut ruleset, &overrides) {
+			warn!("Failed to apply the default push rules for {user_id}: {e}");
+			return Ruleset::server_default(user_id);
+		}
+
+		ruleset
+	}
+
+	/// Applies the admin's overrides of the server-default push rules to an
+	/// existing account, keeping the rest of the user's rules.
+	pub async fn apply_default_push_rules(&self, user_id: &UserId) -> Result {
+		let overrides = self
+			.get_default_push_rules()
+			.await
+			.map_err(|_| err!(Request(NotFound("No default push rules are set."))))?;
+
+		let ty = GlobalAccountDataEventType::PushRules;
+		let mut event: PushRulesEvent = self
+			.services
+			.account_data
+			.get_global(user_id, ty.clone())
+			.await
+			.unwrap_or_else(|_| {
+				PushRulesEvent::new(PushRulesEventContent::new(Ruleset::server_default(user_id)))
+			});
+
+		apply_push_rules(&mut event.content.global, &overrides)?;
+
+		self.services
+			.account_data
+			.update(None, user_id, ty.to_string().into(), &serde_json::to_value(event)?)
+			.await
+	}
+}
+
+/// Applies push rule overrides to a ruleset.
+fn apply_push_rules(ruleset: &mut Ruleset, overrides: &Ruleset) -> Result {
+	let mut placer = Placer::default();
+	for rule in &overrides.override_ {
+		placer.apply(
+			ruleset,
+			RuleKind::Override,
+			&rule.rule_id,
+			rule.enabled,
+			&rule.actions,
+			|| {
+				NewPushRule::Override(NewConditionalPushRule::new(
+					rule.rule_id.clone(),
+					rule.conditions.clone(),
+					rule.actions.clone(),
+				))
+			},
+		)?;
+	}
+
+	let mut placer = Placer::default();
+	for rule in &overrides.content {
+		placer.apply(
+			ruleset,
+			RuleKind::Content,
+			&rule.rule_id,
+			rule.enabled,
+			&rule.actions,
+			|| {
+				NewPushRule::Content(NewPatternedPushRule::new(
+					rule.rule_id.clone(),
+					rule.pattern.clone(),
+					rule.actions.clone(),
+				))
+			},
+		)?;
+	}
+
+	let mut placer = Placer::default();
+	for rule in &overrides.room {
+		placer.apply(
+			ruleset,
+			RuleKind::Room,
+			rule.rule_id.as_str(),
+			rule.enabled,
+			&rule.actions,
+			|| {
+				NewPushRule::Room(NewSimplePushRule::new(
+					rule.rule_id.clone(),
+					rule.actions.clone(),
+				))
+			},
+		)?;
+	}
+
+	let mut placer = Placer::default();
+	for rule in &overrides.sender {
+		placer.apply(
+			ruleset,
+			RuleKind::Sender,
+			rule.rule_id.as_str(),
+			rule.enabled,
+			&rule.actions,
+			|| {
+				NewPushRule::Sender(NewSimplePushRule::new(
+					rule.rule_id.clone(),
+					rule.actions.clone(),
+				))
+			},
+		)?;
+	}
+
+	let mut placer = Placer::default();
+	for rule in &overrides.underride {
+		placer.apply(
+			ruleset,
+			RuleKind::Underride,
+			&rule.rule_id,
+			rule.enabled,
+			&rule.actions,
+			|| {
+				NewPushRule::Underride(NewConditionalPushRule::new(
+					rule.rule_id.clone(),
+					rule.conditions.clone(),
+					rule.actions.clone(),
+				))
+			},
+		)?;
+	}
+
+	Ok(())
+}
+
+/// Keeps the custom rules of one kind in the order they were given.
+#[derive(Default)]
+struct Placer {
+	after: Option<String>,
+}
+
+impl Placer {
+	fn apply<F>(
+		&mut self,
+		ruleset: &mut Ruleset,
+		kind: RuleKind,
+		rule_id: &str,
+		enabled: bool,
+		actions: &[Action],
+		new_rule: F,
+	) -> Result
+	where
+		F: FnOnce() -> NewPushRule,
+	{
+		if rule_id.starts_with('.') {
+			ruleset
+				.set_actions(kind.clone(), rule_id, actions.to_vec())
+				.map_err(|_| {
+					err!(Request(NotFound("Unknown server-default push rule {rule_id}.")))
+				})?;
+
+			ruleset.set_enabled(kind, rule_id, enabled).map_err(|_| {
+				err!(Request(NotFound("Unknown server-default push rule {rule_id}.")))
+			})?;
+
+			return Ok(());
+		}
+
+		// Re-inserting moves the rule back to its place among the overrides.
+		ruleset.remove(kind.clone(), rule_id).ok();
+		if let Err(e) = ruleset.insert(new_rule(), self.after.as_deref(), None) {
+			return Err!(Request(InvalidParam("Cannot apply push rule {rule_id}: {e}")));
+		}
+
+		ruleset
+			.set_enabled(kind, rule_id, enabled)
+			.map_err(|_| err!(Request(NotFound("Push rule {rule_id} was not inserted."))))?;
+
+		self.after = Some(rule_id.to_owned());
+
+		Ok(())
+	}
+}
@@ -1,17 +1,19 @@
+mod defaults;
+
 use std::{fmt::Debug, mem, sync::Arc};
 
 use bytes::BytesMut;
 use conduwuit::utils::response::LimitReadExt;
 use conduwuit_core::{
 	Err, Event, Result, debug_warn, err, trace,
-	utils::{stream::TryIgnore, string_from_bytes},
+	utils::{ReadyExt, stream::TryIgnore, string_from_bytes},
 	warn,
 };
 use conduwuit_database::{Deserialized, Ignore, Interfix, Json, Map};
-use futures::{Stream, StreamExt};
+use futures::{Stream, StreamExt, future};
 use ipaddress::IPAddress;
 use ruma::{
-	DeviceId, OwnedDeviceId, RoomId, UInt, UserId,
+	DeviceId, EventId, OwnedDeviceId, OwnedUserId, RoomId, UInt, UserId,
 	api::{
 		IncomingResponseExt, OutgoingRequest, OutgoingRequestExt,
 		auth_scheme::NoAuthentication,
@@ -31,7 +33,7 @@
 	uint,
 };
 
-use crate::{Dep, client, config, globals, rooms, sending, users};
+use crate::{Dep, account_data, client, config, globals, rooms, sending, users};
 
 pub struct Service {
 	db: Data,
@@ -39,34 +41,42 @@ pub struct Service {
 }
 
 struct Services {
+	account_data: Dep<account_data::Service>,
 	globals: Dep<globals::Service>,
 	config: Dep<config::Service>,
 	client: Dep<client::Service>,
 	state_accessor: Dep<rooms::state_accessor::Service>,
 	state_cache: Dep<rooms::state_cache::Service>,
+	threads: Dep<rooms::threads::Service>,
 	users: Dep<users::Service>,
 	sending: Dep<sending::Service>,
 }
 
 struct Data {
+	global: Arc<Map>,
 	senderkey_pusher: Arc<Map>,
 	pushkey_deviceid: Arc<Map>,
+	pushkey_userid: Arc<Map>,
 }
 
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			db: Data {
+				global: args.db["global"].clone(),
 				senderkey_pusher: args.db["senderkey_pusher"].clone(),
 				pushkey_deviceid: args.db["pushkey_deviceid"].clone(),
+				pushkey_userid: args.db["pushkey_userid"].clone(),
 			},
 			services: Services {
+				account_data: args.depend::<account_data::Service>("account_data"),
 				globals: args.depend::<globals::Service>("globals"),
 				client: args.depend::<client::Service>("client"),
 				config: args.depend::<config::Service>("config"),
 				state_accessor: args
 					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+				threads: args.depend::<rooms::threads::Service>("rooms::threads"),
 				users: args.depend::<users::Service>("users"),
 				sending: args.depend::<sending::Service>("sending"),
 			},
@@ -130,9 +140,14 @@ pub async fn set_pusher(
 				}
 
 				let pushkey = data.pusher.ids.pushkey.as_str();
+				if !data.append {
+					self.delete_other_users_pushers(sender, &data.pusher).await;
+				}
+
 				let key = (sender, pushkey);
 				self.db.senderkey_pusher.put(key, Json(pusher));
 				self.db.pushkey_deviceid.insert(pushkey, sender_device);
+				self.db.pushkey_userid.put_raw((pushkey, sender), []);
 			},
 			| set_pusher::v3::PusherAction::Delete(ids) => {
 				self.delete_pusher(sender, ids.pushkey.as_str()).await;
@@ -147,6 +162,7 @@ pub async fn delete_pusher(&self, sender: &UserId, pushkey: &str) {
 		let key = (sender, pushkey);
 		self.db.senderkey_pusher.del(key);
 		self.db.pushkey_deviceid.remove(pushkey);
+		self.db.pushkey_userid.del((pushkey, sender));
 
 		self.services
 			.sending
@@ -155,6 +171,47 @@ pub async fn delete_pusher(&self, sender: &UserId, pushkey: &str) {
 			.ok();
 	}
 
+	/// Removes the pushers other users have with the same app ID and pushkey
+	/// as the given pusher, which is only allowed once per pushkey unless the
+	/// client asks to append it.
+	async fn delete_other_users_pushers(&self, sender: &UserId, pusher: &Pusher) {
+		let pushkey = pusher.ids.pushkey.as_str();
+		let others: Vec<OwnedUserId> = self
+			.db
+			.pushkey_userid
+			.keys_prefix(&(pushkey, Interfix))
+			.ignore_err()
+			.ready_filter_map(|(_, user): (Ignore, &UserId)| {
+				(user != sender).then(|| user.to_owned())
+			})
+			.filter_map(async |user| {
+				self.get_pusher(&user, pushkey)
+					.await
+					.is_ok_and(|other| other.ids.app_id == pusher.ids.app_id)
+					.then_some(user)
+			})
+			.collect()
+			.await;
+
+		for user in others {
+			self.delete_pusher(&user, &pusher.ids.pushkey).await;
+		}
+	}
+
+	/// Indexes the existing pushers by pushkey. Returns the number of pushers
+	/// indexed.
+	pub async fn index_pushkeys(&self) -> usize {
+		self.db
+			.senderkey_pusher
+			.keys()
+			.ignore_err()
+			.ready_fold(0_usize, |count, (user, pushkey): (&UserId, &str)| {
+				self.db.pushkey_userid.put_raw((pushkey, user), []);
+				count.saturating_add(1)
+			})
+			.await
+	}
+
 	pub async fn get_pusher_device(&self, pushkey: &str) -> Result<OwnedDeviceId> {
 		self.db.pushkey_deviceid.get(pushkey).await.deserialized()
 	}
@@ -313,6 +370,7 @@ pub async fn send_push_notice<E>(
 			.await;
 
 		let serialized = event.to_format();
+		let thread_root = rooms::threads::event_thread_root(event);
 		for action in self
 			.get_actions(
 				user,
@@ -320,6 +378,7 @@ pub async fn send_push_notice<E>(
 				power_levels.clone(),
 				&serialized,
 				event.room_id().unwrap(),
+				thread_root.as_deref(),
 			)
 			.await
 		{
@@ -359,6 +418,9 @@ pub async fn push_joined_count(&self, room_id: &RoomId) -> UInt {
 			.unwrap_or_else(|_| uint!(0))
 	}
 
+	/// Builds the context push rules for an event are evaluated in. For events
+	/// in a thread, the user's subscription to the thread is looked up for the
+	/// `thread_subscription` condition.
 	#[tracing::instrument(skip(self, user), level = "debug")]
 	pub async fn push_condition_ctx(
 		&self,
@@ -366,6 +428,7 @@ pub async fn push_condition_ctx(
 		power_levels: RoomPowerLevels,
 		room_id: &RoomId,
 		room_joined_count: UInt,
+		thread_root: Option<&EventId>,
 	) -> PushConditionRoomCtx {
 		let power_levels = PushConditionPowerLevelsCtx::from(power_levels);
 
@@ -376,13 +439,25 @@ pub async fn push_condition_ctx(
 			.await
 			.unwrap_or_else(|_| user.localpart().to_owned());
 
-		PushConditionRoomCtx::new(
+		let ctx = PushConditionRoomCtx::new(
 			room_id.to_owned(),
 			room_joined_count,
 			user.to_owned(),
 			user_display_name,
 		)
-		.with_power_levels(power_levels)
+		.with_power_levels(power_levels);
+
+		let Some(thread_root) = thread_root else {
+			return ctx;
+		};
+
+		let subscribed = self
+			.services
+			.threads
+			.is_subscribed(user, room_id, thread_root)
+			.await;
+
+		ctx.with_has_thread_subscription_fn(move |_| Box::pin(future::ready(subscribed)))
 	}
 
 	#[tracing::instrument(skip(self, user, ruleset, pdu), level = "debug")]
@@ -393,10 +468,11 @@ pub async fn get_actions<'a>(
 		power_levels: RoomPowerLevels,
 		pdu: &Raw<AnySyncTimelineEvent>,
 		room_id: &RoomId,
+		thread_root: Option<&EventId>,
 	) -> &'a [Action] {
 		let room_joined_count = self.push_joined_count(room_id).await;
 		let ctx = self
-			.push_condition_ctx(user, power_levels, room_id, room_joined_count)
+			.push_condition_ctx(user, power_levels, room_id, room_joined_count, thread_root)
 			.await;
 
 		ruleset.get_actions(pdu, &ctx).await
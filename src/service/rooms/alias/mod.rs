@@ -1,23 +1,31 @@
 mod remote;
 
-use std::sync::Arc;
+use std::{fmt::Write, sync::Arc};
 
+use async_trait::async_trait;
 use conduwuit::{
-	Err, Result, err,
+	Err, Result, debug, err,
+	matrix::pdu::PartialPdu,
 	utils::{ReadyExt, stream::TryIgnore},
 };
 use database::{Deserialized, Ignore, Interfix, Map};
 use futures::{Stream, StreamExt};
+use lru_cache::LruCache;
 use ruma::{
 	OwnedRoomAliasId, OwnedRoomId, OwnedServerName, OwnedUserId, RoomAliasId, RoomId,
-	RoomOrAliasId, UserId, events::StateEventType,
+	RoomOrAliasId, UserId,
+	events::{StateEventType, room::canonical_alias::RoomCanonicalAliasEventContent},
 };
 
-use crate::{Dep, admin, appservice, appservice::RegistrationInfo, globals, rooms, sending};
+use self::remote::{REMOTE_CACHE_CAPACITY, RemoteCache};
+use crate::{
+	Dep, admin, appservice, appservice::RegistrationInfo, config, globals, rooms, sending,
+};
 
 pub struct Service {
 	db: Data,
 	services: Services,
+	remote_cache: RemoteCache,
 }
 
 struct Data {
@@ -29,12 +37,16 @@ struct Data {
 struct Services {
 	admin: Dep<admin::Service>,
 	appservice: Dep<appservice::Service>,
+	config: Dep<config::Service>,
 	globals: Dep<globals::Service>,
 	sending: Dep<sending::Service>,
+	state: Dep<rooms::state::Service>,
 	state_accessor: Dep<rooms::state_accessor::Service>,
 	state_cache: Dep<rooms::state_cache::Service>,
+	timeline: Dep<rooms::timeline::Service>,
 }
 
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
@@ -46,15 +58,28 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			services: Services {
 				admin: args.depend::<admin::Service>("admin"),
 				appservice: args.depend::<appservice::Service>("appservice"),
+				config: args.depend::<config::Service>("config"),
 				globals: args.depend::<globals::Service>("globals"),
 				sending: args.depend::<sending::Service>("sending"),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
 				state_accessor: args
 					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
 			},
+			remote_cache: RemoteCache::new(LruCache::new(REMOTE_CACHE_CAPACITY)),
 		}))
 	}
 
+	async fn clear_cache(&self) { self.remote_cache.lock().clear(); }
+
+	async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
+		let remote_cache = self.remote_cache.lock().len();
+		writeln!(out, "remote_alias_cache: {remote_cache}")?;
+
+		Ok(())
+	}
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
@@ -161,8 +186,6 @@ pub async fn resolve_alias(
 			.server_is_ours(room_alias.server_name());
 
 		if !server_is_ours {
-			// TODO: The spec advises servers may cache remote room aliases temporarily.
-			// We might want to look at doing that.
 			return self.remote_resolve(room_alias).await;
 		}
 
@@ -211,6 +234,104 @@ pub fn all_local_aliases(&self) -> impl Stream<Item = (OwnedRoomId, &str)> + Sen
 			.map(|(alias_localpart, room_id): (&str, OwnedRoomId)| (room_id, alias_localpart))
 	}
 
+	/// Removes a deleted local alias from the room's `m.room.canonical_alias`
+	/// event, both as the canonical alias and from the alternative aliases.
+	///
+	/// The updated event is sent by `user_id` if they are allowed to, otherwise
+	/// by the server user if it is in the room. Returns whether an event was
+	/// sent.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub async fn remove_from_canonical_alias(
+		&self,
+		room_id: &RoomId,
+		alias: &RoomAliasId,
+		user_id: &UserId,
+	) -> Result<bool> {
+		let state_lock = self.services.state.mutex.lock(room_id.as_str()).await;
+
+		let Ok(mut content) = self
+			.services
+			.state_accessor
+			.room_state_get_content::<RoomCanonicalAliasEventContent>(
+				room_id,
+				&StateEventType::RoomCanonicalAlias,
+				"",
+			)
+			.await
+		else {
+			return Ok(false);
+		};
+
+		let was_canonical = content
+			.alias
+			.as_ref()
+			.is_some_and(|canonical| canonical.as_str() == alias.as_str());
+		let alt_aliases_len = content.alt_aliases.len();
+		content
+			.alt_aliases
+			.retain(|alt_alias| alt_alias.as_str() != alias.as_str());
+		if !was_canonical && content.alt_aliases.len() == alt_aliases_len {
+			return Ok(false);
+		}
+
+		if was_canonical {
+			content.alias = None;
+		}
+
+		let server_user = &self.services.globals.server_user;
+		let power_levels = self
+			.services
+			.state_accessor
+			.get_room_power_levels(room_id)
+			.await;
+
+		let sender = if power_levels
+			.user_can_send_state(user_id, StateEventType::RoomCanonicalAlias)
+			&& self.services.state_cache.is_joined(user_id, room_id).await
+		{
+			user_id
+		} else if power_levels
+			.user_can_send_state(server_user, StateEventType::RoomCanonicalAlias)
+			&& self
+				.services
+				.state_cache
+				.is_joined(server_user, room_id)
+				.await
+		{
+			server_user
+		} else {
+			debug!("No local user can update the canonical alias of {room_id}");
+			return Ok(false);
+		};
+
+		self.services
+			.timeline
+			.build_and_append_pdu(
+				PartialPdu::state(String::new(), &content),
+				sender,
+				Some(room_id),
+				&state_lock,
+			)
+			.await?;
+
+		Ok(true)
+	}
+
+	/// Lists local aliases pointing to rooms which no local user is joined to.
+	pub fn dangling_local_aliases(&self) -> impl Stream<Item = (OwnedRoomId, &str)> + Send + '_ {
+		self.all_local_aliases()
+			.filter_map(async |(room_id, alias_localpart)| {
+				self.services
+					.state_cache
+					.local_users_in_room(&room_id)
+					.boxed()
+					.next()
+					.await
+					.is_none()
+					.then_some((room_id, alias_localpart))
+			})
+	}
+
 	async fn user_can_remove_alias(&self, alias: &RoomAliasId, user_id: &UserId) -> Result<bool> {
 		let room_id = self
 			.resolve_local_alias(alias)
@@ -1,14 +1,46 @@
-use conduwuit::{Result, debug, error};
+use std::time::{Duration, Instant};
+
+use conduwuit::{Err, Result, SyncMutex, debug, error};
 use federation::query::get_room_information::v1::Response;
-use ruma::{OwnedRoomId, OwnedServerName, RoomAliasId, ServerName, api::federation};
+use lru_cache::LruCache;
+use ruma::{
+	OwnedRoomAliasId, OwnedRoomId, OwnedServerName, RoomAliasId, ServerName, api::federation,
+};
+
+pub(super) type RemoteCache = SyncMutex<LruCache<OwnedRoomAliasId, CachedAlias>>;
+
+/// Maximum number of remote alias resolutions kept in the cache.
+pub(super) const REMOTE_CACHE_CAPACITY: usize = 4096;
+
+/// Longest time a remote alias resolution is cached for, regardless of the
+/// configured TTL.
+const MAX_CACHE_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+/// A remote alias resolution. Aliases the remote server reported as missing
+/// are cached without a room.
+pub(super) struct CachedAlias {
+	resolved: Option<(OwnedRoomId, Vec<OwnedServerName>)>,
+	expires: Instant,
+}
 
 impl super::Service {
 	/// Resolves the given room alias to a room ID and a list of servers that
 	/// are in the room by asking the remote server which owns it.
+	///
+	/// Resolutions are cached for `remote_alias_cache_ttl` seconds, and aliases
+	/// which do not exist for `remote_alias_negative_cache_ttl` seconds.
 	pub(super) async fn remote_resolve(
 		&self,
 		room_alias: &RoomAliasId,
 	) -> Result<(OwnedRoomId, Vec<OwnedServerName>)> {
+		if let Some(cached) = self.remote_cached(room_alias) {
+			debug!("Resolved {room_alias:?} from cache");
+			return match cached {
+				| Some(resolved) => Ok(resolved),
+				| None => Err!(Request(NotFound("Alias does not exist."))),
+			};
+		}
+
 		debug!("Asking {} to resolve {room_alias:?}", room_alias.server_name());
 		match self
 			.remote_request(room_alias, room_alias.server_name())
@@ -16,15 +48,58 @@ pub(super) async fn remote_resolve(
 		{
 			| Err(e) => {
 				error!("Unable to resolve remote room alias {}: {e}", room_alias);
+				if e.is_not_found() {
+					let ttl = self.services.config.remote_alias_negative_cache_ttl;
+					self.remote_cache_insert(room_alias, None, ttl);
+				}
+
 				Err(e)
 			},
 			| Ok(Response { room_id, servers, .. }) => {
 				debug!("Remote resolved {room_alias:?} to {room_id:?} with servers {servers:?}");
-				Ok((room_id, servers))
+				let resolved = (room_id, servers);
+				let ttl = self.services.config.remote_alias_cache_ttl;
+				self.remote_cache_insert(room_alias, Some(resolved.clone()), ttl);
+
+				Ok(resolved)
 			},
 		}
 	}
 
+	fn remote_cached(
+		&self,
+		room_alias: &RoomAliasId,
+	) -> Option<Option<(OwnedRoomId, Vec<OwnedServerName>)>> {
+		let mut cache = self.remote_cache.lock();
+		let cached = cache.get_mut(room_alias)?;
+		if cached.expires > Instant::now() {
+			return Some(cached.resolved.clone());
+		}
+
+		cache.remove(room_alias);
+		None
+	}
+
+	fn remote_cache_insert(
+		&self,
+		room_alias: &RoomAliasId,
+		resolved: Option<(OwnedRoomId, Vec<OwnedServerName>)>,
+		ttl: u64,
+	) {
+		if ttl == 0 {
+			return;
+		}
+
+		let ttl = Duration::from_secs(ttl).min(MAX_CACHE_TTL);
+		let Some(expires) = Instant::now().checked_add(ttl) else {
+			return;
+		};
+
+		self.remote_cache
+			.lock()
+			.insert(room_alias.to_owned(), CachedAlias { resolved, expires });
+	}
+
 	async fn remote_request(
 		&self,
 		room_alias: &RoomAliasId,
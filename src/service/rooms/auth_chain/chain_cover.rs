@@ -0,0 +1,248 @@
+//! Chain cover index storage
+//!
+//! Stores the chain cover index of [`chain_cover`] in the database.
+//! State events are queued for indexing as they are appended, and the worker
+//! indexes them in the background; on first start it indexes the current state
+//! of every known room. Events which aren't indexed yet when a state resolution
+//! needs them are indexed then, under the same per-room lock.
+
+use std::{collections::HashSet, mem};
+
+use conduwuit::{
+	Err, Result, debug, debug_warn, info,
+	state_res::chain_cover::{self, ChainPos, ChainStore},
+	utils::stream::{ReadyExt, TryIgnore},
+};
+use database::Deserialized;
+use futures::{StreamExt, TryStreamExt};
+use ruma::{EventId, OwnedEventId, OwnedRoomId, RoomId};
+
+use crate::rooms::short::{ShortEventId, ShortStateKey};
+
+/// Marker in the `global` map set once the worker has indexed the current
+/// state of the rooms which existed before the index.
+const INDEXED_EXISTING_ROOMS: &[u8] = b"chain_cover_indexed_existing_rooms";
+
+/// Maximum number of events waiting to be indexed by the worker. Further
+/// events are left to be indexed when a state resolution needs them.
+const MAX_QUEUED: usize = 10_000;
+
+impl super::Service {
+	/// Computes the events which are in the auth chains of some but not all of
+	/// the given state sets, using the chain cover index. Fails if one of the
+	/// events or of their auth events can't be indexed.
+	#[tracing::instrument(name = "auth_chain_difference", level = "debug", skip_all)]
+	pub async fn auth_chain_difference(
+		&self,
+		room_id: &RoomId,
+		state_sets: &[Vec<&EventId>],
+	) -> Result<HashSet<OwnedEventId>> {
+		let mut positions = Vec::with_capacity(state_sets.len());
+		for state_set in state_sets {
+			let mut set = Vec::with_capacity(state_set.len());
+			for event_id in state_set {
+				let Some(pos) = self.chain_position(room_id, event_id).await? else {
+					return Err!(Request(NotFound(
+						"{event_id} is not indexed in the chain cover"
+					)));
+				};
+
+				set.push(pos);
+			}
+
+			positions.push(set);
+		}
+
+		chain_cover::auth_chain_difference(self, &positions).await
+	}
+
+	/// Gets the position of an event in the chain cover index, indexing it and
+	/// its auth events first if needed. Fails if the event or one of the events
+	/// of its auth chain is missing.
+	pub async fn chain_position(
+		&self,
+		room_id: &RoomId,
+		event_id: &EventId,
+	) -> Result<Option<ChainPos>> {
+		if let Some(pos) = self.get_position(event_id).await {
+			return Ok(Some(pos));
+		}
+
+		let _lock = self.chain_lock.lock(room_id).await;
+		chain_cover::index_event(self, room_id, event_id).await?;
+
+		Ok(self.get_position(event_id).await)
+	}
+
+	/// Queues an event to be indexed by the worker, unless the queue is full.
+	pub fn queue_indexing(&self, room_id: &RoomId, event_id: &EventId) {
+		let mut queue = self.index_queue.lock();
+		if queue.len() >= MAX_QUEUED {
+			return;
+		}
+
+		queue.push((room_id.to_owned(), event_id.to_owned()));
+		drop(queue);
+
+		self.queued.notify_one();
+	}
+
+	/// Counts the chains of the index and the events placed on them.
+	pub async fn chain_cover_usage(&self) -> (usize, usize) {
+		let chains = self.db.chainid_maxseq.count().await;
+		let events = self.db.shorteventid_chainpos.count().await;
+
+		(chains, events)
+	}
+
+	/// Indexes the events of the queue.
+	pub(super) async fn index_queued(&self) {
+		let queue = mem::take(&mut *self.index_queue.lock());
+		for (room_id, event_id) in queue {
+			if let Err(e) = self.chain_position(&room_id, &event_id).await {
+				debug_warn!(%room_id, %event_id, "Failed to index event in the chain cover: {e}");
+			}
+		}
+	}
+
+	/// Indexes the current state, and so the auth DAG, of every room once,
+	/// indexing the queued events between rooms. Stops early without recording
+	/// completion when the server shuts down.
+	pub(super) async fn index_existing_rooms(&self) {
+		if self.db.global.get(INDEXED_EXISTING_ROOMS).await.is_ok() {
+			return;
+		}
+
+		let rooms: Vec<OwnedRoomId> = self.services.metadata.iter_ids().collect().await;
+		info!(
+			rooms = rooms.len(),
+			"Indexing the auth events of existing rooms in the chain cover"
+		);
+
+		for room_id in &rooms {
+			if !self.services.server.running() {
+				return;
+			}
+
+			let Ok(shortstatehash) = self.services.state.get_room_shortstatehash(room_id).await
+			else {
+				continue;
+			};
+
+			let state: Vec<OwnedEventId> = self
+				.services
+				.state_accessor
+				.state_full_ids(shortstatehash)
+				.map(|(_, event_id): (ShortStateKey, OwnedEventId)| event_id)
+				.collect()
+				.await;
+
+			for event_id in &state {
+				if let Err(e) = self.chain_position(room_id, event_id).await {
+					debug_warn!(%room_id, %event_id, "Failed to index event in the chain cover: {e}");
+				}
+			}
+
+			debug!(%room_id, events = state.len(), "Indexed the current state in the chain cover");
+			self.index_queued().await;
+		}
+
+		self.db.global.insert(INDEXED_EXISTING_ROOMS, []);
+		info!("Indexed the auth events of existing rooms in the chain cover");
+	}
+}
+
+impl ChainStore for super::Service {
+	async fn get_position(&self, event_id: &EventId) -> Option<ChainPos> {
+		let shorteventid = self.services.short.get_shorteventid(event_id).await.ok()?;
+
+		self.db
+			.shorteventid_chainpos
+			.qry(&shorteventid)
+			.await
+			.deserialized()
+			.ok()
+	}
+
+	async fn set_position(&self, event_id: &EventId, (chain, seq): ChainPos) {
+		let shorteventid = self
+			.services
+			.short
+			.get_or_create_shorteventid(event_id)
+			.await;
+
+		self.db
+			.shorteventid_chainpos
+			.put(shorteventid, (chain, seq));
+		self.db
+			.chainidseq_shorteventid
+			.put((chain, seq), shorteventid);
+		self.db.chainid_maxseq.put(chain, seq);
+	}
+
+	async fn chain_tip(&self, chain: u64) -> u64 {
+		self.db
+			.chainid_maxseq
+			.qry(&chain)
+			.await
+			.deserialized()
+			.unwrap_or(0)
+	}
+
+	fn new_chain(&self) -> Result<u64> { self.services.globals.next_count() }
+
+	async fn get_link(&self, (chain, seq): ChainPos, target_chain: u64) -> u64 {
+		self.db
+			.chainlinks
+			.qry(&(chain, seq, target_chain))
+			.await
+			.deserialized()
+			.unwrap_or(0)
+	}
+
+	fn set_link(&self, (chain, seq): ChainPos, (target_chain, target_seq): ChainPos) {
+		self.db
+			.chainlinks
+			.put((chain, seq, target_chain), target_seq);
+	}
+
+	async fn chain_links(&self, chain: u64, from: u64, to: u64) -> Result<Vec<ChainPos>> {
+		self.db
+			.chainlinks
+			.stream_from(&(chain, from))
+			.ready_take_while(|res: &Result<((u64, u64, u64), u64)>| {
+				let Ok(((chain_, seq, _), _)) = res else {
+					return true;
+				};
+
+				*chain_ == chain && *seq <= to
+			})
+			.map_ok(|((_, _, target_chain), target_seq)| (target_chain, target_seq))
+			.try_collect()
+			.await
+	}
+
+	async fn chain_events(&self, chain: u64, from: u64, to: u64) -> Result<Vec<OwnedEventId>> {
+		let shorteventids: Vec<ShortEventId> = self
+			.db
+			.chainidseq_shorteventid
+			.stream_from(&(chain, from))
+			.ignore_err()
+			.ready_take_while(|((chain_, seq), _): &((u64, u64), ShortEventId)| {
+				*chain_ == chain && *seq <= to
+			})
+			.map(|(_, shorteventid)| shorteventid)
+			.collect()
+			.await;
+
+		self.services
+			.short
+			.multi_get_eventid_from_short(futures::stream::iter(shorteventids))
+			.try_collect()
+			.await
+	}
+
+	async fn get_event(&self, event_id: &EventId) -> Option<conduwuit::PduEvent> {
+		self.services.timeline.get_pdu(event_id).await.ok()
+	}
+}
@@ -7,7 +7,12 @@
 use crate::rooms::short::ShortEventId;
 
 pub(super) struct Data {
+	pub(super) global: Arc<Map>,
 	shorteventid_authchain: Arc<Map>,
+	pub(super) shorteventid_chainpos: Arc<Map>,
+	pub(super) chainidseq_shorteventid: Arc<Map>,
+	pub(super) chainid_maxseq: Arc<Map>,
+	pub(super) chainlinks: Arc<Map>,
 	pub(super) auth_chain_cache: SyncMutex<LruCache<Vec<u64>, Arc<[ShortEventId]>>>,
 }
 
@@ -19,7 +24,12 @@ pub(super) fn new(args: &crate::Args<'_>) -> Self {
 		let cache_size = usize_from_f64(cache_size * config.cache_capacity_modifier)
 			.expect("valid cache size");
 		Self {
+			global: db["global"].clone(),
 			shorteventid_authchain: db["shorteventid_authchain"].clone(),
+			shorteventid_chainpos: db["shorteventid_chainpos"].clone(),
+			chainidseq_shorteventid: db["chainidseq_shorteventid"].clone(),
+			chainid_maxseq: db["chainid_maxseq"].clone(),
+			chainlinks: db["chainlinks"].clone(),
 			auth_chain_cache: SyncMutex::new(LruCache::new(cache_size)),
 		}
 	}
@@ -1,3 +1,4 @@
+mod chain_cover;
 mod data;
 
 use std::{
@@ -7,43 +8,82 @@
 	time::Instant,
 };
 
+use async_trait::async_trait;
 use conduwuit::{
-	Err, Result, at, debug, debug_error, trace,
+	Err, Result, Server, SyncMutex, at, debug, debug_error, trace,
 	utils::{
-		IterStream,
+		IterStream, MutexMap,
 		stream::{ReadyExt, TryBroadbandExt},
 	},
 	validated,
 };
 use futures::{FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt};
-use ruma::{EventId, OwnedEventId, RoomId};
+use ruma::{EventId, OwnedEventId, OwnedRoomId, RoomId};
+use tokio::sync::Notify;
 
 use self::data::Data;
-use crate::{Dep, rooms, rooms::short::ShortEventId};
+use crate::{Dep, globals, rooms, rooms::short::ShortEventId};
 
 pub struct Service {
 	services: Services,
 	db: Data,
+	chain_lock: MutexMap<OwnedRoomId, ()>,
+	index_queue: SyncMutex<Vec<(OwnedRoomId, OwnedEventId)>>,
+	queued: Notify,
+	interrupt: Notify,
 }
 
 struct Services {
+	server: Arc<Server>,
+	globals: Dep<globals::Service>,
+	metadata: Dep<rooms::metadata::Service>,
 	short: Dep<rooms::short::Service>,
+	state: Dep<rooms::state::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
 	timeline: Dep<rooms::timeline::Service>,
 }
 
 type Bucket<'a> = BTreeSet<(u64, &'a EventId)>;
 
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			services: Services {
+				server: args.server.clone(),
+				globals: args.depend::<globals::Service>("globals"),
+				metadata: args.depend::<rooms::metadata::Service>("rooms::metadata"),
 				short: args.depend::<rooms::short::Service>("rooms::short"),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
 			},
 			db: Data::new(&args),
+			chain_lock: MutexMap::new(),
+			index_queue: SyncMutex::new(Vec::new()),
+			queued: Notify::new(),
+			interrupt: Notify::new(),
 		}))
 	}
 
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		self.index_existing_rooms().await;
+
+		while self.services.server.running() {
+			self.index_queued().await;
+
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				() = self.queued.notified() => (),
+			}
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
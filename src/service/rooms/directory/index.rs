@@ -0,0 +1,122 @@
+//! Search index of the public room directory.
+//!
+//! Each public room is indexed by the lowercased words of its name, topic,
+//! canonical alias and directory category. Searches match rooms containing a
+//! word starting with every word of the search term.
+
+use std::collections::{BTreeSet, HashSet};
+
+use conduwuit::utils::stream::TryIgnore;
+use database::{Deserialized, Json};
+use futures::StreamExt;
+use ruma::{OwnedRoomId, RoomId};
+use serde::{Deserialize, Serialize};
+
+/// Words longer than this are truncated before indexing.
+const MAX_TERM_LEN: usize = 64;
+
+/// Terms a room is currently indexed under, kept so they can be removed when
+/// the room is reindexed.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct IndexedTerms {
+	terms: Vec<String>,
+}
+
+impl super::Service {
+	/// Rebuilds the search index entries of a room from its current state.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub async fn index_room(&self, room_id: &RoomId) {
+		let state_accessor = &self.services.state_accessor;
+		let (name, topic, canonical_alias, entry) = futures::join!(
+			state_accessor.get_name(room_id),
+			state_accessor.get_room_topic(room_id),
+			state_accessor.get_canonical_alias(room_id),
+			self.entry(room_id),
+		);
+
+		let mut terms = BTreeSet::new();
+		terms.extend(name.iter().flat_map(|name| tokenize(name)));
+		terms.extend(topic.iter().flat_map(|topic| tokenize(topic)));
+		terms.extend(
+			canonical_alias
+				.iter()
+				.flat_map(|alias| tokenize(alias.alias())),
+		);
+		terms.extend(
+			entry
+				.category
+				.iter()
+				.flat_map(|category| tokenize(category)),
+		);
+
+		self.deindex_room(room_id).await;
+		for term in &terms {
+			self.db.directoryterm_roomid.put_raw((term, room_id), []);
+		}
+
+		let terms = IndexedTerms { terms: terms.into_iter().collect() };
+		self.db.roomid_directoryterms.raw_put(room_id, Json(terms));
+	}
+
+	/// Removes all search index entries of a room.
+	pub(super) async fn deindex_room(&self, room_id: &RoomId) {
+		let Ok(IndexedTerms { terms }) = self
+			.db
+			.roomid_directoryterms
+			.get(room_id)
+			.await
+			.deserialized()
+		else {
+			return;
+		};
+
+		for term in &terms {
+			self.db.directoryterm_roomid.del((term, room_id));
+		}
+
+		self.db.roomid_directoryterms.remove(room_id);
+	}
+
+	/// Finds the rooms in the search index matching every word of the search
+	/// term. Returns None when the search term contains no searchable words,
+	/// in which case no rooms should be filtered out.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub async fn search(&self, search_term: &str) -> Option<HashSet<OwnedRoomId>> {
+		let mut matches: Option<HashSet<OwnedRoomId>> = None;
+		for word in tokenize(search_term) {
+			let rooms: HashSet<OwnedRoomId> = self
+				.db
+				.directoryterm_roomid
+				.keys_raw_prefix(word.as_bytes())
+				.ignore_err()
+				.map(|(_, room_id): (&str, OwnedRoomId)| room_id)
+				.collect()
+				.await;
+
+			let rooms = match matches {
+				| Some(matches) => matches.intersection(&rooms).cloned().collect(),
+				| None => rooms,
+			};
+
+			if rooms.is_empty() {
+				return Some(rooms);
+			}
+
+			matches = Some(rooms);
+		}
+
+		matches
+	}
+}
+
+/// Splits text into lowercased alphanumeric words.
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+	text.split(|c: char| !c.is_alphanumeric())
+		.filter(|word| !word.is_empty())
+		.map(|word| {
+			word.chars()
+				.take(MAX_TERM_LEN)
+				.collect::<String>()
+				.to_lowercase()
+		})
+}
@@ -1,37 +1,144 @@
-use std::sync::Arc;
+mod index;
+mod remote;
 
-use conduwuit::{Result, utils::stream::TryIgnore};
-use database::Map;
-use futures::Stream;
+use std::{fmt::Write, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Result, debug,
+	utils::{ReadyExt, stream::TryIgnore},
+	warn,
+};
+use database::{Deserialized, Json, Map};
+use futures::{Stream, StreamExt};
+use lru_cache::LruCache;
 use ruma::{OwnedRoomId, RoomId, api::client::room::Visibility};
+use serde::{Deserialize, Serialize};
+use tokio::{
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
+};
+
+use self::remote::{REMOTE_CACHE_CAPACITY, RemoteCache};
+use crate::{Dep, config, rooms, sending};
 
 pub struct Service {
 	db: Data,
+	services: Services,
+	remote_cache: RemoteCache,
+	interrupt: Notify,
 }
 
 struct Data {
 	publicroomids: Arc<Map>,
+	roomid_directoryentry: Arc<Map>,
+	roomid_directoryterms: Arc<Map>,
+	directoryterm_roomid: Arc<Map>,
+}
+
+struct Services {
+	config: Dep<config::Service>,
+	sending: Dep<sending::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
+}
+
+/// Curation applied by admins to a room in the public room directory.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DirectoryEntry {
+	/// Pinned rooms are listed before all other rooms, ordered by ascending
+	/// position.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub pinned: Option<u32>,
+
+	/// Free-form category the room is grouped under. Categories are matched by
+	/// directory searches like the room name and topic.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub category: Option<String>,
+
+	/// Hidden rooms remain public, but are omitted from the directory listing.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub hidden: bool,
+}
+
+impl DirectoryEntry {
+	fn is_default(&self) -> bool {
+		self.pinned.is_none() && self.category.is_none() && !self.hidden
+	}
 }
 
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			db: Data {
 				publicroomids: args.db["publicroomids"].clone(),
+				roomid_directoryentry: args.db["roomid_directoryentry"].clone(),
+				roomid_directoryterms: args.db["roomid_directoryterms"].clone(),
+				directoryterm_roomid: args.db["directoryterm_roomid"].clone(),
+			},
+			services: Services {
+				config: args.depend::<config::Service>("config"),
+				sending: args.depend::<sending::Service>("sending"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 			},
+			remote_cache: RemoteCache::new(LruCache::new(REMOTE_CACHE_CAPACITY)),
+			interrupt: Notify::new(),
 		}))
 	}
 
+	#[tracing::instrument(skip_all, name = "directory", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		let ttl = self.services.config.remote_room_directory_cache_ttl;
+		if ttl == 0 {
+			debug!("Remote room directory caching is disabled");
+			return Ok(());
+		}
+
+		let mut i = interval(Duration::from_secs(ttl));
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		i.reset();
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			if let Err(e) = self.refresh_remote_cache().await {
+				warn!(?e, "Failed to refresh cached remote room directories");
+			}
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
+	async fn clear_cache(&self) { self.remote_cache.lock().clear(); }
+
+	async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
+		let remote_cache = self.remote_cache.lock().len();
+		writeln!(out, "remote_directory_cache: {remote_cache}")?;
+
+		Ok(())
+	}
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
 impl Service {
 	/// Marks a room as public, adding it to the room directory. Has no relation
 	/// to the join rule.
-	pub fn set_public(&self, room_id: &RoomId) { self.db.publicroomids.insert(room_id, []); }
+	pub async fn set_public(&self, room_id: &RoomId) {
+		self.db.publicroomids.insert(room_id, []);
+		self.index_room(room_id).await;
+	}
 
 	/// Removes a room from the public room directory.
-	pub fn set_not_public(&self, room_id: &RoomId) { self.db.publicroomids.remove(room_id); }
+	pub async fn set_not_public(&self, room_id: &RoomId) {
+		self.db.publicroomids.remove(room_id);
+		self.deindex_room(room_id).await;
+	}
 
 	/// Lists all public rooms in the directory.
 	pub fn public_rooms(&self) -> impl Stream<Item = OwnedRoomId> + Send {
@@ -51,4 +158,52 @@ pub async fn visibility(&self, room_id: &RoomId) -> Visibility {
 			Visibility::Private
 		}
 	}
+
+	/// Fetches the admin curation of a room in the directory. Rooms which were
+	/// never curated return the default entry.
+	pub async fn entry(&self, room_id: &RoomId) -> DirectoryEntry {
+		self.db
+			.roomid_directoryentry
+			.get(room_id)
+			.await
+			.deserialized()
+			.unwrap_or_default()
+	}
+
+	/// Lists all rooms which have been curated by admins, whether or not they
+	/// are currently public.
+	pub fn curated_rooms(&self) -> impl Stream<Item = (OwnedRoomId, DirectoryEntry)> + Send + '_ {
+		self.db.roomid_directoryentry.stream().ignore_err()
+	}
+
+	/// Updates the admin curation of a room in the directory. The search index
+	/// is refreshed since categories are searchable.
+	pub async fn update_entry<F>(&self, room_id: &RoomId, update: F)
+	where
+		F: FnOnce(&mut DirectoryEntry) + Send,
+	{
+		let mut entry = self.entry(room_id).await;
+		update(&mut entry);
+
+		if entry.is_default() {
+			self.db.roomid_directoryentry.remove(room_id);
+		} else {
+			self.db.roomid_directoryentry.raw_put(room_id, Json(&entry));
+		}
+
+		if self.is_public_room(room_id).await {
+			self.index_room(room_id).await;
+		}
+	}
+
+	/// Lists the public rooms which are not hidden by admins, along with their
+	/// curation.
+	pub fn listed_rooms(&self) -> impl Stream<Item = (OwnedRoomId, DirectoryEntry)> + Send + '_ {
+		self.public_rooms()
+			.then(async |room_id| {
+				let entry = self.entry(&room_id).await;
+				(room_id, entry)
+			})
+			.ready_filter(|(_, entry)| !entry.hidden)
+	}
 }
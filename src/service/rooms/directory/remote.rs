@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use conduwuit::{Result, SyncMutex, debug, utils::IterStream};
+use futures::StreamExt;
+use lru_cache::LruCache;
+use ruma::{
+	OwnedServerName, ServerName, UInt,
+	api::federation::directory::get_public_rooms_filtered::v1::{Request, Response},
+	assign,
+	directory::{Filter, RoomNetwork},
+};
+
+pub(super) type RemoteCache = SyncMutex<LruCache<RemoteQuery, CachedDirectory>>;
+
+/// Maximum number of remote directory pages kept in the cache.
+pub(super) const REMOTE_CACHE_CAPACITY: usize = 512;
+
+/// Maximum number of remote directory pages refreshed at the same time.
+const REFRESH_CONCURRENCY: usize = 8;
+
+/// Parameters of a public rooms request to a remote server.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(super) struct RemoteQuery {
+	server: OwnedServerName,
+	limit: Option<UInt>,
+	since: Option<String>,
+	filter: String,
+}
+
+pub(super) struct CachedDirectory {
+	filter: Filter,
+	response: Response,
+	fetched: Instant,
+	accessed: Instant,
+}
+
+impl super::Service {
+	/// Fetches a page of a remote server's public room directory. Responses are
+	/// cached for `remote_room_directory_cache_ttl` seconds, and pages which
+	/// are still being requested are refreshed in the background.
+	#[tracing::instrument(skip(self, filter), level = "debug")]
+	pub async fn remote_public_rooms(
+		&self,
+		server: &ServerName,
+		limit: Option<UInt>,
+		since: Option<&str>,
+		filter: &Filter,
+	) -> Result<Response> {
+		let ttl = Duration::from_secs(self.services.config.remote_room_directory_cache_ttl);
+		if ttl.is_zero() {
+			return self.remote_request(server, limit, since, filter).await;
+		}
+
+		let query = RemoteQuery {
+			server: server.to_owned(),
+			limit,
+			since: since.map(ToOwned::to_owned),
+			filter: serde_json::to_string(filter)?,
+		};
+
+		if let Some(cached) = self.remote_cache.lock().get_mut(&query) {
+			if cached.fetched.elapsed() < ttl {
+				cached.accessed = Instant::now();
+				return Ok(cached.response.clone());
+			}
+		}
+
+		let response = self.remote_request(server, limit, since, filter).await?;
+		let now = Instant::now();
+		self.remote_cache.lock().insert(query, CachedDirectory {
+			filter: filter.clone(),
+			response: response.clone(),
+			fetched: now,
+			accessed: now,
+		});
+
+		Ok(response)
+	}
+
+	/// Refetches cached remote directory pages which were requested since they
+	/// were last fetched, and evicts the others.
+	pub(super) async fn refresh_remote_cache(&self) -> Result {
+		let stale: Vec<_> = {
+			let mut cache = self.remote_cache.lock();
+			let unused: Vec<_> = cache
+				.iter()
+				.filter(|(_, cached)| cached.accessed <= cached.fetched)
+				.map(|(query, _)| query.clone())
+				.collect();
+
+			for query in &unused {
+				cache.remove(query);
+			}
+
+			cache
+				.iter()
+				.map(|(query, cached)| (query.clone(), cached.filter.clone()))
+				.collect()
+		};
+
+		debug!(count = stale.len(), "Refreshing cached remote room directories");
+		stale
+			.into_iter()
+			.stream()
+			.for_each_concurrent(REFRESH_CONCURRENCY, |(query, filter)| async move {
+				let response = self
+					.remote_request(&query.server, query.limit, query.since.as_deref(), &filter)
+					.await;
+
+				match response {
+					| Ok(response) =>
+						if let Some(cached) = self.remote_cache.lock().get_mut(&query) {
+							cached.response = response;
+							cached.fetched = Instant::now();
+						},
+					| Err(e) => {
+						debug!(?query, "Failed to refresh remote room directory: {e}");
+						self.remote_cache.lock().remove(&query);
+					},
+				}
+			})
+			.await;
+
+		Ok(())
+	}
+
+	async fn remote_request(
+		&self,
+		server: &ServerName,
+		limit: Option<UInt>,
+		since: Option<&str>,
+		filter: &Filter,
+	) -> Result<Response> {
+		let request = assign!(Request::new(), {
+			limit,
+			since: since.map(ToOwned::to_owned),
+			filter: assign!(Filter::new(), {
+				generic_search_term: filter.generic_search_term.clone(),
+				room_types: filter.room_types.clone(),
+			}),
+			room_network: RoomNetwork::Matrix,
+		});
+
+		self.services
+			.sending
+			.send_federation_request(server, request)
+			.await
+	}
+}
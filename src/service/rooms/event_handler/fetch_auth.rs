@@ -7,7 +7,10 @@
 };
 use tokio::join;
 
-use crate::rooms::event_handler::{build_local_dag, fetch_and_handle_outliers::DagBuilderTree};
+use crate::rooms::{
+	event_handler::{build_local_dag, fetch_and_handle_outliers::DagBuilderTree},
+	pdu_metadata::IngestionCheck,
+};
 
 impl super::Service {
 	/// Fetches (and persists) the incoming event's entire auth chain by asking
@@ -109,6 +112,7 @@ pub(super) async fn fetch_and_persist_event_auth<Pdu>(
 		// rather than rejected.
 		self.authorise_remote_auth_chain(
 			&auth_chain_map,
+			origin,
 			room_version_rules,
 			create_event.as_pdu(),
 		)
@@ -125,6 +129,7 @@ pub(super) async fn fetch_and_persist_event_auth<Pdu>(
 	async fn authorise_remote_auth_chain<Pdu>(
 		&self,
 		auth_chain_map: &HashMap<OwnedEventId, PduEvent>,
+		origin: &ServerName,
 		room_version_rules: &RoomVersionRules,
 		create_event: &Pdu,
 	) -> Result<()>
@@ -184,7 +189,15 @@ async fn authorise_remote_auth_chain<Pdu>(
 					},
 					| hash_map::Entry::Occupied(_) => {
 						// Duplicate auth events by key are not allowed.
-						self.reject_and_persist(&event_id, &pdu.to_canonical_object());
+						self.reject_and_persist(
+							origin,
+							&pdu.room_id_or_hash(),
+							&event_id,
+							&pdu.to_canonical_object(),
+							IngestionCheck::AuthEvents,
+							"Auth event's type and state_key combination exists multiple times",
+						)
+						.await;
 						continue 'outer;
 					},
 				}
@@ -198,7 +211,15 @@ async fn authorise_remote_auth_chain<Pdu>(
 				)
 				.await?
 			{
-				self.reject_and_persist(&event_id, &pdu.to_canonical_object());
+				self.reject_and_persist(
+					origin,
+					&pdu.room_id_or_hash(),
+					&event_id,
+					&pdu.to_canonical_object(),
+					IngestionCheck::AuthRules,
+					"Event authorisation fails based on event's claimed auth events",
+				)
+				.await;
 			}
 		}
 
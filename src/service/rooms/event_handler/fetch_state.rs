@@ -28,7 +28,7 @@ impl super::Service {
 	/// /_matrix/federation/v1/state, very slow in large rooms), and persists
 	/// them directly.
 	#[tracing::instrument(skip_all)]
-	pub(super) async fn fetch_state(
+	pub async fn fetch_state(
 		&self,
 		origin: &ServerName,
 		create_event: &PduEvent,
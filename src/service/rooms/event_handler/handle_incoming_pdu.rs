@@ -11,7 +11,7 @@
 use ruma::{CanonicalJsonValue, EventId, RoomId, ServerName, UserId};
 use tokio::sync::mpsc;
 
-use crate::rooms::timeline::RawPduId;
+use crate::rooms::{pdu_metadata::IngestionCheck, timeline::RawPduId};
 
 impl super::Service {
 	/// Handles an incoming PDU from federation.
@@ -79,9 +79,12 @@ pub async fn handle_incoming_pdu<'a>(
 			self.acl_check(origin, room_id),
 		)
 		.await
-		.inspect_err(
-			|e| debug_error!(%origin, "failed to handle incoming PDU {event_id}: {e}"),
-		)?;
+		.inspect_err(|e| {
+			debug_error!(%origin, "failed to handle incoming PDU {event_id}: {e}");
+
+			// Only the ACL check can fail here.
+			self.record_dropped(origin, room_id, event_id, IngestionCheck::Acl, e);
+		})?;
 
 		if is_disabled {
 			return Err!(Request(Forbidden(
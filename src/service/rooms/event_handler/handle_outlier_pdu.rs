@@ -1,11 +1,12 @@
 use std::collections::{BTreeMap, HashMap, hash_map};
 
 use conduwuit::{
-	Err, Event, EventTypeExt, PduEvent, Result, debug, debug_warn, err, info, trace,
+	Err, Error, Event, EventTypeExt, PduEvent, Result, debug, debug_warn, err, info, trace,
 };
 use ruma::{CanonicalJsonObject, CanonicalJsonValue, EventId, OwnedEventId, RoomId, ServerName};
 
 use super::get_room_version_rules;
+use crate::rooms::pdu_metadata::{IngestionAudit, IngestionCheck, IngestionOutcome};
 
 impl super::Service {
 	/// Handles a PDU as an outlier, performing basic checks like signatures and
@@ -53,6 +54,7 @@ pub async fn handle_outlier_pdu<'a, Pdu>(
 					err=?e,
 					"Dropping incoming PDU from {origin} because it violates the room event format"
 				);
+				self.record_dropped(origin, room_id, event_id, IngestionCheck::Format, e);
 			})?;
 
 		value.remove("unsigned");
@@ -61,7 +63,10 @@ pub async fn handle_outlier_pdu<'a, Pdu>(
 		// 3. Check content hash, redacting the event if it fails.
 		let mut incoming_pdu = self
 			.signature_hash_check_2_3(value, &room_version_rules)
-			.await?;
+			.await
+			.inspect_err(|e| {
+				self.record_dropped(origin, room_id, event_id, IngestionCheck::Signatures, e);
+			})?;
 
 		// Now that we have checked the signature and hashes we can add the eventID and
 		// convert to our PduEvent type
@@ -140,10 +145,17 @@ pub async fn handle_outlier_pdu<'a, Pdu>(
 					 {auth_event_id}",
 					event_id,
 				);
-				self.reject_and_persist(event_id, &incoming_pdu);
-				return Err!(Request(Forbidden(
-					"Event has rejected auth event: {auth_event_id}"
-				)));
+				let reason = format!("Event has rejected auth event: {auth_event_id}");
+				self.reject_and_persist(
+					origin,
+					room_id,
+					event_id,
+					&incoming_pdu,
+					IngestionCheck::AuthEvents,
+					&reason,
+				)
+				.await;
+				return Err!(Request(Forbidden("{reason}")));
 			}
 			if auth_event.room_id_or_hash() != room_id {
 				debug_warn!(
@@ -152,10 +164,17 @@ pub async fn handle_outlier_pdu<'a, Pdu>(
 					expected_room_id=%room_id,
 					"Rejecting incoming event which depends on an auth event in another room.",
 				);
-				self.reject_and_persist(event_id, &incoming_pdu);
-				return Err!(Request(Forbidden(
-					"Event depends on a cross-room auth event: {auth_event_id}"
-				)));
+				let reason = format!("Event depends on a cross-room auth event: {auth_event_id}");
+				self.reject_and_persist(
+					origin,
+					room_id,
+					event_id,
+					&incoming_pdu,
+					IngestionCheck::AuthEvents,
+					&reason,
+				)
+				.await;
+				return Err!(Request(Forbidden("{reason}")));
 			}
 		}
 
@@ -171,10 +190,17 @@ pub async fn handle_outlier_pdu<'a, Pdu>(
 				.to_owned();
 
 			let Some(state_key) = auth_event.state_key() else {
-				self.reject_and_persist(event_id, &incoming_pdu);
-				return Err!(Request(Forbidden(debug_warn!(
-					"Event references non-state event as an auth event: {id}",
-				))));
+				let reason = format!("Event references non-state event as an auth event: {id}");
+				self.reject_and_persist(
+					origin,
+					room_id,
+					event_id,
+					&incoming_pdu,
+					IngestionCheck::AuthEvents,
+					&reason,
+				)
+				.await;
+				return Err!(Request(Forbidden(debug_warn!("{reason}"))));
 			};
 			let key = auth_event.kind().with_state_key(state_key);
 			match auth_events_by_key.entry(key) {
@@ -182,13 +208,22 @@ pub async fn handle_outlier_pdu<'a, Pdu>(
 					v.insert(auth_event);
 				},
 				| hash_map::Entry::Occupied(_) => {
-					self.reject_and_persist(event_id, &incoming_pdu);
-					return Err!(Request(Forbidden(debug_warn!(
+					let reason = format!(
 						"Auth event's type and state_key combination exists multiple times: {}, \
 						 {}",
 						auth_event.kind,
 						auth_event.state_key().unwrap_or("")
-					))));
+					);
+					self.reject_and_persist(
+						origin,
+						room_id,
+						event_id,
+						&incoming_pdu,
+						IngestionCheck::AuthEvents,
+						&reason,
+					)
+					.await;
+					return Err!(Request(Forbidden(debug_warn!("{reason}"))));
 				},
 			}
 		}
@@ -202,10 +237,17 @@ pub async fn handle_outlier_pdu<'a, Pdu>(
 			)
 			.await?
 		{
-			self.reject_and_persist(event_id, &incoming_pdu);
-			return Err!(Request(Forbidden(debug_warn!(
-				"Event authorisation fails based on event's claimed auth events"
-			))));
+			let reason = "Event authorisation fails based on event's claimed auth events";
+			self.reject_and_persist(
+				origin,
+				room_id,
+				event_id,
+				&incoming_pdu,
+				IngestionCheck::AuthRules,
+				reason,
+			)
+			.await;
+			return Err!(Request(Forbidden(debug_warn!("{reason}"))));
 		}
 
 		// 7. Persist the event as an outlier.
@@ -218,9 +260,46 @@ pub async fn handle_outlier_pdu<'a, Pdu>(
 		Ok((pdu_event, incoming_pdu))
 	}
 
-	/// Marks the event as rejected and then saves it as an outlier.
-	pub(super) fn reject_and_persist(&self, event_id: &EventId, pdu: &CanonicalJsonObject) {
+	/// Marks the event as rejected, records why and then saves it as an
+	/// outlier.
+	pub(super) async fn reject_and_persist(
+		&self,
+		origin: &ServerName,
+		room_id: &RoomId,
+		event_id: &EventId,
+		pdu: &CanonicalJsonObject,
+		check: IngestionCheck,
+		reason: &str,
+	) {
+		let audit =
+			IngestionAudit::new(IngestionOutcome::Rejected, check, origin, room_id, reason);
+
 		self.services.pdu_metadata.mark_event_rejected(event_id);
+		self.services
+			.pdu_metadata
+			.record_ingestion(event_id, &audit)
+			.await;
 		self.services.outlier.add_pdu_outlier(event_id, pdu);
 	}
+
+	/// Records why an incoming event was dropped without being persisted. The
+	/// verdict is only kept in memory.
+	pub(super) fn record_dropped(
+		&self,
+		origin: &ServerName,
+		room_id: &RoomId,
+		event_id: &EventId,
+		check: IngestionCheck,
+		error: &Error,
+	) {
+		let audit = IngestionAudit::new(
+			IngestionOutcome::Dropped,
+			check,
+			origin,
+			room_id,
+			error.message(),
+		);
+
+		self.services.pdu_metadata.record_dropped(event_id, &audit);
+	}
 }
@@ -10,12 +10,13 @@
 mod policy_server;
 mod resolve_state;
 mod state_at_incoming;
+mod state_res_stats;
 mod upgrade_outlier_pdu;
 
 use std::{collections::HashMap, fmt::Write, sync::Arc, time::Instant};
 
 use async_trait::async_trait;
-use conduwuit::{Err, Event, PduEvent, Result, Server, SyncRwLock, utils::MutexMap};
+use conduwuit::{Err, Event, PduEvent, Result, Server, SyncMutex, SyncRwLock, utils::MutexMap};
 pub use fetch_and_handle_outliers::{
 	DagBuilderTree, GET_MISSING_EVENTS_MAX_BATCH_SIZE, build_local_dag,
 };
@@ -25,11 +26,14 @@
 };
 use tokio::sync::{Notify, mpsc};
 
+use self::state_res_stats::StateResStats;
+pub use self::state_res_stats::{RoomStateResStats, StateResolution};
 use crate::{Dep, globals, rooms, sending, server_keys};
 pub struct Service {
 	pub mutex_federation: RoomMutexMap,
 	pub federation_handletime: SyncRwLock<HandleTimeMap>,
 	pub extremity_squashers: SyncRwLock<HashMap<OwnedRoomId, mpsc::Sender<(usize, bool)>>>,
+	state_res_stats: SyncMutex<StateResStats>,
 	services: Services,
 	server_shutdown: Notify,
 	me: std::sync::Weak<Self>,
@@ -41,6 +45,7 @@ struct Services {
 	auth_chain: Dep<rooms::auth_chain::Service>,
 	metadata: Dep<rooms::metadata::Service>,
 	outlier: Dep<rooms::outlier::Service>,
+	partial_state: Dep<rooms::partial_state::Service>,
 	pdu_metadata: Dep<rooms::pdu_metadata::Service>,
 	server_keys: Dep<server_keys::Service>,
 	short: Dep<rooms::short::Service>,
@@ -63,12 +68,15 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			mutex_federation: RoomMutexMap::new(),
 			federation_handletime: HandleTimeMap::new().into(),
 			extremity_squashers: SyncRwLock::new(HashMap::new()),
+			state_res_stats: SyncMutex::new(StateResStats::default()),
 			services: Services {
 				globals: args.depend::<globals::Service>("globals"),
 				sending: args.depend::<sending::Service>("sending"),
 				auth_chain: args.depend::<rooms::auth_chain::Service>("rooms::auth_chain"),
 				metadata: args.depend::<rooms::metadata::Service>("rooms::metadata"),
 				outlier: args.depend::<rooms::outlier::Service>("rooms::outlier"),
+				partial_state: args
+					.depend::<rooms::partial_state::Service>("rooms::partial_state"),
 				server_keys: args.depend::<server_keys::Service>("server_keys"),
 				pdu_metadata: args.depend::<rooms::pdu_metadata::Service>("rooms::pdu_metadata"),
 				short: args.depend::<rooms::short::Service>("rooms::short"),
@@ -92,6 +100,9 @@ async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
 		let federation_handletime = self.federation_handletime.read().len();
 		writeln!(out, "federation_handletime: {federation_handletime}")?;
 
+		let (rooms, slowest) = self.state_res_stats_usage();
+		writeln!(out, "state_res_stats: {rooms} rooms ({slowest} slowest resolutions)")?;
+
 		Ok(())
 	}
 
@@ -3,7 +3,7 @@
 use base64::Engine;
 use conduwuit::{
 	Err, Event, EventTypeExt, PduEvent, Result, debug, debug::DebugInspect, debug_error,
-	debug_info, err, info, matrix::StateKey, state_res, trace,
+	debug_info, debug_warn, err, info, is_true, matrix::StateKey, state_res, trace,
 };
 use futures::future::ready;
 use ruma::{
@@ -14,8 +14,11 @@
 	room_version_rules::{EventIdFormatVersion, RoomVersionRules},
 };
 
+use super::get_room_version_rules;
 use crate::rooms::{
-	event_handler::parse_incoming_pdu::expect_event_id_array, timeline::pdu_fits,
+	event_handler::parse_incoming_pdu::expect_event_id_array,
+	pdu_metadata::{IngestionAudit, IngestionCheck, IngestionOutcome},
+	timeline::pdu_fits,
 };
 
 /// Checks that the given event ID matches the expected format by attempting to
@@ -297,6 +300,68 @@ pub(super) async fn current_state_check_6(
 		.map_err(|e| err!(Request(Forbidden("Auth check failed: {e:?}"))))
 	}
 
+	/// Re-checks an event which was accepted while its room had partial state,
+	/// and so could not be checked against the memberships missing from it,
+	/// against the given full state. Events which fail are soft-failed.
+	///
+	/// Returns whether the event is authorised.
+	pub async fn recheck_partial_state_event(
+		&self,
+		pdu: &PduEvent,
+		create_event: &PduEvent,
+		state: &HashMap<u64, OwnedEventId>,
+	) -> bool {
+		let state_fetch = |k: StateEventType, s: StateKey| async move {
+			let shortstatekey = self.services.short.get_shortstatekey(&k, &s).await.ok()?;
+
+			let event_id = state.get(&shortstatekey)?;
+			self.services.timeline.get_pdu(event_id).await.ok()
+		};
+
+		let auth_check = match get_room_version_rules(create_event) {
+			| Ok(room_version_rules) => state_res::event_auth::auth_check(
+				&room_version_rules,
+				pdu,
+				None, // TODO: third party invite
+				|ty, sk| state_fetch(ty.clone(), sk.into()),
+				create_event.as_pdu(),
+			)
+			.await
+			.map_err(|e| err!("Auth check failed: {e:?}")),
+			| Err(e) => Err(e),
+		};
+
+		let passes = auth_check
+			.inspect_err(|e| debug_warn!(event_id = %pdu.event_id, "{e}"))
+			.is_ok_and(is_true!());
+
+		if !passes {
+			debug_info!(
+				event_id = %pdu.event_id,
+				"Soft-failing event which fails authorisation against the full state"
+			);
+
+			let audit = IngestionAudit::new(
+				IngestionOutcome::SoftFailed,
+				IngestionCheck::CurrentState,
+				pdu.sender().server_name(),
+				&pdu.room_id_or_hash(),
+				"Event authorisation fails based on the full room state fetched after a \
+				 partial-state join",
+			);
+
+			self.services
+				.pdu_metadata
+				.mark_event_soft_failed(pdu.event_id());
+			self.services
+				.pdu_metadata
+				.record_ingestion(pdu.event_id(), &audit)
+				.await;
+		}
+
+		passes
+	}
+
 	/// Performs PDU check 7 - does the policy server allow this event.
 	///
 	/// If the policy server forbids the event, false is returned. If there is a
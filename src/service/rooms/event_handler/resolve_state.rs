@@ -2,17 +2,19 @@
 	borrow::Borrow,
 	collections::{HashMap, HashSet},
 	sync::Arc,
+	time::{Instant, SystemTime},
 };
 
 use conduwuit::{
-	Error, Result, err,
+	Result, debug_warn, err,
 	state_res::{self, StateMap},
 	trace,
-	utils::stream::{IterStream, ReadyExt, TryWidebandExt, WidebandExt},
+	utils::stream::{IterStream, ReadyExt, WidebandExt},
 };
-use futures::{FutureExt, StreamExt, TryFutureExt, TryStreamExt, future::try_join};
-use ruma::{OwnedEventId, RoomId, room_version_rules::RoomVersionRules};
+use futures::{FutureExt, StreamExt, TryFutureExt, TryStreamExt};
+use ruma::{EventId, OwnedEventId, RoomId, room_version_rules::RoomVersionRules};
 
+use super::StateResolution;
 use crate::rooms::state_compressor::CompressedState;
 
 impl super::Service {
@@ -38,19 +40,7 @@ pub async fn resolve_state(
 			.await;
 
 		trace!("Loading fork states");
-		let fork_states = [current_state_ids, incoming_state];
-		let auth_chain_sets = fork_states
-			.iter()
-			.try_stream()
-			.wide_and_then(|state| {
-				self.services
-					.auth_chain
-					.event_ids_iter(room_id, state.values().map(Borrow::borrow))
-					.try_collect()
-			})
-			.try_collect::<Vec<HashSet<OwnedEventId>>>();
-
-		let fork_states = fork_states
+		let fork_states: Vec<StateMap<OwnedEventId>> = [current_state_ids, incoming_state]
 			.iter()
 			.stream()
 			.wide_then(|fork_state| {
@@ -63,14 +53,12 @@ pub async fn resolve_state(
 					.ready_filter_map(|(ty_sk, id)| Some((ty_sk.ok()?, id)))
 					.collect()
 			})
-			.map(Ok::<_, Error>)
-			.try_collect::<Vec<StateMap<OwnedEventId>>>();
-
-		let (fork_states, auth_chain_sets) = try_join(fork_states, auth_chain_sets).await?;
+			.collect()
+			.await;
 
 		trace!("Resolving state");
 		let state = self
-			.state_resolution(room_version_rules, fork_states.iter(), &auth_chain_sets)
+			.state_resolution(room_id, room_version_rules, &fork_states)
 			.boxed()
 			.await?;
 
@@ -98,25 +86,95 @@ pub async fn resolve_state(
 		Ok(Arc::new(new_room_state))
 	}
 
-	pub async fn state_resolution<'a, StateSets>(
-		&'a self,
-		room_version_rules: &'a RoomVersionRules,
-		state_sets: StateSets,
-		auth_chain_sets: &'a [HashSet<OwnedEventId>],
-	) -> Result<StateMap<OwnedEventId>>
-	where
-		StateSets: Iterator<Item = &'a StateMap<OwnedEventId>> + Clone + Send,
-	{
+	/// Resolves the state sets of a room, computing their auth chain
+	/// difference with the chain cover index, or from their full auth chains
+	/// when the index can't cover them. Both steps are timed and recorded in
+	/// the state resolution statistics.
+	pub async fn state_resolution(
+		&self,
+		room_id: &RoomId,
+		room_version_rules: &RoomVersionRules,
+		state_sets: &[StateMap<OwnedEventId>],
+	) -> Result<StateMap<OwnedEventId>> {
+		if let Some((first, rest)) = state_sets.split_first() {
+			if rest.iter().all(|state_set| state_set == first) {
+				trace!("No conflicting state, skipping state resolution");
+				return Ok(first.clone());
+			}
+		}
+
+		let started = Instant::now();
+		let event_ids: Vec<Vec<&EventId>> = state_sets
+			.iter()
+			.map(|state_set| state_set.values().map(Borrow::borrow).collect())
+			.collect();
+
+		let auth_chain_diff = match self
+			.services
+			.auth_chain
+			.auth_chain_difference(room_id, &event_ids)
+			.await
+		{
+			| Ok(auth_chain_diff) => auth_chain_diff,
+			| Err(e) => {
+				debug_warn!(%room_id, "Falling back to the full auth chains: {e}");
+				self.full_auth_chain_difference(room_id, &event_ids).await?
+			},
+		};
+
+		let auth_chain_time = started.elapsed();
+		let auth_chain_diff_len = auth_chain_diff.len();
+
+		let started = Instant::now();
 		let event_fetch = |event_id| self.event_fetch(event_id);
 		let event_exists = |event_id| self.event_exists(event_id);
-		state_res::resolve(
+		let state = state_res::resolve_with_auth_chain_diff(
 			room_version_rules,
-			state_sets,
-			auth_chain_sets,
+			state_sets.iter(),
+			auth_chain_diff,
 			&event_fetch,
 			&event_exists,
 		)
 		.map_err(|e| err!(error!("State resolution failed: {e:?}")))
-		.await
+		.await?;
+
+		self.record_state_res(StateResolution {
+			room_id: room_id.to_owned(),
+			ts: SystemTime::now(),
+			auth_chain_time,
+			resolve_time: started.elapsed(),
+			state_sets: state_sets.len(),
+			auth_chain_diff: auth_chain_diff_len,
+		});
+
+		Ok(state)
+	}
+
+	/// Computes the auth chain difference of the state sets from their full
+	/// auth chains.
+	async fn full_auth_chain_difference(
+		&self,
+		room_id: &RoomId,
+		state_sets: &[Vec<&EventId>],
+	) -> Result<HashSet<OwnedEventId>> {
+		let mut counts: HashMap<OwnedEventId, usize> = HashMap::new();
+		for state_set in state_sets {
+			let auth_chain: HashSet<OwnedEventId> = self
+				.services
+				.auth_chain
+				.event_ids_iter(room_id, state_set.iter().copied())
+				.try_collect()
+				.await?;
+
+			for event_id in auth_chain {
+				let count = counts.entry(event_id).or_default();
+				*count = count.saturating_add(1);
+			}
+		}
+
+		Ok(counts
+			.into_iter()
+			.filter_map(|(event_id, count)| (count < state_sets.len()).then_some(event_id))
+			.collect())
 	}
 }
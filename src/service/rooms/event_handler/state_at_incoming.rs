@@ -1,17 +1,13 @@
-use std::{
-	borrow::Borrow,
-	collections::{HashMap, HashSet},
-	iter::Iterator,
-};
+use std::collections::HashMap;
 
 use conduwuit::{
 	Result, debug, debug_error, err, error,
 	matrix::{Event, StateMap},
 	trace,
-	utils::stream::{BroadbandExt, IterStream, ReadyExt, TryBroadbandExt, TryWidebandExt},
+	utils::stream::{BroadbandExt, IterStream, ReadyExt, TryBroadbandExt, WidebandExt},
 };
-use futures::{FutureExt, StreamExt, TryFutureExt, TryStreamExt, future::try_join};
-use ruma::{OwnedEventId, RoomId, room_version_rules::RoomVersionRules};
+use futures::{FutureExt, StreamExt, TryFutureExt, TryStreamExt};
+use ruma::{OwnedEventId, room_version_rules::RoomVersionRules};
 
 use crate::rooms::short::ShortStateHash;
 
@@ -133,20 +129,17 @@ async fn state_before_incoming_resolved<Pdu>(
 
 		trace!("Calculating fork states...");
 		let room_id = &incoming_pdu.room_id_or_hash();
-		let (fork_states, auth_chain_sets): (Vec<StateMap<_>>, Vec<HashSet<_>>) =
-			extremity_sstatehashes
-				.into_iter()
-				.try_stream()
-				.wide_and_then(|(sstatehash, prev_event)| {
-					self.state_at_incoming_fork(room_id, sstatehash, prev_event)
-				})
-				.try_collect()
-				.map_ok(Vec::into_iter)
-				.map_ok(Iterator::unzip)
-				.await?;
+		let fork_states: Vec<StateMap<_>> = extremity_sstatehashes
+			.into_iter()
+			.stream()
+			.wide_then(|(sstatehash, prev_event)| {
+				self.state_at_incoming_fork(sstatehash, prev_event)
+			})
+			.collect()
+			.await;
 
 		let Ok(new_state) = self
-			.state_resolution(room_version_rules, fork_states.iter(), &auth_chain_sets)
+			.state_resolution(room_id, room_version_rules, &fork_states)
 			.boxed()
 			.await
 			.inspect_err(|e| error!("State resolution failed: {e:?}"))
@@ -171,13 +164,12 @@ async fn state_before_incoming_resolved<Pdu>(
 	}
 
 	/// Determines the state at an incoming fork (aka the state at a prev
-	/// event), returning the resolved state and its associated auth chain.
+	/// event).
 	async fn state_at_incoming_fork<Pdu>(
 		&self,
-		room_id: &RoomId,
 		sstatehash: ShortStateHash,
 		prev_event: Pdu,
-	) -> Result<(StateMap<OwnedEventId>, HashSet<OwnedEventId>)>
+	) -> StateMap<OwnedEventId>
 	where
 		Pdu: Event,
 	{
@@ -200,13 +192,7 @@ async fn state_at_incoming_fork<Pdu>(
 			// Now it's the state after the pdu
 		}
 
-		let auth_chain = self
-			.services
-			.auth_chain
-			.event_ids_iter(room_id, leaf_state.values().map(Borrow::borrow))
-			.try_collect();
-
-		let fork_state = leaf_state
+		leaf_state
 			.iter()
 			.stream()
 			.broad_then(|(k, id)| {
@@ -217,8 +203,6 @@ async fn state_at_incoming_fork<Pdu>(
 			})
 			.ready_filter_map(Result::ok)
 			.collect()
-			.map(Ok);
-
-		try_join(fork_state, auth_chain).await
+			.await
 	}
 }
@@ -0,0 +1,116 @@
+//! State resolution statistics
+//!
+//! Every state resolution done while handling incoming events is timed, split
+//! into computing the auth chain difference and resolving the conflicts, so
+//! that admins can find the rooms which are expensive to keep up with.
+
+use std::{
+	collections::HashMap,
+	time::{Duration, SystemTime},
+};
+
+use conduwuit::debug;
+use ruma::OwnedRoomId;
+
+/// Number of most expensive state resolutions kept.
+const SLOWEST_CAPACITY: usize = 32;
+
+#[derive(Default)]
+pub(super) struct StateResStats {
+	rooms: HashMap<OwnedRoomId, RoomStateResStats>,
+	slowest: Vec<StateResolution>,
+}
+
+/// Accumulated state resolution timings of a room since startup.
+#[derive(Clone, Debug, Default)]
+pub struct RoomStateResStats {
+	pub count: u64,
+	pub total: Duration,
+	pub max: Duration,
+	pub last: Duration,
+}
+
+/// Timings of a single state resolution.
+#[derive(Clone, Debug)]
+pub struct StateResolution {
+	pub room_id: OwnedRoomId,
+	pub ts: SystemTime,
+
+	/// Time spent computing the auth chain difference of the state sets.
+	pub auth_chain_time: Duration,
+
+	/// Time spent resolving the conflicted state.
+	pub resolve_time: Duration,
+
+	/// Number of state sets which were resolved.
+	pub state_sets: usize,
+
+	/// Number of events in the auth chain difference.
+	pub auth_chain_diff: usize,
+}
+
+impl StateResolution {
+	#[inline]
+	#[must_use]
+	pub fn total(&self) -> Duration { self.auth_chain_time.saturating_add(self.resolve_time) }
+}
+
+impl super::Service {
+	/// Gets the state resolution timings of every room, most time spent first,
+	/// and the most expensive state resolutions, most expensive first.
+	#[must_use]
+	pub fn state_res_stats(
+		&self,
+	) -> (Vec<(OwnedRoomId, RoomStateResStats)>, Vec<StateResolution>) {
+		let stats = self.state_res_stats.lock();
+
+		let mut rooms: Vec<_> = stats
+			.rooms
+			.iter()
+			.map(|(room_id, room)| (room_id.clone(), room.clone()))
+			.collect();
+
+		rooms.sort_by(|(_, a), (_, b)| b.total.cmp(&a.total));
+
+		(rooms, stats.slowest.clone())
+	}
+
+	pub(super) fn record_state_res(&self, resolution: StateResolution) {
+		let total = resolution.total();
+		debug!(
+			room_id = %resolution.room_id,
+			auth_chain_time = ?resolution.auth_chain_time,
+			resolve_time = ?resolution.resolve_time,
+			state_sets = resolution.state_sets,
+			auth_chain_diff = resolution.auth_chain_diff,
+			"State resolution done"
+		);
+
+		let mut stats = self.state_res_stats.lock();
+		let room = stats.rooms.entry(resolution.room_id.clone()).or_default();
+
+		room.count = room.count.saturating_add(1);
+		room.total = room.total.saturating_add(total);
+		room.max = room.max.max(total);
+		room.last = total;
+
+		let slowest = &mut stats.slowest;
+		if slowest.len() >= SLOWEST_CAPACITY
+			&& slowest
+				.last()
+				.is_some_and(|fastest| fastest.total() >= total)
+		{
+			return;
+		}
+
+		let pos = slowest.partition_point(|other| other.total() >= total);
+		slowest.insert(pos, resolution);
+		slowest.truncate(SLOWEST_CAPACITY);
+	}
+
+	pub(super) fn state_res_stats_usage(&self) -> (usize, usize) {
+		let stats = self.state_res_stats.lock();
+
+		(stats.rooms.len(), stats.slowest.len())
+	}
+}
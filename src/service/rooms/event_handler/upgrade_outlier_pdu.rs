@@ -9,7 +9,10 @@
 use tokio::join;
 
 use super::get_room_version_rules;
-use crate::rooms::timeline::RawPduId;
+use crate::rooms::{
+	pdu_metadata::{IngestionAudit, IngestionCheck, IngestionOutcome},
+	timeline::RawPduId,
+};
 
 impl super::Service {
 	#[tracing::instrument(name="upgrade_outlier", skip_all, fields(event_id=%incoming_pdu.event_id()))]
@@ -69,11 +72,24 @@ pub(super) async fn upgrade_outlier_to_timeline_pdu(
 			.state_before_check_5(&incoming_pdu, &room_version_rules, create_event, origin)
 			.await?;
 
-		if !passes_state_before {
-			self.reject_and_persist(incoming_pdu.event_id(), &val);
-			return Err!(Request(Forbidden(debug_warn!(
-				"Event authorisation fails based on the state before the event"
-			))));
+		// While the room has partial state, the state lacks the membership of most
+		// senders, so only the auth events of the event can be relied upon; they were
+		// already checked when the event was handled as an outlier. Events failing
+		// checks 5 or 6 meanwhile are re-checked once the full state has been fetched.
+		let partial_state = self.services.partial_state.is_partial_state(room_id).await;
+
+		if !passes_state_before && !partial_state {
+			let reason = "Event authorisation fails based on the state before the event";
+			self.reject_and_persist(
+				origin,
+				room_id,
+				incoming_pdu.event_id(),
+				&val,
+				IngestionCheck::StateBefore,
+				reason,
+			)
+			.await;
+			return Err!(Request(Forbidden(debug_warn!("{reason}"))));
 		}
 
 		// Now that we know the event passes both self-authentication, and
@@ -89,6 +105,12 @@ pub(super) async fn upgrade_outlier_to_timeline_pdu(
 			"Locking the room"
 		);
 		let state_lock = self.services.state.mutex.lock(room_id).await;
+
+		// The full state may have been applied while the state before the event was
+		// resolved, in which case the event is checked as usual.
+		let partial_state =
+			partial_state && self.services.partial_state.is_partial_state(room_id).await;
+
 		let passes_current_state = self
 			.current_state_check_6(&incoming_pdu, &room_version_rules, create_event)
 			.await
@@ -114,15 +136,29 @@ pub(super) async fn upgrade_outlier_to_timeline_pdu(
 			} else {
 				true
 			};
-		let mut should_soft_fail = !redaction_permitted || !passes_current_state;
+		let mut soft_fail = if !redaction_permitted {
+			Some((IngestionCheck::Redaction, "The sender may not redact the target event"))
+		} else if !passes_state_before && !partial_state {
+			Some((
+				IngestionCheck::StateBefore,
+				"Event authorisation fails based on the state before the event",
+			))
+		} else if !passes_current_state && !partial_state {
+			Some((
+				IngestionCheck::CurrentState,
+				"Event authorisation fails based on the current room state",
+			))
+		} else {
+			None
+		};
 
-		if !should_soft_fail {
+		if soft_fail.is_none() {
 			// Now we can perform check 7, which is ensuring the event passes policy server
 			// checks.
 			// We explicitly only do this if we aren't already going to soft-fail the event,
 			// since the policy server refusing this event also soft-fails it.
 			debug!("Checking policy server for event");
-			should_soft_fail = !self
+			if !self
 				.policy_server_check_7(&incoming_pdu, &mut val, &room_version_rules)
 				.await
 				.inspect(|passes| {
@@ -131,7 +167,12 @@ pub(super) async fn upgrade_outlier_to_timeline_pdu(
 							"Event did not pass the policy server check and will be soft-failed"
 						);
 					}
-				})?;
+				})? {
+				soft_fail = Some((
+					IngestionCheck::PolicyServer,
+					"The policy server of the room refused the event",
+				));
+			}
 
 			// TODO: this is supposed to hide redactions from policy servers and janitorial
 			// bots, however, for full efficacy it also needs to hide redactions for
@@ -150,7 +191,10 @@ pub(super) async fn upgrade_outlier_to_timeline_pdu(
 					debug_info!(
 						"Soft-failing valid redaction because it targets a non-accepted event"
 					);
-					should_soft_fail = true;
+					soft_fail = Some((
+						IngestionCheck::Redaction,
+						"The redaction targets an event which is not accepted",
+					));
 				}
 			}
 		} else {
@@ -163,6 +207,7 @@ pub(super) async fn upgrade_outlier_to_timeline_pdu(
 
 		// The PDU has now passed all checks! We can now promote it (or soft-fail it if
 		// the verdict is such).
+		let should_soft_fail = soft_fail.is_some();
 		trace!("Appending pdu to timeline");
 		let pdu_id = self
 			.services
@@ -177,12 +222,25 @@ pub(super) async fn upgrade_outlier_to_timeline_pdu(
 			)
 			.await?;
 
-		if should_soft_fail {
+		if partial_state && soft_fail.is_none() && !(passes_state_before && passes_current_state)
+		{
+			self.services
+				.partial_state
+				.recheck_after_resync(room_id, incoming_pdu.event_id());
+		}
+
+		if let Some((check, reason)) = soft_fail {
 			debug_info!(
 				elapsed = ?timer.elapsed(),
 				event_id = %incoming_pdu.event_id,
 				"Event was soft failed"
 			);
+			let audit =
+				IngestionAudit::new(IngestionOutcome::SoftFailed, check, origin, room_id, reason);
+			self.services
+				.pdu_metadata
+				.record_ingestion(incoming_pdu.event_id(), &audit)
+				.await;
 		} else {
 			debug_info!(
 				elapsed = ?timer.elapsed(),
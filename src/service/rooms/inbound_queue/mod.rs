@@ -0,0 +1,592 @@
+//! Inbound PDU queue
+//!
+//! PDUs received over federation are persisted to a queue per room and handled
+//! by a pool of workers, each working on one room at a time. Rooms take turns
+//! in batches: rooms with local users go first, and rooms with only PDUs from
+//! the servers in `deprioritize_joins_through_servers` go last. Within a batch
+//! the origins of a room take turns, so a single server flooding a room holds
+//! up neither the other rooms nor the other servers in that room. Servers with
+//! too many queued PDUs have their transactions refused until their queue
+//! drains.
+
+use std::{
+	collections::{BTreeMap, HashMap, VecDeque},
+	fmt::Write,
+	panic::AssertUnwindSafe,
+	sync::Arc,
+};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Error, Result, Server, SyncMutex, debug, debug_warn, error,
+	utils::{ReadyExt, stream::TryIgnore},
+};
+use database::{Deserialized, Json, Map};
+use futures::{FutureExt, StreamExt, future::join_all};
+use ruma::{
+	CanonicalJsonObject, OwnedEventId, OwnedRoomId, OwnedServerName, RoomId, ServerName,
+	api::error::{ErrorKind::LimitExceeded, LimitExceededErrorData},
+};
+use tokio::sync::{Notify, oneshot};
+
+use crate::{Dep, globals, rooms};
+
+/// Maximum number of PDUs of a room handled before the next room gets a turn.
+const BATCH_SIZE: usize = 32;
+
+/// Rooms with local users.
+const TIER_LOCAL: usize = 0;
+
+/// Rooms without local users.
+const TIER_REMOTE: usize = 1;
+
+/// Rooms with only PDUs from deprioritised servers queued.
+const TIER_DEPRIORITIZED: usize = 2;
+
+pub struct Service {
+	db: Data,
+	services: Services,
+	queue: SyncMutex<Queue>,
+	waiters: SyncMutex<HashMap<u64, oneshot::Sender<Result>>>,
+	ready: Notify,
+	interrupt: Notify,
+}
+
+struct Data {
+	/// Queued PDUs, keyed by room, count, origin and event ID.
+	roomcount_inboundpdu: Arc<Map>,
+}
+
+struct Services {
+	server: Arc<Server>,
+	event_handler: Dep<rooms::event_handler::Service>,
+	globals: Dep<globals::Service>,
+	state_cache: Dep<rooms::state_cache::Service>,
+}
+
+#[derive(Default)]
+struct Queue {
+	rooms: HashMap<OwnedRoomId, RoomQueue>,
+
+	/// Rooms waiting for a worker, by tier.
+	tiers: [VecDeque<OwnedRoomId>; 3],
+
+	/// Queued and in-progress PDUs by origin.
+	origins: HashMap<OwnedServerName, usize>,
+}
+
+#[derive(Default)]
+struct RoomQueue {
+	origins: BTreeMap<OwnedServerName, OriginQueue>,
+	has_local_users: bool,
+
+	/// Whether the room is waiting for a worker or being worked on.
+	scheduled: bool,
+}
+
+#[derive(Default)]
+struct OriginQueue {
+	entries: VecDeque<Entry>,
+	deprioritized: bool,
+}
+
+struct Entry {
+	count: u64,
+	origin: OwnedServerName,
+	event_id: OwnedEventId,
+}
+
+/// Number of queued PDUs per room and per origin, largest first.
+pub struct QueueDepth {
+	pub rooms: Vec<(OwnedRoomId, usize)>,
+	pub origins: Vec<(OwnedServerName, usize)>,
+}
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			db: Data {
+				roomcount_inboundpdu: args.db["roomcount_inboundpdu"].clone(),
+			},
+			services: Services {
+				server: args.server.clone(),
+				event_handler: args
+					.depend::<rooms::event_handler::Service>("rooms::event_handler"),
+				globals: args.depend::<globals::Service>("globals"),
+				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+			},
+			queue: SyncMutex::default(),
+			waiters: SyncMutex::default(),
+			ready: Notify::new(),
+			interrupt: Notify::new(),
+		}))
+	}
+
+	async fn worker(self: Arc<Self>) -> Result {
+		self.restore().await;
+
+		let config = &self.services.server.config;
+		let num_workers = match config.inbound_pdu_workers {
+			| 0 => config.max_concurrent_inbound_transactions.max(1),
+			| num_workers => num_workers,
+		};
+
+		join_all((0..num_workers).map(|_| self.process())).await;
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
+	async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
+		let depth = self.depth();
+		let pdus: usize = depth.rooms.iter().map(|(_, queued)| queued).sum();
+		let rooms = depth.rooms.len();
+		let origins = depth.origins.len();
+		writeln!(out, "inbound_queue: {pdus} PDUs in {rooms} rooms from {origins} origins")?;
+
+		Ok(())
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+impl Service {
+	/// Refuses further PDUs from a server which already has too many PDUs
+	/// waiting to be handled.
+	pub fn check_backpressure(&self, origin: &ServerName) -> Result {
+		let limit = self
+			.services
+			.server
+			.config
+			.max_queued_inbound_pdus_per_origin;
+
+		let queued = self.queue.lock().origins.get(origin).copied().unwrap_or(0);
+
+		if queued >= limit {
+			debug_warn!(%origin, queued, "Refusing transaction from origin with a full queue");
+			return Err(Error::BadRequest(
+				LimitExceeded(LimitExceededErrorData::default()),
+				"Too many of your PDUs are waiting to be processed, try again later",
+			));
+		}
+
+		Ok(())
+	}
+
+	/// Queues the PDUs of a room received from `origin`, in the order they
+	/// should be handled. Returns a receiver for the result of handling each
+	/// PDU.
+	pub async fn enqueue(
+		&self,
+		origin: &ServerName,
+		room_id: &RoomId,
+		pdus: Vec<(OwnedEventId, CanonicalJsonObject)>,
+	) -> Result<Vec<(OwnedEventId, oneshot::Receiver<Result>)>> {
+		let has_local_users = self.has_local_users(room_id).await;
+
+		let mut receivers = Vec::with_capacity(pdus.len());
+		let mut entries = Vec::with_capacity(pdus.len());
+		for (event_id, pdu) in pdus {
+			let count = self.services.globals.next_count()?;
+
+			// The waiter goes first, so that restoring never queues the PDU too.
+			let (sender, receiver) = oneshot::channel();
+			self.waiters.lock().insert(count, sender);
+			self.db
+				.roomcount_inboundpdu
+				.put((room_id, count, origin, &event_id), Json(pdu));
+
+			receivers.push((event_id.clone(), receiver));
+			entries.push(Entry {
+				count,
+				origin: origin.to_owned(),
+				event_id,
+			});
+		}
+
+		self.push(room_id, has_local_users, entries);
+
+		Ok(receivers)
+	}
+
+	/// Gets the number of queued PDUs per room and per origin.
+	#[must_use]
+	pub fn depth(&self) -> QueueDepth {
+		let queue = self.queue.lock();
+		let mut rooms: Vec<_> = queue
+			.rooms
+			.iter()
+			.map(|(room_id, room)| {
+				let queued = room
+					.origins
+					.values()
+					.map(|origin| origin.entries.len())
+					.sum();
+
+				(room_id.clone(), queued)
+			})
+			.collect();
+
+		let mut origins: Vec<_> = queue
+			.origins
+			.iter()
+			.map(|(origin, queued)| (origin.clone(), *queued))
+			.collect();
+
+		rooms.sort_by(|a, b| b.1.cmp(&a.1));
+		origins.sort_by(|a, b| b.1.cmp(&a.1));
+
+		QueueDepth { rooms, origins }
+	}
+
+	/// Queues again the PDUs which were not handled before the last shutdown.
+	/// Transactions may already be queueing PDUs by then; those have a waiter
+	/// and are skipped.
+	async fn restore(&self) {
+		let queued: Vec<(OwnedRoomId, u64, OwnedServerName, OwnedEventId)> = self
+			.db
+			.roomcount_inboundpdu
+			.keys()
+			.ignore_err()
+			.ready_filter(|(_, count, ..): &(OwnedRoomId, u64, OwnedServerName, OwnedEventId)| {
+				!self.waiters.lock().contains_key(count)
+			})
+			.collect()
+			.await;
+
+		if queued.is_empty() {
+			return;
+		}
+
+		debug!(pdus = queued.len(), "Restoring the inbound PDU queue");
+		let mut rooms: BTreeMap<OwnedRoomId, Vec<Entry>> = BTreeMap::new();
+		for (room_id, count, origin, event_id) in queued {
+			rooms
+				.entry(room_id)
+				.or_default()
+				.push(Entry { count, origin, event_id });
+		}
+
+		for (room_id, entries) in rooms {
+			let has_local_users = self.has_local_users(&room_id).await;
+			self.push(&room_id, has_local_users, entries);
+		}
+	}
+
+	fn push(&self, room_id: &RoomId, has_local_users: bool, entries: Vec<Entry>) {
+		let deprioritized = &self
+			.services
+			.server
+			.config
+			.deprioritize_joins_through_servers;
+
+		let mut queue = self.queue.lock();
+		for entry in &entries {
+			let queued = queue.origins.entry(entry.origin.clone()).or_default();
+			*queued = queued.saturating_add(1);
+		}
+
+		let room = queue.rooms.entry(room_id.to_owned()).or_default();
+		room.has_local_users = has_local_users;
+		for entry in entries {
+			let origin =
+				room.origins
+					.entry(entry.origin.clone())
+					.or_insert_with(|| OriginQueue {
+						deprioritized: deprioritized.contains(&entry.origin),
+						..Default::default()
+					});
+
+			origin.entries.push_back(entry);
+		}
+
+		if !room.scheduled {
+			room.scheduled = true;
+			let tier = room.tier();
+			queue.tiers[tier].push_back(room_id.to_owned());
+			drop(queue);
+			self.ready.notify_one();
+		}
+	}
+
+	async fn process(&self) {
+		while self.services.server.running() {
+			let Some((room_id, batch)) = self.next_batch() else {
+				tokio::select! {
+					() = self.ready.notified() => continue,
+					() = self.interrupt.notified() => break,
+				}
+			};
+
+			self.handle_batch(&room_id, batch).await;
+			self.reschedule(&room_id);
+		}
+	}
+
+	fn next_batch(&self) -> Option<(OwnedRoomId, Vec<Entry>)> {
+		let mut queue = self.queue.lock();
+		let room_id = queue.tiers.iter_mut().find_map(VecDeque::pop_front)?;
+		let batch = queue
+			.rooms
+			.get_mut(&room_id)
+			.map(|room| room.take_batch(BATCH_SIZE))
+			.unwrap_or_default();
+
+		Some((room_id, batch))
+	}
+
+	async fn handle_batch(&self, room_id: &RoomId, batch: Vec<Entry>) {
+		let _room_lock = self
+			.services
+			.event_handler
+			.mutex_federation
+			.lock(room_id.as_str())
+			.await;
+
+		for entry in batch {
+			// Whatever is left stays queued until the next startup.
+			if !self.services.server.running() {
+				break;
+			}
+
+			let key = (room_id, entry.count, &entry.origin, &entry.event_id);
+			let result = match self
+				.db
+				.roomcount_inboundpdu
+				.qry(&key)
+				.await
+				.deserialized::<CanonicalJsonObject>()
+			{
+				| Err(e) => Err(e),
+				| Ok(pdu) => {
+					let handle = self.services.event_handler.handle_incoming_pdu(
+						&entry.origin,
+						room_id,
+						&entry.event_id,
+						pdu,
+						false,
+					);
+
+					AssertUnwindSafe(handle.boxed())
+						.catch_unwind()
+						.await
+						.map_err(Error::from_panic)
+						.inspect_err(|e| error!(%room_id, "Panic while handling queued PDU: {e}"))
+						.and_then(|result| result.map(|_| ()))
+				},
+			};
+
+			self.db.roomcount_inboundpdu.del(key);
+			self.finish(&entry.origin);
+			if let Some(waiter) = self.waiters.lock().remove(&entry.count) {
+				// The transaction may have given up waiting.
+				waiter.send(result).ok();
+			}
+		}
+	}
+
+	fn finish(&self, origin: &ServerName) {
+		let mut queue = self.queue.lock();
+		if let Some(queued) = queue.origins.get_mut(origin) {
+			*queued = queued.saturating_sub(1);
+			if *queued == 0 {
+				queue.origins.remove(origin);
+			}
+		}
+	}
+
+	/// Gives the room another turn if it still has queued PDUs.
+	fn reschedule(&self, room_id: &RoomId) {
+		let mut queue = self.queue.lock();
+		let Some(room) = queue.rooms.get_mut(room_id) else {
+			return;
+		};
+
+		if room.origins.is_empty() {
+			queue.rooms.remove(room_id);
+			return;
+		}
+
+		let tier = room.tier();
+		queue.tiers[tier].push_back(room_id.to_owned());
+		drop(queue);
+		self.ready.notify_one();
+	}
+
+	async fn has_local_users(&self, room_id: &RoomId) -> bool {
+		let server_user = &self.services.globals.server_user;
+		self.services
+			.state_cache
+			.active_local_users_in_room(room_id)
+			.ready_any(|user_id| user_id != *server_user)
+			.await
+	}
+}
+
+impl RoomQueue {
+	fn tier(&self) -> usize {
+		if self.origins.values().all(|origin| origin.deprioritized) {
+			TIER_DEPRIORITIZED
+		} else if self.has_local_users {
+			TIER_LOCAL
+		} else {
+			TIER_REMOTE
+		}
+	}
+
+	/// Takes up to `max` PDUs, one from each origin in turn, serving the
+	/// origins which aren't deprioritised first.
+	fn take_batch(&mut self, max: usize) -> Vec<Entry> {
+		let mut batch = Vec::with_capacity(max);
+		for deprioritized in [false, true] {
+			loop {
+				let before = batch.len();
+				for origin in self
+					.origins
+					.values_mut()
+					.filter(|origin| origin.deprioritized == deprioritized)
+				{
+					if batch.len() >= max {
+						break;
+					}
+
+					batch.extend(origin.entries.pop_front());
+				}
+
+				if batch.len() == before || batch.len() >= max {
+					break;
+				}
+			}
+		}
+
+		self.origins.retain(|_, origin| !origin.entries.is_empty());
+
+		batch
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ruma::{EventId, OwnedServerName, ServerName, server_name};
+
+	use super::{Entry, OriginQueue, RoomQueue, TIER_DEPRIORITIZED, TIER_LOCAL, TIER_REMOTE};
+
+	/// Builds a room queue with the given number of PDUs queued per origin.
+	fn room(has_local_users: bool, origins: &[(&str, usize, bool)]) -> RoomQueue {
+		let mut room = RoomQueue { has_local_users, ..Default::default() };
+		let mut count: u64 = 0;
+		for &(origin, queued, deprioritized) in origins {
+			let origin: OwnedServerName = ServerName::parse(origin).expect("valid server name");
+			let entries = (0..queued)
+				.map(|_| {
+					count = count.saturating_add(1);
+					Entry {
+						count,
+						origin: origin.clone(),
+						event_id: EventId::parse(format!("${count}:{origin}"))
+							.expect("valid event ID"),
+					}
+				})
+				.collect();
+
+			room.origins
+				.insert(origin, OriginQueue { entries, deprioritized });
+		}
+
+		room
+	}
+
+	fn origins(batch: &[Entry]) -> Vec<&str> {
+		batch.iter().map(|entry| entry.origin.as_str()).collect()
+	}
+
+	#[test]
+	fn tier_local() {
+		let room = room(true, &[("a.example", 1, false), ("b.example", 1, true)]);
+
+		assert_eq!(room.tier(), TIER_LOCAL);
+	}
+
+	#[test]
+	fn tier_remote() {
+		let room = room(false, &[("a.example", 1, false), ("b.example", 1, true)]);
+
+		assert_eq!(room.tier(), TIER_REMOTE);
+	}
+
+	#[test]
+	fn tier_deprioritized() {
+		let room = room(true, &[("a.example", 1, true), ("b.example", 1, true)]);
+
+		assert_eq!(room.tier(), TIER_DEPRIORITIZED);
+	}
+
+	#[test]
+	fn batch_round_robin() {
+		let mut room = room(false, &[
+			("a.example", 3, false),
+			("b.example", 1, false),
+			("c.example", 2, false),
+		]);
+
+		let batch = room.take_batch(10);
+
+		assert_eq!(origins(&batch), [
+			"a.example",
+			"b.example",
+			"c.example",
+			"a.example",
+			"c.example",
+			"a.example"
+		]);
+		assert!(room.origins.is_empty());
+	}
+
+	#[test]
+	fn batch_keeps_order_within_origin() {
+		let mut room = room(false, &[("a.example", 3, false)]);
+
+		let batch = room.take_batch(10);
+		let counts: Vec<_> = batch.iter().map(|entry| entry.count).collect();
+
+		assert_eq!(counts, [1, 2, 3]);
+	}
+
+	#[test]
+	fn batch_limit() {
+		let mut room = room(false, &[
+			("a.example", 3, false),
+			("b.example", 1, false),
+			("c.example", 2, false),
+		]);
+
+		let batch = room.take_batch(4);
+
+		assert_eq!(origins(&batch), ["a.example", "b.example", "c.example", "a.example"]);
+		assert_eq!(room.origins.len(), 2);
+		assert_eq!(room.origins[server_name!("a.example")].entries.len(), 1);
+		assert_eq!(room.origins[server_name!("c.example")].entries.len(), 1);
+	}
+
+	#[test]
+	fn batch_deprioritized_last() {
+		let mut room = room(true, &[("a.example", 2, true), ("b.example", 2, false)]);
+
+		let batch = room.take_batch(10);
+
+		assert_eq!(origins(&batch), ["b.example", "b.example", "a.example", "a.example"]);
+	}
+
+	#[test]
+	fn batch_limit_before_deprioritized() {
+		let mut room = room(true, &[("a.example", 2, true), ("b.example", 2, false)]);
+
+		let batch = room.take_batch(2);
+
+		assert_eq!(origins(&batch), ["b.example", "b.example"]);
+		assert_eq!(room.origins.len(), 1);
+		assert_eq!(room.tier(), TIER_DEPRIORITIZED);
+	}
+}
@@ -34,7 +34,7 @@
 use crate::{
 	Dep, antispam, globals,
 	rooms::{
-		event_handler, metadata, outlier, pdu_metadata, short,
+		event_handler, metadata, outlier, partial_state, pdu_metadata, short,
 		state::{self, RoomMutexGuard},
 		state_accessor, state_cache,
 		state_compressor::{self, CompressedState, HashSetCompressStateEvent},
@@ -55,6 +55,7 @@ struct Services {
 	globals: Dep<globals::Service>,
 	metadata: Dep<metadata::Service>,
 	outlier: Dep<outlier::Service>,
+	partial_state: Dep<partial_state::Service>,
 	pdu_metadata: Dep<pdu_metadata::Service>,
 	sending: Dep<sending::Service>,
 	server_keys: Dep<server_keys::Service>,
@@ -79,6 +80,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				globals: args.depend::<globals::Service>("globals"),
 				metadata: args.depend::<metadata::Service>("rooms::metadata"),
 				outlier: args.depend::<outlier::Service>("rooms::outlier"),
+				partial_state: args.depend::<partial_state::Service>("rooms::partial_state"),
 				pdu_metadata: args.depend::<pdu_metadata::Service>("rooms::pdu_metadata"),
 				sending: args.depend::<sending::Service>("sending"),
 				server_keys: args.depend::<server_keys::Service>("server_keys"),
@@ -384,7 +386,7 @@ pub async fn join_remote_room(
 
 		// It has enough fields to be called a proper event now
 		let mut join_event = join_event_stub;
-		let send_join_request = federation::membership::create_join_event::v2::Request::new(
+		let mut send_join_request = federation::membership::create_join_event::v2::Request::new(
 			room_id.to_owned(),
 			event_id.clone(),
 			self.services
@@ -392,6 +394,7 @@ pub async fn join_remote_room(
 				.convert_to_outgoing_federation_event(join_event.clone())
 				.await,
 		);
+		send_join_request.omit_members = self.services.server.config.partial_state_joins;
 
 		// NOTE: send_join can take a long time to respond, but from the point of view
 		// of other servers, we may already have finished joining. This means they
@@ -418,7 +421,8 @@ pub async fn join_remote_room(
 			},
 		};
 
-		info!("send_join finished");
+		let members_omitted = send_join_response.room_state.members_omitted;
+		info!(%members_omitted, "send_join finished");
 
 		if join_authorized_via_users_server.is_some() {
 			if let Some(signed_raw) = &send_join_response.room_state.event {
@@ -528,7 +532,10 @@ pub async fn join_remote_room(
 					return state;
 				}
 				self.services.outlier.add_pdu_outlier(&event_id, &value);
-				self.services.pdu_metadata.clear_pdu_markers(&event_id);
+				self.services
+					.pdu_metadata
+					.clear_pdu_markers(&event_id)
+					.await;
 				if let Some(state_key) = &pdu.state_key {
 					let shortstatekey = self
 						.services
@@ -557,10 +564,13 @@ pub async fn join_remote_room(
 					.validate_and_add_event_id_no_fetch(pdu, &room_version_rules)
 			})
 			.ready_filter_map(Result::ok)
-			.ready_for_each(|(event_id, value)| {
+			.for_each(async |(event_id, value)| {
 				trace!(%event_id, "Adding PDU as an outlier from send_join auth_chain");
 				self.services.outlier.add_pdu_outlier(&event_id, &value);
-				self.services.pdu_metadata.clear_pdu_markers(&event_id);
+				self.services
+					.pdu_metadata
+					.clear_pdu_markers(&event_id)
+					.await;
 			})
 			.await;
 
@@ -674,6 +684,29 @@ pub async fn join_remote_room(
 		}
 		drop(cork);
 
+		if members_omitted {
+			let mut servers = vec![remote_server.clone()];
+			for server in send_join_response
+				.room_state
+				.servers_in_room
+				.iter()
+				.flatten()
+				.filter_map(|server| OwnedServerName::try_from(server.as_str()).ok())
+			{
+				if !servers.contains(&server) {
+					servers.push(server);
+				}
+			}
+
+			info!("Joined with partial state, fetching the full state in the background");
+			self.services.partial_state.mark_partial_state(
+				room_id,
+				event_id.clone(),
+				servers,
+				statehash_after_join,
+			);
+		}
+
 		self.services.sync.wake_all_joined(room_id).await;
 
 		Ok(())
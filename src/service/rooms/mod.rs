@@ -2,10 +2,12 @@
 pub mod auth_chain;
 pub mod directory;
 pub mod event_handler;
+pub mod inbound_queue;
 pub mod lazy_loading;
 pub mod membership;
 pub mod metadata;
 pub mod outlier;
+pub mod partial_state;
 pub mod pdu_metadata;
 pub mod read_receipt;
 pub mod search;
@@ -14,6 +16,7 @@
 pub mod state_accessor;
 pub mod state_cache;
 pub mod state_compressor;
+pub mod state_reset;
 pub mod summary;
 pub mod threads;
 pub mod timeline;
@@ -27,10 +30,12 @@ pub struct Service {
 	pub auth_chain: Arc<auth_chain::Service>,
 	pub directory: Arc<directory::Service>,
 	pub event_handler: Arc<event_handler::Service>,
+	pub inbound_queue: Arc<inbound_queue::Service>,
 	pub lazy_loading: Arc<lazy_loading::Service>,
 	pub membership: Arc<membership::Service>,
 	pub metadata: Arc<metadata::Service>,
 	pub outlier: Arc<outlier::Service>,
+	pub partial_state: Arc<partial_state::Service>,
 	pub pdu_metadata: Arc<pdu_metadata::Service>,
 	pub read_receipt: Arc<read_receipt::Service>,
 	pub search: Arc<search::Service>,
@@ -39,6 +44,7 @@ pub struct Service {
 	pub state_accessor: Arc<state_accessor::Service>,
 	pub state_cache: Arc<state_cache::Service>,
 	pub state_compressor: Arc<state_compressor::Service>,
+	pub state_reset: Arc<state_reset::Service>,
 	pub summary: Arc<summary::Service>,
 	pub threads: Arc<threads::Service>,
 	pub timeline: Arc<timeline::Service>,
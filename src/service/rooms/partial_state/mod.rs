@@ -0,0 +1,569 @@
+//! Partial-state rooms (MSC3706/MSC3902)
+//!
+//! When joining a remote room with `partial_state_joins` enabled, the resident
+//! server omits the membership events of other users from the `send_join`
+//! response. The room is usable right away, but its state is incomplete until
+//! the worker has fetched the full state at the join event with `/state_ids`
+//! and applied it. Anything that needs the complete member list waits for that
+//! with [`Service::wait_for_full_state`].
+//!
+//! Events which could only be accepted because the partial state lacked the
+//! memberships they would be checked against are recorded, and re-checked
+//! against the full state when it is applied. Those which fail are soft-failed
+//! and their state changes are left out of the room's current state.
+
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Arc,
+	time::Duration,
+};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Err, Event, Result, Server, SyncMutex, debug, debug_warn, info,
+	matrix::pdu::PduCount,
+	utils::{
+		self,
+		stream::{ReadyExt, TryIgnore},
+	},
+	warn,
+};
+use database::{Deserialized, Ignore, Interfix, Json, Map};
+use futures::{Stream, StreamExt};
+use ruma::{
+	EventId, OwnedEventId, OwnedRoomId, OwnedServerName, OwnedUserId, RoomId, UserId,
+	events::StateEventType,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+	sync::Notify,
+	time::{sleep, timeout},
+};
+
+use crate::{
+	Dep, globals,
+	rooms::{
+		self,
+		short::{ShortStateHash, ShortStateKey},
+		state_compressor::{CompressedState, HashSetCompressStateEvent},
+	},
+	sync, users,
+};
+
+/// Delay before retrying the resync of rooms that failed.
+const RESYNC_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of failed resyncs after which a room is no longer retried until the
+/// server restarts.
+const MAX_RESYNC_ATTEMPTS: usize = 10;
+
+/// How long [`Service::wait_for_full_state`] waits before giving up.
+const FULL_STATE_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Service {
+	db: Data,
+	services: Services,
+	failures: SyncMutex<HashMap<OwnedRoomId, usize>>,
+	pending: Notify,
+	resynced: Notify,
+	interrupt: Notify,
+}
+
+struct Data {
+	roomid_partialstate: Arc<Map>,
+	roomideventid_partialstaterecheck: Arc<Map>,
+}
+
+struct Services {
+	server: Arc<Server>,
+	event_handler: Dep<rooms::event_handler::Service>,
+	globals: Dep<globals::Service>,
+	short: Dep<rooms::short::Service>,
+	state: Dep<rooms::state::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
+	state_cache: Dep<rooms::state_cache::Service>,
+	state_compressor: Dep<rooms::state_compressor::Service>,
+	sync: Dep<sync::Service>,
+	timeline: Dep<rooms::timeline::Service>,
+	users: Dep<users::Service>,
+}
+
+/// A room joined with partial state, which is waiting for its full state.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PartialState {
+	/// The join event which was sent with `omit_members`.
+	pub event_id: OwnedEventId,
+
+	/// The servers in the room according to the `send_join` response, starting
+	/// with the server the room was joined through.
+	pub servers: Vec<OwnedServerName>,
+
+	/// The partial state after the join event.
+	pub shortstatehash: ShortStateHash,
+
+	/// When the room was joined, in milliseconds since the unix epoch.
+	pub ts: u64,
+}
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			db: Data {
+				roomid_partialstate: args.db["roomid_partialstate"].clone(),
+				roomideventid_partialstaterecheck: args.db["roomideventid_partialstaterecheck"]
+					.clone(),
+			},
+			services: Services {
+				server: args.server.clone(),
+				event_handler: args
+					.depend::<rooms::event_handler::Service>("rooms::event_handler"),
+				globals: args.depend::<globals::Service>("globals"),
+				short: args.depend::<rooms::short::Service>("rooms::short"),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
+				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+				state_compressor: args
+					.depend::<rooms::state_compressor::Service>("rooms::state_compressor"),
+				sync: args.depend::<sync::Service>("sync"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
+				users: args.depend::<users::Service>("users"),
+			},
+			failures: SyncMutex::new(HashMap::new()),
+			pending: Notify::new(),
+			resynced: Notify::new(),
+			interrupt: Notify::new(),
+		}))
+	}
+
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		while self.services.server.running() {
+			let rooms: Vec<OwnedRoomId> = self.partial_state_rooms().collect().await;
+
+			let mut failed = false;
+			for room_id in &rooms {
+				if !self.services.server.running() {
+					break;
+				}
+
+				if self.gave_up(room_id) {
+					continue;
+				}
+
+				if let Err(e) = self.resync(room_id).await {
+					warn!(%room_id, "Failed to resync the full state of a partial-state room: {e}");
+					failed = true;
+
+					let mut failures = self.failures.lock();
+					let attempts = failures.entry(room_id.clone()).or_default();
+					*attempts = attempts.saturating_add(1);
+					if *attempts >= MAX_RESYNC_ATTEMPTS {
+						warn!(
+							%room_id,
+							"Giving up on the full state of a partial-state room after \
+							 {MAX_RESYNC_ATTEMPTS} attempts until the next restart"
+						);
+						self.resynced.notify_waiters();
+					}
+				}
+			}
+
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				() = self.pending.notified() => (),
+				() = sleep(RESYNC_RETRY_INTERVAL), if failed => (),
+			}
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+impl Service {
+	/// Whether the room was joined with partial state and is still waiting for
+	/// its full state.
+	pub async fn is_partial_state(&self, room_id: &RoomId) -> bool {
+		self.db.roomid_partialstate.get(room_id).await.is_ok()
+	}
+
+	/// Gets the partial-state information of a room.
+	pub async fn get_partial_state(&self, room_id: &RoomId) -> Result<PartialState> {
+		self.db
+			.roomid_partialstate
+			.get(room_id)
+			.await
+			.deserialized()
+	}
+
+	/// Streams the rooms which are waiting for their full state.
+	pub fn partial_state_rooms(&self) -> impl Stream<Item = OwnedRoomId> + Send + '_ {
+		self.db.roomid_partialstate.keys().ignore_err()
+	}
+
+	/// The servers known to be in a partial-state room from the `send_join`
+	/// response. Empty once the room has its full state.
+	pub async fn servers_in_room(&self, room_id: &RoomId) -> Vec<OwnedServerName> {
+		self.get_partial_state(room_id)
+			.await
+			.map(|partial| partial.servers)
+			.unwrap_or_default()
+	}
+
+	/// Marks a room as joined with partial state and queues the resync of its
+	/// full state.
+	pub fn mark_partial_state(
+		&self,
+		room_id: &RoomId,
+		event_id: OwnedEventId,
+		servers: Vec<OwnedServerName>,
+		shortstatehash: ShortStateHash,
+	) {
+		let partial = PartialState {
+			event_id,
+			servers,
+			shortstatehash,
+			ts: utils::millis_since_unix_epoch(),
+		};
+
+		self.db.roomid_partialstate.raw_put(room_id, Json(partial));
+		self.pending.notify_one();
+	}
+
+	/// Waits until the room has its full state. Returns immediately for rooms
+	/// which weren't joined with partial state, and gives up after
+	/// [`FULL_STATE_WAIT_TIMEOUT`] or once the resync of the room was given up,
+	/// leaving the caller with the partial state.
+	pub async fn wait_for_full_state(&self, room_id: &RoomId) {
+		let wait = wait_until(&self.resynced, async || {
+			let done = !self.is_partial_state(room_id).await || self.gave_up(room_id);
+			if !done {
+				debug!(%room_id, "Waiting for the full state of a partial-state room");
+			}
+
+			done
+		});
+
+		if timeout(FULL_STATE_WAIT_TIMEOUT, wait).await.is_err() {
+			debug_warn!(%room_id, "Timed out waiting for the full state of a partial-state room");
+		}
+	}
+
+	/// Waits until every partial-state room the user is joined to has its full
+	/// state, so their member lists can be relied on for device lists.
+	pub async fn wait_for_full_state_of_user(&self, user_id: &UserId) {
+		let rooms: Vec<OwnedRoomId> = self
+			.partial_state_rooms()
+			.filter_map(async |room_id| {
+				self.services
+					.state_cache
+					.is_joined(user_id, &room_id)
+					.await
+					.then_some(room_id)
+			})
+			.collect()
+			.await;
+
+		for room_id in &rooms {
+			self.wait_for_full_state(room_id).await;
+		}
+	}
+
+	/// Records an event which was accepted without the memberships missing
+	/// from the partial state of its room, to re-check it once the full state
+	/// is applied.
+	pub fn recheck_after_resync(&self, room_id: &RoomId, event_id: &EventId) {
+		self.db
+			.roomideventid_partialstaterecheck
+			.put_raw((room_id, event_id), []);
+	}
+
+	fn rechecks<'a>(
+		&'a self,
+		room_id: &'a RoomId,
+	) -> impl Stream<Item = &'a EventId> + Send + 'a {
+		self.db
+			.roomideventid_partialstaterecheck
+			.keys_prefix(&(room_id, Interfix))
+			.ignore_err()
+			.map(|(_, event_id): (Ignore, &EventId)| event_id)
+	}
+
+	async fn clear_rechecks(&self, room_id: &RoomId) {
+		let prefix = (room_id, Interfix);
+		self.db
+			.roomideventid_partialstaterecheck
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| self.db.roomideventid_partialstaterecheck.remove(key))
+			.await;
+	}
+
+	fn gave_up(&self, room_id: &RoomId) -> bool {
+		self.failures
+			.lock()
+			.get(room_id)
+			.is_some_and(|attempts| *attempts >= MAX_RESYNC_ATTEMPTS)
+	}
+
+	/// Fetches the full state at the join event of a partial-state room and
+	/// replaces the room's state with it, keeping the state changes which
+	/// happened since the join.
+	async fn resync(&self, room_id: &RoomId) -> Result {
+		let partial = self.get_partial_state(room_id).await?;
+		if !self
+			.services
+			.state_cache
+			.server_in_room(self.services.globals.server_name(), room_id)
+			.await
+		{
+			debug!(%room_id, "Left partial-state room before its full state was fetched");
+			self.db.roomid_partialstate.remove(room_id);
+			self.clear_rechecks(room_id).await;
+			self.resynced.notify_waiters();
+			return Ok(());
+		}
+
+		let join_event = self.services.timeline.get_pdu(&partial.event_id).await?;
+		let create_event = self
+			.services
+			.state_accessor
+			.room_state_get(room_id, &StateEventType::RoomCreate, "")
+			.await?;
+
+		info!(%room_id, servers = partial.servers.len(), "Resyncing the full state of a partial-state room");
+
+		let mut full_state = None;
+		for server in &partial.servers {
+			if self.services.globals.server_is_ours(server) {
+				continue;
+			}
+
+			match self
+				.services
+				.event_handler
+				.fetch_state(server, &create_event, room_id, &partial.event_id)
+				.await
+			{
+				| Ok(state) => {
+					full_state = Some(state);
+					break;
+				},
+				| Err(e) => debug_warn!(%room_id, %server, "Failed to fetch the full state: {e}"),
+			}
+		}
+
+		let Some(mut full_state) = full_state else {
+			return Err!("No server could provide the full state at {}.", partial.event_id);
+		};
+
+		// `/state_ids` returns the state before the join event.
+		let Some(state_key) = join_event.state_key() else {
+			return Err!(Database("Join event {} has no state key.", partial.event_id));
+		};
+
+		let shortstatekey = self
+			.services
+			.short
+			.get_or_create_shortstatekey(&StateEventType::RoomMember, state_key)
+			.await;
+
+		full_state.insert(shortstatekey, partial.event_id.clone());
+
+		let state_lock = self.services.state.mutex.lock(room_id).await;
+
+		let partial_at_join: HashMap<ShortStateKey, OwnedEventId> = self
+			.services
+			.state_accessor
+			.state_full_ids(partial.shortstatehash)
+			.collect()
+			.await;
+
+		let current_shortstatehash = self.services.state.get_room_shortstatehash(room_id).await?;
+		let changed_since_join: Vec<(ShortStateKey, OwnedEventId)> = self
+			.services
+			.state_accessor
+			.state_full_ids(current_shortstatehash)
+			.ready_filter(|(shortstatekey, event_id): &(ShortStateKey, OwnedEventId)| {
+				partial_at_join.get(shortstatekey) != Some(event_id)
+			})
+			.collect()
+			.await;
+
+		// Events accepted without the full state only count towards it once they pass
+		// against it, in the order they were appended.
+		let mut rechecks: Vec<(PduCount, OwnedEventId)> = self
+			.rechecks(room_id)
+			.then(async |event_id| {
+				let count = self.services.timeline.get_pdu_count(event_id).await.ok();
+				(count, event_id.to_owned())
+			})
+			.ready_filter_map(|(count, event_id)| Some((count?, event_id)))
+			.collect()
+			.await;
+
+		rechecks.sort_unstable();
+
+		let recheck_ids: HashSet<&OwnedEventId> =
+			rechecks.iter().map(|(_, event_id)| event_id).collect();
+
+		let recheck_keys: HashMap<OwnedEventId, ShortStateKey> = changed_since_join
+			.iter()
+			.filter(|(_, event_id)| recheck_ids.contains(event_id))
+			.map(|(shortstatekey, event_id)| (event_id.clone(), *shortstatekey))
+			.collect();
+
+		full_state.extend(
+			changed_since_join
+				.into_iter()
+				.filter(|(_, event_id)| !recheck_keys.contains_key(event_id)),
+		);
+
+		let mut soft_failed: usize = 0;
+		for (_, event_id) in &rechecks {
+			let Ok(pdu) = self.services.timeline.get_pdu(event_id).await else {
+				continue;
+			};
+
+			if !self
+				.services
+				.event_handler
+				.recheck_partial_state_event(&pdu, &create_event, &full_state)
+				.await
+			{
+				soft_failed = soft_failed.saturating_add(1);
+				continue;
+			}
+
+			if let Some(shortstatekey) = recheck_keys.get(event_id) {
+				full_state.insert(*shortstatekey, event_id.clone());
+			}
+		}
+
+		let members_before: HashSet<OwnedUserId> = self
+			.services
+			.state_cache
+			.room_members(room_id)
+			.collect()
+			.await;
+
+		let compressed: CompressedState = self
+			.services
+			.state_compressor
+			.compress_state_events(full_state.iter().map(|(ssk, eid)| (ssk, eid.as_ref())))
+			.collect()
+			.await;
+
+		let HashSetCompressStateEvent { shortstatehash, added, removed } = self
+			.services
+			.state_compressor
+			.save_state(room_id, Arc::new(compressed))
+			.await?;
+
+		self.services
+			.state
+			.force_state(room_id, shortstatehash, added, removed, &state_lock)
+			.await?;
+
+		self.db.roomid_partialstate.remove(room_id);
+		self.clear_rechecks(room_id).await;
+		self.failures.lock().remove(room_id);
+		drop(state_lock);
+
+		self.resynced.notify_waiters();
+
+		// Local users in the room couldn't see the device lists of the members
+		// which were omitted from the join.
+		let new_members: Vec<OwnedUserId> = self
+			.services
+			.state_cache
+			.room_members(room_id)
+			.ready_filter(|user_id| !members_before.contains(user_id))
+			.collect()
+			.await;
+
+		if self
+			.services
+			.state_accessor
+			.is_encrypted_room(room_id)
+			.await
+		{
+			self.services
+				.users
+				.mark_room_device_key_updates(room_id, new_members.iter().map(AsRef::as_ref))
+				.await;
+		}
+
+		self.services.sync.wake_all_joined(room_id).await;
+
+		info!(
+			%room_id,
+			new_members = new_members.len(),
+			rechecked = rechecks.len(),
+			soft_failed,
+			elapsed_ms = utils::millis_since_unix_epoch().saturating_sub(partial.ts),
+			"Resynced the full state of a partial-state room"
+		);
+
+		Ok(())
+	}
+}
+
+/// Waits on `notify` until `done` returns true. The notification is registered
+/// before `done` is checked, so one sent in between isn't missed.
+async fn wait_until(notify: &Notify, mut done: impl AsyncFnMut() -> bool) {
+	loop {
+		let notified = notify.notified();
+		if done().await {
+			return;
+		}
+
+		notified.await;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	use super::*;
+
+	#[tokio::test]
+	async fn waits_until_resynced() {
+		let resynced = Arc::new(Notify::new());
+		let full_state = Arc::new(AtomicBool::new(false));
+
+		let waiter = tokio::spawn({
+			let resynced = resynced.clone();
+			let full_state = full_state.clone();
+			async move {
+				wait_until(&resynced, async || full_state.load(Ordering::Acquire)).await;
+			}
+		});
+
+		// Still partial: a notification alone doesn't end the wait.
+		tokio::task::yield_now().await;
+		resynced.notify_waiters();
+		tokio::task::yield_now().await;
+		assert!(!waiter.is_finished());
+
+		full_state.store(true, Ordering::Release);
+		resynced.notify_waiters();
+		timeout(Duration::from_secs(1), waiter)
+			.await
+			.expect("waiter should finish once the room has its full state")
+			.unwrap();
+	}
+
+	#[tokio::test]
+	async fn returns_immediately_with_full_state() {
+		let resynced = Notify::new();
+		timeout(Duration::from_secs(1), wait_until(&resynced, async || true))
+			.await
+			.expect("rooms with their full state shouldn't wait");
+	}
+}
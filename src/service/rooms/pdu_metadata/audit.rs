@@ -0,0 +1,288 @@
+//! Ingestion audit trail
+//!
+//! Records why, when and from which origin incoming events were rejected,
+//! soft-failed or dropped, so that admins can find out why an event never
+//! reached the timeline. Only the latest verdicts of each room are kept, and
+//! verdicts against dropped events, which were never persisted, are only kept
+//! in memory.
+
+use conduwuit::{
+	Result, err,
+	utils::{millis_since_unix_epoch, stream::TryIgnore},
+};
+use database::{Deserialized, Ignore, Interfix, Json};
+use futures::{Stream, StreamExt, stream};
+use ruma::{EventId, OwnedEventId, OwnedRoomId, OwnedServerName, RoomId, ServerName};
+use serde::{Deserialize, Serialize};
+
+/// Number of verdicts kept per room, the oldest are pruned past it.
+const MAX_ROOM_AUDITS: usize = 1000;
+
+/// Number of verdicts against dropped events kept in memory.
+pub(super) const MAX_DROPPED_AUDITS: usize = 1000;
+
+/// What happened to an incoming event.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestionOutcome {
+	/// The event was persisted as a rejected outlier.
+	Rejected,
+
+	/// The event was accepted but left out of the timeline and current state.
+	SoftFailed,
+
+	/// The event was not persisted at all.
+	Dropped,
+}
+
+/// The check an incoming event did not pass.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestionCheck {
+	/// The origin is denied by the server ACL of the room.
+	Acl,
+
+	/// PDU check 1: the event does not follow the format of the room version.
+	Format,
+
+	/// PDU checks 2 and 3: the signatures of the event are invalid.
+	Signatures,
+
+	/// The auth events of the event are rejected, duplicated, not state events
+	/// or in another room.
+	AuthEvents,
+
+	/// PDU check 4: the event is not authorised by its auth events.
+	AuthRules,
+
+	/// PDU check 5: the event is not authorised by the state before it.
+	StateBefore,
+
+	/// PDU check 6: the event is not authorised by the current room state.
+	CurrentState,
+
+	/// PDU check 7: the policy server of the room refused the event.
+	PolicyServer,
+
+	/// The sender may not redact the target event, or the target event is not
+	/// accepted.
+	Redaction,
+}
+
+/// A recorded verdict against an incoming event.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IngestionAudit {
+	pub outcome: IngestionOutcome,
+	pub check: IngestionCheck,
+	pub reason: String,
+	pub origin: OwnedServerName,
+	pub room_id: OwnedRoomId,
+
+	/// When the verdict was made, in milliseconds since the unix epoch.
+	pub ts: u64,
+}
+
+impl IngestionOutcome {
+	#[must_use]
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			| Self::Rejected => "rejected",
+			| Self::SoftFailed => "soft-failed",
+			| Self::Dropped => "dropped",
+		}
+	}
+}
+
+impl IngestionCheck {
+	#[must_use]
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			| Self::Acl => "server ACL",
+			| Self::Format => "event format",
+			| Self::Signatures => "signatures",
+			| Self::AuthEvents => "auth events",
+			| Self::AuthRules => "auth rules",
+			| Self::StateBefore => "state before the event",
+			| Self::CurrentState => "current room state",
+			| Self::PolicyServer => "policy server",
+			| Self::Redaction => "redaction",
+		}
+	}
+}
+
+impl IngestionAudit {
+	#[must_use]
+	pub fn new(
+		outcome: IngestionOutcome,
+		check: IngestionCheck,
+		origin: &ServerName,
+		room_id: &RoomId,
+		reason: impl Into<String>,
+	) -> Self {
+		Self {
+			outcome,
+			check,
+			reason: reason.into(),
+			origin: origin.to_owned(),
+			room_id: room_id.to_owned(),
+			ts: millis_since_unix_epoch(),
+		}
+	}
+}
+
+impl super::Service {
+	/// Records the verdict against an incoming event, replacing any earlier
+	/// one. Prunes the oldest verdicts of the room once it is over the per-room
+	/// limit.
+	pub async fn record_ingestion(&self, event_id: &EventId, audit: &IngestionAudit) {
+		if audit.outcome == IngestionOutcome::Dropped {
+			self.record_dropped(event_id, audit);
+			return;
+		}
+
+		self.remove_ingestion_audit(event_id).await;
+		self.db
+			.roomidts_ingestionaudit
+			.put_raw((&audit.room_id, audit.ts, event_id), []);
+
+		self.db
+			.eventid_ingestionaudit
+			.raw_put(event_id, Json(audit));
+
+		self.prune_ingestion_audits(&audit.room_id).await;
+	}
+
+	/// Records the verdict against a dropped event in memory, forgetting the
+	/// oldest past the limit.
+	pub fn record_dropped(&self, event_id: &EventId, audit: &IngestionAudit) {
+		let mut dropped = self.dropped.lock();
+		dropped.retain(|(dropped, _)| dropped != event_id);
+		if dropped.len() >= MAX_DROPPED_AUDITS {
+			dropped.pop_front();
+		}
+
+		dropped.push_back((event_id.to_owned(), audit.clone()));
+	}
+
+	/// Gets the last recorded verdict against an event.
+	pub async fn ingestion_audit(&self, event_id: &EventId) -> Result<IngestionAudit> {
+		if let Ok(audit) = self
+			.db
+			.eventid_ingestionaudit
+			.get(event_id)
+			.await
+			.deserialized()
+		{
+			return Ok(audit);
+		}
+
+		self.dropped
+			.lock()
+			.iter()
+			.find(|(dropped, _)| dropped == event_id)
+			.map(|(_, audit)| audit.clone())
+			.ok_or_else(|| err!(Request(NotFound("No verdict was recorded for {event_id}."))))
+	}
+
+	/// Streams the events of a room with a recorded verdict against them,
+	/// oldest first, followed by the dropped events.
+	pub fn room_ingestion_audits<'a>(
+		&'a self,
+		room_id: &'a RoomId,
+	) -> impl Stream<Item = (OwnedEventId, IngestionAudit)> + Send + 'a {
+		let dropped: Vec<_> = self
+			.dropped
+			.lock()
+			.iter()
+			.filter(|(_, audit)| audit.room_id == *room_id)
+			.cloned()
+			.collect();
+
+		self.db
+			.roomidts_ingestionaudit
+			.keys_prefix(&(room_id, Interfix))
+			.ignore_err()
+			.filter_map(async |(_, _, event_id): (Ignore, Ignore, OwnedEventId)| {
+				let audit = self.ingestion_audit(&event_id).await.ok()?;
+				Some((event_id, audit))
+			})
+			.chain(stream::iter(dropped))
+	}
+
+	/// Removes the recorded verdict against an event.
+	pub(super) async fn remove_ingestion_audit(&self, event_id: &EventId) {
+		self.dropped
+			.lock()
+			.retain(|(dropped, _)| dropped != event_id);
+
+		let Ok(audit) = self
+			.db
+			.eventid_ingestionaudit
+			.get(event_id)
+			.await
+			.deserialized::<IngestionAudit>()
+		else {
+			return;
+		};
+
+		self.db
+			.roomidts_ingestionaudit
+			.del((&audit.room_id, audit.ts, event_id));
+
+		self.db.eventid_ingestionaudit.remove(event_id);
+
+		if let Some(count) = self.audit_counts.lock().get_mut(&audit.room_id) {
+			*count = count.saturating_sub(1);
+		}
+	}
+
+	/// Counts a verdict recorded in a room, counting the verdicts already
+	/// stored the first time the room is seen.
+	async fn count_ingestion_audit(&self, room_id: &RoomId) -> usize {
+		if let Some(count) = self.audit_counts.lock().get_mut(room_id) {
+			*count = count.saturating_add(1);
+			return *count;
+		}
+
+		let count = self
+			.db
+			.roomidts_ingestionaudit
+			.keys_prefix_raw(&(room_id, Interfix))
+			.ignore_err()
+			.count()
+			.await;
+
+		self.audit_counts.lock().insert(room_id.to_owned(), count);
+		count
+	}
+
+	/// Prunes the oldest verdicts of a room once it has more than
+	/// [`MAX_ROOM_AUDITS`].
+	async fn prune_ingestion_audits(&self, room_id: &RoomId) {
+		let count = self.count_ingestion_audit(room_id).await;
+		let excess = count.saturating_sub(MAX_ROOM_AUDITS);
+		if excess == 0 {
+			return;
+		}
+
+		let oldest: Vec<(u64, OwnedEventId)> = self
+			.db
+			.roomidts_ingestionaudit
+			.keys_prefix(&(room_id, Interfix))
+			.ignore_err()
+			.map(|(_, ts, event_id): (Ignore, u64, OwnedEventId)| (ts, event_id))
+			.take(excess)
+			.collect()
+			.await;
+
+		for (ts, event_id) in &oldest {
+			self.db.roomidts_ingestionaudit.del((room_id, *ts, event_id));
+
+			self.db.eventid_ingestionaudit.remove(event_id);
+		}
+
+		if let Some(count) = self.audit_counts.lock().get_mut(room_id) {
+			*count = count.saturating_sub(oldest.len());
+		}
+	}
+}
@@ -27,6 +27,8 @@ pub(super) struct Data {
 	referencedevents: Arc<Map>,
 	softfailedeventids: Arc<Map>,
 	rejectedeventids: Arc<Map>,
+	pub(super) eventid_ingestionaudit: Arc<Map>,
+	pub(super) roomidts_ingestionaudit: Arc<Map>,
 	services: Services,
 }
 
@@ -42,6 +44,8 @@ pub(super) fn new(args: &crate::Args<'_>) -> Self {
 			referencedevents: db["referencedevents"].clone(),
 			softfailedeventids: db["softfailedeventids"].clone(),
 			rejectedeventids: db["rejectedeventids"].clone(),
+			eventid_ingestionaudit: db["eventid_ingestionaudit"].clone(),
+			roomidts_ingestionaudit: db["roomidts_ingestionaudit"].clone(),
 			services: Services {
 				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
 			},
@@ -1,11 +1,16 @@
+mod audit;
 mod bundled_aggregations;
 mod data;
-use std::sync::Arc;
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::Arc,
+};
 
-use conduwuit::{Result, matrix::PduCount};
+use conduwuit::{Result, SyncMutex, matrix::PduCount};
 use futures::{StreamExt, future::try_join};
-use ruma::{EventId, RoomId, UserId, api::Direction};
+use ruma::{EventId, OwnedEventId, OwnedRoomId, RoomId, UserId, api::Direction};
 
+pub use self::audit::{IngestionAudit, IngestionCheck, IngestionOutcome};
 use self::data::Data;
 use crate::{
 	Dep,
@@ -15,6 +20,8 @@
 pub struct Service {
 	services: Services,
 	db: Data,
+	dropped: SyncMutex<VecDeque<(OwnedEventId, IngestionAudit)>>,
+	audit_counts: SyncMutex<HashMap<OwnedRoomId, usize>>,
 }
 
 struct Services {
@@ -33,6 +40,8 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 			},
 			db: Data::new(&args),
+			dropped: SyncMutex::new(VecDeque::with_capacity(audit::MAX_DROPPED_AUDITS)),
+			audit_counts: SyncMutex::new(HashMap::new()),
 		}))
 	}
 
@@ -163,5 +172,10 @@ pub async fn is_event_accepted(&self, event_id: &EventId) -> bool {
 			&& !self.db.is_event_soft_failed(event_id).await
 	}
 
-	pub fn clear_pdu_markers(&self, event_id: &EventId) { self.db.clear_pdu_markers(event_id); }
+	/// Removes any soft-fail or rejection markers applied to the target PDU,
+	/// along with the recorded verdict.
+	pub async fn clear_pdu_markers(&self, event_id: &EventId) {
+		self.db.clear_pdu_markers(event_id);
+		self.remove_ingestion_audit(event_id).await;
+	}
 }
@@ -2,21 +2,26 @@
 
 use conduwuit::{
 	Result,
-	utils::{ReadyExt, stream::TryIgnore},
+	utils::{self, ReadyExt, stream::TryIgnore},
 };
-use database::{Deserialized, Json, Map};
+use database::{Deserialized, Interfix, Json, Map};
 use futures::{Stream, StreamExt};
 use ruma::{
-	CanonicalJsonObject, OwnedRoomId, OwnedUserId, RoomId, UserId,
-	events::{AnySyncEphemeralRoomEvent, receipt::ReceiptEvent},
+	CanonicalJsonObject, EventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
+	events::{
+		AnySyncEphemeralRoomEvent,
+		receipt::{ReceiptEvent, ReceiptThread},
+	},
 	serde::Raw,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{Dep, globals};
 
 pub(super) struct Data {
 	roomuserid_privateread: Arc<Map>,
 	roomuserid_lastprivatereadupdate: Arc<Map>,
+	roomuserthreadid_privateread: Arc<Map>,
 	services: Services,
 	readreceiptid_readreceipt: Arc<Map>,
 }
@@ -27,12 +32,22 @@ struct Services {
 
 pub(super) type ReceiptItem = (OwnedUserId, u64, Raw<AnySyncEphemeralRoomEvent>);
 
+/// A private read marker in one thread of a room.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(super) struct PrivateRead {
+	/// PDU count of the event read up to.
+	pub(super) count: u64,
+	/// Milliseconds since the epoch when the marker was set.
+	pub(super) ts: u64,
+}
+
 impl Data {
 	pub(super) fn new(args: &crate::Args<'_>) -> Self {
 		let db = &args.db;
 		Self {
 			roomuserid_privateread: db["roomuserid_privateread"].clone(),
 			roomuserid_lastprivatereadupdate: db["roomuserid_lastprivatereadupdate"].clone(),
+			roomuserthreadid_privateread: db["roomuserthreadid_privateread"].clone(),
 			readreceiptid_readreceipt: db["readreceiptid_readreceipt"].clone(),
 			services: Services {
 				globals: args.depend::<globals::Service>("globals"),
@@ -46,14 +61,21 @@ pub(super) async fn readreceipt_update(
 		room_id: &RoomId,
 		event: &ReceiptEvent,
 	) {
-		// Remove old entry
+		// Remove the user's old receipt for the same thread
+		let thread = receipt_thread(event);
 		let last_possible_key = (room_id, u64::MAX);
 		self.readreceiptid_readreceipt
-			.rev_keys_from_raw(&last_possible_key)
+			.rev_stream_from_raw(&last_possible_key)
 			.ignore_err()
-			.ready_take_while(|key| key.starts_with(room_id.as_bytes()))
-			.ready_filter_map(|key| key.ends_with(user_id.as_bytes()).then_some(key))
-			.ready_for_each(|key| self.readreceiptid_readreceipt.del(key))
+			.ready_take_while(|(key, _)| key.starts_with(room_id.as_bytes()))
+			.ready_filter_map(|(key, val)| {
+				key.ends_with(user_id.as_bytes()).then_some((key, val))
+			})
+			.ready_filter(|(_, val)| {
+				serde_json::from_slice::<ReceiptEvent>(val)
+					.is_ok_and(|old| receipt_thread(&old) == thread)
+			})
+			.ready_for_each(|(key, _)| self.readreceiptid_readreceipt.del(key))
 			.await;
 
 		let count = self.services.globals.next_count().unwrap();
@@ -86,14 +108,56 @@ pub(super) fn readreceipts_since<'a>(
 			.ignore_err()
 	}
 
-	pub(super) fn private_read_set(&self, room_id: &RoomId, user_id: &UserId, pdu_count: u64) {
+	pub(super) fn private_read_set(
+		&self,
+		room_id: &RoomId,
+		user_id: &UserId,
+		pdu_count: u64,
+		thread: &ReceiptThread,
+	) {
 		let key = (room_id, user_id);
 		let next_count = self.services.globals.next_count().unwrap();
 
-		self.roomuserid_privateread.put(key, pdu_count);
+		// The room-wide marker follows the unthreaded and main timeline markers.
+		if !matches!(thread, ReceiptThread::Thread(_)) {
+			self.roomuserid_privateread.put(key, pdu_count);
+		}
+
+		let private_read = PrivateRead {
+			count: pdu_count,
+			ts: utils::millis_since_unix_epoch(),
+		};
+
+		let thread_key = (room_id, user_id, thread.as_str().unwrap_or_default());
+		self.roomuserthreadid_privateread
+			.put(thread_key, Json(private_read));
 		self.roomuserid_lastprivatereadupdate.put(key, next_count);
 	}
 
+	/// Iterates the user's private read markers in the room with the threads
+	/// they apply to.
+	pub(super) fn private_reads<'a>(
+		&'a self,
+		room_id: &'a RoomId,
+		user_id: &'a UserId,
+	) -> impl Stream<Item = (ReceiptThread, PrivateRead)> + Send + 'a {
+		type KeyVal<'a> = ((&'a RoomId, &'a UserId, &'a str), PrivateRead);
+
+		let prefix = (room_id, user_id, Interfix);
+		self.roomuserthreadid_privateread
+			.stream_prefix(&prefix)
+			.ignore_err()
+			.ready_filter_map(|((.., thread), private_read): KeyVal<'_>| {
+				let thread = match thread {
+					| "" => ReceiptThread::Unthreaded,
+					| "main" => ReceiptThread::Main,
+					| thread => ReceiptThread::Thread(EventId::parse(thread).ok()?),
+				};
+
+				Some((thread, private_read))
+			})
+	}
+
 	pub(super) async fn private_read_get_count(
 		&self,
 		room_id: &RoomId,
@@ -116,3 +180,15 @@ pub(super) async fn last_privateread_update(
 			.unwrap_or(0)
 	}
 }
+
+/// The thread of the receipts in a receipt event. Receipt events stored here
+/// contain a single receipt.
+fn receipt_thread(event: &ReceiptEvent) -> Option<&ReceiptThread> {
+	event
+		.content
+		.values()
+		.flat_map(|receipts| receipts.values())
+		.flat_map(|users| users.values())
+		.map(|receipt| &receipt.thread)
+		.next()
+}
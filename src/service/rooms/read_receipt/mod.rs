@@ -3,24 +3,26 @@
 use std::{collections::BTreeMap, sync::Arc};
 
 use conduwuit::{
-	Result, debug, err,
+	Err, Result, debug, err,
 	matrix::{
 		Event,
 		pdu::{PduCount, PduId, RawPduId},
 	},
 	warn,
 };
-use futures::{Stream, TryFutureExt, try_join};
+use futures::{Stream, StreamExt};
 use ruma::{
-	OwnedEventId, OwnedUserId, RoomId, UserId,
+	MilliSecondsSinceUnixEpoch, OwnedEventId, RoomId, UserId,
 	events::{
 		AnySyncEphemeralRoomEvent, SyncEphemeralRoomEvent,
-		receipt::{ReceiptEvent, ReceiptEventContent, Receipts},
+		receipt::{
+			Receipt, ReceiptEvent, ReceiptEventContent, ReceiptThread, ReceiptType, Receipts,
+		},
 	},
 	serde::Raw,
 };
 
-use self::data::{Data, ReceiptItem};
+use self::data::{Data, PrivateRead, ReceiptItem};
 use crate::{Dep, rooms, sending, sync};
 
 pub struct Service {
@@ -68,39 +70,67 @@ pub async fn readreceipt_update(
 		self.services.sync.wake_all_joined(room_id).await;
 	}
 
-	/// Gets the latest private read receipt from the user in the room
+	/// Gets the latest private read receipts from the user in the room, one
+	/// for each thread they have read in.
 	pub async fn private_read_get(
 		&self,
 		room_id: &RoomId,
 		user_id: &UserId,
 	) -> Result<Raw<AnySyncEphemeralRoomEvent>> {
-		let pdu_count = self.private_read_get_count(room_id, user_id).map_err(|e| {
-			err!(Database(warn!("No private read receipt was set in {room_id}: {e}")))
-		});
-		let shortroomid = self.services.short.get_shortroomid(room_id).map_err(|e| {
-			err!(Database(warn!("Short room ID does not exist in database for {room_id}: {e}")))
-		});
-
-		let (pdu_count, shortroomid) = try_join!(pdu_count, shortroomid)?;
-		let shorteventid = PduCount::Normal(pdu_count);
-		let pdu_id: RawPduId = PduId { shortroomid, shorteventid }.into();
-		let pdu = self.services.timeline.get_pdu_from_id(&pdu_id).await?;
-
-		let event_id: OwnedEventId = pdu.event_id().to_owned();
-		let user_id: OwnedUserId = user_id.to_owned();
-
-		let mut receipt = ruma::events::receipt::Receipt::default();
-		// TODO: start storing the timestamp so we can return one
-		receipt.ts = None;
-		receipt.thread = ruma::events::receipt::ReceiptThread::Unthreaded;
-
-		let content: BTreeMap<OwnedEventId, Receipts> = BTreeMap::from_iter([(
-			event_id,
-			BTreeMap::from_iter([(
-				ruma::events::receipt::ReceiptType::ReadPrivate,
-				BTreeMap::from_iter([(user_id, receipt)]),
-			)]),
-		)]);
+		let shortroomid = self
+			.services
+			.short
+			.get_shortroomid(room_id)
+			.await
+			.map_err(|e| {
+				err!(Database(warn!(
+					"Short room ID does not exist in database for {room_id}: {e}"
+				)))
+			})?;
+
+		let mut private_reads: Vec<_> = self
+			.db
+			.private_reads(room_id, user_id)
+			.map(|(thread, PrivateRead { count, ts })| (thread, count, Some(ts)))
+			.collect()
+			.await;
+
+		// Markers set before threads were tracked only exist room-wide.
+		if !private_reads
+			.iter()
+			.any(|(thread, ..)| !matches!(thread, ReceiptThread::Thread(_)))
+		{
+			if let Ok(count) = self.private_read_get_count(room_id, user_id).await {
+				private_reads.push((ReceiptThread::Unthreaded, count, None));
+			}
+		}
+
+		let mut content: BTreeMap<OwnedEventId, Receipts> = BTreeMap::new();
+		for (thread, count, ts) in private_reads {
+			let shorteventid = PduCount::Normal(count);
+			let pdu_id: RawPduId = PduId { shortroomid, shorteventid }.into();
+			let Ok(pdu) = self.services.timeline.get_pdu_from_id(&pdu_id).await else {
+				continue;
+			};
+
+			let mut receipt = Receipt::default();
+			receipt.ts = ts
+				.and_then(|ts| ts.try_into().ok())
+				.map(MilliSecondsSinceUnixEpoch);
+			receipt.thread = thread;
+
+			content
+				.entry(pdu.event_id().to_owned())
+				.or_default()
+				.entry(ReceiptType::ReadPrivate)
+				.or_default()
+				.insert(user_id.to_owned(), receipt);
+		}
+
+		if content.is_empty() {
+			return Err!(Database(warn!("No private read receipt was set in {room_id}")));
+		}
+
 		let receipt_event_content = ReceiptEventContent(content);
 		let receipt_sync_event = SyncEphemeralRoomEvent::new(receipt_event_content);
 
@@ -122,14 +152,21 @@ pub fn readreceipts_since<'a>(
 		self.db.readreceipts_since(room_id, since.unwrap_or(0))
 	}
 
-	/// Sets a private read marker at PDU `count`.
+	/// Sets a private read marker at PDU `count` in the given thread.
 	#[inline]
 	#[tracing::instrument(skip(self), level = "debug")]
-	pub fn private_read_set(&self, room_id: &RoomId, user_id: &UserId, count: u64) {
-		self.db.private_read_set(room_id, user_id, count);
+	pub fn private_read_set(
+		&self,
+		room_id: &RoomId,
+		user_id: &UserId,
+		count: u64,
+		thread: &ReceiptThread,
+	) {
+		self.db.private_read_set(room_id, user_id, count, thread);
 	}
 
-	/// Returns the private read marker PDU count.
+	/// Returns the room-wide private read marker PDU count, which follows the
+	/// unthreaded and main timeline markers.
 	#[inline]
 	#[tracing::instrument(skip(self), level = "debug")]
 	pub async fn private_read_get_count(
@@ -184,10 +221,7 @@ pub fn pack_receipts<I>(receipts: I) -> Raw<SyncEphemeralRoomEvent<ReceiptEventC
 
 #[cfg(test)]
 mod tests {
-	use ruma::{
-		events::receipt::{Receipt, ReceiptType},
-		user_id,
-	};
+	use ruma::{OwnedUserId, user_id};
 
 	use super::*;
 
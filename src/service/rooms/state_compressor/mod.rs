@@ -1,3 +1,5 @@
+mod recompress;
+
 use std::{
 	collections::{BTreeSet, HashMap},
 	fmt::{Debug, Write},
@@ -7,7 +9,7 @@
 
 use async_trait::async_trait;
 use conduwuit::{
-	Result, SyncMutex,
+	Result, Server, SyncMutex,
 	arrayvec::ArrayVec,
 	at, checked, err, expected, utils,
 	utils::{bytes, math::usize_from_f64, stream::IterStream},
@@ -17,6 +19,10 @@
 use lru_cache::LruCache;
 use ruma::{EventId, RoomId};
 
+pub use self::recompress::{
+	DEFAULT_MAX_DEPTH, DEFAULT_MAX_LOAD_FACTOR, Recompressed, StateChain, StateChainStats,
+	StateLayer,
+};
 use crate::{
 	Dep, rooms,
 	rooms::short::{ShortEventId, ShortId, ShortStateHash, ShortStateKey},
@@ -29,6 +35,7 @@ pub struct Service {
 }
 
 struct Services {
+	server: Arc<Server>,
 	short: Dep<rooms::short::Service>,
 	state: Dep<rooms::state::Service>,
 }
@@ -78,6 +85,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				shortstatehash_statediff: args.db["shortstatehash_statediff"].clone(),
 			},
 			services: Services {
+				server: args.server.clone(),
 				short: args.depend::<rooms::short::Service>("rooms::short"),
 				state: args.depend::<rooms::state::Service>("rooms::state"),
 			},
@@ -110,6 +118,15 @@ async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
 		Ok(())
 	}
 
+	async fn worker(self: Arc<Self>) -> Result {
+		if self.services.server.config.state_recompress_on_startup {
+			self.recompress_state(DEFAULT_MAX_DEPTH, DEFAULT_MAX_LOAD_FACTOR)
+				.await?;
+		}
+
+		Ok(())
+	}
+
 	async fn clear_cache(&self) { self.stateinfo_cache.lock().clear(); }
 
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
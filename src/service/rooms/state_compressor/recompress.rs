@@ -0,0 +1,357 @@
+//! State group diagnostics and re-compression
+//!
+//! State groups are stored as diffs against a parent group, down to a snapshot
+//! holding the full state. Loading a state group reads every layer of its
+//! chain, so chains which grew deep or whose diffs outweigh the state itself
+//! are slow to load. Re-compression rewrites such groups into fresh snapshots
+//! under the same shortstatehash, so `shorteventid_shortstatehash` and every
+//! other reference to them stays valid, and so do the diffs of their children,
+//! which only depend on the full state of their parent.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	mem::size_of,
+	sync::Arc,
+};
+
+use conduwuit::{
+	Err, Result, debug, err, info, utils,
+	utils::stream::{ReadyExt, TryIgnore},
+};
+use futures::StreamExt;
+
+use super::{CompressedState, CompressedStateEvent, StateDiff};
+use crate::rooms::short::ShortStateHash;
+
+/// Deepest diff chain built by `save_state_from_diff`; anything deeper
+/// predates the current layering.
+pub const DEFAULT_MAX_DEPTH: usize = 4;
+
+/// Default number of diff entries read to load a state group, relative to the
+/// size of its full state, above which the group is rewritten as a snapshot.
+pub const DEFAULT_MAX_LOAD_FACTOR: usize = 2;
+
+/// A layer of a state group's diff chain.
+#[derive(Clone, Copy, Debug)]
+pub struct StateLayer {
+	pub shortstatehash: ShortStateHash,
+	pub added: usize,
+	pub removed: usize,
+}
+
+/// Shape of the diff chain of a state group.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StateChain {
+	/// Number of layers, counting the group itself and the snapshot at the
+	/// bottom.
+	pub depth: usize,
+
+	/// Number of events in the full state.
+	pub full: usize,
+
+	/// Number of diff entries read to load the full state.
+	pub load: usize,
+}
+
+/// Summary of the diff chains of every state group.
+#[derive(Debug, Default)]
+pub struct StateChainStats {
+	pub groups: usize,
+
+	/// Number of state groups by depth of their diff chain.
+	pub depths: BTreeMap<usize, usize>,
+
+	/// Number of diff entries stored over all state groups.
+	pub entries: usize,
+
+	/// Largest number of diff entries read to load a state group.
+	pub max_load: usize,
+}
+
+/// Result of a re-compression.
+#[derive(Debug, Default)]
+pub struct Recompressed {
+	pub scanned: usize,
+	pub rewritten: usize,
+
+	/// Number of state entries written into the new snapshots.
+	pub entries: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct StateGroup {
+	parent: Option<ShortStateHash>,
+	added: usize,
+	removed: usize,
+}
+
+impl super::Service {
+	/// Gets the layers of a state group's diff chain, from the group itself
+	/// down to the snapshot at the bottom.
+	pub async fn state_layers(&self, shortstatehash: ShortStateHash) -> Result<Vec<StateLayer>> {
+		let mut layers: Vec<StateLayer> = Vec::new();
+		let mut next = Some(shortstatehash);
+		while let Some(shortstatehash) = next {
+			if layers
+				.iter()
+				.any(|layer| layer.shortstatehash == shortstatehash)
+			{
+				return Err!(Database("Diff chain of state group {shortstatehash} has a cycle."));
+			}
+
+			let value = self
+				.db
+				.shortstatehash_statediff
+				.qry(&shortstatehash)
+				.await
+				.map_err(|e| {
+					err!(Database("Failed to find state group {shortstatehash}: {e}"))
+				})?;
+
+			let group = parse_state_group(&value);
+			layers.push(StateLayer {
+				shortstatehash,
+				added: group.added,
+				removed: group.removed,
+			});
+
+			next = group.parent;
+		}
+
+		Ok(layers)
+	}
+
+	/// Scans every state group and summarizes the depth and size of their diff
+	/// chains.
+	pub async fn state_chain_stats(&self) -> StateChainStats {
+		let groups = self.state_groups().await;
+		let chains = state_chains(&groups);
+
+		let mut stats = StateChainStats {
+			groups: groups.len(),
+			entries: groups
+				.values()
+				.map(|group| group.added.saturating_add(group.removed))
+				.sum(),
+			..Default::default()
+		};
+
+		for chain in chains.values() {
+			let count = stats.depths.entry(chain.depth).or_default();
+			*count = count.saturating_add(1);
+			stats.max_load = stats.max_load.max(chain.load);
+		}
+
+		stats
+	}
+
+	/// Rewrites every state group whose diff chain is deeper than `max_depth`,
+	/// or which needs more than `max_load_factor` times the size of its state
+	/// in diff entries to load, into a snapshot of its full state.
+	pub async fn recompress_state(
+		&self,
+		max_depth: usize,
+		max_load_factor: usize,
+	) -> Result<Recompressed> {
+		let groups = self.state_groups().await;
+		let chains = state_chains(&groups);
+
+		// Parents are always shallower than their children, so they are
+		// rewritten first and their children see the shortened chains.
+		let mut order: Vec<(usize, ShortStateHash)> = chains
+			.iter()
+			.map(|(shortstatehash, chain)| (chain.depth, *shortstatehash))
+			.collect();
+
+		order.sort_unstable();
+
+		let mut recompressed = Recompressed {
+			scanned: order.len(),
+			..Default::default()
+		};
+
+		// Chains which got shorter because an ancestor was rewritten.
+		let mut changed: HashMap<ShortStateHash, StateChain> = HashMap::new();
+		for (_, shortstatehash) in order {
+			let Some(group) = groups.get(&shortstatehash).copied() else {
+				continue;
+			};
+
+			let parent = group
+				.parent
+				.and_then(|parent| changed.get(&parent).or_else(|| chains.get(&parent)))
+				.copied()
+				.unwrap_or_default();
+
+			let chain = parent.extend(&group);
+			if chain.depth <= 1
+				|| (chain.depth <= max_depth
+					&& chain.load <= chain.full.saturating_mul(max_load_factor))
+			{
+				if group
+					.parent
+					.is_some_and(|parent| changed.contains_key(&parent))
+				{
+					changed.insert(shortstatehash, chain);
+				}
+
+				continue;
+			}
+
+			let full = self.rewrite_snapshot(shortstatehash).await?;
+			debug!(
+				%shortstatehash,
+				depth = chain.depth,
+				load = chain.load,
+				full,
+				"Rewrote state group as a snapshot"
+			);
+
+			changed.insert(shortstatehash, StateChain { depth: 1, full, load: full });
+			recompressed.rewritten = recompressed.rewritten.saturating_add(1);
+			recompressed.entries = recompressed.entries.saturating_add(full);
+		}
+
+		if recompressed.rewritten > 0 {
+			self.stateinfo_cache.lock().clear();
+		}
+
+		info!(
+			scanned = recompressed.scanned,
+			rewritten = recompressed.rewritten,
+			entries = recompressed.entries,
+			"Finished re-compressing state groups"
+		);
+
+		Ok(recompressed)
+	}
+
+	/// Replaces a state group with a snapshot of its full state. Returns the
+	/// number of events in the snapshot.
+	async fn rewrite_snapshot(&self, shortstatehash: ShortStateHash) -> Result<usize> {
+		let full_state = self
+			.new_shortstatehash_info(shortstatehash)
+			.await?
+			.pop()
+			.map(|info| info.full_state)
+			.ok_or_else(|| err!(Database("State group {shortstatehash} has no layers.")))?;
+
+		let full = full_state.len();
+		self.save_statediff(shortstatehash, &StateDiff {
+			parent: None,
+			added: full_state,
+			removed: Arc::new(CompressedState::new()),
+		});
+
+		Ok(full)
+	}
+
+	async fn state_groups(&self) -> HashMap<ShortStateHash, StateGroup> {
+		self.db
+			.shortstatehash_statediff
+			.raw_stream()
+			.ignore_err()
+			.ready_filter_map(|(key, value)| {
+				let shortstatehash = utils::u64_from_bytes(key).ok()?;
+
+				Some((shortstatehash, parse_state_group(value)))
+			})
+			.collect()
+			.await
+	}
+}
+
+impl StateChain {
+	/// Computes the shape of a diff chain from its layers, as returned by
+	/// [`Service::state_layers`](super::Service::state_layers).
+	#[must_use]
+	pub fn from_layers(layers: &[StateLayer]) -> Self {
+		layers.iter().rev().fold(Self::default(), |chain, layer| {
+			chain.extend(&StateGroup {
+				parent: None,
+				added: layer.added,
+				removed: layer.removed,
+			})
+		})
+	}
+
+	fn extend(self, group: &StateGroup) -> Self {
+		Self {
+			depth: self.depth.saturating_add(1),
+			full: self
+				.full
+				.saturating_add(group.added)
+				.saturating_sub(group.removed),
+			load: self
+				.load
+				.saturating_add(group.added)
+				.saturating_add(group.removed),
+		}
+	}
+}
+
+/// Computes the diff chain of every state group, sharing the work between
+/// groups with common ancestors. Groups whose parent is missing are treated as
+/// snapshots.
+fn state_chains(
+	groups: &HashMap<ShortStateHash, StateGroup>,
+) -> HashMap<ShortStateHash, StateChain> {
+	let mut chains = HashMap::with_capacity(groups.len());
+	for &shortstatehash in groups.keys() {
+		let mut path = Vec::new();
+		let mut base = StateChain::default();
+		let mut next = Some(shortstatehash);
+		while let Some(shortstatehash) = next {
+			if let Some(chain) = chains.get(&shortstatehash) {
+				base = *chain;
+				break;
+			}
+
+			let Some(group) = groups.get(&shortstatehash) else {
+				break;
+			};
+
+			// Guards against cycles from a corrupted database.
+			if path.len() >= groups.len() {
+				break;
+			}
+
+			path.push((shortstatehash, *group));
+			next = group.parent;
+		}
+
+		for (shortstatehash, group) in path.into_iter().rev() {
+			base = base.extend(&group);
+			chains.insert(shortstatehash, base);
+		}
+	}
+
+	chains
+}
+
+/// Reads the parent and the size of a state diff without decoding it.
+fn parse_state_group(value: &[u8]) -> StateGroup {
+	const STRIDE: usize = size_of::<ShortStateHash>();
+	const ENTRY: usize = size_of::<CompressedStateEvent>();
+
+	let parent = value
+		.get(..STRIDE)
+		.and_then(|parent| utils::u64_from_bytes(parent).ok())
+		.filter(|parent| *parent != 0);
+
+	let mut added: usize = 0;
+	let mut i = STRIDE;
+	while let Some(entry) = value.get(i..i.saturating_add(ENTRY)) {
+		// The separator between the added and the removed entries.
+		if entry.starts_with(&0_u64.to_be_bytes()) {
+			break;
+		}
+
+		added = added.saturating_add(1);
+		i = i.saturating_add(ENTRY);
+	}
+
+	let removed = value.len().saturating_sub(i.saturating_add(STRIDE)) / ENTRY;
+
+	StateGroup { parent, added, removed }
+}
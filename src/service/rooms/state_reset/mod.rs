@@ -0,0 +1,351 @@
+//! State reset detection
+//!
+//! A state reset happens when state resolution puts an older event back into
+//! the room state, or drops a key altogether, typically kicking members out of
+//! the room or reverting the power levels. Every time an incoming event
+//! replaces the room state, the keys it changed are checked for memberships
+//! and power levels that went back to an earlier event. Suspected resets are
+//! recorded and announced in the admin room, and can be repaired by resolving
+//! the state again with the state of trusted servers.
+
+mod repair;
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt::Write,
+	sync::Arc,
+};
+
+use conduwuit::{
+	Result, Server, debug_warn,
+	matrix::{Event, PduEvent},
+	utils::{self, stream::TryIgnore},
+	warn,
+};
+use database::{Deserialized, Interfix, Json, Map};
+use futures::{Stream, StreamExt, stream::BoxStream};
+use ruma::{
+	EventId, Int, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId,
+	events::{StateEventType, room::member::MembershipState},
+};
+use serde::{Deserialize, Serialize};
+
+pub use self::repair::{RepairPlan, StateChange};
+use crate::{
+	Dep, admin, globals,
+	rooms::{
+		self,
+		short::ShortStateHash,
+		state_compressor::{CompressedState, parse_compressed_state_event},
+	},
+};
+
+pub struct Service {
+	db: Data,
+	services: Services,
+}
+
+struct Data {
+	roomidcount_statereset: Arc<Map>,
+}
+
+struct Services {
+	server: Arc<Server>,
+	admin: Dep<admin::Service>,
+	event_handler: Dep<rooms::event_handler::Service>,
+	globals: Dep<globals::Service>,
+	short: Dep<rooms::short::Service>,
+	state: Dep<rooms::state::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
+	state_compressor: Dep<rooms::state_compressor::Service>,
+	timeline: Dep<rooms::timeline::Service>,
+}
+
+/// A suspected state reset.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StateReset {
+	/// When the reset happened, in milliseconds since the unix epoch.
+	pub ts: u64,
+
+	/// The incoming event whose state resolution caused the reset.
+	pub event_id: OwnedEventId,
+
+	/// The room state before the reset.
+	pub previous_shortstatehash: Option<ShortStateHash>,
+
+	/// The room state after the reset.
+	pub shortstatehash: ShortStateHash,
+
+	pub regressions: Vec<Regression>,
+
+	/// Whether the room state was repaired since.
+	#[serde(default)]
+	pub repaired: bool,
+}
+
+/// A state key which went back to an earlier event.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Regression {
+	pub event_type: StateEventType,
+	pub state_key: String,
+
+	/// The event which was in the room state before the reset.
+	pub previous: OwnedEventId,
+
+	/// The older event which replaced it, or none if the key was removed.
+	pub current: Option<OwnedEventId>,
+
+	/// What the regression means, e.g. the membership change.
+	pub description: String,
+}
+
+#[derive(Default, Deserialize)]
+struct PowerLevels {
+	#[serde(default)]
+	users: BTreeMap<OwnedUserId, Int>,
+}
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			db: Data {
+				roomidcount_statereset: args.db["roomidcount_statereset"].clone(),
+			},
+			services: Services {
+				server: args.server.clone(),
+				admin: args.depend::<admin::Service>("admin"),
+				event_handler: args
+					.depend::<rooms::event_handler::Service>("rooms::event_handler"),
+				globals: args.depend::<globals::Service>("globals"),
+				short: args.depend::<rooms::short::Service>("rooms::short"),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
+				state_compressor: args
+					.depend::<rooms::state_compressor::Service>("rooms::state_compressor"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
+			},
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+impl Service {
+	/// Checks a change of the room state caused by the state resolution of an
+	/// incoming event for memberships and power levels which went back to an
+	/// earlier event. Suspected resets are recorded and announced in the admin
+	/// room.
+	pub async fn check_state_change(
+		&self,
+		room_id: &RoomId,
+		event_id: &EventId,
+		(previous_shortstatehash, shortstatehash): (Option<ShortStateHash>, ShortStateHash),
+		(added, removed): (&CompressedState, &CompressedState),
+	) {
+		if !self.services.server.config.state_reset_detection || removed.is_empty() {
+			return;
+		}
+
+		let added: HashMap<_, _> = added
+			.iter()
+			.copied()
+			.map(parse_compressed_state_event)
+			.collect();
+
+		let mut regressions = Vec::new();
+		for &removed in removed {
+			let (shortstatekey, shorteventid) = parse_compressed_state_event(removed);
+			let Ok((event_type, state_key)) = self
+				.services
+				.short
+				.get_statekey_from_short(shortstatekey)
+				.await
+			else {
+				continue;
+			};
+
+			if event_type != StateEventType::RoomMember
+				&& event_type != StateEventType::RoomPowerLevels
+			{
+				continue;
+			}
+
+			let Ok(previous) = self.get_pdu_from_short(shorteventid).await else {
+				continue;
+			};
+
+			let current = match added.get(&shortstatekey) {
+				| Some(&current) => match self.get_pdu_from_short(current).await {
+					| Ok(current) => Some(current),
+					| Err(_) => continue,
+				},
+				| None => None,
+			};
+
+			// Legitimate changes replace the previous event with a later one.
+			if current
+				.as_ref()
+				.is_some_and(|current| current.depth >= previous.depth)
+			{
+				continue;
+			}
+
+			let description = describe_regression(&event_type, &previous, current.as_ref());
+			regressions.push(Regression {
+				event_type,
+				state_key: state_key.to_string(),
+				previous: previous.event_id,
+				current: current.map(|current| current.event_id),
+				description,
+			});
+		}
+
+		if regressions.is_empty() {
+			return;
+		}
+
+		let reset = StateReset {
+			ts: utils::millis_since_unix_epoch(),
+			event_id: event_id.to_owned(),
+			previous_shortstatehash,
+			shortstatehash,
+			regressions,
+			repaired: false,
+		};
+
+		warn!(
+			%room_id,
+			%event_id,
+			regressions = reset.regressions.len(),
+			"Suspected state reset"
+		);
+
+		if let Err(e) = self.record_state_reset(room_id, &reset).await {
+			debug_warn!(%room_id, "Failed to record state reset: {e}");
+		}
+	}
+
+	/// Streams the recorded state resets, newest first, optionally only those
+	/// of one room. Items are the room, the count of the reset and the reset.
+	pub fn state_resets<'a>(
+		&'a self,
+		room_id: Option<&'a RoomId>,
+	) -> impl Stream<Item = (OwnedRoomId, u64, StateReset)> + Send + 'a {
+		type KeyVal = ((OwnedRoomId, u64), StateReset);
+
+		let map = &self.db.roomidcount_statereset;
+		let resets: BoxStream<'_, KeyVal> = match room_id {
+			| Some(room_id) => map
+				.rev_stream_prefix(&(room_id, Interfix))
+				.ignore_err()
+				.boxed(),
+			| None => map.rev_stream().ignore_err().boxed(),
+		};
+
+		resets.map(|((room_id, count), reset): KeyVal| (room_id, count, reset))
+	}
+
+	/// Gets a recorded state reset.
+	pub async fn get_state_reset(&self, room_id: &RoomId, count: u64) -> Result<StateReset> {
+		self.db
+			.roomidcount_statereset
+			.qry(&(room_id, count))
+			.await
+			.deserialized()
+	}
+
+	async fn record_state_reset(&self, room_id: &RoomId, reset: &StateReset) -> Result {
+		let count = self.services.globals.next_count()?;
+		self.db
+			.roomidcount_statereset
+			.put((room_id, count), Json(reset));
+
+		if !self.services.server.config.admin_room_notices {
+			return Ok(());
+		}
+
+		let mut msg =
+			format!("Suspected state reset in {room_id} after {}:\n```\n", reset.event_id);
+
+		for regression in &reset.regressions {
+			writeln!(
+				msg,
+				"{} {}: {}",
+				regression.event_type, regression.state_key, regression.description
+			)?;
+		}
+
+		write!(
+			msg,
+			"```\nCompare the room state with trusted servers using `!admin rooms state-reset \
+			 repair {room_id} <servers>`."
+		)?;
+
+		self.services.admin.notice(&msg).await;
+
+		Ok(())
+	}
+
+	async fn get_pdu_from_short(&self, shorteventid: u64) -> Result<PduEvent> {
+		let event_id: OwnedEventId = self
+			.services
+			.short
+			.get_eventid_from_short(shorteventid)
+			.await?;
+
+		self.services.timeline.get_pdu(&event_id).await
+	}
+}
+
+fn describe_regression(
+	event_type: &StateEventType,
+	previous: &PduEvent,
+	current: Option<&PduEvent>,
+) -> String {
+	match event_type {
+		| StateEventType::RoomMember => {
+			let membership = |pdu: Option<&PduEvent>| {
+				pdu.and_then(|pdu| pdu.get_content::<MembershipContent>().ok())
+					.map_or_else(|| "none".to_owned(), |content| content.membership.to_string())
+			};
+
+			format!(
+				"membership went back from {} to {}",
+				membership(Some(previous)),
+				membership(current)
+			)
+		},
+		| _ => {
+			let users = |pdu: Option<&PduEvent>| {
+				pdu.and_then(|pdu| pdu.get_content::<PowerLevels>().ok())
+					.unwrap_or_default()
+					.users
+			};
+
+			let current_users = users(current);
+			let demoted: Vec<String> = users(Some(previous))
+				.into_iter()
+				.filter_map(|(user_id, level)| {
+					let current_level = current_users.get(&user_id).copied().unwrap_or_default();
+					(current_level < level)
+						.then(|| format!("{user_id} {level} -> {current_level}"))
+				})
+				.collect();
+
+			if demoted.is_empty() {
+				"power levels went back to an earlier event".to_owned()
+			} else {
+				format!(
+					"power levels went back to an earlier event, demoting {}",
+					demoted.join(", ")
+				)
+			}
+		},
+	}
+}
+
+#[derive(Deserialize)]
+struct MembershipContent {
+	membership: MembershipState,
+}
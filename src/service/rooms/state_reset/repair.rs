@@ -0,0 +1,290 @@
+use std::{
+	collections::{BTreeSet, HashMap},
+	sync::Arc,
+};
+
+use conduwuit::{
+	Err, Result,
+	matrix::{Event, PduEvent, StateMap},
+	utils::stream::{IterStream, ReadyExt},
+};
+use database::Json;
+use futures::StreamExt;
+use ruma::{OwnedEventId, OwnedServerName, RoomId, events::StateEventType};
+
+use super::MembershipContent;
+use crate::rooms::{
+	short::{ShortStateHash, ShortStateKey},
+	state_compressor::{CompressedState, HashSetCompressStateEvent},
+};
+
+/// A change a repair would make to the room state.
+#[derive(Clone, Debug)]
+pub struct StateChange {
+	pub event_type: StateEventType,
+	pub state_key: String,
+
+	/// The event currently in the room state, if any.
+	pub before: Option<OwnedEventId>,
+
+	/// The event in the repaired room state, if any.
+	pub after: Option<OwnedEventId>,
+
+	/// The membership change, for membership events.
+	pub summary: Option<String>,
+}
+
+/// The room state resolved again together with the state of trusted servers.
+pub struct RepairPlan {
+	/// The servers whose state took part in the resolution.
+	pub servers: Vec<OwnedServerName>,
+
+	/// The servers which couldn't provide their state, with the error.
+	pub failed: Vec<(OwnedServerName, String)>,
+
+	pub changes: Vec<StateChange>,
+
+	/// The room state the plan was made against.
+	base: ShortStateHash,
+
+	state: Arc<CompressedState>,
+}
+
+impl super::Service {
+	/// Resolves the room state again together with the state the given servers
+	/// have at the latest event of the room, without applying it. The local
+	/// room state takes part in the resolution unless `exclude_local` is set.
+	pub async fn plan_repair(
+		&self,
+		room_id: &RoomId,
+		servers: &[OwnedServerName],
+		exclude_local: bool,
+	) -> Result<RepairPlan> {
+		let room_version = self.services.state.get_room_version(room_id).await?;
+		let Some(room_version_rules) = room_version.rules() else {
+			return Err!(Request(UnsupportedRoomVersion(
+				"Room version {room_version} has no defined rules."
+			)));
+		};
+
+		let create_event = self
+			.services
+			.state_accessor
+			.room_state_get(room_id, &StateEventType::RoomCreate, "")
+			.await?;
+
+		let latest: PduEvent = self
+			.services
+			.timeline
+			.latest_pdu_in_room(room_id)
+			.await?
+			.into_pdu();
+
+		let current_shortstatehash = self.services.state.get_room_shortstatehash(room_id).await?;
+		let current: HashMap<ShortStateKey, OwnedEventId> = self
+			.services
+			.state_accessor
+			.state_full_ids(current_shortstatehash)
+			.collect()
+			.await;
+
+		let mut state_sets = Vec::with_capacity(servers.len().saturating_add(1));
+		if !exclude_local {
+			state_sets.push(self.state_map(&current).await);
+		}
+
+		let mut used = Vec::new();
+		let mut failed = Vec::new();
+		for server in servers {
+			if self.services.globals.server_is_ours(server) {
+				continue;
+			}
+
+			let mut state = match self
+				.services
+				.event_handler
+				.fetch_state(server, &create_event, room_id, &latest.event_id)
+				.await
+			{
+				| Ok(state) => state,
+				| Err(e) => {
+					failed.push((server.clone(), e.to_string()));
+					continue;
+				},
+			};
+
+			// `/state_ids` returns the state before the event.
+			if let Some(state_key) = latest.state_key() {
+				let shortstatekey = self
+					.services
+					.short
+					.get_or_create_shortstatekey(&latest.kind().to_string().into(), state_key)
+					.await;
+
+				state.insert(shortstatekey, latest.event_id.clone());
+			}
+
+			state_sets.push(self.state_map(&state).await);
+			used.push(server.clone());
+		}
+
+		if used.is_empty() {
+			return Err!("None of the servers could provide the room state: {failed:?}");
+		}
+
+		let resolved = self
+			.services
+			.event_handler
+			.state_resolution(room_id, &room_version_rules, &state_sets)
+			.await?;
+
+		let resolved: HashMap<ShortStateKey, OwnedEventId> = resolved
+			.into_iter()
+			.stream()
+			.then(|((event_type, state_key), event_id)| async move {
+				let shortstatekey = self
+					.services
+					.short
+					.get_or_create_shortstatekey(&event_type, &state_key)
+					.await;
+
+				(shortstatekey, event_id)
+			})
+			.collect()
+			.await;
+
+		let changes = self.state_changes(&current, &resolved).await;
+		let state = self
+			.services
+			.state_compressor
+			.compress_state_events(resolved.iter().map(|(ssk, eid)| (ssk, eid.as_ref())))
+			.collect()
+			.await;
+
+		Ok(RepairPlan {
+			servers: used,
+			failed,
+			changes,
+			base: current_shortstatehash,
+			state: Arc::new(state),
+		})
+	}
+
+	/// Replaces the room state with the state of a repair plan and marks the
+	/// recorded state resets of the room as repaired. Fails if the room state
+	/// changed since the plan was made, as the plan would undo that change.
+	pub async fn apply_repair(&self, room_id: &RoomId, plan: RepairPlan) -> Result {
+		let state_lock = self.services.state.mutex.lock(room_id).await;
+		if self.services.state.get_room_shortstatehash(room_id).await? != plan.base {
+			return Err!(Request(Unknown(
+				"The room state changed since the repair was planned; plan it again."
+			)));
+		}
+
+		let HashSetCompressStateEvent { shortstatehash, added, removed } = self
+			.services
+			.state_compressor
+			.save_state(room_id, plan.state)
+			.await?;
+
+		self.services
+			.state
+			.force_state(room_id, shortstatehash, added, removed, &state_lock)
+			.await?;
+
+		drop(state_lock);
+		self.mark_repaired(room_id).await;
+
+		Ok(())
+	}
+
+	async fn mark_repaired(&self, room_id: &RoomId) {
+		let resets: Vec<_> = self
+			.state_resets(Some(room_id))
+			.ready_filter(|(_, _, reset)| !reset.repaired)
+			.collect()
+			.await;
+
+		for (room_id, count, mut reset) in resets {
+			reset.repaired = true;
+			self.db
+				.roomidcount_statereset
+				.put((&room_id, count), Json(reset));
+		}
+	}
+
+	async fn state_map(
+		&self,
+		state: &HashMap<ShortStateKey, OwnedEventId>,
+	) -> StateMap<OwnedEventId> {
+		let shortstatekeys = state.keys().copied().stream();
+		let event_ids = state.values().cloned().stream();
+
+		self.services
+			.short
+			.multi_get_statekey_from_short(shortstatekeys)
+			.zip(event_ids)
+			.ready_filter_map(|(ty_sk, id)| Some((ty_sk.ok()?, id)))
+			.collect()
+			.await
+	}
+
+	async fn state_changes(
+		&self,
+		before: &HashMap<ShortStateKey, OwnedEventId>,
+		after: &HashMap<ShortStateKey, OwnedEventId>,
+	) -> Vec<StateChange> {
+		let keys: BTreeSet<ShortStateKey> = before.keys().chain(after.keys()).copied().collect();
+
+		let mut changes = Vec::new();
+		for shortstatekey in keys {
+			let before = before.get(&shortstatekey);
+			let after = after.get(&shortstatekey);
+			if before == after {
+				continue;
+			}
+
+			let Ok((event_type, state_key)) = self
+				.services
+				.short
+				.get_statekey_from_short(shortstatekey)
+				.await
+			else {
+				continue;
+			};
+
+			let summary = if event_type == StateEventType::RoomMember {
+				Some(format!(
+					"{} -> {}",
+					self.membership(before).await,
+					self.membership(after).await
+				))
+			} else {
+				None
+			};
+
+			changes.push(StateChange {
+				event_type,
+				state_key: state_key.to_string(),
+				before: before.cloned(),
+				after: after.cloned(),
+				summary,
+			});
+		}
+
+		changes
+	}
+
+	async fn membership(&self, event_id: Option<&OwnedEventId>) -> String {
+		let Some(event_id) = event_id else {
+			return "none".to_owned();
+		};
+
+		self.services
+			.timeline
+			.get_pdu(event_id)
+			.await
+			.and_then(|pdu| pdu.get_content::<MembershipContent>())
+			.map_or_else(|_| "unknown".to_owned(), |content| content.membership.to_string())
+	}
+}
@@ -1,3 +1,5 @@
+mod subscriptions;
+
 use std::{collections::BTreeMap, sync::Arc};
 
 use conduwuit_core::{
@@ -17,7 +19,8 @@
 };
 use serde_json::json;
 
-use crate::{Dep, rooms, rooms::short::ShortRoomId};
+pub use self::subscriptions::{ThreadSubscription, event_thread_root};
+use crate::{Dep, globals, rooms, rooms::short::ShortRoomId};
 
 pub struct Service {
 	db: Data,
@@ -25,12 +28,14 @@ pub struct Service {
 }
 
 struct Services {
+	globals: Dep<globals::Service>,
 	short: Dep<rooms::short::Service>,
 	timeline: Dep<rooms::timeline::Service>,
 }
 
 pub(super) struct Data {
 	threadid_userids: Arc<Map>,
+	userroomthreadid_subscription: Arc<Map>,
 }
 
 impl crate::Service for Service {
@@ -38,8 +43,10 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			db: Data {
 				threadid_userids: args.db["threadid_userids"].clone(),
+				userroomthreadid_subscription: args.db["userroomthreadid_subscription"].clone(),
 			},
 			services: Services {
+				globals: args.depend::<globals::Service>("globals"),
 				short: args.depend::<rooms::short::Service>("rooms::short"),
 				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
 			},
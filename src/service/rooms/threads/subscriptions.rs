@@ -0,0 +1,194 @@
+//! Thread subscriptions (MSC4306)
+//!
+//! A user is either subscribed to a thread, manually or automatically, or has
+//! unsubscribed from it. Unsubscriptions are kept so that an automatic
+//! subscription caused by an event the user had already seen when they
+//! unsubscribed is refused instead of silently undoing their choice.
+
+use conduwuit_core::{Err, Event, Result, err, matrix::pdu::PduCount};
+use conduwuit_database::{Deserialized, Json};
+use ruma::{EventId, OwnedEventId, RoomId, UserId, events::room::encrypted::Relation};
+use serde::{Deserialize, Serialize};
+
+use crate::rooms::timeline::ExtractRelatesTo;
+
+/// A user's subscription state for one thread.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ThreadSubscription {
+	/// Whether the user is subscribed to the thread; false once they have
+	/// unsubscribed from it.
+	pub subscribed: bool,
+
+	/// Whether the subscription was made automatically, e.g. because the user
+	/// took part in or was mentioned in the thread.
+	pub automatic: bool,
+
+	/// Stream position at which the subscription last changed.
+	pub count: u64,
+}
+
+/// Gets the root of the thread an event belongs to, if any.
+#[must_use]
+pub fn event_thread_root<E>(event: &E) -> Option<OwnedEventId>
+where
+	E: Event,
+{
+	match event.get_content::<ExtractRelatesTo>() {
+		| Ok(ExtractRelatesTo { relates_to: Relation::Thread(thread) }) => Some(thread.event_id),
+		| _ => None,
+	}
+}
+
+impl super::Service {
+	/// Gets the user's subscription state for a thread, including an
+	/// unsubscription.
+	pub async fn get_thread_subscription(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread_root: &EventId,
+	) -> Result<ThreadSubscription> {
+		let key = (user_id, room_id, thread_root);
+		self.db
+			.userroomthreadid_subscription
+			.qry(&key)
+			.await
+			.deserialized()
+	}
+
+	/// Whether the user is currently subscribed to a thread.
+	pub async fn is_subscribed(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread_root: &EventId,
+	) -> bool {
+		self.get_thread_subscription(user_id, room_id, thread_root)
+			.await
+			.is_ok_and(|subscription| subscription.subscribed)
+	}
+
+	/// Subscribes the user to a thread manually, replacing any previous
+	/// subscription or unsubscription.
+	pub async fn subscribe_thread(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread_root: &EventId,
+	) -> Result {
+		self.check_thread_root(room_id, thread_root).await?;
+		self.set_thread_subscription(user_id, room_id, thread_root, true, false)
+	}
+
+	/// Subscribes the user to a thread on behalf of an event in it.
+	///
+	/// Existing subscriptions are left untouched. If the user unsubscribed from
+	/// the thread after the event was sent, the subscription is refused with
+	/// `M_CONFLICTING_UNSUBSCRIPTION`.
+	pub async fn auto_subscribe_thread(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread_root: &EventId,
+		cause: PduCount,
+	) -> Result {
+		match self
+			.get_thread_subscription(user_id, room_id, thread_root)
+			.await
+		{
+			| Ok(subscription) if subscription.subscribed => return Ok(()),
+			| Ok(subscription) if cause <= PduCount::Normal(subscription.count) =>
+				return Err!(Request(ConflictingUnsubscription(
+					"The user unsubscribed from this thread after the event was sent."
+				))),
+			| _ => {},
+		}
+
+		self.set_thread_subscription(user_id, room_id, thread_root, true, true)
+	}
+
+	/// Subscribes the user to a thread on behalf of the given event, which must
+	/// be part of the thread.
+	pub async fn auto_subscribe_thread_by_event(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread_root: &EventId,
+		cause: &EventId,
+	) -> Result {
+		self.check_thread_root(room_id, thread_root).await?;
+
+		let pdu = self
+			.services
+			.timeline
+			.get_pdu(cause)
+			.await
+			.map_err(|_| err!(Request(NotFound("Event not found."))))?;
+
+		let in_thread = pdu.event_id() == thread_root
+			|| event_thread_root(&pdu).as_deref() == Some(thread_root);
+
+		if pdu.room_id() != Some(room_id) || !in_thread {
+			return Err!(Request(NotInThread("The event is not part of this thread.")));
+		}
+
+		let count = self.services.timeline.get_pdu_count(cause).await?;
+		self.auto_subscribe_thread(user_id, room_id, thread_root, count)
+			.await
+	}
+
+	/// Unsubscribes the user from a thread they are subscribed to.
+	pub async fn unsubscribe_thread(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread_root: &EventId,
+	) -> Result {
+		if !self.is_subscribed(user_id, room_id, thread_root).await {
+			return Err!(Request(NotFound("Not subscribed to this thread.")));
+		}
+
+		self.set_thread_subscription(user_id, room_id, thread_root, false, false)
+	}
+
+	fn set_thread_subscription(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread_root: &EventId,
+		subscribed: bool,
+		automatic: bool,
+	) -> Result {
+		let subscription = ThreadSubscription {
+			subscribed,
+			automatic,
+			count: self.services.globals.next_count()?,
+		};
+
+		let key = (user_id, room_id, thread_root);
+		self.db
+			.userroomthreadid_subscription
+			.put(key, Json(subscription));
+
+		Ok(())
+	}
+
+	async fn check_thread_root(&self, room_id: &RoomId, thread_root: &EventId) -> Result {
+		let root = self
+			.services
+			.timeline
+			.get_pdu(thread_root)
+			.await
+			.map_err(|_| err!(Request(NotFound("Thread root not found."))))?;
+
+		if root.room_id() != Some(room_id) {
+			return Err!(Request(NotFound("Thread root not found.")));
+		}
+
+		if event_thread_root(&root).is_some() {
+			return Err!(Request(InvalidParam("Events in a thread cannot be thread roots.")));
+		}
+
+		Ok(())
+	}
+}
@@ -30,6 +30,7 @@
 	events::{
 		AnySyncTimelineEvent, GlobalAccountDataEventType, TimelineEventType,
 		push_rules::PushRulesEvent,
+		receipt::ReceiptThread,
 		room::{
 			encrypted::Relation,
 			member::{MembershipState, RoomMemberEventContent},
@@ -44,7 +45,11 @@
 use super::{ExtractBody, ExtractRelatesTo, ExtractRelatesToEventId, RoomMutexGuard};
 use crate::{
 	appservice::RegistrationInfo,
-	rooms::state_compressor::{CompressedState, HashSetCompressStateEvent},
+	rooms::{
+		state_compressor::{CompressedState, HashSetCompressStateEvent},
+		threads::event_thread_root,
+		user::Notification,
+	},
 };
 
 impl super::Service {
@@ -137,6 +142,8 @@ pub async fn append_incoming_pdu<'a>(
 			}
 		}
 
+		self.update_directory_index(pdu, &room_id).await;
+
 		self.services.sync.wake_all_joined(&room_id).await;
 
 		if *pdu.kind() == TimelineEventType::RoomMember {
@@ -258,6 +265,13 @@ async fn derive_new_state(
 			.await?;
 
 		debug!("Forcing new room state");
+		let previous_shortstatehash = self
+			.services
+			.state
+			.get_room_shortstatehash(&room_id)
+			.await
+			.ok();
+
 		let HashSetCompressStateEvent { shortstatehash, added, removed } = self
 			.services
 			.state_compressor
@@ -266,8 +280,20 @@ async fn derive_new_state(
 
 		self.services
 			.state
-			.force_state(&room_id, shortstatehash, added, removed, state_lock)
-			.await
+			.force_state(&room_id, shortstatehash, added.clone(), removed.clone(), state_lock)
+			.await?;
+
+		self.services
+			.state_reset
+			.check_state_change(
+				&room_id,
+				event_id,
+				(previous_shortstatehash, shortstatehash),
+				(&added, &removed),
+			)
+			.await;
+
+		Ok(())
 	}
 
 	/// Populates the unsigned data of a PDU
@@ -376,6 +402,14 @@ pub async fn append_pdu<'a, Leaves>(
 
 		drop(insert_lock);
 
+		if pdu.state_key().is_some() {
+			self.services
+				.auth_chain
+				.queue_indexing(room_id, pdu.event_id());
+		}
+
+		self.update_timeline_gaps(pdu).await;
+
 		// See if the event matches any known pushers via power level
 		if *pdu.kind() != TimelineEventType::RoomCreate {
 			tokio::join!(
@@ -392,13 +426,40 @@ pub async fn append_pdu<'a, Leaves>(
 			);
 		}
 
+		// Sending an event in a thread only marks that thread as read.
+		let thread = match pdu.get_content::<ExtractRelatesTo>() {
+			| Ok(ExtractRelatesTo { relates_to: Relation::Thread(thread) }) =>
+				ReceiptThread::Thread(thread.event_id),
+			| _ => ReceiptThread::Unthreaded,
+		};
+
 		self.services
 			.read_receipt
-			.private_read_set(room_id, pdu.sender(), count1);
+			.private_read_set(room_id, pdu.sender(), count1, &thread);
 
-		self.services
-			.user
-			.reset_notification_counts(pdu.sender(), room_id);
+		match &thread {
+			| ReceiptThread::Thread(thread) => {
+				self.services
+					.user
+					.reset_thread_notification_counts(pdu.sender(), room_id, thread)
+					.await;
+
+				// Taking part in a thread subscribes local users to it.
+				if self.services.globals.user_is_local(pdu.sender()) {
+					self.services
+						.threads
+						.auto_subscribe_thread(pdu.sender(), room_id, thread, count2)
+						.await
+						.ok();
+				}
+			},
+			| _ => {
+				self.services
+					.user
+					.reset_notification_counts(pdu.sender(), room_id)
+					.await;
+			},
+		}
 
 		self.send_to_interested_appservices(pdu, &pdu_id, room_id)
 			.await;
@@ -451,6 +512,7 @@ async fn notify_local_users(&self, pdu: &PduEvent, pdu_id: &RawPduId, room_id: &
 			return;
 		}
 
+		let thread_root = event_thread_root(pdu);
 		let serialized: Raw<AnySyncTimelineEvent> = pdu.to_format();
 		let room_joined_count = self.services.pusher.push_joined_count(room_id).await;
 
@@ -471,10 +533,17 @@ async fn notify_local_users(&self, pdu: &PduEvent, pdu_id: &RawPduId, room_id: &
 			let ctx = self
 				.services
 				.pusher
-				.push_condition_ctx(user, power_levels.clone(), room_id, room_joined_count)
+				.push_condition_ctx(
+					user,
+					power_levels.clone(),
+					room_id,
+					room_joined_count,
+					thread_root.as_deref(),
+				)
 				.await;
 
-			for action in rules_for_user.get_actions(&serialized, &ctx).await {
+			let actions = rules_for_user.get_actions(&serialized, &ctx).await;
+			for action in actions {
 				match action {
 					| Action::Notify => notify = true,
 					| Action::SetTweak(Tweak::Highlight(
@@ -493,10 +562,31 @@ async fn notify_local_users(&self, pdu: &PduEvent, pdu_id: &RawPduId, room_id: &
 
 			if notify {
 				notifies.push(user.clone());
+				self.services.user.add_notification(
+					user,
+					pdu_id.pdu_count().into_unsigned(),
+					&Notification {
+						room_id: room_id.to_owned(),
+						event_id: pdu.event_id().to_owned(),
+						actions: actions.to_vec(),
+						highlight,
+						ts: utils::millis_since_unix_epoch(),
+					},
+				);
 			}
 
 			if highlight {
 				highlights.push(user.clone());
+
+				// Being mentioned in a thread subscribes the user to it, unless they
+				// have unsubscribed since.
+				if let Some(thread_root) = &thread_root {
+					self.services
+						.threads
+						.auto_subscribe_thread(user, room_id, thread_root, pdu_id.pdu_count())
+						.await
+						.ok();
+				}
 			}
 
 			self.services
@@ -511,8 +601,14 @@ async fn notify_local_users(&self, pdu: &PduEvent, pdu_id: &RawPduId, room_id: &
 				.await;
 		}
 
-		self.db
-			.increment_notification_counts(room_id, notifies, highlights);
+		// The counts are reset under the same lock when the user reads the room.
+		let _insert_lock = self.mutex_insert.lock(room_id).await;
+		self.db.increment_notification_counts(
+			room_id,
+			thread_root.as_deref(),
+			notifies,
+			highlights,
+		);
 	}
 
 	/// Handles PDU effects based on the type of incoming event.
@@ -595,6 +691,31 @@ async fn handle_pdu_effects(
 		Ok(())
 	}
 
+	/// Refreshes the room directory search index when an event changes the
+	/// name, topic or canonical alias of a public room. Must be called after
+	/// the room state has been updated.
+	pub(super) async fn update_directory_index(&self, pdu: &PduEvent, room_id: &RoomId) {
+		if pdu
+			.state_key()
+			.is_none_or(|state_key| !state_key.is_empty())
+		{
+			return;
+		}
+
+		if !matches!(
+			*pdu.kind(),
+			TimelineEventType::RoomName
+				| TimelineEventType::RoomTopic
+				| TimelineEventType::RoomCanonicalAlias
+		) {
+			return;
+		}
+
+		if self.services.directory.is_public_room(room_id).await {
+			self.services.directory.index_room(room_id).await;
+		}
+	}
+
 	/// The server of `target_user_id`, if this membership event brings it into
 	/// the room for the first time.
 	async fn joining_server(
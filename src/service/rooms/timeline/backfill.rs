@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
 use conduwuit::{Err, PduEvent};
 use conduwuit_core::{
@@ -12,16 +12,28 @@
 use futures::FutureExt;
 use ruma::{
 	CanonicalJsonObject, EventId, OwnedServerName, RoomId, ServerName, api::federation,
-	events::TimelineEventType, uint,
+	events::TimelineEventType,
 };
 use serde_json::value::RawValue as RawJsonValue;
 
 use super::ExtractBody;
 
+/// Maximum number of events requested from a server in a single backfill.
+const BACKFILL_LIMIT: u16 = 100;
+
+/// Maximum number of missing events to start a single backfill from.
+const BACKFILL_MAX_EVENT_IDS: usize = 20;
+
 impl super::Service {
-	/// Performs backfill, if it is required.
+	/// Performs backfill, if the next `limit` events before `from` run into a
+	/// gap in the timeline.
 	#[tracing::instrument(name = "backfill", level = "trace", skip(self))]
-	pub async fn backfill_if_required(&self, room_id: &RoomId, from: PduCount) -> Result<()> {
+	pub async fn backfill_if_required(
+		&self,
+		room_id: &RoomId,
+		from: PduCount,
+		limit: usize,
+	) -> Result<()> {
 		if self
 			.services
 			.state_cache
@@ -39,17 +51,31 @@ pub async fn backfill_if_required(&self, room_id: &RoomId, from: PduCount) -> Re
 			return Ok(());
 		}
 
-		let first_pdu = self
-			.first_item_in_room(room_id)
-			.await
-			.expect("Room is not empty");
+		let mut missing = BTreeSet::new();
+		for (event_id, gap) in self.gaps_before(room_id, from, limit).await {
+			if missing.len() >= BACKFILL_MAX_EVENT_IDS {
+				break;
+			}
 
-		if first_pdu.0 < from {
-			// No backfill required, there are still events between them
-			debug!("No backfill required in room {room_id}, {:?} < {from}", first_pdu.0);
+			missing.extend(gap.iter().cloned());
+			self.record_backfill_attempt(&event_id, &gap).await;
+		}
+
+		let missing: Vec<_> = missing.into_iter().take(BACKFILL_MAX_EVENT_IDS).collect();
+
+		if missing.is_empty() {
+			// No backfill required, the history before `from` is complete
+			debug!("No backfill required in room {room_id} before {from}");
 			return Ok(());
 		}
 
+		let max_events = self
+			.services
+			.server
+			.config
+			.max_fetch_prev_events
+			.min(BACKFILL_LIMIT);
+
 		let servers = self.candidate_backfill_servers(room_id).await;
 
 		let mut federated_room = false;
@@ -66,14 +92,14 @@ pub async fn backfill_if_required(&self, room_id: &RoomId, from: PduCount) -> Re
 					&backfill_server,
 					federation::backfill::get_backfill::v1::Request::new(
 						room_id.to_owned(),
-						vec![first_pdu.1.event_id().to_owned()],
-						uint!(100),
+						missing.clone(),
+						max_events.into(),
 					),
 				)
 				.await;
 			match response {
 				| Ok(response) => {
-					for pdu in response.pdus {
+					for pdu in response.pdus.into_iter().take(max_events.into()) {
 						if let Err(e) = self.backfill_pdu(&backfill_server, pdu).boxed().await {
 							debug_warn!("Failed to add backfilled pdu in room {room_id}: {e}");
 						}
@@ -213,6 +239,8 @@ pub async fn backfill_pdu(&self, origin: &ServerName, pdu: Box<RawJsonValue>) ->
 
 		drop(insert_lock);
 
+		self.update_timeline_gaps(&pdu).await;
+
 		// Backfilled events skip append_pdu, so this is the only place their
 		// prev_events can be marked as referenced. See issue #2115.
 		self.services
@@ -166,6 +166,8 @@ pub async fn build_and_append_pdu(
 			.state
 			.set_room_state(&room_id, statehashid, state_lock);
 
+		self.update_directory_index(&pdu, &room_id).await;
+
 		self.services.sync.wake_all_joined(&room_id).await;
 
 		if *pdu.kind() == TimelineEventType::RoomMember {
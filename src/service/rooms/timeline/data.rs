@@ -15,9 +15,13 @@
 pub(super) struct Data {
 	eventid_outlierpdu: Arc<Map>,
 	eventid_pduid: Arc<Map>,
+	pub(super) eventid_timelinegap: Arc<Map>,
 	pduid_pdu: Arc<Map>,
+	pub(super) preveventid_timelinegap: Arc<Map>,
 	userroomid_highlightcount: Arc<Map>,
 	userroomid_notificationcount: Arc<Map>,
+	userroomthreadid_highlightcount: Arc<Map>,
+	userroomthreadid_notificationcount: Arc<Map>,
 	pub(super) db: Arc<Database>,
 	services: Services,
 }
@@ -34,9 +38,13 @@ pub(super) fn new(args: &crate::Args<'_>) -> Self {
 		Self {
 			eventid_outlierpdu: db["eventid_outlierpdu"].clone(),
 			eventid_pduid: db["eventid_pduid"].clone(),
+			eventid_timelinegap: db["eventid_timelinegap"].clone(),
 			pduid_pdu: db["pduid_pdu"].clone(),
+			preveventid_timelinegap: db["preveventid_timelinegap"].clone(),
 			userroomid_highlightcount: db["userroomid_highlightcount"].clone(),
 			userroomid_notificationcount: db["userroomid_notificationcount"].clone(),
+			userroomthreadid_highlightcount: db["userroomthreadid_highlightcount"].clone(),
+			userroomthreadid_notificationcount: db["userroomthreadid_notificationcount"].clone(),
 			db: args.db.clone(),
 			services: Services {
 				short: args.depend::<rooms::short::Service>("rooms::short"),
@@ -251,9 +259,12 @@ fn from_json_slice((pdu_id, pdu): KeyVal<'_>) -> Result<PdusIterItem> {
 		Ok((pdu_id.pdu_count(), pdu))
 	}
 
+	/// Increments the notification counts of the users in the room. Events in
+	/// a thread also count towards the users' counts for that thread.
 	pub(super) fn increment_notification_counts(
 		&self,
 		room_id: &RoomId,
+		thread: Option<&EventId>,
 		notifies: Vec<OwnedUserId>,
 		highlights: Vec<OwnedUserId>,
 	) {
@@ -264,6 +275,12 @@ pub(super) fn increment_notification_counts(
 			userroom_id.push(0xFF);
 			userroom_id.extend_from_slice(room_id.as_bytes());
 			increment(&self.userroomid_notificationcount, &userroom_id);
+
+			if let Some(thread) = thread {
+				userroom_id.push(0xFF);
+				userroom_id.extend_from_slice(thread.as_bytes());
+				increment(&self.userroomthreadid_notificationcount, &userroom_id);
+			}
 		}
 
 		for user in highlights {
@@ -271,6 +288,12 @@ pub(super) fn increment_notification_counts(
 			userroom_id.push(0xFF);
 			userroom_id.extend_from_slice(room_id.as_bytes());
 			increment(&self.userroomid_highlightcount, &userroom_id);
+
+			if let Some(thread) = thread {
+				userroom_id.push(0xFF);
+				userroom_id.extend_from_slice(thread.as_bytes());
+				increment(&self.userroomthreadid_highlightcount, &userroom_id);
+			}
 		}
 	}
 
@@ -0,0 +1,225 @@
+//! Timeline gaps
+//!
+//! A timeline event whose prev events are not in our timeline sits right after
+//! a gap: the history before it is incomplete. This happens at the join event
+//! of rooms joined after they were created, after gaps too large to fill while
+//! handling incoming events, and at the oldest backfilled events. Gaps are
+//! recorded with the prev events they are missing and closed as those events
+//! reach the timeline, so that pagination can backfill exactly the missing
+//! events. A gap is given up on after a few backfill attempts, and prev events
+//! we hold as rejected or soft-failed outliers are never asked for.
+
+use conduwuit::{
+	Result,
+	matrix::{
+		event::Event,
+		pdu::{PduCount, PduEvent},
+	},
+	utils::stream::{IterStream, TryIgnore},
+};
+use database::{Deserialized, Ignore, Interfix, Json};
+use futures::StreamExt;
+use ruma::{EventId, OwnedEventId, RoomId};
+use serde::{Deserialize, Serialize};
+
+/// Number of times the missing events of a gap are asked for before the gap is
+/// left as it is.
+const MAX_BACKFILL_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct TimelineGap {
+	missing: Vec<OwnedEventId>,
+
+	#[serde(default)]
+	attempts: u32,
+}
+
+impl super::Service {
+	/// Gets the prev events of a timeline event which aren't in the timeline.
+	/// Errors if there is no gap before the event.
+	pub async fn timeline_gap(&self, event_id: &EventId) -> Result<Vec<OwnedEventId>> {
+		self.db
+			.eventid_timelinegap
+			.get(event_id)
+			.await
+			.deserialized()
+			.map(|gap: TimelineGap| gap.missing)
+	}
+
+	/// Collects the gaps the next `limit` events before `from` run into,
+	/// nearest first, with the missing prev events which are still worth
+	/// backfilling. Includes the gap at the start of the room once it is
+	/// reached.
+	pub async fn gaps_before(
+		&self,
+		room_id: &RoomId,
+		from: PduCount,
+		limit: usize,
+	) -> Vec<(OwnedEventId, Vec<OwnedEventId>)> {
+		let window: Vec<_> = self
+			.pdus_rev(room_id, Some(from))
+			.ignore_err()
+			.take(limit)
+			.collect()
+			.await;
+
+		let mut gaps = Vec::new();
+		for (_, pdu) in &window {
+			if let Some(missing) = self.fillable_gap(pdu.event_id()).await {
+				gaps.push((pdu.event_id().to_owned(), missing));
+			}
+		}
+
+		if window.len() < limit {
+			// Rooms joined before gaps were tracked have no record of the gap
+			// at their start.
+			let first = match window.last() {
+				| Some((_, pdu)) => Some(pdu.clone()),
+				| None => self
+					.first_item_in_room(room_id)
+					.await
+					.ok()
+					.map(|(_, pdu)| pdu),
+			};
+
+			if let Some(first) = first
+				&& self.timeline_gap(first.event_id()).await.is_err()
+			{
+				let missing = self.missing_prev_events(&first).await;
+				if !missing.is_empty() {
+					gaps.push((first.event_id().to_owned(), missing));
+				}
+			}
+		}
+
+		gaps
+	}
+
+	/// Counts a backfill attempt for the gap before a timeline event, recording
+	/// the gap first if it wasn't tracked yet.
+	pub(super) async fn record_backfill_attempt(
+		&self,
+		event_id: &EventId,
+		missing: &[OwnedEventId],
+	) {
+		let mut gap = match self
+			.db
+			.eventid_timelinegap
+			.get(event_id)
+			.await
+			.deserialized::<TimelineGap>()
+		{
+			| Ok(gap) => gap,
+			| Err(_) => {
+				for prev_event_id in missing {
+					self.db
+						.preveventid_timelinegap
+						.put_raw((prev_event_id, event_id), []);
+				}
+
+				TimelineGap { missing: missing.to_vec(), attempts: 0 }
+			},
+		};
+
+		gap.attempts = gap.attempts.saturating_add(1);
+		self.db.eventid_timelinegap.raw_put(event_id, Json(gap));
+	}
+
+	/// Gets the missing prev events of the gap before a timeline event which
+	/// may still be backfilled, unless the gap was given up on.
+	async fn fillable_gap(&self, event_id: &EventId) -> Option<Vec<OwnedEventId>> {
+		let gap: TimelineGap = self
+			.db
+			.eventid_timelinegap
+			.get(event_id)
+			.await
+			.deserialized()
+			.ok()?;
+
+		if gap.attempts >= MAX_BACKFILL_ATTEMPTS {
+			return None;
+		}
+
+		let missing: Vec<_> = gap
+			.missing
+			.into_iter()
+			.stream()
+			.filter_map(async |event_id| self.is_fillable(&event_id).await.then_some(event_id))
+			.collect()
+			.await;
+
+		(!missing.is_empty()).then_some(missing)
+	}
+
+	/// Records the gap before a new timeline event, if some of its prev events
+	/// aren't in the timeline, and closes the gaps of the events which were
+	/// missing this one.
+	pub(super) async fn update_timeline_gaps(&self, pdu: &PduEvent) {
+		let event_id = pdu.event_id();
+		let missing = self.missing_prev_events(pdu).await;
+		if !missing.is_empty() {
+			for prev_event_id in &missing {
+				self.db
+					.preveventid_timelinegap
+					.put_raw((prev_event_id, event_id), []);
+			}
+
+			self.db
+				.eventid_timelinegap
+				.raw_put(event_id, Json(TimelineGap { missing, attempts: 0 }));
+		}
+
+		let closed: Vec<OwnedEventId> = self
+			.db
+			.preveventid_timelinegap
+			.keys_prefix(&(event_id, Interfix))
+			.ignore_err()
+			.map(|(_, next): (Ignore, OwnedEventId)| next)
+			.collect()
+			.await;
+
+		for next in closed {
+			self.db.preveventid_timelinegap.del((event_id, &next));
+
+			let Ok(mut gap) = self
+				.db
+				.eventid_timelinegap
+				.get(&next)
+				.await
+				.deserialized::<TimelineGap>()
+			else {
+				continue;
+			};
+
+			gap.missing.retain(|missing| missing != event_id);
+			if gap.missing.is_empty() {
+				self.db.eventid_timelinegap.remove(&next);
+			} else {
+				self.db.eventid_timelinegap.raw_put(&next, Json(gap));
+			}
+		}
+	}
+
+	async fn missing_prev_events(&self, pdu: &PduEvent) -> Vec<OwnedEventId> {
+		pdu.prev_events()
+			.stream()
+			.filter_map(async |prev_event_id| {
+				(self.get_pdu_id(prev_event_id).await.is_err()
+					&& self.is_fillable(prev_event_id).await)
+					.then(|| prev_event_id.to_owned())
+			})
+			.collect()
+			.await
+	}
+
+	/// Whether a missing prev event can still reach the timeline. Events we
+	/// hold as rejected or soft-failed outliers never will.
+	async fn is_fillable(&self, event_id: &EventId) -> bool {
+		!self.services.pdu_metadata.is_event_rejected(event_id).await
+			&& !self
+				.services
+				.pdu_metadata
+				.is_event_soft_failed(event_id)
+				.await
+	}
+}
@@ -3,8 +3,10 @@
 mod build;
 mod create;
 mod data;
+mod gaps;
 mod helpers;
 mod redact;
+mod timestamp;
 
 use std::{fmt::Write, sync::Arc};
 
@@ -27,7 +29,7 @@
 use serde::Deserialize;
 
 use self::data::Data;
-pub use self::{create::pdu_fits, data::PdusIterItem};
+pub use self::{create::pdu_fits, data::PdusIterItem, timestamp::LocalMatch};
 use crate::{
 	Dep, account_data, admin, appservice, config, globals, pusher, rooms, sending, server_keys,
 	sync, users,
@@ -35,9 +37,9 @@
 
 // Update Relationships
 #[derive(Deserialize)]
-struct ExtractRelatesTo {
+pub(crate) struct ExtractRelatesTo {
 	#[serde(rename = "m.relates_to")]
-	relates_to: Relation,
+	pub(crate) relates_to: Relation,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -67,6 +69,8 @@ struct Services {
 	appservice: Dep<appservice::Service>,
 	admin: Dep<admin::Service>,
 	alias: Dep<rooms::alias::Service>,
+	auth_chain: Dep<rooms::auth_chain::Service>,
+	directory: Dep<rooms::directory::Service>,
 	event_handler: Dep<rooms::event_handler::Service>,
 	config: Dep<config::Service>,
 	globals: Dep<globals::Service>,
@@ -80,6 +84,7 @@ struct Services {
 	state: Dep<rooms::state::Service>,
 	state_accessor: Dep<rooms::state_accessor::Service>,
 	state_compressor: Dep<rooms::state_compressor::Service>,
+	state_reset: Dep<rooms::state_reset::Service>,
 	state_cache: Dep<rooms::state_cache::Service>,
 	sync: Dep<sync::Service>,
 	threads: Dep<rooms::threads::Service>,
@@ -100,7 +105,9 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				appservice: args.depend::<appservice::Service>("appservice"),
 				admin: args.depend::<admin::Service>("admin"),
 				alias: args.depend::<rooms::alias::Service>("rooms::alias"),
+				auth_chain: args.depend::<rooms::auth_chain::Service>("rooms::auth_chain"),
 				config: args.depend::<config::Service>("config"),
+				directory: args.depend::<rooms::directory::Service>("rooms::directory"),
 				event_handler: args
 					.depend::<rooms::event_handler::Service>("rooms::event_handler"),
 				globals: args.depend::<globals::Service>("globals"),
@@ -116,6 +123,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 				state_compressor: args
 					.depend::<rooms::state_compressor::Service>("rooms::state_compressor"),
+				state_reset: args.depend::<rooms::state_reset::Service>("rooms::state_reset"),
 				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
 				sync: args.depend::<sync::Service>("sync"),
 				threads: args.depend::<rooms::threads::Service>("rooms::threads"),
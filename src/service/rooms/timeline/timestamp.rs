@@ -0,0 +1,207 @@
+use std::pin::pin;
+
+use conduwuit::{PduCount, PduEvent, debug, debug_warn};
+use conduwuit_core::{
+	Err, Result,
+	matrix::event::Event,
+	utils::stream::{ReadyExt, TryIgnore},
+};
+use futures::{FutureExt, StreamExt};
+use ruma::{
+	MilliSecondsSinceUnixEpoch, OwnedEventId, RoomId,
+	api::{Direction, federation::event::get_event_by_timestamp},
+	events::TimelineEventType,
+};
+
+/// Number of events examined past the first one on the far side of the
+/// requested timestamp. Timeline order only roughly follows
+/// `origin_server_ts`, so a few further events may still be closer.
+const SCAN_TOLERANCE: usize = 50;
+
+/// Result of searching the local timeline for the event closest to a
+/// timestamp.
+pub struct LocalMatch {
+	/// The closest event in the requested direction, if any.
+	pub event: Option<PduEvent>,
+
+	/// Whether history is missing locally which could contain a closer event,
+	/// either before the scanned events or in a gap among them, so other
+	/// servers in the room should be asked.
+	pub incomplete: bool,
+}
+
+impl super::Service {
+	/// Finds the event closest to `ts` in the direction `dir` in the local
+	/// timeline: the earliest event at or after `ts` going forward, or the
+	/// latest event at or before `ts` going backward.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub async fn local_event_by_timestamp(
+		&self,
+		room_id: &RoomId,
+		ts: MilliSecondsSinceUnixEpoch,
+		dir: Direction,
+	) -> LocalMatch {
+		let is_candidate = |pdu: &PduEvent| match dir {
+			| Direction::Forward => pdu.origin_server_ts() >= ts,
+			| Direction::Backward => pdu.origin_server_ts() <= ts,
+		};
+
+		let is_closer = |pdu: &PduEvent, best: &PduEvent| match dir {
+			| Direction::Forward => pdu.origin_server_ts() < best.origin_server_ts(),
+			| Direction::Backward => pdu.origin_server_ts() > best.origin_server_ts(),
+		};
+
+		let mut best: Option<PduEvent> = None;
+		let mut oldest: Option<PduEvent> = None;
+		let mut past_ts: usize = 0;
+		let mut exhausted = true;
+		let mut gap = false;
+
+		let until = self.scan_start(room_id, ts).await;
+		let mut pdus = pin!(self.pdus_rev(room_id, until).ignore_err());
+		while let Some((_, pdu)) = pdus.next().await {
+			if pdu.origin_server_ts() < ts {
+				past_ts = past_ts.saturating_add(1);
+			}
+
+			if past_ts > SCAN_TOLERANCE {
+				exhausted = false;
+				break;
+			}
+
+			// Missing history before any scanned event could hold a closer one.
+			if !gap && self.timeline_gap(pdu.event_id()).await.is_ok() {
+				gap = true;
+			}
+
+			if is_candidate(&pdu) && best.as_ref().is_none_or(|best| is_closer(&pdu, best)) {
+				best = Some(pdu.clone());
+			}
+
+			oldest = Some(pdu);
+		}
+
+		let incomplete = gap
+			|| (exhausted
+				&& oldest.as_ref().is_none_or(|oldest| {
+					*oldest.kind() != TimelineEventType::RoomCreate
+						&& oldest.origin_server_ts() > ts
+				}));
+
+		LocalMatch { event: best, incomplete }
+	}
+
+	/// Finds where to start scanning the timeline backwards for the events
+	/// around `ts`: `SCAN_TOLERANCE` events after the first event at or after
+	/// `ts`, or the end of the timeline. The first event is found by bisecting
+	/// the PDU counts of the room, relying on timeline order roughly following
+	/// `origin_server_ts`, so only a few events are read.
+	async fn scan_start(
+		&self,
+		room_id: &RoomId,
+		ts: MilliSecondsSinceUnixEpoch,
+	) -> Option<PduCount> {
+		let first = pin!(self.pdus(room_id, None).ignore_err()).next().await?;
+		let last = pin!(self.pdus_rev(room_id, None).ignore_err())
+			.next()
+			.await?;
+
+		let mut lo = first.0.into_signed();
+		let mut hi = last.0.into_signed().saturating_add(1);
+		while lo < hi {
+			let mid = lo.saturating_add(hi.saturating_sub(lo) / 2);
+
+			// The newest event at or before `mid`.
+			let until = PduCount::from_signed(mid.saturating_add(1));
+			let mut newest = pin!(self.pdus_rev(room_id, Some(until)).ignore_err());
+			let Some((count, pdu)) = newest.next().await else {
+				lo = mid.saturating_add(1);
+				continue;
+			};
+
+			let count = count.into_signed();
+			if count < lo {
+				// No events between `lo` and `mid`.
+				lo = mid.saturating_add(1);
+			} else if pdu.origin_server_ts() < ts {
+				lo = count.saturating_add(1);
+			} else {
+				hi = count;
+			}
+		}
+
+		let from = PduCount::from_signed(lo.saturating_sub(1));
+		let (end, _) = self
+			.pdus(room_id, Some(from))
+			.ignore_err()
+			.take(SCAN_TOLERANCE)
+			.ready_fold(None, |_, item| Some(item))
+			.await?;
+
+		Some(PduCount::from_signed(end.into_signed().saturating_add(1)))
+	}
+
+	/// Finds the event closest to `ts` in the direction `dir`, asking other
+	/// servers in the room when the local timeline is missing the history
+	/// which could contain it. Events found remotely are fetched and
+	/// persisted so clients can paginate from them.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub async fn event_by_timestamp(
+		&self,
+		room_id: &RoomId,
+		ts: MilliSecondsSinceUnixEpoch,
+		dir: Direction,
+	) -> Result<PduEvent> {
+		let LocalMatch { event, incomplete } =
+			self.local_event_by_timestamp(room_id, ts, dir).await;
+
+		if incomplete {
+			if let Some(remote) = self.remote_event_by_timestamp(room_id, ts, dir).await {
+				let closer = event.as_ref().is_none_or(|local| match dir {
+					| Direction::Forward => remote.1 < local.origin_server_ts(),
+					| Direction::Backward => remote.1 > local.origin_server_ts(),
+				});
+
+				if closer {
+					match self.get_remote_pdu(room_id, &remote.0).boxed().await {
+						| Ok(pdu) => return Ok(pdu),
+						| Err(e) => {
+							debug_warn!(event_id = %remote.0, "Failed to fetch event closest to timestamp: {e}");
+						},
+					}
+				}
+			}
+		}
+
+		match event {
+			| Some(pdu) => Ok(pdu),
+			| None => Err!(Request(NotFound("Unable to find an event in that direction."))),
+		}
+	}
+
+	async fn remote_event_by_timestamp(
+		&self,
+		room_id: &RoomId,
+		ts: MilliSecondsSinceUnixEpoch,
+		dir: Direction,
+	) -> Option<(OwnedEventId, MilliSecondsSinceUnixEpoch)> {
+		for server in self.candidate_backfill_servers(room_id).await {
+			debug!(%server, "Asking for the event closest to timestamp");
+			let request = get_event_by_timestamp::v1::Request::new(room_id.to_owned(), ts, dir);
+
+			match self
+				.services
+				.sending
+				.send_federation_request(&server, request)
+				.await
+			{
+				| Ok(response) => return Some((response.event_id, response.origin_server_ts)),
+				| Err(e) => {
+					debug_warn!(%server, "Failed to get the event closest to timestamp: {e}");
+				},
+			}
+		}
+
+		None
+	}
+}
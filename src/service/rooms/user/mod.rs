@@ -1,26 +1,66 @@
-use std::sync::Arc;
+mod threads;
 
-use conduwuit::Result;
-use database::{Deserialized, Map};
-use ruma::{RoomId, UserId};
+use std::{sync::Arc, time::Duration};
 
-use crate::{Dep, globals};
+use async_trait::async_trait;
+use conduwuit::{
+	Result, Server, debug,
+	utils::{
+		self,
+		stream::{ReadyExt, TryIgnore},
+	},
+};
+use database::{Deserialized, Json, Map};
+use futures::{Stream, StreamExt};
+use ruma::{OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId, push::Action};
+use serde::{Deserialize, Serialize};
+use tokio::{
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
+};
+
+pub use self::threads::ThreadCounts;
+use crate::{Dep, globals, rooms};
 
 pub struct Service {
 	db: Data,
 	services: Services,
+	interrupt: Notify,
+}
+
+/// An event which notified a user, as recorded in their notification log.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Notification {
+	pub room_id: OwnedRoomId,
+	pub event_id: OwnedEventId,
+	/// Push actions the user's push rules produced for the event.
+	pub actions: Vec<Action>,
+	/// Whether the actions include a highlight tweak.
+	pub highlight: bool,
+	/// Milliseconds since the epoch when the notification was recorded.
+	pub ts: u64,
 }
 
+/// Interval between removals of expired notifications.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 struct Data {
 	userroomid_notificationcount: Arc<Map>,
 	userroomid_highlightcount: Arc<Map>,
 	roomuserid_lastnotificationread: Arc<Map>,
+	useridcount_notification: Arc<Map>,
+	tsuseridcount_notification: Arc<Map>,
+	userroomthreadid_notificationcount: Arc<Map>,
+	userroomthreadid_highlightcount: Arc<Map>,
 }
 
 struct Services {
+	server: Arc<Server>,
 	globals: Dep<globals::Service>,
+	timeline: Dep<rooms::timeline::Service>,
 }
 
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
@@ -29,23 +69,62 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				userroomid_highlightcount: args.db["userroomid_highlightcount"].clone(),
 				roomuserid_lastnotificationread: args.db["roomuserid_lastnotificationread"]
 					.clone(),
+				useridcount_notification: args.db["useridcount_notification"].clone(),
+				tsuseridcount_notification: args.db["tsuseridcount_notification"].clone(),
+				userroomthreadid_notificationcount: args.db["userroomthreadid_notificationcount"]
+					.clone(),
+				userroomthreadid_highlightcount: args.db["userroomthreadid_highlightcount"]
+					.clone(),
 			},
 			services: Services {
+				server: args.server.clone(),
 				globals: args.depend::<globals::Service>("globals"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
 			},
+			interrupt: Notify::new(),
 		}))
 	}
 
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		if self.services.server.config.notification_log_max_age_secs == 0 {
+			return Ok(());
+		}
+
+		let mut i = interval(PRUNE_INTERVAL);
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			self.prune_notifications().await;
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
 impl Service {
-	/// Resets the notification counts for a room the user is in.
-	pub fn reset_notification_counts(&self, user_id: &UserId, room_id: &RoomId) {
+	/// Resets the notification counts for a room the user is in, including
+	/// the counts of every thread in the room.
+	pub async fn reset_notification_counts(&self, user_id: &UserId, room_id: &RoomId) {
+		let _insert_lock = self.services.timeline.mutex_insert.lock(room_id).await;
+
 		let userroom_id = (user_id, room_id);
 		self.db.userroomid_highlightcount.put(userroom_id, 0_u64);
 		self.db.userroomid_notificationcount.put(userroom_id, 0_u64);
+		self.clear_thread_notification_counts(user_id, room_id)
+			.await;
+
+		self.mark_notifications_read(user_id, room_id);
+	}
 
+	fn mark_notifications_read(&self, user_id: &UserId, room_id: &RoomId) {
 		let roomuser_id = (room_id, user_id);
 		let count = self.services.globals.next_count().unwrap();
 		self.db
@@ -86,4 +165,64 @@ pub async fn last_notification_read(&self, user_id: &UserId, room_id: &RoomId) -
 			.deserialized()
 			.unwrap_or(0)
 	}
+
+	/// Records an event which notified the user. `count` is the PDU count of
+	/// the event, which orders the user's notification log.
+	pub fn add_notification(&self, user_id: &UserId, count: u64, notification: &Notification) {
+		let key = (user_id, count);
+		self.db
+			.useridcount_notification
+			.put(key, Json(notification));
+
+		self.db
+			.tsuseridcount_notification
+			.put_raw((notification.ts, user_id, count), []);
+	}
+
+	/// Iterates the user's notifications from newest to oldest, starting
+	/// below the PDU count `before` if given. Yields the PDU count of each
+	/// notification with it.
+	pub fn notifications<'a>(
+		&'a self,
+		user_id: &'a UserId,
+		before: Option<u64>,
+	) -> impl Stream<Item = (u64, Notification)> + Send + 'a {
+		type KeyVal<'a> = ((&'a UserId, u64), Notification);
+
+		let from = (user_id, before.map_or(u64::MAX, |count| count.saturating_sub(1)));
+		self.db
+			.useridcount_notification
+			.rev_stream_from(&from)
+			.ignore_err()
+			.ready_take_while(move |((user_id_, _), _): &KeyVal<'_>| *user_id_ == user_id)
+			.map(|((_, count), notification): KeyVal<'_>| (count, notification))
+	}
+
+	/// Removes notifications older than `notification_log_max_age_secs`.
+	async fn prune_notifications(&self) {
+		let max_age = self.services.server.config.notification_log_max_age_secs;
+		let cutoff =
+			utils::millis_since_unix_epoch().saturating_sub(max_age.saturating_mul(1000));
+
+		// The index is ordered by timestamp, so only expired entries are read.
+		let expired: Vec<(u64, OwnedUserId, u64)> = self
+			.db
+			.tsuseridcount_notification
+			.keys()
+			.ignore_err()
+			.ready_take_while(|(ts, ..): &(u64, OwnedUserId, u64)| *ts < cutoff)
+			.collect()
+			.await;
+
+		for (ts, user_id, count) in &expired {
+			self.db.useridcount_notification.del((user_id, *count));
+			self.db
+				.tsuseridcount_notification
+				.del((*ts, user_id, *count));
+		}
+
+		if !expired.is_empty() {
+			debug!(count = expired.len(), "Removed expired notifications");
+		}
+	}
 }
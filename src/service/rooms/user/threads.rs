@@ -0,0 +1,182 @@
+//! Per-thread notification counts
+//!
+//! Events in a thread count towards both the room's notification counts and
+//! the counts of their thread. The counts for the main timeline are the room's
+//! counts less those of every thread.
+
+use std::collections::BTreeMap;
+
+use conduwuit::utils::stream::{ReadyExt, TryIgnore};
+use database::{Deserialized, Interfix};
+use futures::StreamExt;
+use ruma::{EventId, OwnedEventId, RoomId, UserId};
+
+/// Notification and highlight counts of a thread.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ThreadCounts {
+	pub notification_count: u64,
+	pub highlight_count: u64,
+}
+
+impl super::Service {
+	/// Gets the notification counts of each thread with unread notifications
+	/// in a room the user is in, keyed by thread root.
+	pub async fn thread_notification_counts(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+	) -> BTreeMap<OwnedEventId, ThreadCounts> {
+		type KeyVal<'a> = ((&'a UserId, &'a RoomId, &'a EventId), u64);
+
+		let prefix = (user_id, room_id, Interfix);
+		let mut counts: BTreeMap<OwnedEventId, ThreadCounts> = BTreeMap::new();
+
+		self.db
+			.userroomthreadid_notificationcount
+			.stream_prefix(&prefix)
+			.ignore_err()
+			.ready_for_each(|((.., thread), count): KeyVal<'_>| {
+				counts
+					.entry(thread.to_owned())
+					.or_default()
+					.notification_count = count;
+			})
+			.await;
+
+		self.db
+			.userroomthreadid_highlightcount
+			.stream_prefix(&prefix)
+			.ignore_err()
+			.ready_for_each(|((.., thread), count): KeyVal<'_>| {
+				counts.entry(thread.to_owned()).or_default().highlight_count = count;
+			})
+			.await;
+
+		counts.retain(|_, counts| counts.notification_count > 0 || counts.highlight_count > 0);
+		counts
+	}
+
+	/// Gets the notification counts of the main timeline of a room the user is
+	/// in, excluding events in threads.
+	pub async fn main_notification_counts(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+	) -> ThreadCounts {
+		let threads = self.thread_notification_counts(user_id, room_id).await;
+		let (notification_count, highlight_count) = (
+			self.notification_count(user_id, room_id).await,
+			self.highlight_count(user_id, room_id).await,
+		);
+
+		threads.values().fold(
+			ThreadCounts { notification_count, highlight_count },
+			|main, thread| ThreadCounts {
+				notification_count: main
+					.notification_count
+					.saturating_sub(thread.notification_count),
+				highlight_count: main.highlight_count.saturating_sub(thread.highlight_count),
+			},
+		)
+	}
+
+	/// Resets the notification counts of the main timeline of a room the user
+	/// is in, leaving the counts of its threads unread.
+	pub async fn reset_main_notification_counts(&self, user_id: &UserId, room_id: &RoomId) {
+		// Held so that no event is counted between reading and writing the
+		// counts.
+		let _insert_lock = self.services.timeline.mutex_insert.lock(room_id).await;
+
+		let threads = self.thread_notification_counts(user_id, room_id).await;
+		let (notification_count, highlight_count) =
+			threads
+				.values()
+				.fold((0_u64, 0_u64), |(notifications, highlights), thread| {
+					(
+						notifications.saturating_add(thread.notification_count),
+						highlights.saturating_add(thread.highlight_count),
+					)
+				});
+
+		let userroom_id = (user_id, room_id);
+		self.db
+			.userroomid_notificationcount
+			.put(userroom_id, notification_count);
+		self.db
+			.userroomid_highlightcount
+			.put(userroom_id, highlight_count);
+
+		self.mark_notifications_read(user_id, room_id);
+	}
+
+	/// Resets the notification counts of one thread in a room the user is in.
+	pub async fn reset_thread_notification_counts(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread: &EventId,
+	) {
+		// Held so that no event is counted between reading and writing the
+		// counts.
+		let _insert_lock = self.services.timeline.mutex_insert.lock(room_id).await;
+
+		let userroomthread_id = (user_id, room_id, thread);
+		let (thread_notifications, thread_highlights): (u64, u64) = (
+			self.db
+				.userroomthreadid_notificationcount
+				.qry(&userroomthread_id)
+				.await
+				.deserialized()
+				.unwrap_or(0),
+			self.db
+				.userroomthreadid_highlightcount
+				.qry(&userroomthread_id)
+				.await
+				.deserialized()
+				.unwrap_or(0),
+		);
+
+		self.db
+			.userroomthreadid_notificationcount
+			.del(userroomthread_id);
+		self.db
+			.userroomthreadid_highlightcount
+			.del(userroomthread_id);
+
+		let userroom_id = (user_id, room_id);
+		let notification_count = self
+			.notification_count(user_id, room_id)
+			.await
+			.saturating_sub(thread_notifications);
+		let highlight_count = self
+			.highlight_count(user_id, room_id)
+			.await
+			.saturating_sub(thread_highlights);
+
+		self.db
+			.userroomid_notificationcount
+			.put(userroom_id, notification_count);
+		self.db
+			.userroomid_highlightcount
+			.put(userroom_id, highlight_count);
+
+		self.mark_notifications_read(user_id, room_id);
+	}
+
+	pub(super) async fn clear_thread_notification_counts(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+	) {
+		let prefix = (user_id, room_id, Interfix);
+		for map in [
+			&self.db.userroomthreadid_notificationcount,
+			&self.db.userroomthreadid_highlightcount,
+		] {
+			map.keys_prefix_raw(&prefix)
+				.ignore_err()
+				.ready_for_each(|key| map.remove(key))
+				.await;
+		}
+	}
+}
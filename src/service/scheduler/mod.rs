@@ -0,0 +1,283 @@
+//! # Scheduler service
+//!
+//! Runs admin commands periodically on cron-style schedules. Scheduled jobs
+//! are persisted in the database and checked at the start of every minute;
+//! their output is posted to the admin room.
+
+use std::{
+	collections::HashSet,
+	sync::Arc,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Err, Result, SyncMutex, debug, err, info,
+	utils::{self, cron::Schedule, stream::TryIgnore},
+};
+use database::{Deserialized, Json, Map};
+use futures::{Stream, StreamExt, future::join_all};
+use ruma::{OwnedUserId, events::room::message::RoomMessageEventContent};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Notify, task::JoinSet, time::sleep};
+
+use crate::{
+	Dep,
+	admin::{self, InvocationSource},
+};
+
+pub struct Service {
+	db: Data,
+	services: Services,
+	running: SyncMutex<HashSet<String>>,
+	interrupt: Notify,
+}
+
+struct Data {
+	scheduleid_job: Arc<Map>,
+}
+
+struct Services {
+	admin: Dep<admin::Service>,
+}
+
+/// An admin command registered to run on a schedule.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Job {
+	/// Cron expression the job runs on, in UTC.
+	pub schedule: String,
+
+	/// Admin command to run, without the `!admin` prefix.
+	pub command: String,
+
+	/// Paused jobs are kept but not run.
+	#[serde(default)]
+	pub paused: bool,
+
+	/// Only post the output to the admin room when the command fails.
+	#[serde(default)]
+	pub quiet: bool,
+
+	pub created_by: Option<OwnedUserId>,
+
+	/// Milliseconds since the epoch when the job was last started.
+	pub last_run: Option<u64>,
+
+	/// Whether the last run of the command succeeded.
+	pub last_succeeded: Option<bool>,
+}
+
+/// Length of generated job IDs.
+const JOB_ID_LENGTH: usize = 8;
+
+const MINUTE_MILLIS: u64 = 60_000;
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			db: Data {
+				scheduleid_job: args.db["scheduleid_job"].clone(),
+			},
+			services: Services {
+				admin: args.depend::<admin::Service>("admin"),
+			},
+			running: SyncMutex::new(HashSet::new()),
+			interrupt: Notify::new(),
+		}))
+	}
+
+	#[tracing::instrument(skip_all, name = "scheduler", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		let mut runs = JoinSet::new();
+		loop {
+			let into_minute = utils::millis_since_unix_epoch() % MINUTE_MILLIS;
+			let until_next_minute = MINUTE_MILLIS.saturating_sub(into_minute);
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				() = sleep(Duration::from_millis(until_next_minute)) => (),
+			}
+
+			while runs.try_join_next().is_some() {}
+
+			let service = self.clone();
+			runs.spawn(async move { service.run_due(SystemTime::now()).await });
+		}
+
+		// Commands still running would otherwise race the shutdown of the
+		// services they use.
+		if !runs.is_empty() {
+			debug!(runs = runs.len(), "Aborting scheduled commands still running");
+		}
+
+		runs.shutdown().await;
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+impl Service {
+	/// Registers a new job and returns its ID.
+	pub fn add(
+		&self,
+		schedule: &str,
+		command: String,
+		quiet: bool,
+		created_by: Option<OwnedUserId>,
+	) -> Result<String> {
+		let schedule: Schedule = schedule.parse()?;
+		if schedule.next_after(SystemTime::now()).is_none() {
+			return Err!("The schedule {schedule} never matches any time.");
+		}
+
+		if command.trim().is_empty() {
+			return Err!("No command to schedule.");
+		}
+
+		let id = utils::random_string(JOB_ID_LENGTH);
+		let job = Job {
+			schedule: schedule.to_string(),
+			command,
+			paused: false,
+			quiet,
+			created_by,
+			last_run: None,
+			last_succeeded: None,
+		};
+
+		self.db.scheduleid_job.raw_put(&id, Json(&job));
+		info!(%id, %schedule, command = %job.command, "Scheduled admin command");
+
+		Ok(id)
+	}
+
+	/// Fetches a job by ID.
+	pub async fn get(&self, id: &str) -> Result<Job> {
+		self.db
+			.scheduleid_job
+			.get(id)
+			.await
+			.deserialized()
+			.map_err(|_| err!("No scheduled command with ID {id:?}."))
+	}
+
+	/// Lists all jobs with their IDs.
+	pub fn jobs(&self) -> impl Stream<Item = (String, Job)> + Send + '_ {
+		self.db.scheduleid_job.stream().ignore_err()
+	}
+
+	/// Pauses or resumes a job.
+	pub async fn set_paused(&self, id: &str, paused: bool) -> Result {
+		let mut job = self.get(id).await?;
+		job.paused = paused;
+		self.db.scheduleid_job.raw_put(id, Json(&job));
+
+		Ok(())
+	}
+
+	/// Removes a job. A run of the job in progress is not interrupted.
+	pub async fn remove(&self, id: &str) -> Result {
+		self.get(id).await?;
+		self.db.scheduleid_job.remove(id);
+
+		Ok(())
+	}
+
+	/// When a job will next run, if it is not paused.
+	#[must_use]
+	pub fn next_run(job: &Job) -> Option<SystemTime> {
+		if job.paused {
+			return None;
+		}
+
+		job.schedule
+			.parse::<Schedule>()
+			.ok()?
+			.next_after(SystemTime::now())
+	}
+
+	/// Runs all jobs due at the given time which are not already running.
+	async fn run_due(&self, now: SystemTime) {
+		let minute = minute_of(now);
+		let due: Vec<_> = self
+			.jobs()
+			.filter(|(id, job)| {
+				let due = !job.paused
+					&& job.last_run.map(minute_of_millis) != Some(minute)
+					&& job
+						.schedule
+						.parse::<Schedule>()
+						.is_ok_and(|schedule| schedule.matches(now))
+					&& self.running.lock().insert(id.clone());
+
+				async move { due }
+			})
+			.collect()
+			.await;
+
+		join_all(due.into_iter().map(|(id, job)| async move {
+			self.run(&id, job, now).await;
+			self.running.lock().remove(&id);
+		}))
+		.await;
+	}
+
+	#[tracing::instrument(skip(self, job, now), level = "info")]
+	async fn run(&self, id: &str, mut job: Job, now: SystemTime) {
+		debug!(command = %job.command, "Running scheduled admin command");
+		job.last_run = Some(millis_of(now));
+		self.db.scheduleid_job.raw_put(id, Json(&job));
+
+		let result = self
+			.services
+			.admin
+			.command_in_place(job.command.clone(), None, InvocationSource::Internal)
+			.await;
+
+		let (succeeded, output) = match result {
+			| Ok(output) => (true, output),
+			| Err(output) => (false, Some(output)),
+		};
+
+		// The job may have been paused or removed while it was running.
+		if let Ok(mut current) = self.get(id).await {
+			current.last_run = job.last_run;
+			current.last_succeeded = Some(succeeded);
+			self.db.scheduleid_job.raw_put(id, Json(&current));
+		}
+
+		if job.quiet && succeeded {
+			return;
+		}
+
+		let status = if succeeded { "completed" } else { "failed" };
+		let mut body = format!("Scheduled command `{id}` (`{}`) {status}", job.command);
+		if let Some(output) = output {
+			body.push_str(":\n\n");
+			body.push_str(output.body());
+		}
+
+		let admin = &self.services.admin;
+		let content = admin
+			.text_or_file(RoomMessageEventContent::notice_markdown(body))
+			.await;
+
+		admin.send_message(content).await.ok();
+	}
+}
+
+fn millis_of(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_millis()
+		.try_into()
+		.unwrap_or(u64::MAX)
+}
+
+fn minute_of(time: SystemTime) -> u64 { minute_of_millis(millis_of(time)) }
+
+fn minute_of_millis(millis: u64) -> u64 { millis / MINUTE_MILLIS }
@@ -5,6 +5,7 @@
 mod sender;
 
 use std::{
+	collections::BTreeSet,
 	fmt::Debug,
 	hash::{DefaultHasher, Hash, Hasher},
 	iter::once,
@@ -15,7 +16,7 @@
 use conduwuit::{
 	Result, Server, debug, debug_warn, err, error,
 	smallvec::SmallVec,
-	utils::{ReadyExt, TryReadyExt, available_parallelism, math::usize_from_u64_truncated},
+	utils::{IterStream, TryReadyExt, available_parallelism, math::usize_from_u64_truncated},
 	warn,
 };
 use futures::{FutureExt, Stream, StreamExt};
@@ -53,6 +54,7 @@ pub struct Service {
 struct Services {
 	client: Dep<client::Service>,
 	globals: Dep<globals::Service>,
+	partial_state: Dep<rooms::partial_state::Service>,
 	state_cache: Dep<rooms::state_cache::Service>,
 	user: Dep<rooms::user::Service>,
 	users: Dep<users::Service>,
@@ -96,6 +98,8 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			services: Services {
 				client: args.depend::<client::Service>("client"),
 				globals: args.depend::<globals::Service>("globals"),
+				partial_state: args
+					.depend::<rooms::partial_state::Service>("rooms::partial_state"),
 				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
 				user: args.depend::<rooms::user::Service>("rooms::user"),
 				users: args.depend::<users::Service>("users"),
@@ -186,11 +190,7 @@ pub fn send_pdu_appservice(&self, appservice_id: String, pdu_id: RawPduId) -> Re
 
 	#[tracing::instrument(skip(self, room_id, pdu_id), level = "debug")]
 	pub async fn send_pdu_room(&self, room_id: &RoomId, pdu_id: &RawPduId) -> Result {
-		let servers = self
-			.services
-			.state_cache
-			.room_servers(room_id)
-			.ready_filter(|server_name| !self.services.globals.server_is_ours(server_name));
+		let servers = self.room_servers(room_id).await;
 
 		self.send_pdu_servers(servers, pdu_id).await
 	}
@@ -230,11 +230,7 @@ pub fn send_edu_server(&self, server: &ServerName, serialized: EduBuf) -> Result
 
 	#[tracing::instrument(skip(self, room_id, serialized), level = "debug")]
 	pub async fn send_edu_room(&self, room_id: &RoomId, serialized: EduBuf) -> Result {
-		let servers = self
-			.services
-			.state_cache
-			.room_servers(room_id)
-			.ready_filter(|server_name| !self.services.globals.server_is_ours(server_name));
+		let servers = self.room_servers(room_id).await;
 
 		self.send_edu_servers(servers, serialized).await
 	}
@@ -263,13 +259,24 @@ pub async fn send_edu_servers<S>(&self, servers: S, serialized: EduBuf) -> Resul
 
 	#[tracing::instrument(skip(self, room_id), level = "debug")]
 	pub async fn flush_room(&self, room_id: &RoomId) -> Result<()> {
-		let servers = self
+		let servers = self.room_servers(room_id).await;
+
+		self.flush_servers(servers).await
+	}
+
+	/// The remote servers in a room, including those only known from the
+	/// `send_join` response while the room has partial state.
+	async fn room_servers(&self, room_id: &RoomId) -> impl Stream<Item = OwnedServerName> + Send {
+		let mut servers: BTreeSet<OwnedServerName> = self
 			.services
 			.state_cache
 			.room_servers(room_id)
-			.ready_filter(|server_name| !self.services.globals.server_is_ours(server_name));
+			.collect()
+			.await;
 
-		self.flush_servers(servers).await
+		servers.extend(self.services.partial_state.servers_in_room(room_id).await);
+		servers.retain(|server_name| !self.services.globals.server_is_ours(server_name));
+		servers.into_iter().stream()
 	}
 
 	#[tracing::instrument(skip(self, servers), level = "debug")]
@@ -11,8 +11,8 @@
 	account_data, admin, announcements, antispam, appservice, client, config, emergency,
 	federation, firstrun, globals, key_backups, mailer,
 	manager::Manager,
-	media, moderation, oauth, oidc, presence, pusher, registration_tokens, rooms, sending,
-	server_keys,
+	media, moderation, oauth, oidc, presence, pusher, registration_tokens, rooms, scheduler,
+	sending, server_keys,
 	service::{self, Args, Map, Service},
 	sync, threepid, transactions, uiaa, users,
 };
@@ -46,6 +46,7 @@ pub struct Services {
 	pub moderation: Arc<moderation::Service>,
 	pub announcements: Arc<announcements::Service>,
 	pub antispam: Arc<antispam::Service>,
+	pub scheduler: Arc<scheduler::Service>,
 
 	manager: Mutex<Option<Arc<Manager>>>,
 	pub(crate) service: Arc<Map>,
@@ -94,10 +95,12 @@ macro_rules! build {
 				auth_chain: build!(rooms::auth_chain::Service),
 				directory: build!(rooms::directory::Service),
 				event_handler: build!(rooms::event_handler::Service),
+				inbound_queue: build!(rooms::inbound_queue::Service),
 				lazy_loading: build!(rooms::lazy_loading::Service),
 				membership: build!(rooms::membership::Service),
 				metadata: build!(rooms::metadata::Service),
 				outlier: build!(rooms::outlier::Service),
+				partial_state: build!(rooms::partial_state::Service),
 				pdu_metadata: build!(rooms::pdu_metadata::Service),
 				read_receipt: build!(rooms::read_receipt::Service),
 				search: build!(rooms::search::Service),
@@ -106,6 +109,7 @@ macro_rules! build {
 				state_accessor: build!(rooms::state_accessor::Service),
 				state_cache: build!(rooms::state_cache::Service),
 				state_compressor: build!(rooms::state_compressor::Service),
+				state_reset: build!(rooms::state_reset::Service),
 				summary: build!(rooms::summary::Service),
 				threads: build!(rooms::threads::Service),
 				timeline: build!(rooms::timeline::Service),
@@ -123,6 +127,7 @@ macro_rules! build {
 			moderation: build!(moderation::Service),
 			announcements: build!(announcements::Service),
 			antispam: build!(antispam::Service),
+			scheduler: build!(scheduler::Service),
 
 			manager: Mutex::new(None),
 			service,
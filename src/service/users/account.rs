@@ -19,7 +19,6 @@
 		push_rules::PushRulesEvent, room::message::RoomMessageEventContent,
 	},
 	profile::ProfileFieldValue,
-	push::Ruleset,
 };
 use ruminuwuity::invite_permission_config::{FilterLevel, InvitePermissionConfigEvent};
 
@@ -191,7 +190,7 @@ pub async fn create_local_account(
 				user_id,
 				GlobalAccountDataEventType::PushRules.to_string().into(),
 				&serde_json::to_value(PushRulesEvent::new(
-					Ruleset::server_default(user_id).into(),
+					self.services.pusher.default_ruleset(user_id).await.into(),
 				))
 				.expect("should be able to serialize push rules"),
 			)
@@ -1,7 +1,13 @@
-use conduwuit::{Err, Result, trace};
-use conduwuit_database::{Deserialized, Json};
+use std::sync::atomic::Ordering;
+
+use conduwuit::{
+	Err, Result, debug, trace,
+	utils::stream::{ReadyExt, TryIgnore},
+};
+use conduwuit_database::{Deserialized, Interfix, Json};
+use futures::{Stream, StreamExt};
 use ruma::{
-	DeviceId, OwnedDeviceId, UserId,
+	DeviceId, OwnedDeviceId, OwnedUserId, UserId,
 	api::client::dehydrated_device::{
 		DehydratedDeviceData, put_dehydrated_device::unstable::Request,
 	},
@@ -20,6 +26,9 @@ pub struct DehydratedDevice {
 
 impl super::Service {
 	/// Creates or recreates the user's dehydrated device.
+	///
+	/// A replaced dehydrated device is removed along with its to-device events
+	/// and one-time keys.
 	pub async fn set_dehydrated_device(&self, user_id: &UserId, request: Request) -> Result {
 		self.status(user_id).await.ensure_active()?;
 
@@ -118,4 +127,67 @@ pub async fn get_dehydrated_device(&self, user_id: &UserId) -> Result<Dehydrated
 			.await
 			.deserialized()
 	}
+
+	/// Streams the users with a dehydrated device.
+	pub fn users_with_dehydrated_device(&self) -> impl Stream<Item = OwnedUserId> + Send + '_ {
+		self.db.userid_dehydrateddevice.keys().ignore_err()
+	}
+
+	/// Number of to-device events dropped from dehydrated devices since the
+	/// server started.
+	pub fn dehydrated_device_events_pruned(&self) -> u64 {
+		self.dehydrated_events_pruned.load(Ordering::Relaxed)
+	}
+
+	/// Drops the oldest to-device events of every dehydrated device beyond
+	/// `dehydrated_device_max_to_device_events`. Returns the number of dropped
+	/// events.
+	pub async fn prune_dehydrated_devices(&self) -> u64 {
+		let max = self.services.config.dehydrated_device_max_to_device_events;
+		if max == 0 {
+			return 0;
+		}
+
+		self.users_with_dehydrated_device()
+			.fold(0_u64, |pruned, user_id| async move {
+				let Ok(device_id) = self.get_dehydrated_device_id(&user_id).await else {
+					return pruned;
+				};
+
+				let events = self
+					.prune_dehydrated_device_events(&user_id, &device_id, max)
+					.await;
+
+				pruned.saturating_add(events)
+			})
+			.await
+	}
+
+	async fn prune_dehydrated_device_events(
+		&self,
+		user_id: &UserId,
+		device_id: &DeviceId,
+		max: usize,
+	) -> u64 {
+		let prefix = (user_id, device_id, Interfix);
+		let pruned = self
+			.db
+			.todeviceid_events
+			.rev_keys_prefix_raw(&prefix)
+			.ignore_err()
+			.skip(max)
+			.ready_fold(0_u64, |pruned, key| {
+				self.db.todeviceid_events.remove(key);
+				pruned.saturating_add(1)
+			})
+			.await;
+
+		if pruned > 0 {
+			debug!(%user_id, %device_id, pruned, "Pruned to-device events of dehydrated device");
+			self.dehydrated_events_pruned
+				.fetch_add(pruned, Ordering::Relaxed);
+		}
+
+		pruned
+	}
 }
@@ -102,7 +102,8 @@ pub async fn remove_device(&self, user_id: &UserId, device_id: &DeviceId) {
 			.ready_for_each(|key| self.db.todeviceid_events.remove(key))
 			.await;
 
-		// TODO: Remove onetimekeys
+		// Remove one-time and fallback keys
+		self.remove_one_time_keys(user_id, device_id).await;
 
 		// Remove OAuth session information
 		self.services.oauth.remove_session(user_id, device_id).await;
@@ -211,6 +212,17 @@ pub async fn add_to_device_event(
 		self.services.sync.wake(target_user_id).await;
 	}
 
+	/// Counts the to-device events waiting in the target device's inbox.
+	pub async fn count_to_device_events(&self, user_id: &UserId, device_id: &DeviceId) -> usize {
+		let prefix = (user_id, device_id, Interfix);
+		self.db
+			.todeviceid_events
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.count()
+			.await
+	}
+
 	/// Gets all to-device events between the two counts.
 	pub fn get_to_device_events<'a>(
 		&'a self,
@@ -4,7 +4,7 @@
 	Err, Error, Result, err,
 	utils::{ReadyExt, stream::TryIgnore, string::Unquoted},
 };
-use database::{Deserialized, Ignore, Json};
+use database::{Deserialized, Ignore, Interfix, Json};
 use futures::{Stream, StreamExt, TryFutureExt};
 use ruma::{
 	DeviceId, OneTimeKeyAlgorithm, OneTimeKeyId, OneTimeKeyName, OwnedKeyId, OwnedOneTimeKeyId,
@@ -226,6 +226,24 @@ pub async fn count_one_time_keys(
 		algorithm_counts
 	}
 
+	/// Removes all one-time and fallback keys of a device.
+	pub(super) async fn remove_one_time_keys(&self, user_id: &UserId, device_id: &DeviceId) {
+		let prefix = (user_id, device_id, Interfix);
+		self.db
+			.onetimekeyid_onetimekeys
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| self.db.onetimekeyid_onetimekeys.remove(key))
+			.await;
+
+		self.db
+			.fallbackkeyid_fallbackkey
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| self.db.fallbackkeyid_fallbackkey.remove(key))
+			.await;
+	}
+
 	/// Returns a list of *unused* fallback key types.
 	pub async fn list_unused_fallback_key_types(
 		&self,
@@ -439,6 +457,21 @@ fn keys_changed_user_or_room<'a>(
 			.map(|((_, count), user_id): KeyVal<'_>| (user_id, count))
 	}
 
+	/// Marks the device keys of the given users as updated in one room only, so
+	/// that its local members fetch them.
+	pub async fn mark_room_device_key_updates<'a, I>(&self, room_id: &RoomId, user_ids: I)
+	where
+		I: Iterator<Item = &'a UserId> + Send,
+	{
+		for user_id in user_ids {
+			let count = self.services.globals.next_count().unwrap();
+			let key = (room_id, count);
+			self.db.keychangeid_userid.put_raw(key, user_id);
+		}
+
+		self.services.sync.wake_all_joined(room_id).await;
+	}
+
 	/// Marks that a user's device keys have been updated, so that other users
 	/// can be notified of the change.
 	pub async fn mark_device_key_update(&self, user_id: &UserId) {
@@ -5,10 +5,15 @@
 pub(super) mod keys;
 pub(super) mod profile;
 pub(super) mod remote;
+pub(super) mod retention;
 
-use std::{mem, sync::Arc};
+use std::{
+	mem,
+	sync::{Arc, atomic::AtomicU64},
+};
 
 pub use account::{AccessTokenStatus, AccountStatus};
+use async_trait::async_trait;
 use conduwuit::{
 	Err, Error, Result, err,
 	utils::{self},
@@ -16,11 +21,16 @@
 use database::Map;
 pub use device::DeviceToken;
 pub use profile::ProfileFieldChange;
+pub use retention::DEVICE_EXPIRY_EVENT_TYPE;
 use ruma::{UserId, api::error::ErrorKind, encryption::CrossSigningKey, serde::Raw};
 use serde::{Deserialize, Serialize};
+use tokio::{
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
+};
 
 use crate::{
-	Dep, account_data, admin, appservice, config, firstrun, globals, oauth, presence,
+	Dep, account_data, admin, appservice, config, firstrun, globals, oauth, presence, pusher,
 	rooms::{self, alias, membership},
 	sync, threepid,
 };
@@ -52,6 +62,8 @@ pub fn new(password: &str) -> Result<Self> {
 pub struct Service {
 	services: Services,
 	db: Data,
+	dehydrated_events_pruned: AtomicU64,
+	interrupt: Notify,
 }
 
 struct Services {
@@ -65,6 +77,7 @@ struct Services {
 	membership: Dep<membership::Service>,
 	oauth: Dep<oauth::Service>,
 	presence: Dep<presence::Service>,
+	pusher: Dep<pusher::Service>,
 	state: Dep<rooms::state::Service>,
 	state_accessor: Dep<rooms::state_accessor::Service>,
 	state_cache: Dep<rooms::state_cache::Service>,
@@ -103,6 +116,7 @@ struct Data {
 	useridprofilekey_value: Arc<Map>,
 }
 
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
@@ -117,6 +131,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				membership: args.depend::<membership::Service>("rooms::membership"),
 				oauth: args.depend::<oauth::Service>("oauth"),
 				presence: args.depend::<presence::Service>("presence"),
+				pusher: args.depend::<pusher::Service>("pusher"),
 				state: args.depend::<rooms::state::Service>("rooms::state"),
 				state_accessor: args
 					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
@@ -154,9 +169,36 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				useridprofilekey_value: args.db["useridprofilekey_value"].clone(),
 				userdeviceid_tokenexpires: args.db["userdeviceid_tokenexpires"].clone(),
 			},
+			dehydrated_events_pruned: AtomicU64::new(0),
+			interrupt: Notify::new(),
 		}))
 	}
 
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		if !self.retention_enabled() {
+			return Ok(());
+		}
+
+		let mut i = interval(retention::RETENTION_INTERVAL);
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+		// The first tick completes immediately; skip it so the first pass runs one
+		// interval after startup.
+		i.tick().await;
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			self.run_retention().await;
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
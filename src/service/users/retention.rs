@@ -0,0 +1,220 @@
+//! Device retention
+//!
+//! Devices that stopped syncing keep receiving to-device events forever, and
+//! abandoned sessions keep their one-time keys claimable. The users worker
+//! periodically trims every device's to-device inbox to
+//! `max_to_device_events_per_device`, trims dehydrated devices' inboxes to
+//! `dehydrated_device_max_to_device_events` and removes devices that haven't
+//! been seen for `inactive_device_expiry_secs`, unless their owner opted out.
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::Write,
+	time::Duration,
+};
+
+use conduwuit::{
+	Result, debug, info,
+	utils::{self, ReadyExt, stream::TryIgnore},
+	warn,
+};
+use database::{Ignore, Interfix};
+use futures::StreamExt;
+use ruma::{
+	OwnedDeviceId, OwnedUserId, UserId, api::client::device::Device,
+	events::GlobalAccountDataEventType,
+};
+use serde::Deserialize;
+
+/// Global account data type with which users can keep their inactive devices
+/// from being removed, by setting `opt_out` to true in its content.
+pub const DEVICE_EXPIRY_EVENT_TYPE: &str = "org.continuwuity.device_expiry";
+
+/// Interval between retention passes.
+pub(super) const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Deserialize)]
+struct DeviceExpiryEvent {
+	content: DeviceExpiryContent,
+}
+
+#[derive(Deserialize)]
+struct DeviceExpiryContent {
+	#[serde(default)]
+	opt_out: bool,
+}
+
+impl super::Service {
+	/// Whether any retention limit is configured.
+	pub(super) fn retention_enabled(&self) -> bool {
+		let config = &self.services.config;
+		config.max_to_device_events_per_device != 0
+			|| config.dehydrated_device_max_to_device_events != 0
+			|| config.inactive_device_expiry_secs != 0
+	}
+
+	pub(super) async fn run_retention(&self) {
+		let pruned = self.prune_to_device_inboxes().await;
+		if pruned > 0 {
+			debug!(pruned, "Pruned to-device events of devices over the limit");
+		}
+
+		let pruned = self.prune_dehydrated_devices().await;
+		if pruned > 0 {
+			debug!(pruned, "Pruned to-device events of dehydrated devices over the limit");
+		}
+
+		if let Err(e) = self.expire_inactive_devices().await {
+			warn!("Failed to expire inactive devices: {e}");
+		}
+	}
+
+	/// Drops the oldest to-device events of every device beyond
+	/// `max_to_device_events_per_device`. Returns the number of dropped events.
+	pub async fn prune_to_device_inboxes(&self) -> usize {
+		type Key = (OwnedUserId, OwnedDeviceId, Ignore);
+
+		let max = self.services.config.max_to_device_events_per_device;
+		if max == 0 {
+			return 0;
+		}
+
+		let mut inboxes = BTreeMap::<(OwnedUserId, OwnedDeviceId), usize>::new();
+		self.db
+			.todeviceid_events
+			.keys()
+			.ignore_err()
+			.ready_for_each(|(user_id, device_id, _): Key| {
+				let count = inboxes.entry((user_id, device_id)).or_default();
+				*count = count.saturating_add(1);
+			})
+			.await;
+
+		let mut pruned: usize = 0;
+		for ((user_id, device_id), count) in inboxes {
+			let excess = count.saturating_sub(max);
+			if excess == 0 {
+				continue;
+			}
+
+			let prefix = (&user_id, &device_id, Interfix);
+			self.db
+				.todeviceid_events
+				.keys_prefix_raw(&prefix)
+				.ignore_err()
+				.take(excess)
+				.ready_for_each(|key| self.db.todeviceid_events.remove(key))
+				.await;
+
+			pruned = pruned.saturating_add(excess);
+		}
+
+		pruned
+	}
+
+	/// Removes the devices of local users that haven't been seen for
+	/// `inactive_device_expiry_secs` and notifies the admin room. Dehydrated
+	/// devices and the devices of users who opted out are kept. Returns the
+	/// number of removed devices.
+	pub async fn expire_inactive_devices(&self) -> Result<usize> {
+		type KeyVal = ((OwnedUserId, OwnedDeviceId), Device);
+
+		let max_age = self.services.config.inactive_device_expiry_secs;
+		if max_age == 0 {
+			return Ok(0);
+		}
+
+		let cutoff =
+			utils::millis_since_unix_epoch().saturating_sub(max_age.saturating_mul(1000));
+
+		let candidates: Vec<(OwnedUserId, OwnedDeviceId)> = self
+			.db
+			.userdeviceid_metadata
+			.stream()
+			.ignore_err()
+			.ready_filter_map(|((user_id, device_id), device): KeyVal| {
+				let last_seen = device.last_seen_ts?;
+				(u64::from(last_seen.get()) < cutoff).then_some((user_id, device_id))
+			})
+			.collect()
+			.await;
+
+		let mut expired = BTreeMap::<OwnedUserId, Vec<OwnedDeviceId>>::new();
+		for (user_id, device_id) in candidates {
+			if !self.services.globals.user_is_local(&user_id)
+				|| self.device_expiry_opted_out(&user_id).await
+				|| self
+					.get_dehydrated_device_id(&user_id)
+					.await
+					.is_ok_and(|dehydrated| dehydrated == device_id)
+			{
+				continue;
+			}
+
+			self.remove_device(&user_id, &device_id).await;
+			expired.entry(user_id).or_default().push(device_id);
+		}
+
+		let removed = expired.values().map(Vec::len).sum();
+		if removed == 0 {
+			return Ok(0);
+		}
+
+		info!(removed, "Removed devices inactive for more than {max_age} seconds");
+		if self.services.config.admin_room_notices {
+			let mut msg = format!(
+				"Removed {removed} device(s) of {} user(s) that were inactive for more than \
+				 {max_age} seconds:\n```\n",
+				expired.len()
+			);
+
+			for (user_id, device_ids) in &expired {
+				writeln!(msg, "{user_id}: {}", device_ids.join(", "))?;
+			}
+
+			msg.push_str("```");
+			self.services.admin.notice(&msg).await;
+		}
+
+		Ok(removed)
+	}
+
+	/// Whether the user opted out of the removal of their inactive devices.
+	pub async fn device_expiry_opted_out(&self, user_id: &UserId) -> bool {
+		self.services
+			.account_data
+			.get_global(user_id, GlobalAccountDataEventType::from(DEVICE_EXPIRY_EVENT_TYPE))
+			.await
+			.is_ok_and(|event: DeviceExpiryEvent| event.content.opt_out)
+	}
+
+	/// Removes the one-time and fallback keys of devices that no longer exist.
+	/// Returns the number of devices whose keys were removed.
+	pub async fn remove_orphaned_one_time_keys(&self) -> usize {
+		type Key = (OwnedUserId, OwnedDeviceId, Ignore);
+
+		let devices: BTreeSet<(OwnedUserId, OwnedDeviceId)> = self
+			.db
+			.onetimekeyid_onetimekeys
+			.keys()
+			.ignore_err()
+			.chain(self.db.fallbackkeyid_fallbackkey.keys().ignore_err())
+			.map(|(user_id, device_id, _): Key| (user_id, device_id))
+			.collect()
+			.await;
+
+		let mut removed: usize = 0;
+		for (user_id, device_id) in devices {
+			if self
+				.get_device_metadata(&user_id, &device_id)
+				.await
+				.is_err()
+			{
+				self.remove_one_time_keys(&user_id, &device_id).await;
+				removed = removed.saturating_add(1);
+			}
+		}
+
+		removed
+	}
+}